@@ -1,7 +1,15 @@
-use std::{env, io::Write, process::Command as RunCommand, slice::Iter};
+use std::{
+    env,
+    io::{IsTerminal, Write},
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    process::Command as RunCommand,
+    slice::Iter,
+    sync::OnceLock,
+};
 
 use flags::Flag;
-use settings::remove_lock;
+use settings::{SETTINGS_YAML_VERSION, remove_lock};
 use statebox::StateBox;
 use utils::{PostAction, choice};
 
@@ -14,6 +22,128 @@ enum HandlerResult {
 // Extraction of complex type
 type Subcommand = Option<Vec<fn(parents: &[String]) -> Command>>;
 
+// Colour the help output only when a person is reading it
+fn use_color() -> bool {
+    std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+// Render two-column rows with the second column aligned after the widest first one
+fn aligned(rows: &[(String, String)]) -> Vec<String> {
+    let width = rows.iter().map(|(left, _)| left.chars().count()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(left, right)| format!("  {left:<width$}  {right}"))
+        .collect()
+}
+
+// Escape text for roff: backslashes, and a leading `.` or `'` that would be
+// read as a request
+fn roff_escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{text}")
+    } else {
+        text
+    }
+}
+
+// Version of the binary, recorded by `main` since this crate has its own
+static VERSION: OnceLock<String> = OnceLock::new();
+
+pub fn set_version(version: &str) {
+    let _ = VERSION.set(version.to_string());
+}
+
+fn version_info(name: &str) -> String {
+    format!(
+        "{} {} (settings schema {})",
+        name,
+        VERSION.get().map(String::as_str).unwrap_or(env!("CARGO_PKG_VERSION")),
+        SETTINGS_YAML_VERSION
+    )
+}
+
+// Match `input` against candidates that each have one or more names (the first
+// being the primary one). An exact match always wins; otherwise a prefix that
+// matches exactly one candidate is accepted. Ambiguous prefixes return the
+// primary names of every candidate they match.
+fn match_name(input: &str, candidates: &[Vec<String>]) -> Result<Option<usize>, Vec<String>> {
+    if let Some(i) = candidates
+        .iter()
+        .position(|names| names.iter().any(|name| name == input))
+    {
+        return Ok(Some(i));
+    }
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let matches = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, names)| names.iter().any(|name| name.starts_with(input)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [i] => Ok(Some(*i)),
+        _ => Err(matches.iter().map(|i| candidates[*i][0].clone()).collect()),
+    }
+}
+
+// External subcommands are executables named `pax-<name>`, git-style
+const PLUGIN_PREFIX: &str = "pax-";
+
+// Directories searched for plugins: `PAX_PLUGIN_PATH` first, then `PATH`
+fn plugin_dirs() -> Vec<PathBuf> {
+    ["PAX_PLUGIN_PATH", "PATH"]
+        .iter()
+        .filter_map(env::var_os)
+        .flat_map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .collect()
+}
+
+fn is_executable(path: &std::path::Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Locate the executable backing external subcommand `name`, if any.
+pub fn find_plugin(name: &str) -> Option<PathBuf> {
+    plugin_dirs()
+        .into_iter()
+        .map(|dir| dir.join(format!("{PLUGIN_PREFIX}{name}")))
+        .find(|path| is_executable(path))
+}
+
+/// Names of all external subcommands available, sorted.
+pub fn discover_plugins() -> Vec<String> {
+    let mut plugins = Vec::new();
+    for dir in plugin_dirs() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if let Some(name) = entry.file_name().to_str().and_then(|x| x.strip_prefix(PLUGIN_PREFIX))
+                && !name.is_empty()
+                && is_executable(&entry.path())
+            {
+                plugins.push(name.to_string());
+            }
+        }
+    }
+    plugins.sort();
+    plugins.dedup();
+    plugins
+}
+
+// Run a flag that isn't a breakpoint, counting it first if it counts repeats
+fn apply_flag(flag: &Flag, states: &mut StateBox, val: Option<String>) {
+    if flag.counting {
+        let count = flag.count(states);
+        states.shove(&flag.long, count + 1);
+    }
+    (flag.run_func)(states, val)
+}
+
 pub struct Command {
     pub name: String,
     pub aliases: Vec<String>,
@@ -21,6 +151,8 @@ pub struct Command {
     pub flags: Vec<Flag>,
     pub subcommands: Subcommand,
     states: StateBox,
+    // Long names of the flags supplied on this invocation
+    seen_flags: Vec<String>,
     pub run_func: fn(states: &StateBox, args: Option<&[String]>) -> PostAction,
     pub hierarchy: Vec<String>,
 }
@@ -36,6 +168,7 @@ impl PartialEq for Command {
             flags: _,
             subcommands: _,
             states: _,
+            seen_flags: _,
             run_func: _,
             hierarchy: _,
         }: &Self,
@@ -62,10 +195,15 @@ impl Command {
             flags,
             subcommands,
             states: StateBox::new(),
+            seen_flags: Vec::new(),
             run_func,
             hierarchy: hierarchy.to_vec(),
         }
     }
+    // Name the binary was invoked as, i.e. the top of the hierarchy
+    fn root_name(&self) -> &str {
+        self.hierarchy.first().unwrap_or(&self.name)
+    }
     // Returns a hierarchy list of all the parents of the command for the "Run" tip at the bottom of the help command.
     fn compile_parents(&self) -> Vec<String> {
         let mut hierarchy = self.hierarchy.clone();
@@ -74,49 +212,67 @@ impl Command {
     }
     pub fn help(&self) -> String {
         // Make help message
+        let color = use_color();
+        let header = |title: &str| {
+            if color {
+                format!("\x1B[1;94m{title}:\x1B[0m")
+            } else {
+                format!("{title}:")
+            }
+        };
         let mut help = String::new();
         help.push_str(&format!("{}\n", self.about));
         let mut commands = String::new();
         let mut aliases = String::new();
 
         // Show possible commands, flags, and aliases
-        let mut attrs = String::from(&format!("Usage:\n  {} [flags]\n", self.name));
-        let mut flags = String::from("\nFlags:\n");
-
-        // Apply flags to the command
-        for flag in &self.flags {
-            flags.push_str(&format!("  {}\n", flag.help()));
+        let mut attrs = format!("{}\n", header("Usage"));
+        for line in self.usage(&self.name) {
+            attrs.push_str(&format!("  {line}\n"));
         }
+        let mut flags = format!("\n{}\n", header("Flags"));
 
-        // Add the help flag
-        flags.push_str(&format!("  -h, --help\thelp for {}", self.name));
+        // Apply flags to the command, followed by the help and version flags
+        let mut rows = self
+            .flags
+            .iter()
+            .map(|flag| (flag.usage(), flag.description()))
+            .collect::<Vec<_>>();
+        rows.push((String::from("-h, --help"), format!("help for {}", self.name)));
+        rows.push((String::from("-V, --version"), String::from("print version information")));
+        flags.push_str(&aligned(&rows).join("\n"));
 
         // Check if there are subcommands or aliases
         if let Some(subcommands) = &self.subcommands
             && *subcommands != Vec::new()
         {
-            attrs.push_str(&format!("  {} [command]\n", self.name));
-            commands = String::from("\nAvailable Commands:\n");
-            for command in subcommands {
-                let command = (command)(&[]);
-                commands.push_str(&format!(
-                    "  {}{}\t{}\n",
-                    command.name,
-                    {
-                        let mut str = String::new();
-                        let mut i = 8 - command.name.len() as isize;
-                        while i > 0 {
-                            str.push(' ');
-                            i -= 1;
-                        }
-                        str
-                    },
-                    command.about
-                ));
+            commands = format!("\n{}\n", header("Available Commands"));
+            let rows = subcommands
+                .iter()
+                .map(|command| {
+                    let command = (command)(&[]);
+                    (command.name, command.about)
+                })
+                .collect::<Vec<_>>();
+            for row in aligned(&rows) {
+                commands.push_str(&format!("{row}\n"));
+            }
+            // Only the top-level command dispatches to plugins
+            if self.hierarchy.is_empty() {
+                let plugins = discover_plugins()
+                    .into_iter()
+                    .filter(|name| !rows.iter().any(|(builtin, _)| builtin == name))
+                    .collect::<Vec<_>>();
+                if !plugins.is_empty() {
+                    commands.push_str(&format!("\n{}\n", header("Extensions")));
+                    for plugin in plugins {
+                        commands.push_str(&format!("  {plugin}\n"));
+                    }
+                }
             }
         }
         if self.aliases != Vec::<String>::new() {
-            aliases = format!("\nAliases:\n  {}, ", self.name);
+            aliases = format!("\n{}\n  {}, ", header("Aliases"), self.name);
             for alias in &self.aliases {
                 aliases.push_str(&format!("{}, ", alias));
             }
@@ -134,6 +290,99 @@ impl Command {
         }
         help
     }
+    // Usage lines for --help and the man page's SYNOPSIS, e.g. "pax install [flags]"
+    fn usage(&self, invocation: &str) -> Vec<String> {
+        let mut lines = vec![format!("{invocation} [flags]")];
+        if self.subcommands.as_ref().is_some_and(|subcommands| !subcommands.is_empty()) {
+            lines.push(format!("{invocation} [command]"));
+        }
+        lines
+    }
+    // Name of this command's man page, e.g. "pax-install"
+    fn page_name(&self) -> String {
+        self.compile_parents().join("-")
+    }
+    /// A section 1 man page in roff for this command: NAME, SYNOPSIS,
+    /// DESCRIPTION, OPTIONS and, when it has any, SUBCOMMANDS.
+    pub fn generate_manpage(&self) -> String {
+        let page = self.page_name();
+        let invocation = self.compile_parents().join(" ");
+        let version = VERSION.get().map(String::as_str).unwrap_or(env!("CARGO_PKG_VERSION"));
+        let subcommands = self
+            .subcommands
+            .iter()
+            .flatten()
+            .map(|command| (command)(&self.compile_parents()))
+            .collect::<Vec<_>>();
+
+        let mut man = format!(
+            ".TH \"{}\" 1 \"\" \"{} {}\" \"User Commands\"\n",
+            page.to_uppercase(),
+            self.root_name(),
+            version
+        );
+        man.push_str(&format!(".SH NAME\n{} \\- {}\n", page, roff_escape(&self.about)));
+        let synopsis = self
+            .usage(&invocation)
+            .iter()
+            .map(|line| format!(".B {}\n{}\n", invocation, roff_escape(line[invocation.len()..].trim())))
+            .collect::<Vec<_>>();
+        man.push_str(&format!(".SH SYNOPSIS\n{}", synopsis.join(".br\n")));
+        man.push_str(&format!(".SH DESCRIPTION\n{}\n", roff_escape(&self.about)));
+        if !self.aliases.is_empty() {
+            man.push_str(&format!(".PP\nAliases: {}.\n", self.aliases.join(", ")));
+        }
+
+        man.push_str(".SH OPTIONS\n");
+        // Same text as the Flags section of --help
+        let mut rows = self
+            .flags
+            .iter()
+            .map(|flag| {
+                let help = flag.help();
+                let (usage, about) = help.split_once('\t').unwrap_or((&help, ""));
+                (usage.trim().to_string(), about.to_string())
+            })
+            .collect::<Vec<_>>();
+        rows.push((String::from("-h, --help"), format!("help for {}", self.name)));
+        rows.push((String::from("-V, --version"), String::from("print version information")));
+        for (usage, about) in rows {
+            man.push_str(&format!(".TP\n.B {}\n{}\n", usage.replace('-', "\\-"), roff_escape(&about)));
+        }
+
+        if !subcommands.is_empty() {
+            man.push_str(".SH SUBCOMMANDS\n");
+            for command in &subcommands {
+                man.push_str(&format!(".TP\n.B {}\n{}\n", command.name, roff_escape(&command.about)));
+            }
+            let see_also = subcommands
+                .iter()
+                .map(|command| format!("\\fB{}\\fR(1)", command.page_name()))
+                .collect::<Vec<_>>();
+            man.push_str(&format!(".SH SEE ALSO\n{}\n", see_also.join(",\n")));
+        }
+        man
+    }
+    /// Man pages for this command and every subcommand below it, as
+    /// `(file name, roff)` pairs: `pax.1`, `pax-install.1`, ...
+    pub fn generate_manpages(&self) -> Vec<(String, String)> {
+        let mut pages = vec![(format!("{}.1", self.page_name()), self.generate_manpage())];
+        for command in self.subcommands.iter().flatten() {
+            pages.extend((command)(&self.compile_parents()).generate_manpages());
+        }
+        pages
+    }
+    // Write every man page into `dir`, for `--generate-man`
+    fn write_manpages(&self, dir: &str) -> Result<usize, String> {
+        let dir = std::path::Path::new(dir);
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let pages = self.generate_manpages();
+        for (name, page) in &pages {
+            let path = dir.join(name);
+            std::fs::write(&path, page).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(pages.len())
+    }
     // Run the command
     pub fn run(self, mut raw_args: Iter<'_, String>) {
         let mut m_self = self;
@@ -160,6 +409,10 @@ impl Command {
             }
             first_arg = false;
         }
+        if let Err(error) = m_self.check_flag_groups() {
+            println!("Error: {error}\n{}\n\n{error}", m_self.help());
+            return;
+        }
         if let Some((flag_idx, val)) = opr {
             let flag = &m_self.flags[flag_idx];
             (flag.run_func)(&mut m_self.states, val)
@@ -168,6 +421,36 @@ impl Command {
         }
     }
 
+    // Validate required flags and mutually exclusive flags after parsing
+    fn check_flag_groups(&self) -> Result<(), String> {
+        // A breakpoint flag (e.g. --version) replaces the command, so skip the checks
+        let breakpoint = self
+            .flags
+            .iter()
+            .any(|flag| flag.breakpoint && self.seen_flags.contains(&flag.long));
+        if breakpoint {
+            return Ok(());
+        }
+        for flag in &self.flags {
+            let seen = self.seen_flags.contains(&flag.long);
+            if flag.required && !seen {
+                return Err(format!("required flag '--{}' not set", flag.long));
+            }
+            if seen
+                && let Some(other) = flag
+                    .conflicts_with
+                    .iter()
+                    .find(|other| self.seen_flags.iter().any(|x| x == *other))
+            {
+                return Err(format!(
+                    "flags '--{}' and '--{}' cannot be used together",
+                    flag.long, other
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn handle_long_flag(
         &mut self,
         l_arg: &str,
@@ -180,10 +463,45 @@ impl Command {
                 println!("{}", self.help());
                 HandlerResult::ReturnEarly
             }
+            // Version flag
+            "version" => {
+                println!("{}", version_info(self.root_name()));
+                HandlerResult::ReturnEarly
+            }
+            // Hidden, for packagers: write the man pages for the whole command tree
+            "generate-man" if self.hierarchy.is_empty() => {
+                let fault = match args.next().map(|dir| (dir, self.write_manpages(dir))) {
+                    Some((dir, Ok(count))) => {
+                        println!("Wrote {count} man pages to {dir}");
+                        return HandlerResult::ReturnEarly;
+                    }
+                    Some((_, Err(fault))) => fault,
+                    None => String::from("--generate-man needs a directory"),
+                };
+                eprintln!("Error: {fault}");
+                std::process::exit(1)
+            }
             _ => {
-                // Regular flags
+                // Regular flags, matched exactly or by unambiguous prefix
+                let names = self
+                    .flags
+                    .iter()
+                    .map(|flag| vec![flag.long.clone()])
+                    .collect::<Vec<_>>();
+                let matched = match match_name(l_arg, &names) {
+                    Ok(matched) => matched,
+                    Err(candidates) => {
+                        let error = format!(
+                            "ambiguous flag: '{l_arg}' could be {}",
+                            candidates.iter().map(|x| format!("'--{x}'")).collect::<Vec<_>>().join(", ")
+                        );
+                        println!("Error: {error}\n{}\n\n{error}", self.help());
+                        return HandlerResult::ReturnEarly;
+                    }
+                };
                 for (i, flag) in self.flags.iter().enumerate() {
-                    if flag.long == l_arg {
+                    if matched == Some(i) {
+                        self.seen_flags.push(flag.long.clone());
                         let val = if flag.consumer {
                             args.next().cloned()
                         } else {
@@ -195,7 +513,7 @@ impl Command {
                             }
                             *opr = Some((i, val));
                         } else {
-                            (flag.run_func)(&mut self.states, val)
+                            apply_flag(flag, &mut self.states, val)
                         }
                         return HandlerResult::ContinueOuter;
                     }
@@ -220,9 +538,15 @@ impl Command {
                     println!("{}", self.help());
                     return HandlerResult::ReturnEarly;
                 }
+                // Version flag
+                'V' => {
+                    println!("{}", version_info(self.root_name()));
+                    return HandlerResult::ReturnEarly;
+                }
                 c => {
                     for (i, flag) in self.flags.iter().enumerate() {
                         if flag.short == Some(c) {
+                            self.seen_flags.push(flag.long.clone());
                             let val = if flag.consumer {
                                 args.next().cloned()
                             } else {
@@ -234,7 +558,7 @@ impl Command {
                                 }
                                 *opr = Some((i, val));
                             } else {
-                                (flag.run_func)(&mut self.states, val)
+                                apply_flag(flag, &mut self.states, val)
                             }
                             continue 'mid;
                         }
@@ -251,20 +575,52 @@ impl Command {
     fn try_handle_subcommand(&self, arg: &str, args: &mut Iter<'_, String>) -> Result<(), ()> {
         let parents = &self.compile_parents();
         if let Some(subcommands) = &self.subcommands {
-            for command in subcommands {
-                let command = (command)(parents);
-                if command.name == *arg {
-                    command.run(args.clone());
-                    return Ok(());
-                } else {
-                    for alias in &command.aliases {
-                        if alias == arg {
-                            command.run(args.clone());
-                            return Ok(());
-                        }
+            let mut commands = subcommands
+                .iter()
+                .map(|command| (command)(parents))
+                .collect::<Vec<_>>();
+            let names = commands
+                .iter()
+                .map(|command| {
+                    let mut names = vec![command.name.clone()];
+                    names.extend(command.aliases.iter().cloned());
+                    names
+                })
+                .collect::<Vec<_>>();
+            let exact = names.iter().position(|names| names.iter().any(|x| x == arg));
+            // Plugins are only consulted once no built-in matches exactly,
+            // and before prefixes so `pax-<name>` can't be shadowed by one
+            if exact.is_none()
+                && self.hierarchy.is_empty()
+                && let Some(plugin) = find_plugin(arg)
+            {
+                match RunCommand::new(&plugin).args(args.clone()).status() {
+                    Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+                    Err(e) => {
+                        println!("\x1B[91mFailed to run {}: {}\x1B[0m", plugin.display(), e);
+                        std::process::exit(1);
                     }
                 }
             }
+            match match_name(arg, &names) {
+                Ok(Some(i)) => {
+                    commands.swap_remove(i).run(args.clone());
+                    return Ok(());
+                }
+                Ok(None) => (),
+                Err(candidates) => {
+                    let error = format!(
+                        "ambiguous command \"{arg}\" for \"{}\" could be {}",
+                        self.name,
+                        candidates.join(", ")
+                    );
+                    println!(
+                        "Error: {error}\nRun {} --help for usage.\n{error}",
+                        self.name
+                    );
+                    return Ok(());
+                }
+            }
             let error = format!("unknown command \"{arg}\" for \"{}\"", self.name);
             println!(
                 "Error: {error}\nRun {} --help for usage.\n{error}",
@@ -348,3 +704,145 @@ impl Command {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<Vec<String>> {
+        [
+            vec!["install", "i"],
+            vec!["info"],
+            vec!["isocreate"],
+            vec!["remove", "r"],
+            vec!["repo"],
+        ]
+        .iter()
+        .map(|names| names.iter().map(|x| x.to_string()).collect())
+        .collect()
+    }
+
+    #[test]
+    fn test_unambiguous_prefix() {
+        assert_eq!(match_name("inst", &candidates()), Ok(Some(0)));
+        assert_eq!(match_name("iso", &candidates()), Ok(Some(2)));
+        assert_eq!(match_name("rem", &candidates()), Ok(Some(3)));
+        assert_eq!(match_name("upgrade", &candidates()), Ok(None));
+    }
+
+    #[test]
+    fn test_ambiguous_prefix() {
+        assert_eq!(
+            match_name("in", &candidates()),
+            Err(vec![String::from("install"), String::from("info")])
+        );
+        assert_eq!(
+            match_name("re", &candidates()),
+            Err(vec![String::from("remove"), String::from("repo")])
+        );
+    }
+
+    #[test]
+    fn test_exact_match_wins() {
+        // "r" is an alias of remove, but also a prefix of remove and repo
+        assert_eq!(match_name("r", &candidates()), Ok(Some(3)));
+        assert_eq!(match_name("i", &candidates()), Ok(Some(0)));
+        assert_eq!(match_name("repo", &candidates()), Ok(Some(4)));
+    }
+
+    #[test]
+    fn test_help_columns_align() {
+        let flags = vec![
+            Flag::new(Some('y'), "yes", "Skip prompts", false, false, |_, _| {}),
+            Flag::new(None, "allowerasing", "Allow overwriting", false, false, |_, _| {}),
+        ];
+        let command = Command::new("demo", Vec::new(), "Demo", flags, None, |_, _| PostAction::Return, &[]);
+        let help = command.help();
+        assert!(!help.contains('\x1B'));
+        let columns = ["Skip prompts", "Allow overwriting", "help for demo", "print version"]
+            .iter()
+            .map(|about| {
+                let line = help.lines().find(|line| line.contains(about)).unwrap();
+                line.find(about).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(columns.iter().all(|column| *column == columns[0]));
+    }
+
+    #[test]
+    fn test_counting_flag() {
+        let verbose = || Flag::counter(Some('v'), "verbose", "More output", |_, _| {});
+        let count = |args: &[&str]| {
+            let mut command = Command::new("demo", Vec::new(), "Demo", vec![verbose()], None, |_, _| PostAction::Return, &[]);
+            let rest: Vec<String> = Vec::new();
+            for arg in args {
+                let handled = match arg.strip_prefix("--") {
+                    Some(long) => command.handle_long_flag(long, &mut rest.iter(), &mut None),
+                    None => command.handle_short_flags(&arg[1..], &mut rest.iter(), &mut None),
+                };
+                assert!(matches!(handled, HandlerResult::ContinueOuter));
+            }
+            command.flags[0].count(&command.states)
+        };
+        assert_eq!(count(&[]), 0);
+        assert_eq!(count(&["-v"]), 1);
+        assert_eq!(count(&["-vv"]), 2);
+        assert_eq!(count(&["-v", "--verbose", "-v"]), 3);
+    }
+
+    #[test]
+    fn test_flag_groups() {
+        let check = |args: &[&str]| {
+            let flags = vec![
+                Flag::new(Some('o'), "output", "Output file", false, false, |_, _| {}).required(),
+                Flag::new(Some('q'), "quiet", "Less output", false, false, |_, _| {}).conflicts_with(&["verbose"]),
+                Flag::new(Some('v'), "verbose", "More output", false, false, |_, _| {}),
+            ];
+            let mut command = Command::new("demo", Vec::new(), "Demo", flags, None, |_, _| PostAction::Return, &[]);
+            let rest: Vec<String> = Vec::new();
+            for arg in args {
+                let handled = match arg.strip_prefix("--") {
+                    Some(long) => command.handle_long_flag(long, &mut rest.iter(), &mut None),
+                    None => command.handle_short_flags(&arg[1..], &mut rest.iter(), &mut None),
+                };
+                assert!(matches!(handled, HandlerResult::ContinueOuter));
+            }
+            command.check_flag_groups()
+        };
+        assert_eq!(check(&["--output"]), Ok(()));
+        assert_eq!(check(&["-o", "--quiet"]), Ok(()));
+        assert_eq!(check(&["--quiet"]), Err(String::from("required flag '--output' not set")));
+        assert_eq!(
+            check(&["-o", "-q", "--verbose"]),
+            Err(String::from("flags '--quiet' and '--verbose' cannot be used together"))
+        );
+    }
+
+    #[test]
+    fn test_manpage_sections() {
+        fn child(parents: &[String]) -> Command {
+            let flags = vec![Flag::new(Some('y'), "yes", "Skip prompts", false, false, |_, _| {})];
+            Command::new("install", vec![String::from("i")], "Install packages", flags, None, |_, _| PostAction::Return, parents)
+        }
+        let command = Command::new("pax", Vec::new(), "The package manager", Vec::new(), Some(vec![child]), |_, _| PostAction::Return, &[]);
+
+        let page = command.generate_manpage();
+        for header in [".TH \"PAX\" 1", ".SH NAME", ".SH SYNOPSIS", ".SH DESCRIPTION", ".SH OPTIONS", ".SH SUBCOMMANDS"] {
+            assert!(page.contains(header), "missing {header} in:\n{page}");
+        }
+        assert!(page.contains("pax \\- The package manager"));
+        // The synopsis is the usage from --help
+        assert!(page.contains(".SH SYNOPSIS\n.B pax\n[flags]\n.br\n.B pax\n[command]\n"), "{page}");
+        assert!(command.help().contains("pax [flags]\n  pax [command]\n"));
+
+        let pages = command.generate_manpages();
+        let names = pages.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["pax.1", "pax-install.1"]);
+        let install = &pages[1].1;
+        assert!(install.contains(".B pax install"));
+        assert!(install.contains(".SH SYNOPSIS\n.B pax install\n[flags]\n.SH"));
+        assert!(install.contains(".B \\-y, \\-\\-yes\nSkip prompts"));
+        assert!(install.contains("Aliases: i."));
+        assert!(!install.contains(".SH SUBCOMMANDS"));
+    }
+}
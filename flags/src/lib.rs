@@ -7,6 +7,11 @@ pub struct Flag {
     pub consumer: bool,
     pub breakpoint: bool,
     pub run_func: fn(parent: &mut StateBox, flag: Option<String>),
+    // Checked by the owning command once all arguments are parsed
+    pub required: bool,
+    pub conflicts_with: Vec<&'static str>,
+    // Counts its repeats (-vvv) in the StateBox under its long name
+    pub counting: bool,
 }
 
 impl PartialEq for Flag {
@@ -20,6 +25,9 @@ impl PartialEq for Flag {
             consumer: _,
             breakpoint: _,
             run_func: _,
+            required: _,
+            conflicts_with: _,
+            counting: _,
         }: &Self,
     ) -> bool {
         false
@@ -42,16 +50,56 @@ impl Flag {
             consumer,
             breakpoint,
             run_func,
+            required: false,
+            conflicts_with: Vec::new(),
+            counting: false,
         }
     }
-    pub fn help(&self) -> String {
-        let mut help = String::new();
+    // A flag that may be repeated, e.g. -v, -vv, -v -v; each occurrence adds one
+    // to the `usize` stored under its long name before `run_func` is called
+    pub fn counter(
+        short: Option<char>,
+        long: &str,
+        about: &str,
+        run_func: fn(parent: &mut StateBox, flag: Option<String>),
+    ) -> Self {
+        Flag {
+            counting: true,
+            ..Flag::new(short, long, about, false, false, run_func)
+        }
+    }
+    // Times a counting flag was given, zero if never
+    pub fn count(&self, states: &StateBox) -> usize {
+        states.get_or(&self.long, 0usize)
+    }
+    // Fail the command if this flag isn't supplied
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+    // Fail the command if this flag is supplied alongside any of the given long flags
+    pub fn conflicts_with(mut self, flags: &[&'static str]) -> Self {
+        self.conflicts_with.extend_from_slice(flags);
+        self
+    }
+    // Left column of the help output, e.g. "-y, --yes"
+    pub fn usage(&self) -> String {
         let short = if let Some(short) = self.short {
             format!("-{short},")
         } else {
             String::from("   ")
         };
-        help.push_str(&format!("{} --{}\t{}", short, self.long, self.about));
-        help
+        format!("{} --{}", short, self.long)
+    }
+    // Right column of the help output
+    pub fn description(&self) -> String {
+        if self.required {
+            format!("{} (required)", self.about)
+        } else {
+            self.about.clone()
+        }
+    }
+    pub fn help(&self) -> String {
+        format!("{}\t{}", self.usage(), self.description())
     }
 }
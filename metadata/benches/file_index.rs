@@ -0,0 +1,83 @@
+//! Compares the linear manifest scan against the sorted file index for
+//! ownership lookups. Run with `cargo bench -p metadata --bench file_index`.
+
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use metadata::file_tracking::FileManifest;
+use metadata::performance::FileIndex;
+
+const PACKAGES: usize = 5000;
+const FILES_PER_PACKAGE: usize = 20;
+const LOOKUPS: usize = 2000;
+
+fn synthetic_manifests() -> Vec<FileManifest> {
+    (0..PACKAGES)
+        .map(|pkg| {
+            let mut manifest = FileManifest::new(format!("package-{pkg}"), String::from("1.0.0"));
+            for file in 0..FILES_PER_PACKAGE {
+                manifest.add_file(
+                    PathBuf::from(format!("/usr/share/package-{pkg}/file-{file}")),
+                    0,
+                    0o644,
+                    String::new(),
+                );
+            }
+            manifest
+        })
+        .collect()
+}
+
+fn lookup_paths() -> Vec<PathBuf> {
+    // Spread across the dataset, including the worst case for the linear scan
+    (0..LOOKUPS)
+        .map(|i| {
+            let pkg = (i * 7919) % PACKAGES;
+            PathBuf::from(format!("/usr/share/package-{pkg}/file-{}", i % FILES_PER_PACKAGE))
+        })
+        .collect()
+}
+
+fn linear_owner<'a>(manifests: &'a [FileManifest], path: &Path) -> Option<&'a str> {
+    manifests
+        .iter()
+        .find(|manifest| manifest.owns(path))
+        .map(|manifest| manifest.package_name.as_str())
+}
+
+fn time(name: &str, mut lookup: impl FnMut(&Path) -> Option<usize>, paths: &[PathBuf]) -> Duration {
+    let start = Instant::now();
+    for path in paths {
+        black_box(lookup(path));
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<8} {:>10.2?} total, {:>10.2?} per lookup",
+        elapsed,
+        elapsed / paths.len() as u32
+    );
+    elapsed
+}
+
+fn main() {
+    let manifests = synthetic_manifests();
+    let paths = lookup_paths();
+
+    let build_start = Instant::now();
+    let index = FileIndex::from_manifests(&manifests);
+    println!(
+        "Indexed {} paths from {} packages in {:.2?}",
+        index.len(),
+        manifests.len(),
+        build_start.elapsed()
+    );
+
+    let linear = time("linear", |path| linear_owner(&manifests, path).map(str::len), &paths);
+    let indexed = time("indexed", |path| index.lookup(path).map(str::len), &paths);
+
+    println!(
+        "Speedup: {:.0}x",
+        linear.as_secs_f64() / indexed.as_secs_f64().max(f64::EPSILON)
+    );
+}
@@ -0,0 +1,33 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: nix::libc::c_int) {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// Turn the first Ctrl-C into a cancellation request that long operations
+/// notice between steps and unwind from. The handler resets itself, so a
+/// second Ctrl-C still kills pax outright.
+pub fn install_interrupt_handler() -> Result<(), String> {
+    let action = SigAction::new(SigHandler::Handler(on_interrupt), SaFlags::SA_RESETHAND, SigSet::empty());
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe { sigaction(Signal::SIGINT, &action) }
+        .map(|_| ())
+        .map_err(|e| format!("Failed to install the interrupt handler: {}", e))
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// `Err` once cancellation has been requested, for `?` between steps.
+pub fn check_cancelled() -> Result<(), String> {
+    if is_cancelled() {
+        Err(String::from("Interrupted; nothing further was changed."))
+    } else {
+        Ok(())
+    }
+}
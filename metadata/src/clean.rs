@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::performance::FileIndex;
+
+/// Something `pax clean` can clear out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanTarget {
+    /// Cached repository indexes and the installed-file index. Both are
+    /// rebuilt the next time they're needed.
+    Cache,
+    /// Downloaded `.pax`/`.deb`/`.rpm` files left in the temp directory.
+    Packages,
+    /// Extraction and install directories left behind by interrupted runs.
+    Leftovers,
+}
+
+const LEFTOVER_PREFIXES: [&str; 2] = ["pax_install_", "pax_extract"];
+
+impl CleanTarget {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            CleanTarget::Cache => "metadata and index caches",
+            CleanTarget::Packages => "downloaded packages",
+            CleanTarget::Leftovers => "temporary extraction leftovers",
+        }
+    }
+
+    /// What this target would remove right now. Missing caches simply
+    /// aren't listed.
+    pub fn paths(&self) -> Result<Vec<PathBuf>, String> {
+        Ok(match self {
+            CleanTarget::Cache => [utils::get_update_dir()?.join("repo_indexes"), FileIndex::location()?]
+                .into_iter()
+                .filter(|path| path.exists())
+                .collect(),
+            CleanTarget::Packages => download_entries(&std::env::temp_dir()),
+            CleanTarget::Leftovers => matching_entries(&std::env::temp_dir(), &LEFTOVER_PREFIXES),
+        })
+    }
+}
+
+/// Whether `name` is one `utils::tmpfile` hands out for downloads:
+/// `pax.` followed by exactly 16 hex digits.
+pub fn is_download_name(name: &str) -> bool {
+    name.strip_prefix("pax.")
+        .is_some_and(|suffix| suffix.len() == 16 && suffix.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Downloads directly in `dir`, going by `is_download_name`.
+pub fn download_entries(dir: &Path) -> Vec<PathBuf> {
+    entries_where(dir, is_download_name)
+}
+
+/// Entries directly in `dir` whose names start with one of `prefixes`.
+pub fn matching_entries(dir: &Path, prefixes: &[&str]) -> Vec<PathBuf> {
+    entries_where(dir, |name| prefixes.iter().any(|prefix| name.starts_with(prefix)))
+}
+
+fn entries_where(dir: &Path, matches: impl Fn(&str) -> bool) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| matches(&entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Bytes taken up by `path`, counting directories recursively and symlinks
+/// as themselves.
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| disk_usage(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Delete `paths`, returning the bytes freed.
+pub fn remove_paths(paths: &[PathBuf]) -> Result<u64, String> {
+    let mut freed = 0;
+    for path in paths {
+        let size = disk_usage(path);
+        let removed = if fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir()) {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+        removed.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        freed += size;
+    }
+    Ok(freed)
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use settings::OriginKind;
 use utils::err;
 
+use crate::download::{DownloadProgress, Throttle, write_stream};
+
 #[derive(Debug, Clone)]
 pub struct CloudflareR2Client {
     bucket: String,
@@ -112,7 +114,8 @@ impl CloudflareR2Client {
         })
     }
 
-    pub async fn download_package(&self, package_info: &PackageInfo) -> Result<Vec<u8>, String> {
+    /// Stream the package into `path` without holding it in memory.
+    pub async fn download_package(&self, package_info: &PackageInfo, path: &std::path::Path) -> Result<u64, String> {
         let response = self.client
             .get(&package_info.url)
             .send()
@@ -123,10 +126,9 @@ impl CloudflareR2Client {
             return err!("Failed to download package: {}", response.status());
         }
 
-        let bytes = response.bytes().await
-            .map_err(|e| format!("Failed to read package data: {}", e))?;
-
-        Ok(bytes.to_vec())
+        let throttle = utils::limit_rate()?.map(Throttle::new);
+        let progress = DownloadProgress::new(&format!("Downloading {}", package_info.name), response.content_length());
+        write_stream(response.bytes_stream(), path, throttle, Some(progress)).await
     }
 
     fn parse_package_list(&self, response: &str) -> Result<Vec<PackageInfo>, String> {
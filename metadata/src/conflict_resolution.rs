@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
 
+use crate::file_tracking::{self, FileConflict};
 use crate::{DepVer, InstalledMetaData};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -283,3 +287,135 @@ impl Default for DependencyResolver {
         Self::new()
     }
 }
+
+/// An answer to one file-overwrite prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwriteChoice {
+    Keep,
+    Overwrite,
+    OverwriteAll,
+    Abort,
+}
+
+impl OverwriteChoice {
+    fn key(self) -> &'static str {
+        match self {
+            OverwriteChoice::Keep => "k",
+            OverwriteChoice::Overwrite => "o",
+            OverwriteChoice::OverwriteAll => "a",
+            OverwriteChoice::Abort => "x",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            OverwriteChoice::Keep => "keep the existing one",
+            OverwriteChoice::Overwrite => "overwrite it",
+            OverwriteChoice::OverwriteAll => "overwrite all",
+            OverwriteChoice::Abort => "abort",
+        }
+    }
+}
+
+/// The answers offered for a conflict. Directories are shared between
+/// packages rather than replaced, so they never need an answer.
+pub fn overwrite_choices(conflict: &FileConflict) -> &'static [OverwriteChoice] {
+    use OverwriteChoice::*;
+    match conflict.conflict_type {
+        file_tracking::ConflictType::DirectoryOwnership => &[],
+        file_tracking::ConflictType::FileOwnership
+        | file_tracking::ConflictType::SymlinkOwnership
+        | file_tracking::ConflictType::UntrackedFile => &[Keep, Overwrite, OverwriteAll, Abort],
+    }
+}
+
+/// Asks what to do about each file an install would overwrite, like dpkg's
+/// conffile prompt. An "overwrite all" answer holds for the rest of the
+/// transaction.
+#[derive(Debug, Default)]
+pub struct ConflictPrompt {
+    overwrite_all: bool,
+}
+
+impl ConflictPrompt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decide every conflict from answers read off `input`, returning the
+    /// paths to keep as they are. An empty answer keeps the existing file.
+    pub fn resolve(
+        &mut self,
+        conflicts: &[FileConflict],
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<Vec<PathBuf>, String> {
+        let mut keep = Vec::new();
+        for conflict in conflicts {
+            let choices = overwrite_choices(conflict);
+            if choices.is_empty() || self.overwrite_all {
+                continue;
+            }
+            match self.ask(conflict, choices, input, output)? {
+                OverwriteChoice::Keep => keep.push(conflict.path.clone()),
+                OverwriteChoice::Overwrite => (),
+                OverwriteChoice::OverwriteAll => self.overwrite_all = true,
+                OverwriteChoice::Abort => {
+                    return Err(format!("Aborted installing {}.", conflict.new_package));
+                }
+            }
+        }
+        Ok(keep)
+    }
+
+    fn ask(
+        &self,
+        conflict: &FileConflict,
+        choices: &[OverwriteChoice],
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<OverwriteChoice, String> {
+        let owner = match conflict.conflict_type {
+            file_tracking::ConflictType::UntrackedFile => String::from("not tracked by any package"),
+            _ => format!("owned by package '{}'", conflict.existing_owner),
+        };
+        let options = choices
+            .iter()
+            .map(|choice| format!("{} ({})", choice.describe(), choice.key()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        loop {
+            write!(
+                output,
+                "\x1B[93m{} would replace {} ({}).\x1B[0m\n  {}? [k] ",
+                conflict.new_package,
+                conflict.path.display(),
+                owner,
+                options
+            )
+            .and_then(|_| output.flush())
+            .map_err(|e| format!("Failed to write prompt: {}", e))?;
+            let mut answer = String::new();
+            if input.read_line(&mut answer).map_err(|e| format!("Failed to read answer: {}", e))? == 0 {
+                return Err(format!("No answer for {}; aborting.", conflict.path.display()));
+            }
+            let answer = answer.trim().to_lowercase();
+            if answer.is_empty() {
+                return Ok(OverwriteChoice::Keep);
+            }
+            if let Some(choice) = choices.iter().find(|choice| choice.key() == answer) {
+                return Ok(*choice);
+            }
+        }
+    }
+}
+
+// One prompt per process, so "overwrite all" carries across every package
+// of an install
+static TRANSACTION_PROMPT: Mutex<ConflictPrompt> = Mutex::new(ConflictPrompt { overwrite_all: false });
+
+/// Ask on the terminal about `conflicts`, returning the paths to keep.
+pub fn prompt_for_conflicts(conflicts: &[FileConflict]) -> Result<Vec<PathBuf>, String> {
+    let mut prompt = TRANSACTION_PROMPT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    prompt.resolve(conflicts, &mut std::io::stdin().lock(), &mut std::io::stdout())
+}
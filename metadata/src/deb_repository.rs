@@ -4,6 +4,20 @@ use std::collections::HashMap;
 use settings::OriginKind;
 use utils::err;
 
+/// Path of a `.deb` under a Debian-style repository root:
+/// `pool/main/<prefix>/<name>/<name>_<version>_<arch>.deb`. The prefix is the
+/// first letter of the name, or `lib` plus the next letter for libraries, and
+/// any epoch is dropped from the version as it is in real file names.
+pub fn pool_path(name: &str, version: &str, arch: &str) -> String {
+    let lower = name.to_lowercase();
+    let prefix: String = match lower.strip_prefix("lib") {
+        Some(rest) if !rest.is_empty() => lower.chars().take(4).collect(),
+        _ => lower.chars().take(1).collect(),
+    };
+    let version = version.split_once(':').map(|(_, rest)| rest).unwrap_or(version);
+    format!("pool/main/{}/{}/{}_{}_{}.deb", prefix, name, name, version, arch)
+}
+
 #[derive(Debug, Clone)]
 pub struct DebRepositoryClient {
     base_url: String,
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::{InstallPackage, ProcessedMetaData, depend_kind::DependKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    Runtime,
+    Build,
+}
+
+/// The resolved dependency closure of one or more packages, as `pax deps`
+/// prints it.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    /// Resolved version of each package. `None` marks a dependency the
+    /// resolver left out, e.g. because it is already installed.
+    pub nodes: BTreeMap<String, Option<String>>,
+    pub edges: BTreeSet<(String, String, EdgeKind)>,
+    /// The version range a package puts on a dependency, for the edges
+    /// that have one.
+    pub requirements: BTreeMap<(String, String), String>,
+    pub roots: Vec<String>,
+}
+
+fn dep_name(dep: &DependKind) -> &str {
+    match dep {
+        DependKind::Latest(name) | DependKind::Volatile(name) => name,
+        DependKind::Specific(dep_ver) => &dep_ver.name,
+    }
+}
+
+impl DependencyGraph {
+    /// Rebuild the graph from resolved packages. The closure is flattened by
+    /// resolution, so edges come from each member's declared dependencies.
+    /// Build dependencies are only resolved for the requested packages.
+    pub fn from_packages(packages: &[InstallPackage], include_build: bool) -> Self {
+        let mut graph = Self::default();
+        let mut members: Vec<&ProcessedMetaData> = Vec::new();
+        for package in packages {
+            graph.roots.push(package.metadata.name.clone());
+            members.push(&package.metadata);
+            members.extend(package.dependency_targets(include_build));
+        }
+        for member in &members {
+            graph.nodes.insert(member.name.clone(), Some(member.version.clone()));
+        }
+
+        for member in &members {
+            let mut add = |deps: &[DependKind], kind: EdgeKind| {
+                for dep in deps {
+                    let target = dep_name(dep).to_string();
+                    graph.nodes.entry(target.clone()).or_insert(None);
+                    if let DependKind::Specific(dep_ver) = dep
+                        && !dep_ver.range.is_unbounded()
+                    {
+                        graph
+                            .requirements
+                            .insert((member.name.clone(), target.clone()), dep_ver.range.to_string());
+                    }
+                    graph.edges.insert((member.name.clone(), target, kind));
+                }
+            };
+            add(&member.runtime_dependencies, EdgeKind::Runtime);
+            if include_build && graph.roots.contains(&member.name) {
+                add(&member.build_dependencies, EdgeKind::Build);
+            }
+        }
+        graph
+    }
+
+    /// The shortest chain of dependents from a requested package down to
+    /// `name`, requested package first. `None` if nothing pulls it in.
+    pub fn chain_to(&self, name: &str) -> Option<Vec<String>> {
+        let mut parents: HashMap<&str, &str> = HashMap::new();
+        let mut queue: VecDeque<&str> = self.roots.iter().map(String::as_str).collect();
+        let mut seen: HashSet<&str> = queue.iter().copied().collect();
+        while let Some(current) = queue.pop_front() {
+            if current == name {
+                let mut chain = vec![current.to_string()];
+                let mut at = current;
+                while let Some(parent) = parents.get(at) {
+                    chain.push(parent.to_string());
+                    at = parent;
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            for (_, to, _) in self.edges.iter().filter(|(from, _, _)| from == current) {
+                if seen.insert(to) {
+                    parents.insert(to, current);
+                    queue.push_back(to);
+                }
+            }
+        }
+        None
+    }
+
+    /// Why `name` is part of the transaction, as `--explain` prints it:
+    /// `libfoo: required by bar (>=1.0.0), which you requested.`
+    pub fn explain(&self, name: &str) -> String {
+        if self.roots.iter().any(|root| root == name) {
+            return format!("{}: explicitly requested.", name);
+        }
+        let Some(chain) = self.chain_to(name) else {
+            return format!("{}: not required by any requested package.", name);
+        };
+        let hops: Vec<String> = chain
+            .windows(2)
+            .rev()
+            .map(|pair| match self.requirements.get(&(pair[0].clone(), pair[1].clone())) {
+                Some(range) => format!("required by {} ({})", pair[0], range),
+                None => format!("required by {}", pair[0]),
+            })
+            .collect();
+        format!("{}: {}, which you requested.", name, hops.join(", "))
+    }
+
+    fn label(&self, name: &str) -> String {
+        match self.nodes.get(name) {
+            Some(Some(version)) => format!("{} {}", name, version),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Graphviz DOT; build edges are dashed.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph deps {\n");
+        for (name, version) in &self.nodes {
+            match version {
+                Some(version) => dot.push_str(&format!("    \"{}\" [label=\"{}\\n{}\"];\n", name, name, version)),
+                None => dot.push_str(&format!("    \"{}\" [style=dotted];\n", name)),
+            }
+        }
+        for (from, to, kind) in &self.edges {
+            match kind {
+                EdgeKind::Runtime => dot.push_str(&format!("    \"{}\" -> \"{}\";\n", from, to)),
+                EdgeKind::Build => dot.push_str(&format!("    \"{}\" -> \"{}\" [style=dashed];\n", from, to)),
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// An indented tree per requested package. A package already expanded
+    /// elsewhere is marked `(*)` instead of being repeated.
+    pub fn to_tree(&self) -> String {
+        let mut tree = String::new();
+        let mut expanded = HashSet::new();
+        for root in &self.roots {
+            self.write_tree(root, None, 0, &mut expanded, &mut tree);
+        }
+        tree
+    }
+
+    fn write_tree(&self, name: &str, kind: Option<EdgeKind>, depth: usize, expanded: &mut HashSet<String>, tree: &mut String) {
+        let suffix = if kind == Some(EdgeKind::Build) { " [build]" } else { "" };
+        let children: Vec<_> = self.edges.iter().filter(|(from, _, _)| from == name).collect();
+        let seen = !children.is_empty() && !expanded.insert(name.to_string());
+        tree.push_str(&format!(
+            "{}{}{}{}\n",
+            "  ".repeat(depth),
+            self.label(name),
+            suffix,
+            if seen { " (*)" } else { "" }
+        ));
+        if !seen {
+            for (_, to, kind) in children {
+                self.write_tree(to, Some(*kind), depth + 1, expanded, tree);
+            }
+        }
+    }
+}
@@ -60,7 +60,7 @@ impl DependKind {
             let dep = match dep {
                 Self::Latest(latest) => {
                     if let Some(data) =
-                        ProcessedMetaData::get_metadata(latest, None, sources, true).await
+                        ProcessedMetaData::get_metadata(latest, None, sources, true).await?
                     {
                         Some(data)
                     } else {
@@ -75,7 +75,7 @@ impl DependKind {
                         sources,
                         true,
                     )
-                    .await
+                    .await?
                     {
                         Some(data)
                     } else {
@@ -96,7 +96,7 @@ impl DependKind {
                     {
                         None
                     } else if let Some(data) =
-                        ProcessedMetaData::get_metadata(volatile, None, sources, true).await
+                        ProcessedMetaData::get_metadata(volatile, None, sources, true).await?
                     {
                         Some(data)
                     } else {
@@ -129,3 +129,25 @@ impl DependKind {
         }
     }
 }
+
+/// A dependency the package can use but doesn't need. It is only installed
+/// when asked for (`pax install --with-optional` or `--with pkg:dep`).
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct OptionalDependency {
+    pub dependency: DependKind,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl std::fmt::Display for OptionalDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.dependency {
+            DependKind::Specific(dep_ver) => write!(f, "{}", dep_ver)?,
+            dependency => write!(f, "{}", dependency.name())?,
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        Ok(())
+    }
+}
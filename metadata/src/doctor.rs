@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::InstalledMetaData;
+
+/// Something `pax doctor` found wrong with the installed metadata store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DoctorProblem {
+    /// An installed package file that doesn't parse, e.g. after a
+    /// truncated write.
+    Unparseable { path: PathBuf, error: String },
+    /// An installed package without a file manifest, so its files can't be
+    /// verified or removed.
+    MissingManifest { package: String },
+    /// A package listing a dependent that isn't installed.
+    DanglingDependent { package: String, dependent: String },
+}
+
+impl std::fmt::Display for DoctorProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DoctorProblem::Unparseable { path, error } => {
+                write!(f, "{} does not parse: {}", path.display(), error)
+            }
+            DoctorProblem::MissingManifest { package } => write!(f, "{} has no file manifest", package),
+            DoctorProblem::DanglingDependent { package, dependent } => {
+                write!(f, "{} lists {} as a dependent, but it is not installed", package, dependent)
+            }
+        }
+    }
+}
+
+/// Check every installed package file in `dir`: that it parses, that its
+/// manifest exists, and that the dependents it lists are installed.
+pub fn check_store(dir: &Path) -> Result<Vec<DoctorProblem>, String> {
+    let mut problems = Vec::new();
+    let mut installed = BTreeMap::new();
+    for path in installed_files(dir)? {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<InstalledMetaData>(&content).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(package) => {
+                installed.insert(package.name.clone(), package);
+            }
+            Err(error) => problems.push(DoctorProblem::Unparseable { path, error }),
+        }
+    }
+
+    let manifests = dir.join("manifests");
+    for package in installed.values() {
+        if !manifests.join(format!("{}.yaml", package.name)).is_file() {
+            problems.push(DoctorProblem::MissingManifest { package: package.name.clone() });
+        }
+        for dependent in &package.dependents {
+            if !installed.contains_key(&dependent.name) {
+                problems.push(DoctorProblem::DanglingDependent {
+                    package: package.name.clone(),
+                    dependent: dependent.name.clone(),
+                });
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Repair what can be repaired: unparseable files are moved aside into
+/// `dir/quarantine` and dangling dependents are pruned. A missing manifest
+/// can't be rebuilt and is left alone. Returns what was done.
+pub fn fix_store(dir: &Path, problems: &[DoctorProblem]) -> Result<Vec<String>, String> {
+    let mut done = Vec::new();
+    let mut dangling: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for problem in problems {
+        match problem {
+            DoctorProblem::Unparseable { path, .. } => {
+                let quarantine = dir.join("quarantine");
+                fs::create_dir_all(&quarantine)
+                    .map_err(|e| format!("Failed to create {}: {}", quarantine.display(), e))?;
+                let target = quarantine.join(path.file_name().unwrap_or_default());
+                fs::rename(path, &target)
+                    .map_err(|e| format!("Failed to move {} aside: {}", path.display(), e))?;
+                done.push(format!("Moved {} to {}", path.display(), target.display()));
+            }
+            DoctorProblem::DanglingDependent { package, dependent } => {
+                dangling.entry(package).or_default().push(dependent);
+            }
+            DoctorProblem::MissingManifest { .. } => (),
+        }
+    }
+
+    for (package, dependents) in dangling {
+        let path = dir.join(format!("{}.json", package));
+        utils::with_file_lock(&path, || {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let mut data: InstalledMetaData = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            data.dependents.retain(|dependent| !dependents.contains(&dependent.name.as_str()));
+            let content = serde_json::to_string_pretty(&data)
+                .map_err(|e| format!("Failed to serialize package metadata: {}", e))?;
+            utils::write_atomic(&path, content.as_bytes())
+        })?;
+        done.push(format!("Pruned {} from {}'s dependents", dependents.join(", "), package));
+    }
+    Ok(done)
+}
+
+fn installed_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))? {
+        let path = entry.map_err(|e| format!("Failed to read entry: {}", e))?.path();
+        if path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
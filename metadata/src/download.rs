@@ -0,0 +1,187 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use futures::{Stream, StreamExt};
+use tokio::io::AsyncWriteExt;
+use utils::err;
+
+use crate::processed::render_progress;
+
+/// Why a download failed: `Retry` is worth trying on another mirror
+/// (connection failures and 5xx), `Fatal` is not.
+pub enum DownloadFailure {
+    Retry(String),
+    Fatal(String),
+}
+
+impl DownloadFailure {
+    pub fn into_message(self) -> String {
+        match self {
+            DownloadFailure::Retry(message) | DownloadFailure::Fatal(message) => message,
+        }
+    }
+}
+
+/// Keeps a transfer at or under `limit` bytes per second by working out how
+/// far ahead of schedule the bytes seen so far are.
+pub struct Throttle {
+    limit: u64,
+    start: Instant,
+    transferred: u64,
+}
+
+impl Throttle {
+    pub fn new(limit: u64) -> Self {
+        Self { limit, start: Instant::now(), transferred: 0 }
+    }
+
+    /// Account for `bytes` more, returning how long to pause before the next
+    /// read to stay on the target rate.
+    pub fn delay_for(&mut self, bytes: u64) -> Duration {
+        self.transferred += bytes;
+        let due = Duration::from_secs_f64(self.transferred as f64 / self.limit as f64);
+        due.saturating_sub(self.start.elapsed())
+    }
+}
+
+/// Feedback for one download: the install progress bar when the size is
+/// known, a running byte count when it is not.
+pub struct DownloadProgress {
+    label: String,
+    total: Option<u64>,
+    // Last percentage or byte count drawn, so chunks don't redraw needlessly
+    shown: u64,
+}
+
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+const SPINNER_STEP: u64 = 256 * 1024;
+
+impl DownloadProgress {
+    pub fn new(label: &str, total: Option<u64>) -> Self {
+        Self { label: label.to_string(), total: total.filter(|total| *total > 0), shown: 0 }
+    }
+
+    fn update(&mut self, received: u64) {
+        match self.total {
+            Some(total) => {
+                let percent = received.min(total) * 100 / total;
+                if percent > self.shown && received < total {
+                    self.shown = percent;
+                    render_progress(&self.label, received as usize, total as usize, &utils::format_size(total));
+                }
+            }
+            None => {
+                if received / SPINNER_STEP > self.shown {
+                    self.shown = received / SPINNER_STEP;
+                    let spinner = SPINNER[(self.shown % SPINNER.len() as u64) as usize];
+                    print!("\r\x1B[K{} {} {}", self.label, spinner, utils::format_size(received));
+                    std::io::Write::flush(&mut std::io::stdout()).ok();
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, received: u64) {
+        match self.total {
+            Some(total) => render_progress(&self.label, total as usize, total as usize, &utils::format_size(total)),
+            None => println!("\r\x1B[K{} {}", self.label, utils::format_size(received)),
+        }
+    }
+}
+
+/// Write `stream` to `path` chunk by chunk, reporting to `progress` and
+/// pausing between chunks when a rate limit is set. Returns the number of
+/// bytes written.
+pub async fn write_stream<S, B, E>(
+    stream: S,
+    path: &Path,
+    mut throttle: Option<Throttle>,
+    mut progress: Option<DownloadProgress>,
+) -> Result<u64, String>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut written = 0;
+    let mut stream = std::pin::pin!(stream);
+    while let Some(chunk) = stream.next().await {
+        // Don't leave a partial download behind on Ctrl-C
+        if let Err(fault) = crate::cancel::check_cancelled() {
+            drop(file);
+            let _ = tokio::fs::remove_file(path).await;
+            return Err(fault);
+        }
+        let chunk = chunk.map_err(|e| format!("Download interrupted: {}", e))?;
+        let chunk = chunk.as_ref();
+        file.write_all(chunk)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        written += chunk.len() as u64;
+        if let Some(progress) = progress.as_mut() {
+            progress.update(written);
+        }
+        if let Some(throttle) = throttle.as_mut() {
+            let delay = throttle.delay_for(chunk.len() as u64);
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    if let Some(progress) = progress.as_mut() {
+        progress.finish(written);
+    }
+    Ok(written)
+}
+
+/// Stream `url` into `path` under a progress line headed `label`,
+/// honouring `--limit-rate`.
+pub async fn download_to_file(url: &str, path: &Path, label: &str) -> Result<u64, DownloadFailure> {
+    let limit = utils::limit_rate().map_err(DownloadFailure::Fatal)?;
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| DownloadFailure::Retry(format!("Failed to download {}: {}", url, e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let message = format!("HTTP error {} when downloading {}", status, url);
+        return Err(if status.is_server_error() {
+            DownloadFailure::Retry(message)
+        } else {
+            DownloadFailure::Fatal(message)
+        });
+    }
+    let progress = DownloadProgress::new(label, response.content_length());
+    write_stream(response.bytes_stream(), path, limit.map(Throttle::new), Some(progress))
+        .await
+        .map_err(DownloadFailure::Retry)
+}
+
+/// Try each mirror's copy in turn until one downloads, returning the mirror
+/// that served it.
+pub async fn fetch_with_failover<T, F, Fut>(
+    candidates: Vec<(settings::Mirror, String)>,
+    mut fetch: F,
+) -> Result<(settings::Mirror, T), String>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<T, DownloadFailure>>,
+{
+    let mut failures = Vec::new();
+    for (mirror, url) in candidates {
+        match fetch(url).await {
+            Ok(value) => return Ok((mirror, value)),
+            Err(DownloadFailure::Retry(message)) => {
+                println!("\x1B[93m[WARN] {}; trying the next mirror\x1B[0m", message);
+                failures.push(message);
+            }
+            Err(DownloadFailure::Fatal(message)) => return Err(message),
+        }
+    }
+    err!("All mirrors failed: {}", failures.join("; "))
+}
@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, File},
-    io::{Read, Write},
+    io::Read,
     path::{Path, PathBuf},
 };
 
 use utils::get_metadata_dir;
+use crate::integrity::IntegrityLog;
+use crate::performance::FileIndex;
 use crate::processed::render_progress;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +72,11 @@ impl FileManifest {
         }
     }
 
+    /// Total size of the package's files; directories and symlinks don't count.
+    pub fn installed_size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
     pub fn add_file(&mut self, path: PathBuf, size: u64, permissions: u32, checksum: String) {
         self.files.push(InstalledFile {
             path,
@@ -91,20 +98,65 @@ impl FileManifest {
         self.symlinks.push(InstalledSymlink { path, target });
     }
 
+    /// Order the files, directories and symlinks by path.
+    pub fn sort_entries(&mut self) {
+        self.files.sort_by(|a, b| a.path.cmp(&b.path));
+        self.directories.sort_by(|a, b| a.path.cmp(&b.path));
+        self.symlinks.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    /// The manifest as saved, entries sorted by path so the same package
+    /// always produces the same bytes.
+    pub fn to_yaml(&self) -> Result<String, String> {
+        let mut sorted = self.clone();
+        sorted.sort_entries();
+        serde_norway::to_string(&sorted)
+            .map_err(|_| format!("Failed to serialize manifest for {}", self.package_name))
+    }
+
     pub fn save(&self) -> Result<(), String> {
         let mut manifest_path = get_metadata_dir()?;
         manifest_path.push("manifests");
         fs::create_dir_all(&manifest_path).ok();
         manifest_path.push(format!("{}.yaml", self.package_name));
 
-        let mut file = File::create(&manifest_path)
-            .map_err(|_| format!("Failed to create manifest file for {}", self.package_name))?;
+        utils::write_atomic(&manifest_path, self.to_yaml()?.as_bytes())?;
 
-        let yaml = serde_norway::to_string(self)
-            .map_err(|_| format!("Failed to serialize manifest for {}", self.package_name))?;
+        // Keep the ownership index in step; a stale index only costs a `pax reindex`
+        let mut index = match FileIndex::load() {
+            Ok(Some(index)) => index,
+            _ => FileIndex::rebuild()?,
+        };
+        index.update_manifest(self);
+        if let Err(e) = index.save() {
+            println!("\x1B[93m[WARN] Failed to update file index: {}\x1B[0m", e);
+        }
+        if let Err(e) = IntegrityLog::record(&self.package_name) {
+            println!("\x1B[93m[WARN] Failed to update integrity log: {}\x1B[0m", e);
+        }
 
-        file.write_all(yaml.as_bytes())
-            .map_err(|_| format!("Failed to write manifest for {}", self.package_name))?;
+        Ok(())
+    }
+
+    /// Delete a package's saved manifest and drop it from the ownership index.
+    pub fn delete(package_name: &str) -> Result<(), String> {
+        let mut manifest_path = get_metadata_dir()?;
+        manifest_path.push("manifests");
+        manifest_path.push(format!("{}.yaml", package_name));
+        if manifest_path.exists() {
+            fs::remove_file(&manifest_path)
+                .map_err(|_| format!("Failed to remove manifest for {}", package_name))?;
+        }
+
+        if let Ok(Some(mut index)) = FileIndex::load() {
+            index.remove_package(package_name);
+            if let Err(e) = index.save() {
+                println!("\x1B[93m[WARN] Failed to update file index: {}\x1B[0m", e);
+            }
+        }
+        if let Err(e) = IntegrityLog::record(package_name) {
+            println!("\x1B[93m[WARN] Failed to update integrity log: {}\x1B[0m", e);
+        }
 
         Ok(())
     }
@@ -125,6 +177,25 @@ impl FileManifest {
             .map_err(|_| format!("Failed to parse manifest for {}", package_name))
     }
 
+    /// Installed files and symlinks, sorted by path.
+    pub fn file_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .chain(self.symlinks.iter().map(|symlink| symlink.path.clone()))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Whether this manifest tracks `path` as a file, directory or symlink.
+    pub fn owns(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file.path == path)
+            || self.directories.iter().any(|dir| dir.path == path)
+            || self.symlinks.iter().any(|symlink| symlink.path == path)
+    }
+
     pub fn remove_files(&self, purge: bool) -> Result<(), String> {
         // Safety check: prevent removal of critical system directories
         let critical_dirs = [
@@ -205,12 +276,15 @@ impl FileManifest {
             }
         }
 
-        // Remove directories (only if empty and not critical)
-        for dir in &self.directories {
+        // Remove directories (only if empty, not critical and not claimed
+        // by another installed package)
+        let manifests = load_all_manifests()?;
+        let removable = self.removable_directories(&manifests);
+        for dir in self.directories_deepest_first() {
             processed += 1;
             
             // Check if this is a critical system directory
-            if critical_dirs.contains(&dir.path.to_str().unwrap_or("")) {
+            if critical_dirs.contains(&dir.path.to_str().unwrap_or("")) || !removable.contains(&dir.path.as_path()) {
                 render_progress("Removing", processed, total_items, &format!("[SKIP] {}", dir.path.display()));
                 continue;
             }
@@ -234,13 +308,41 @@ impl FileManifest {
         Ok(())
     }
 
+    /// Directories in removal order: children before their parents.
+    fn directories_deepest_first(&self) -> Vec<&InstalledDirectory> {
+        let mut dirs: Vec<&InstalledDirectory> = self.directories.iter().collect();
+        dirs.sort_by_key(|dir| std::cmp::Reverse(dir.path.components().count()));
+        dirs
+    }
+
+    /// This package's directories that no other manifest in `manifests`
+    /// claims, and so may be removed with it once empty.
+    pub fn removable_directories<'a>(&'a self, manifests: &[FileManifest]) -> Vec<&'a Path> {
+        self.directories
+            .iter()
+            .map(|dir| dir.path.as_path())
+            .filter(|path| {
+                directory_owners_in(manifests, path)
+                    .iter()
+                    .all(|owner| *owner == self.package_name)
+            })
+            .collect()
+    }
+
+    /// Conflicts with the packages installed on this system.
     pub fn check_conflicts(&self) -> Result<Vec<FileConflict>, String> {
+        self.check_conflicts_against(&InstalledManifests)
+    }
+
+    /// Paths this package would install over that already exist under the
+    /// install root, with their owners looked up in `installed`.
+    pub fn check_conflicts_against<S: ManifestSource + ?Sized>(&self, installed: &S) -> Result<Vec<FileConflict>, String> {
         let mut conflicts = Vec::new();
         
         for file in &self.files {
-            if file.path.exists() {
+            if utils::in_install_root(&file.path).exists() {
                 // Check if file is owned by another package
-                if let Ok(owner) = get_file_owner(&file.path) {
+                if let Some(owner) = installed.owner_of(&file.path) {
                     if owner != self.package_name {
                         conflicts.push(FileConflict {
                             path: file.path.clone(),
@@ -262,32 +364,30 @@ impl FileManifest {
         }
         
         for dir in &self.directories {
-            if dir.path.exists() {
-                if let Ok(owner) = get_file_owner(&dir.path) {
-                    if owner != self.package_name {
-                        conflicts.push(FileConflict {
-                            path: dir.path.clone(),
-                            existing_owner: owner,
-                            new_package: self.package_name.clone(),
-                            conflict_type: ConflictType::DirectoryOwnership,
-                        });
-                    }
-                }
+            if utils::in_install_root(&dir.path).exists()
+                && let Some(owner) = installed.owner_of(&dir.path)
+                && owner != self.package_name
+            {
+                conflicts.push(FileConflict {
+                    path: dir.path.clone(),
+                    existing_owner: owner,
+                    new_package: self.package_name.clone(),
+                    conflict_type: ConflictType::DirectoryOwnership,
+                });
             }
         }
         
         for symlink in &self.symlinks {
-            if symlink.path.exists() {
-                if let Ok(owner) = get_file_owner(&symlink.path) {
-                    if owner != self.package_name {
-                        conflicts.push(FileConflict {
-                            path: symlink.path.clone(),
-                            existing_owner: owner,
-                            new_package: self.package_name.clone(),
-                            conflict_type: ConflictType::SymlinkOwnership,
-                        });
-                    }
-                }
+            if utils::in_install_root(&symlink.path).exists()
+                && let Some(owner) = installed.owner_of(&symlink.path)
+                && owner != self.package_name
+            {
+                conflicts.push(FileConflict {
+                    path: symlink.path.clone(),
+                    existing_owner: owner,
+                    new_package: self.package_name.clone(),
+                    conflict_type: ConflictType::SymlinkOwnership,
+                });
             }
         }
         
@@ -295,11 +395,18 @@ impl FileManifest {
     }
 
     pub fn backup_existing_files(&mut self) -> Result<(), String> {
+        self.backup_files_where(|_| true)
+    }
+
+    /// Back up the existing files for which `wanted` holds. `wanted` sees
+    /// manifest paths; the files themselves are read from the install root.
+    pub fn backup_files_where(&mut self, wanted: impl Fn(&Path) -> bool) -> Result<(), String> {
         let backup_dir = get_backup_dir()?;
         fs::create_dir_all(&backup_dir).ok();
 
         for file in &mut self.files {
-            if file.path.exists() {
+            let existing = utils::in_install_root(&file.path);
+            if wanted(&file.path) && existing.exists() {
                 let backup_path = backup_dir.join(format!(
                     "{}_{}",
                     file.path.file_name().unwrap().to_string_lossy(),
@@ -309,7 +416,7 @@ impl FileManifest {
                         .as_secs()
                 ));
 
-                if let Err(e) = fs::copy(&file.path, &backup_path) {
+                if let Err(e) = fs::copy(&existing, &backup_path) {
                     println!(
                         "\x1B[93m[WARN] Failed to backup file {}: {}\x1B[0m",
                         file.path.display(),
@@ -394,39 +501,82 @@ pub fn cleanup_old_backups() -> Result<(), String> {
     Ok(())
 }
 
-/// Get the package that owns a specific file
-pub fn get_file_owner(path: &Path) -> Result<String, String> {
-    let metadata_dir = get_metadata_dir()?;
-    
-    // Search through all installed package manifests
-    for entry in fs::read_dir(&metadata_dir)
-        .map_err(|e| format!("Failed to read metadata directory: {}", e))? {
+/// Load every saved manifest under installed/manifests.
+pub fn load_all_manifests() -> Result<Vec<FileManifest>, String> {
+    let mut manifests_dir = get_metadata_dir()?;
+    manifests_dir.push("manifests");
+    if !manifests_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut manifests = Vec::new();
+    for entry in fs::read_dir(&manifests_dir)
+        .map_err(|e| format!("Failed to read manifests directory: {}", e))? {
         let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
         let entry_path = entry.path();
-        
-        if entry_path.extension().and_then(|s| s.to_str()) == Some("json") {
-            if let Ok(content) = fs::read_to_string(&entry_path) {
-                if let Ok(manifest) = serde_json::from_str::<FileManifest>(&content) {
-                    // Check if this package owns the file
-                    for file in &manifest.files {
-                        if file.path == path {
-                            return Ok(manifest.package_name.clone());
-                        }
-                    }
-                    for dir in &manifest.directories {
-                        if dir.path == path {
-                            return Ok(manifest.package_name.clone());
-                        }
-                    }
-                    for symlink in &manifest.symlinks {
-                        if symlink.path == path {
-                            return Ok(manifest.package_name.clone());
-                        }
-                    }
-                }
-            }
+
+        if entry_path.extension().and_then(|s| s.to_str()) == Some("yaml")
+            && let Ok(content) = fs::read_to_string(&entry_path)
+            && let Ok(manifest) = serde_norway::from_str::<FileManifest>(&content)
+        {
+            manifests.push(manifest);
         }
     }
-    
+
+    Ok(manifests)
+}
+
+/// Every installed package whose manifest claims the directory `path`.
+pub fn directory_owners(path: &Path) -> Result<Vec<String>, String> {
+    Ok(directory_owners_in(&load_all_manifests()?, path))
+}
+
+fn directory_owners_in(manifests: &[FileManifest], path: &Path) -> Vec<String> {
+    manifests
+        .iter()
+        .filter(|manifest| manifest.directories.iter().any(|dir| dir.path == path))
+        .map(|manifest| manifest.package_name.clone())
+        .collect()
+}
+
+/// Where conflict checks find out which installed package owns a path.
+pub trait ManifestSource {
+    fn owner_of(&self, path: &Path) -> Option<String>;
+}
+
+/// The manifests saved under `/etc/pax/installed`, via the file index when
+/// there is one.
+pub struct InstalledManifests;
+
+impl ManifestSource for InstalledManifests {
+    fn owner_of(&self, path: &Path) -> Option<String> {
+        get_file_owner(path).ok()
+    }
+}
+
+impl ManifestSource for [FileManifest] {
+    fn owner_of(&self, path: &Path) -> Option<String> {
+        self.iter()
+            .find(|manifest| manifest.owns(path))
+            .map(|manifest| manifest.package_name.clone())
+    }
+}
+
+/// Get the package that owns a specific file
+pub fn get_file_owner(path: &Path) -> Result<String, String> {
+    if let Ok(Some(index)) = FileIndex::load() {
+        return index
+            .lookup(path)
+            .map(|owner| owner.to_string())
+            .ok_or_else(|| "File not owned by any package".to_string());
+    }
+
+    // No index yet, search through all installed package manifests
+    for manifest in load_all_manifests()? {
+        if manifest.owns(path) {
+            return Ok(manifest.package_name);
+        }
+    }
+
     Err("File not owned by any package".to_string())
 }
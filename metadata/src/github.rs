@@ -0,0 +1,208 @@
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde_json::Value as JsonValue;
+use settings::Arch;
+use utils::{Version, err};
+
+/// Largest GitHub API response pax reads. Release listings are a few hundred
+/// kilobytes at most; anything beyond this is refused rather than buffered.
+pub const MAX_RESPONSE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// The client GitHub lookups share: bounded timeouts, and the User-Agent
+/// the GitHub API rejects requests without.
+pub fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent(concat!("pax/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// Fetch and parse a GitHub API endpoint. `Ok(None)` means there is nothing
+/// there (a 404, e.g. a repository with no releases); exhausting the rate
+/// limit is an error of its own rather than looking like a missing release.
+pub async fn get_json(url: &str) -> Result<Option<JsonValue>, String> {
+    get_json_capped(url, MAX_RESPONSE_BYTES).await
+}
+
+pub(crate) async fn get_json_capped(url: &str, limit: u64) -> Result<Option<JsonValue>, String> {
+    let Some(body) = get_body(url, limit).await? else {
+        return Ok(None);
+    };
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse GitHub response from {}: {}", url, e))
+}
+
+/// The body of `url` as text, under the same size cap, for release assets.
+pub async fn get_text(url: &str) -> Result<Option<String>, String> {
+    let Some(body) = get_body(url, MAX_RESPONSE_BYTES).await? else {
+        return Ok(None);
+    };
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| format!("Response from {} is not valid UTF-8", url))
+}
+
+async fn get_body(url: &str, limit: u64) -> Result<Option<Vec<u8>>, String> {
+    let response = client()
+        .get(url)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        if header("x-ratelimit-remaining").as_deref() == Some("0") {
+            return match header("x-ratelimit-reset") {
+                Some(reset) => err!("GitHub API rate limit exceeded; it resets at unix time {}", reset),
+                None => err!("GitHub API rate limit exceeded"),
+            };
+        }
+    }
+    if !status.is_success() {
+        return err!("GitHub API returned {} for {}", status, url);
+    }
+    if response.content_length().is_some_and(|length| length > limit) {
+        return err!("GitHub response from {} is larger than {}", url, utils::format_size(limit));
+    }
+
+    let mut body = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Failed to read {}: {}", url, e))?;
+        if body.len() as u64 + chunk.len() as u64 > limit {
+            return err!("GitHub response from {} is larger than {}", url, utils::format_size(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(Some(body))
+}
+
+static INCLUDE_PRERELEASES: AtomicBool = AtomicBool::new(false);
+
+/// `--pre`: let GitHub origins pick pre-releases as well as stable releases.
+pub fn set_include_prereleases(include: bool) {
+    INCLUDE_PRERELEASES.store(include, Ordering::Relaxed);
+}
+
+pub fn include_prereleases() -> bool {
+    INCLUDE_PRERELEASES.load(Ordering::Relaxed)
+}
+
+/// The newest release in a `/releases` listing by the version in its tag,
+/// rather than by GitHub's "latest" designation. Drafts are never picked,
+/// pre-releases only with `include_pre`, and tags that don't parse as a
+/// version are skipped. When no tag parses, the most recently published
+/// release is picked instead, as GitHub's "latest" would.
+pub fn newest_release(releases: &[JsonValue], include_pre: bool) -> Option<&JsonValue> {
+    let flag = |release: &JsonValue, name: &str| release.get(name).and_then(JsonValue::as_bool).unwrap_or(false);
+    let eligible: Vec<&JsonValue> = releases
+        .iter()
+        .filter(|release| !flag(release, "draft"))
+        .filter(|release| include_pre || !flag(release, "prerelease"))
+        .collect();
+    let versioned: Vec<(Version, &JsonValue)> = eligible
+        .iter()
+        .filter_map(|release| {
+            let tag = release.get("tag_name")?.as_str()?;
+            Some((Version::parse(tag.trim_start_matches('v')).ok()?, *release))
+        })
+        .collect();
+    if versioned.is_empty() {
+        // The listing is newest first, so that order settles missing dates
+        return eligible
+            .into_iter()
+            .rev()
+            .max_by_key(|release| release.get("published_at").and_then(JsonValue::as_str).unwrap_or_default());
+    }
+    versioned
+        .into_iter()
+        .filter(|(version, _)| include_pre || version.pre.is_empty())
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+/// Names an asset for `arch` may carry: its own name first, then the
+/// family names projects commonly use instead.
+fn arch_hints(arch: Arch) -> Vec<String> {
+    let family: &[&str] = match arch {
+        Arch::NoArch => &["noarch", "all"],
+        Arch::X86_64v1 | Arch::X86_64v2 | Arch::X86_64v3 | Arch::X86_64v4 => &["x86_64", "amd64"],
+        Arch::Aarch64 => &["arm64"],
+        Arch::Armv7l | Arch::Armv8l => &["armhf", "armv7"],
+    };
+    std::iter::once(arch.to_string()).chain(family.iter().map(|name| name.to_string())).collect()
+}
+
+/// Whether `hint` appears in `name` as a whole token, so `all` matches
+/// `tool-all.json` but not `installer.json`.
+fn mentions(name: &str, hint: &str) -> bool {
+    name.match_indices(hint).any(|(start, _)| {
+        let before = name[..start].chars().next_back();
+        let after = name[start + hint.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// The release assets worth trying on `arch`, best first. Candidates are
+/// the assets matching `pattern` when one is configured, else the `.pax`
+/// and `.json` assets. Like remote Pax discovery, an asset matches an
+/// architecture when its name mentions it; an exact match beats a family
+/// name, and every candidate is in the running when none mention the
+/// architecture. Between equally good matches the `.json` metadata comes
+/// first, since that is what gets parsed.
+pub fn ranked_assets<'a>(assets: &'a [JsonValue], arch: Arch, pattern: Option<&str>) -> Vec<&'a JsonValue> {
+    let name = |asset: &JsonValue| asset.get("name").and_then(JsonValue::as_str).map(str::to_string);
+    let standard = |name: &str| name.ends_with(".pax") || name.ends_with(".json");
+    let mut candidates: Vec<(&JsonValue, String)> = assets
+        .iter()
+        .filter_map(|asset| name(asset).map(|name| (asset, name)))
+        .filter(|(_, name)| pattern.is_some_and(|pattern| settings::glob_match(pattern, name)))
+        .collect();
+    if candidates.is_empty() {
+        candidates = assets
+            .iter()
+            .filter_map(|asset| name(asset).map(|name| (asset, name)))
+            .filter(|(_, name)| standard(name))
+            .collect();
+    }
+
+    let hints = arch_hints(arch);
+    let mut ranked: Vec<(usize, bool, &JsonValue)> = candidates
+        .iter()
+        .filter_map(|(asset, name)| {
+            let rank = hints.iter().position(|hint| mentions(name, hint))?;
+            Some((rank, !name.ends_with(".json"), *asset))
+        })
+        .collect();
+    if ranked.is_empty() {
+        ranked = candidates.iter().map(|(asset, name)| (0, !name.ends_with(".json"), *asset)).collect();
+    }
+    // Stable, so listing order settles the rest
+    ranked.sort_by_key(|(rank, not_json, _)| (*rank, *not_json));
+    ranked.into_iter().map(|(_, _, asset)| asset).collect()
+}
+
+/// The best of `ranked_assets`.
+pub fn select_asset<'a>(assets: &'a [JsonValue], arch: Arch, pattern: Option<&str>) -> Option<&'a JsonValue> {
+    ranked_assets(assets, arch, pattern).into_iter().next()
+}
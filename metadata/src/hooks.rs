@@ -0,0 +1,92 @@
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// When an admin hook runs: after the matching transaction has finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    PostInstall,
+    PostRemove,
+    PostUpgrade,
+}
+
+impl fmt::Display for HookKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookKind::PostInstall => write!(f, "post-install"),
+            HookKind::PostRemove => write!(f, "post-remove"),
+            HookKind::PostUpgrade => write!(f, "post-upgrade"),
+        }
+    }
+}
+
+impl HookKind {
+    /// `/etc/pax/hooks/<kind>.d`
+    pub fn dir(&self) -> Result<PathBuf, String> {
+        Ok(utils::get_dir()?.join("hooks").join(format!("{}.d", self)))
+    }
+}
+
+/// Executable files in `dir`, in lexical order. Dotfiles and anything not
+/// executable are skipped, so a hook can be disabled with `chmod -x`.
+pub fn discover_hooks(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut hooks = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))? {
+        let path = entry.map_err(|e| format!("Failed to read entry: {}", e))?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        let executable = fs::metadata(&path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0);
+        if !hidden && executable {
+            hooks.push(path);
+        }
+    }
+    hooks.sort();
+    Ok(hooks)
+}
+
+/// Run every hook in `dir` with the affected packages as arguments, also
+/// passed space-separated in `PAX_PACKAGES`. Returns a message per hook
+/// that failed; one failing hook doesn't stop the rest.
+pub fn run_hooks_in(dir: &Path, kind: HookKind, packages: &[String]) -> Result<Vec<String>, String> {
+    let mut failures = Vec::new();
+    for hook in discover_hooks(dir)? {
+        let status = Command::new(&hook)
+            .args(packages)
+            .env("PAX_HOOK", kind.to_string())
+            .env("PAX_PACKAGES", packages.join(" "))
+            .status();
+        match status {
+            Ok(status) if status.success() => (),
+            Ok(status) => failures.push(format!("{} hook {} exited with {}", kind, hook.display(), status)),
+            Err(e) => failures.push(format!("Failed to run {} hook {}: {}", kind, hook.display(), e)),
+        }
+    }
+    Ok(failures)
+}
+
+/// Run the admin's `kind` hooks for a finished transaction. The transaction
+/// has already happened, so failures are only warned about.
+pub fn run_hooks(kind: HookKind, packages: &[String]) {
+    if packages.is_empty() {
+        return;
+    }
+    // Hooks regenerate the host's caches, which an alternate root doesn't share
+    if utils::is_alternate_root() {
+        println!("Skipping {} hooks in alternate root.", kind);
+        return;
+    }
+    let failures = match kind.dir().and_then(|dir| run_hooks_in(&dir, kind, packages)) {
+        Ok(failures) => failures,
+        Err(fault) => vec![fault],
+    };
+    for failure in failures {
+        println!("\x1B[93m[WARN] {}\x1B[0m", failure);
+    }
+}
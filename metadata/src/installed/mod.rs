@@ -2,14 +2,26 @@ use serde::{Deserialize, Serialize};
 use settings::OriginKind;
 use std::{
     fs::File,
-    io::{Read, Write},
+    io::Read,
     path::Path,
 };
 use utils::{err, get_metadata_dir};
 
+use crate::depend_kind::OptionalDependency;
+use crate::file_tracking::FileManifest;
 use crate::processed::PreBuilt;
 use crate::{DepVer, MetaDataKind, Specific};
 
+/// Why a package is installed: `Manual` when the user asked for it by name,
+/// `Auto` when it came in as a dependency. Only `Auto` packages are
+/// reclaimed once nothing needs them.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallReason {
+    Manual,
+    Auto,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct InstalledMetaData {
     pub name: String,
@@ -20,8 +32,23 @@ pub struct InstalledMetaData {
     pub dependent: bool,
     #[serde(default)]
     pub installed_by: Option<String>, // Track which package installed this one
+    /// Records from before this was tracked fall back to `dependent`.
+    #[serde(default)]
+    pub install_reason: Option<InstallReason>,
     pub dependencies: Vec<DepVer>,
     pub dependents: Vec<Specific>,
+    #[serde(default)]
+    pub optional_dependencies: Vec<OptionalDependency>,
+    /// Total bytes of the package's files, recorded at install time.
+    #[serde(default)]
+    pub installed_size: Option<u64>,
+    /// Files and symlinks the package installed, recorded at install time.
+    #[serde(default)]
+    pub installed_files: Vec<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
     pub install_kind: InstalledInstallKind,
     pub hash: String,
 }
@@ -43,6 +70,38 @@ impl InstalledMetaData {
             Err(_) => return err!("Failed to parse package `{name}`'s data!"),
         })
     }
+    /// Bytes the package's files take up: the size recorded at install time,
+    /// or summed from its manifest for packages installed before that.
+    pub fn size_on_disk(&self) -> Option<u64> {
+        self.installed_size
+            .or_else(|| FileManifest::load(&self.name).ok().map(|manifest| manifest.installed_size()))
+    }
+    /// Record the size and file list of `manifest`, the files just installed.
+    pub fn record_files(&mut self, manifest: &FileManifest) {
+        self.installed_size = Some(manifest.installed_size());
+        self.installed_files = manifest
+            .file_paths()
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+    }
+    pub fn reason(&self) -> InstallReason {
+        self.install_reason
+            .unwrap_or(if self.dependent { InstallReason::Auto } else { InstallReason::Manual })
+    }
+    /// Set the install reason, keeping the older `dependent` flag in step.
+    pub fn set_reason(&mut self, reason: InstallReason) {
+        self.install_reason = Some(reason);
+        self.dependent = reason == InstallReason::Auto;
+    }
+    /// Carry the reason over a reinstall or upgrade of `previous`: a package
+    /// the user asked for stays manual even when it is fetched again as a
+    /// dependency, and asking for an auto package makes it manual.
+    pub fn settle_reason(&mut self, previous: Option<&Self>) {
+        if previous.is_some_and(|previous| previous.reason() == InstallReason::Manual) {
+            self.set_reason(InstallReason::Manual);
+        }
+    }
     pub fn write(self, path: &Path) -> Result<Option<Self>, String> {
         if !path.exists() || path.is_file() {
             let data = match serde_json::to_string_pretty(&self) {
@@ -51,18 +110,31 @@ impl InstalledMetaData {
                     return err!("Failed to parse InstalledMetaData into string!");
                 }
             };
-            let mut file = match File::create(path) {
-                Ok(file) => file,
-                Err(_) => return err!("Failed to open file as WO!"),
-            };
-            match file.write_all(data.as_bytes()) {
-                Ok(_) => Ok(Some(self)),
-                Err(_) => err!("Failed to write to file!"),
-            }
+            utils::with_file_lock(path, || utils::write_atomic(path, data.as_bytes()))?;
+            Ok(Some(self))
         } else {
             err!("File is of unexpected type!")
         }
     }
+    /// Change the recorded install reason of the installed package `name`,
+    /// for `pax mark`.
+    pub fn mark(name: &str, reason: InstallReason) -> Result<(), String> {
+        let mut path = get_metadata_dir()?;
+        path.push(format!("{}.json", name));
+        if !path.is_file() {
+            return err!("Package `{name}` is not installed!");
+        }
+        mark_at(&path, reason)
+    }
+    /// Make an auto-installed package manual when the user asks to install
+    /// it by name. Returns whether the reason changed.
+    pub fn mark_requested(&self) -> Result<bool, String> {
+        if self.reason() != InstallReason::Auto {
+            return Ok(false);
+        }
+        Self::mark(&self.name, InstallReason::Manual)?;
+        Ok(true)
+    }
     pub fn clear_dependencies(&self, specific: &Specific) -> Result<(), String> {
         let mut path = get_metadata_dir()?;
         let mut data = self.clone();
@@ -95,3 +167,18 @@ pub struct InstalledCompilable {
     pub uninstall: String,
     pub purge: String,
 }
+
+/// Rewrite the install reason in the installed package file at `path`. The
+/// read-modify-write happens under the file's lock, like `write`.
+pub(crate) fn mark_at(path: &Path, reason: InstallReason) -> Result<(), String> {
+    utils::with_file_lock(path, || {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut data: InstalledMetaData = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        data.set_reason(reason);
+        let content = serde_json::to_string_pretty(&data)
+            .map_err(|e| format!("Failed to serialize package metadata: {}", e))?;
+        utils::write_atomic(path, content.as_bytes())
+    })
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+/// Marker recorded in place of a hash when a package's manifest is removed.
+const REMOVED: &str = "-";
+
+/// Whether `PAX_INTEGRITY_LOG=1` asks for manifest hashes to be logged.
+pub fn integrity_log_enabled() -> bool {
+    std::env::var("PAX_INTEGRITY_LOG").is_ok_and(|value| value == "1")
+}
+
+/// A single `(package, manifest_sha256, timestamp)` record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityEntry {
+    pub package: String,
+    /// `None` once the package has been removed.
+    pub manifest_sha256: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityProblem {
+    /// The manifest on disk no longer hashes to the logged value.
+    Modified { expected: String, found: String },
+    /// The log expects a manifest that is no longer on disk.
+    Missing { expected: String },
+    /// A manifest exists that the log never saw written, or saw removed.
+    Unrecorded,
+}
+
+/// Append-only log of manifest hashes, kept beside the installed metadata
+/// (`/etc/pax/integrity.log`) so a tampered manifest can be noticed.
+pub struct IntegrityLog;
+
+impl IntegrityLog {
+    pub fn location() -> Result<PathBuf, String> {
+        let installed = utils::get_metadata_dir()?;
+        let mut path = installed.parent().map(Path::to_path_buf).unwrap_or(installed);
+        path.push("integrity.log");
+        Ok(path)
+    }
+
+    /// Log the current hash of `package`'s manifest, or its removal if the
+    /// manifest is gone. Does nothing unless the log is enabled.
+    pub fn record(package: &str) -> Result<(), String> {
+        if !integrity_log_enabled() {
+            return Ok(());
+        }
+        let hash = manifest_sha256(package)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let line = format!("{}\t{}\t{}\n", package, hash.as_deref().unwrap_or(REMOVED), timestamp);
+
+        let path = Self::location()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        file.write_all(line.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Every entry in the log, oldest first. A missing log reads as empty.
+    pub fn entries() -> Result<Vec<IntegrityEntry>, String> {
+        let path = Self::location()?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Ok(parse_entries(&contents))
+    }
+
+    /// Compare each manifest on disk with its latest log entry.
+    pub fn check() -> Result<Vec<(String, IntegrityProblem)>, String> {
+        let mut latest: HashMap<String, Option<String>> = HashMap::new();
+        for entry in Self::entries()? {
+            latest.insert(entry.package, entry.manifest_sha256);
+        }
+
+        let mut packages: Vec<String> = latest.keys().cloned().collect();
+        for package in installed_manifest_names()? {
+            if !latest.contains_key(&package) {
+                packages.push(package);
+            }
+        }
+        packages.sort();
+
+        let mut problems = Vec::new();
+        for package in packages {
+            let expected = latest.get(&package).cloned().flatten();
+            let problem = match (expected, manifest_sha256(&package)?) {
+                (Some(expected), Some(found)) if expected != found => {
+                    Some(IntegrityProblem::Modified { expected, found })
+                }
+                (Some(expected), None) => Some(IntegrityProblem::Missing { expected }),
+                (None, Some(_)) => Some(IntegrityProblem::Unrecorded),
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                problems.push((package, problem));
+            }
+        }
+        Ok(problems)
+    }
+}
+
+pub(crate) fn parse_entries(contents: &str) -> Vec<IntegrityEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let package = fields.next()?.to_string();
+            let hash = fields.next()?;
+            let timestamp = fields.next()?.parse().ok()?;
+            Some(IntegrityEntry {
+                package,
+                manifest_sha256: (hash != REMOVED).then(|| hash.to_string()),
+                timestamp,
+            })
+        })
+        .collect()
+}
+
+fn manifests_dir() -> Result<PathBuf, String> {
+    let mut path = utils::get_metadata_dir()?;
+    path.push("manifests");
+    Ok(path)
+}
+
+/// SHA-256 of the saved manifest bytes, or `None` if there is no manifest.
+pub fn manifest_sha256(package: &str) -> Result<Option<String>, String> {
+    let path = manifests_dir()?.join(format!("{}.yaml", package));
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bytes = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(Some(format!("{:x}", Sha256::digest(&bytes))))
+}
+
+fn installed_manifest_names() -> Result<Vec<String>, String> {
+    let dir = manifests_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Failed to read manifests directory: {}", e))? {
+        let path = entry.map_err(|e| format!("Failed to read entry: {}", e))?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("yaml")
+            && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+        {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
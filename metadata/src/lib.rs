@@ -15,27 +15,71 @@ pub mod yum_repository;
 pub mod performance;
 pub mod rpm_parser;
 pub mod repo_index;
+pub mod integrity;
+pub mod doctor;
+pub mod dep_graph;
+pub mod download;
+pub mod hooks;
+pub mod clean;
+pub mod solver;
+pub mod runtime;
+pub mod cancel;
+pub mod github;
 
 // Re-export commonly used types
 pub use utils::{DepVer, Specific};
-pub use installed::{InstalledMetaData, InstalledInstallKind};
-pub use processed::{ProcessedMetaData, ProcessedInstallKind, ProcessedCompilable, InstallPackage, QueuedChanges};
+pub use installed::{InstallReason, InstalledMetaData, InstalledInstallKind};
+pub use depend_kind::OptionalDependency;
+pub use processed::{ProcessedMetaData, ProcessedInstallKind, ProcessedCompilable, InstallPackage, QueuedChanges, HashCheck};
 pub use parsers::{MetaDataKind, pax::RawPax};
 pub use package_verification::PackageVerifier;
-pub use package_holds::PackageHoldManager;
+pub use repository_auth::{TrustStore, TrustedKey};
+pub use package_holds::{HoldType, PackageHoldManager};
+pub use hooks::{HookKind, run_hooks};
+pub use runtime::{block_on, runtime};
 pub use utils::get_metadata_dir as get_metadata_path;
 
 // Re-export commonly used functions
 pub use processed::{
-    get_packages, get_package_info, list_installed_packages,
-    get_local_deps, search_packages, collect_updates,
-    upgrade_all, upgrade_only, upgrade_packages, emancipate
+    get_packages, get_package_info, list_installed_packages, find_orphans, orphaned_by_removal,
+    installed_depends, installed_rdepends,
+    get_local_deps, search_packages, category_counts, collect_updates,
+    upgrade_all, upgrade_only, upgrade_packages, emancipate,
+    assume_installed, record_assumed_installed, forget_assumed_installed, is_assumed_installed, set_skip_dependencies, set_ignore_installed,
+    select_optional, OptionalSelection,
 };
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn package_with_deps(name: &str, runtime: &[&str], build: &[&str]) -> ProcessedMetaData {
+        use crate::depend_kind::DependKind;
+
+        ProcessedMetaData {
+            name: name.to_string(),
+            kind: MetaDataKind::Pax,
+            description: String::new(),
+            version: "1.0".to_string(),
+            origin: settings::OriginKind::Pax(String::new()),
+            dependent: false,
+            build_dependencies: build.iter().map(|x| DependKind::Latest(x.to_string())).collect(),
+            runtime_dependencies: runtime.iter().map(|x| DependKind::Latest(x.to_string())).collect(),
+            optional_dependencies: Vec::new(),
+            installed_size: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            install_kind: ProcessedInstallKind::PreBuilt(processed::PreBuilt { critical: Vec::new(), configs: Vec::new() }),
+            hash: String::new(),
+            package_type: String::new(),
+            installed: false,
+            dependencies: Vec::new(),
+            dependents: Vec::new(),
+            installed_files: Vec::new(),
+            available_versions: Vec::new(),
+        }
+    }
+
     #[test]
     fn test_package_verification_sha256() {
         // Test SHA256 hash verification
@@ -72,4 +116,1733 @@ mod tests {
         // Just check that it doesn't panic
         let _ = v2;
     }
+
+    #[test]
+    fn test_file_index_lookup() {
+        use crate::file_tracking::FileManifest;
+        use crate::performance::FileIndex;
+        use std::path::{Path, PathBuf};
+
+        let mut foo = FileManifest::new("foo".to_string(), "1.0.0".to_string());
+        foo.add_file(PathBuf::from("/usr/bin/foo"), 0, 0o755, String::new());
+        let mut bar = FileManifest::new("bar".to_string(), "1.0.0".to_string());
+        bar.add_file(PathBuf::from("/usr/bin/bar"), 0, 0o755, String::new());
+
+        let mut index = FileIndex::from_manifests(&[foo, bar.clone()]);
+        assert_eq!(index.lookup(Path::new("/usr/bin/foo")), Some("foo"));
+        assert_eq!(index.lookup(Path::new("/usr/bin/bar")), Some("bar"));
+        assert_eq!(index.lookup(Path::new("/usr/bin/baz")), None);
+
+        // Updating a package replaces its old paths
+        bar.files.clear();
+        bar.add_file(PathBuf::from("/usr/bin/bar2"), 0, 0o755, String::new());
+        index.update_manifest(&bar);
+        assert_eq!(index.lookup(Path::new("/usr/bin/bar")), None);
+        assert_eq!(index.lookup(Path::new("/usr/bin/bar2")), Some("bar"));
+
+        index.remove_package("foo");
+        assert_eq!(index.lookup(Path::new("/usr/bin/foo")), None);
+
+        // A directory shared by two packages keeps both owners until both are gone
+        let mut baz = FileManifest::new("baz".to_string(), "1.0.0".to_string());
+        baz.add_directory(PathBuf::from("/usr/share/common"), 0o755);
+        bar.add_directory(PathBuf::from("/usr/share/common"), 0o755);
+        index.update_manifest(&bar);
+        index.update_manifest(&baz);
+        let shared = Path::new("/usr/share/common");
+        assert_eq!(index.owners(shared).collect::<Vec<_>>(), vec!["bar", "baz"]);
+        index.remove_package("bar");
+        assert_eq!(index.owners(shared).collect::<Vec<_>>(), vec!["baz"]);
+        index.remove_package("baz");
+        assert_eq!(index.lookup(shared), None);
+    }
+
+    #[test]
+    fn test_queued_changes_plan() {
+        let mut plan = QueuedChanges::new();
+        assert!(plan.is_empty());
+        plan.queue_install("a", "1.0");
+        plan.queue_install("b", "2.1");
+        plan.queue_upgrade("c", "1.0", "1.2");
+        plan.queue_remove("d");
+        plan.download_sizes.insert("a".to_string(), 1024);
+
+        let rendered = plan.render_plan();
+        assert!(rendered.contains("Installing (2): \x1B[92ma(1.0), b(2.1)"));
+        assert!(rendered.contains("Upgrading (1): \x1B[94mc(1.0->1.2)"));
+        assert!(rendered.contains("Removing (1): \x1B[91md"));
+        assert!(rendered.contains("Total download size: at least 1.0 KiB"));
+        assert_eq!(plan.download_size(), (1024, true));
+    }
+
+    #[test]
+    fn test_integrity_log_entries() {
+        let entries = crate::integrity::parse_entries("foo\tabc\t10\nbar\t-\t11\nbroken line\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].manifest_sha256.as_deref(), Some("abc"));
+        assert_eq!(entries[1].manifest_sha256, None);
+        assert_eq!(entries[1].timestamp, 11);
+    }
+
+    #[test]
+    fn test_partial_upgrade_vs_all() {
+        use crate::processed::{plan_partial_upgrade, UpgradeCandidate};
+        use std::collections::BTreeMap;
+        use utils::{Range, VerReq, Version};
+
+        let candidate = |version: &str, deps: Vec<DepVer>| UpgradeCandidate {
+            version: version.to_string(),
+            dependencies: deps,
+        };
+        let needs = |name: &str, lower: VerReq, upper: VerReq| DepVer {
+            name: name.to_string(),
+            range: Range { lower, upper },
+        };
+        let v = |s: &str| Version::parse(s).unwrap();
+
+        let mut installed = BTreeMap::new();
+        installed.insert("foo".to_string(), candidate("1.0", vec![]));
+        installed.insert("bar".to_string(), candidate("1.0", vec![]));
+        installed.insert("baz".to_string(), candidate("1.0", vec![]));
+        let mut available = BTreeMap::new();
+        available.insert(
+            "foo".to_string(),
+            candidate("2.0", vec![needs("baz", VerReq::Ge(v("2.0")), VerReq::NoBound)]),
+        );
+        available.insert("bar".to_string(), candidate("2.0", vec![]));
+        available.insert("baz".to_string(), candidate("2.0", vec![]));
+
+        // A subset pulls in only the dependency its new constraint requires
+        let subset = plan_partial_upgrade(&["foo".to_string()], &installed, &available).unwrap();
+        assert_eq!(subset.upgrade, vec!["baz", "foo"]);
+
+        // Naming everything upgrades everything
+        let all: Vec<String> = installed.keys().cloned().collect();
+        let everything = plan_partial_upgrade(&all, &installed, &available).unwrap();
+        assert_eq!(everything.upgrade, vec!["bar", "baz", "foo"]);
+
+        // An installed dependent pinned below the new version blocks the upgrade
+        installed.insert(
+            "qux".to_string(),
+            candidate("1.0", vec![needs("baz", VerReq::NoBound, VerReq::Lt(v("2.0")))]),
+        );
+        assert!(plan_partial_upgrade(&["foo".to_string()], &installed, &available).is_err());
+        assert!(plan_partial_upgrade(&["bar".to_string()], &installed, &available).is_ok());
+    }
+
+    #[test]
+    fn test_update_held_back_by_dependent() {
+        use crate::processed::{classify_updates, UpdateClass, UpgradeCandidate};
+        use std::collections::{BTreeMap, HashSet};
+        use utils::{Range, VerReq, Version};
+
+        let candidate = |version: &str, deps: Vec<DepVer>| UpgradeCandidate {
+            version: version.to_string(),
+            dependencies: deps,
+        };
+        let old_a = DepVer {
+            name: "a".to_string(),
+            range: Range { lower: VerReq::NoBound, upper: VerReq::Lt(Version::parse("2.0").unwrap()) },
+        };
+
+        let mut installed = BTreeMap::new();
+        installed.insert("a".to_string(), candidate("1.0", vec![]));
+        installed.insert("b".to_string(), candidate("1.0", vec![old_a]));
+        installed.insert("c".to_string(), candidate("1.0", vec![]));
+        installed.insert("d".to_string(), candidate("1.0", vec![]));
+        let mut available = BTreeMap::new();
+        available.insert("a".to_string(), candidate("2.0", vec![]));
+        available.insert("c".to_string(), candidate("1.1", vec![]));
+        available.insert("d".to_string(), candidate("1.1", vec![]));
+        let held: HashSet<String> = ["d".to_string()].into();
+
+        let classes = classify_updates(&installed, &available, &held);
+        assert!(matches!(classes["a"], UpdateClass::HeldBack(ref why) if why.contains('b')));
+        assert_eq!(classes["c"], UpdateClass::Upgradable);
+        assert_eq!(classes["d"], UpdateClass::Held);
+        assert!(!classes.contains_key("b"));
+    }
+
+    /// Held by tests that read or set `PAX_ROOT`, which is process-wide.
+    fn root_lock() -> std::sync::MutexGuard<'static, ()> {
+        static ROOT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        ROOT_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run `body` with `PAX_ROOT` pointing at a fresh directory. Installs
+    /// read the root from the environment, so these tests take turns.
+    fn with_install_root(body: impl FnOnce(&std::path::Path)) {
+        let _guard = root_lock();
+        let root = tempfile::tempdir().unwrap();
+        unsafe { std::env::set_var("PAX_ROOT", root.path()) };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| body(root.path())));
+        unsafe { std::env::remove_var("PAX_ROOT") };
+        if let Err(panic) = result {
+            std::panic::resume_unwind(panic);
+        }
+    }
+
+    /// A .pax archive at `dir/<name>.pax` holding `files`, and the package
+    /// pointing at it.
+    fn local_pax(dir: &std::path::Path, name: &str, files: &[&str]) -> ProcessedMetaData {
+        local_pax_with_manifest(dir, name, files, None)
+    }
+
+    /// Like `local_pax`, with `manifest` embedded as the archive's manifest.yaml.
+    fn local_pax_with_manifest(dir: &std::path::Path, name: &str, files: &[&str], manifest: Option<&str>) -> ProcessedMetaData {
+        let payload = dir.join(format!("{}-payload", name));
+        for file in files {
+            let path = payload.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, name).unwrap();
+        }
+        if let Some(manifest) = manifest {
+            std::fs::create_dir_all(&payload).unwrap();
+            std::fs::write(payload.join("manifest.yaml"), manifest).unwrap();
+        }
+        let archive = dir.join(format!("{}.pax", name));
+        let status = std::process::Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&payload)
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let mut package = package_with_deps(name, &[], &[]);
+        package.origin = settings::OriginKind::Pax(archive.to_string_lossy().to_string());
+        package
+    }
+
+    #[test]
+    fn test_only_dependencies_skips_package() {
+        let names = |targets: Vec<&ProcessedMetaData>| targets.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
+        let plan = InstallPackage {
+            metadata: package_with_deps("app", &["libfoo"], &["gcc"]),
+            run_deps: vec![package_with_deps("libfoo", &[], &[])],
+            build_deps: vec![package_with_deps("gcc", &[], &[])],
+        };
+        assert_eq!(names(plan.dependency_targets(false)), vec!["libfoo"]);
+        assert_eq!(names(plan.dependency_targets(true)), vec!["libfoo", "gcc"]);
+
+        with_install_root(|root| {
+            let packages = tempfile::tempdir().unwrap();
+            let install = InstallPackage {
+                metadata: local_pax(packages.path(), "app", &["usr/share/app/data"]),
+                run_deps: vec![local_pax(packages.path(), "libfoo", &["usr/share/libfoo/data"])],
+                build_deps: vec![local_pax(packages.path(), "gcc", &["usr/share/gcc/data"])],
+            };
+            install.install_dependencies(false, false, true).unwrap();
+
+            // The dependency is placed and tracked, the package and build deps are not
+            assert!(root.join("usr/share/libfoo/data").is_file());
+            assert!(!root.join("usr/share/app").exists());
+            assert!(!root.join("usr/share/gcc").exists());
+            let manifest = file_tracking::FileManifest::load("libfoo").unwrap();
+            assert!(manifest.owns(std::path::Path::new("/usr/share/libfoo/data")));
+            assert!(file_tracking::FileManifest::load("app").is_err());
+
+            // Recorded as an automatic install with no parent, so it can be reclaimed
+            let libfoo = InstalledMetaData::open("libfoo").unwrap();
+            assert_eq!(libfoo.installed_files, vec!["/usr/share/libfoo/data"]);
+            assert_eq!(libfoo.installed_by, None);
+            assert_eq!(libfoo.reason(), InstallReason::Auto);
+            assert!(InstalledMetaData::open("app").is_err());
+            let installed = vec![libfoo];
+            assert_eq!(find_orphans(&installed).len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_requesting_installed_dependency_marks_it_manual() {
+        with_install_root(|_| {
+            let packages = tempfile::tempdir().unwrap();
+            let install = InstallPackage {
+                metadata: local_pax(packages.path(), "app", &["usr/share/app/data"]),
+                run_deps: vec![local_pax(packages.path(), "libfoo", &["usr/share/libfoo/data"])],
+                build_deps: Vec::new(),
+            };
+            install.install_dependencies(false, false, true).unwrap();
+            let libfoo = InstalledMetaData::open("libfoo").unwrap();
+            assert_eq!(libfoo.reason(), InstallReason::Auto);
+
+            // `pax install libfoo` finds it installed and keeps it from being reclaimed
+            assert!(libfoo.mark_requested().unwrap());
+            let libfoo = InstalledMetaData::open("libfoo").unwrap();
+            assert_eq!(libfoo.reason(), InstallReason::Manual);
+            assert!(find_orphans(&[libfoo.clone()]).is_empty());
+            assert!(!libfoo.mark_requested().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_apt_pool_path() {
+        use crate::deb_repository::pool_path;
+
+        assert_eq!(pool_path("curl", "8.5.0-2", "amd64"), "pool/main/c/curl/curl_8.5.0-2_amd64.deb");
+        assert_eq!(pool_path("libssl3", "3.0.11-1", "arm64"), "pool/main/libs/libssl3/libssl3_3.0.11-1_arm64.deb");
+        assert_eq!(pool_path("LibFoo", "1:2.0", "amd64"), "pool/main/libf/LibFoo/LibFoo_2.0_amd64.deb");
+        assert_eq!(pool_path("lib", "1.0", "all"), "pool/main/l/lib/lib_1.0_all.deb");
+    }
+
+    #[test]
+    fn test_versions_from_index() {
+        let index = r#"<html><body>
+            <a href="zlib-1.2.13-1-x86_64v3.pax">zlib-1.2.13-1-x86_64v3.pax</a>
+            <a href="zlib-1.3.1-2-x86_64v3.pax">zlib-1.3.1-2-x86_64v3.pax</a>
+            <a href="zlib-1.3.1-1-x86_64v1.pax">zlib-1.3.1-1-x86_64v1.pax</a>
+            <a href="sub/zlib-1.10.0-1-x86_64v3.pax">zlib-1.10.0-1-x86_64v3.pax</a>
+            <a href="zlib-1.3.1-1.src.pax">source</a>
+            <a href="zstd-1.5.5-1-x86_64v3.pax">zstd</a>
+        </body></html>"#;
+        assert_eq!(
+            ProcessedMetaData::versions_from_index(index, "zlib"),
+            vec!["1.10.0", "1.3.1", "1.2.13"]
+        );
+    }
+
+    #[test]
+    fn test_check_requested_version() {
+        let mut package = package_with_deps("zlib", &[], &[]);
+        // Nothing listed means the origin didn't say, so anything goes
+        assert!(package.check_requested_version("9.9").is_ok());
+
+        package.available_versions = vec!["1.3.1".to_string(), "1.2.13".to_string()];
+        assert!(package.check_requested_version("1.3.1").is_ok());
+        assert!(package.check_requested_version("v1.2.13").is_ok());
+        let fault = package.check_requested_version("1.4.0").unwrap_err();
+        assert!(fault.contains("1.4.0") && fault.contains("1.3.1, 1.2.13"));
+    }
+
+    #[test]
+    fn test_metadata_memo_fetches_once() {
+        use crate::processed::{MetadataMemo, PreBuilt};
+        use settings::OriginKind;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let fetches = AtomicUsize::new(0);
+        let sources = vec![OriginKind::Pax("https://repo.example/pax".to_string())];
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(ProcessedMetaData {
+                name: "zlib".to_string(),
+                kind: MetaDataKind::Pax,
+                description: String::new(),
+                version: "1.3.1".to_string(),
+                origin: sources[0].clone(),
+                dependent: true,
+                build_dependencies: Vec::new(),
+                runtime_dependencies: Vec::new(),
+                optional_dependencies: Vec::new(),
+                installed_size: None,
+                keywords: Vec::new(),
+                categories: Vec::new(),
+                install_kind: ProcessedInstallKind::PreBuilt(PreBuilt { critical: Vec::new(), configs: Vec::new() }),
+                hash: String::new(),
+                package_type: String::new(),
+                installed: false,
+                dependencies: Vec::new(),
+                dependents: Vec::new(),
+                installed_files: Vec::new(),
+                available_versions: Vec::new(),
+            }))
+        };
+
+        let memo = MetadataMemo::default();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            // A diamond: two packages both depend on zlib
+            memo.get_or_fetch(MetadataMemo::key("zlib", None, &sources), fetch).await.unwrap().unwrap();
+            memo.get_or_fetch(MetadataMemo::key("zlib", None, &sources), fetch).await.unwrap().unwrap();
+            assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+            // A different version or source set is a different entry
+            memo.get_or_fetch(MetadataMemo::key("zlib", Some("1.2.13"), &sources), fetch).await.unwrap();
+            memo.get_or_fetch(MetadataMemo::key("zlib", None, &[]), fetch).await.unwrap();
+            assert_eq!(fetches.load(Ordering::SeqCst), 3);
+
+            // A failed lookup, such as an exhausted GitHub rate limit, reaches the caller
+            let fault = memo
+                .get_or_fetch(MetadataMemo::key("openssl", None, &sources), || async {
+                    Err(String::from("GitHub API rate limit exceeded"))
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(fault, "GitHub API rate limit exceeded");
+        });
+    }
+
+    #[test]
+    fn test_package_hash_check() {
+        use crate::processed::{check_package_hash, payload_sha256};
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let extract = dir.path().join("extract");
+        fs::create_dir_all(extract.join("usr/bin")).unwrap();
+        fs::write(extract.join("usr/bin/tool"), b"payload").unwrap();
+        fs::write(extract.join("manifest.yaml"), b"hash: anything").unwrap();
+        let archive = dir.path().join("tool.pax");
+        fs::write(&archive, b"archive bytes").unwrap();
+
+        // The embedded manifest is not part of the payload hash
+        let payload = payload_sha256(&extract).unwrap();
+        fs::write(extract.join("manifest.yaml"), format!("hash: {}", payload)).unwrap();
+        assert_eq!(payload_sha256(&extract).unwrap(), payload);
+
+        // A matching hash lets the install proceed
+        assert_eq!(check_package_hash(&payload, &archive, &extract).unwrap(), HashCheck::Verified);
+        let archive_hash = crate::file_tracking::calculate_file_checksum(&archive).unwrap();
+        assert_eq!(check_package_hash(&archive_hash, &archive, &extract).unwrap(), HashCheck::Verified);
+
+        // A tampered payload no longer matches and aborts it
+        fs::write(extract.join("usr/bin/tool"), b"tampered").unwrap();
+        assert!(matches!(
+            check_package_hash(&payload, &archive, &extract).unwrap(),
+            HashCheck::Mismatch { expected, .. } if expected == payload
+        ));
+
+        // Legacy placeholders only warn
+        for placeholder in ["", "unknown", "0000000000000000"] {
+            assert_eq!(check_package_hash(placeholder, &archive, &extract).unwrap(), HashCheck::Placeholder);
+        }
+    }
+
+    #[test]
+    fn test_install_verifies_embedded_manifest_archive() {
+        use crate::processed::payload_sha256;
+
+        with_install_root(|root| {
+            let packages = tempfile::tempdir().unwrap();
+
+            // The embedded manifest carries the payload hash, which leaves the manifest out
+            let staged = tempfile::tempdir().unwrap();
+            std::fs::create_dir_all(staged.path().join("usr/bin")).unwrap();
+            std::fs::write(staged.path().join("usr/bin/tool"), "tool").unwrap();
+            let payload = payload_sha256(staged.path()).unwrap();
+            let manifest = format!("name: tool\nversion: '1.0'\nhash: {}\n", payload);
+            let mut tool = local_pax_with_manifest(packages.path(), "tool", &["usr/bin/tool"], Some(&manifest));
+            tool.hash = payload.clone();
+            tool.install_with(false, true).unwrap();
+            assert!(root.join("usr/bin/tool").is_file());
+
+            // A tampered payload under the same hash is rejected
+            let manifest = format!("name: bad\nversion: '1.0'\nhash: {}\n", payload);
+            let mut bad = local_pax_with_manifest(packages.path(), "bad", &["usr/bin/bad"], Some(&manifest));
+            bad.hash = payload;
+            assert!(bad.install_with(false, true).unwrap_err().contains("Hash mismatch"));
+            assert!(!root.join("usr/bin/bad").exists());
+        });
+    }
+
+    #[test]
+    fn test_conflicts_stay_inside_install_root() {
+        with_install_root(|root| {
+            let packages = tempfile::tempdir().unwrap();
+            let first = local_pax(packages.path(), "first", &["usr/share/shared/data"]);
+            first.install_with(false, true).unwrap();
+
+            // Tests run without a terminal, so the conflict is backed up and overwritten
+            let second = local_pax(packages.path(), "second", &["usr/share/shared/data"]);
+            second.install_with(false, true).unwrap();
+            assert_eq!(std::fs::read_to_string(root.join("usr/share/shared/data")).unwrap(), "second");
+
+            let manifest = file_tracking::FileManifest::load("second").unwrap();
+            let backup = manifest.files[0].backup_path.as_ref().unwrap();
+            assert!(backup.starts_with(root));
+            assert_eq!(std::fs::read_to_string(backup).unwrap(), "first");
+        });
+    }
+
+    #[test]
+    fn test_file_url_matches_local_dir() {
+        use settings::OriginKind;
+        use std::fs;
+        use std::process::Command;
+
+        let dir = tempfile::tempdir().unwrap();
+        let stage = dir.path().join("stage");
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(stage.join("usr/bin")).unwrap();
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(stage.join("usr/bin/hello"), b"#!/bin/sh\n").unwrap();
+        fs::write(
+            stage.join("manifest.yaml"),
+            "name: hello\ndescription: test\nversion: 1.0.0\norigin: local\nbuild: ''\ninstall: ''\nuninstall: ''\npurge: ''\nhash: unknown\n",
+        )
+        .unwrap();
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(repo.join("hello-1.0.0.pax"))
+            .arg("-C")
+            .arg(&stage)
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let path = repo.to_string_lossy().to_string();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let resolve = |source: OriginKind| {
+            runtime.block_on(ProcessedMetaData::get_metadata("hello", None, &[source], false)).unwrap()
+        };
+        let local = resolve(OriginKind::LocalDir(path.clone())).expect("found via LocalDir");
+        for url in [format!("file://{}", path), format!("file://localhost{}", path)] {
+            let found = resolve(OriginKind::Pax(url)).expect("found via file:// URL");
+            assert_eq!((found.name, found.version, found.origin), (local.name.clone(), local.version.clone(), local.origin.clone()));
+        }
+    }
+
+    #[test]
+    fn test_dependency_graph_formats() {
+        use crate::dep_graph::DependencyGraph;
+
+        let package = package_with_deps;
+        let install = InstallPackage {
+            metadata: package("app", &["libfoo", "libbar"], &["gcc"]),
+            run_deps: vec![package("libfoo", &["libc"], &[]), package("libbar", &["libc"], &[]), package("libc", &[], &[])],
+            build_deps: vec![package("gcc", &[], &[])],
+        };
+
+        let graph = DependencyGraph::from_packages(std::slice::from_ref(&install), true);
+        let dot = graph.to_dot();
+        assert!(dot.contains("\"app\" -> \"libfoo\";"));
+        assert!(dot.contains("\"libbar\" -> \"libc\";"));
+        assert!(dot.contains("\"app\" -> \"gcc\" [style=dashed];"));
+
+        assert_eq!(
+            graph.to_tree(),
+            "app 1.0\n  gcc 1.0 [build]\n  libbar 1.0\n    libc 1.0\n  libfoo 1.0\n    libc 1.0\n"
+        );
+
+        // Without --include-build there is no build edge
+        let runtime_only = DependencyGraph::from_packages(std::slice::from_ref(&install), false);
+        assert!(!runtime_only.to_dot().contains("gcc"));
+    }
+
+    #[test]
+    fn test_mirror_failover() {
+        use crate::download::{fetch_with_failover, DownloadFailure};
+        use settings::{mirror_candidates, parse_mirror_list};
+
+        let mirrors = parse_mirror_list("# primary first\nhttps://down.example/oreon/\n\nhttps://up.example/pub/oreon\n");
+        let url = "https://down.example/oreon/x86_64v3/foo-1.0.pax";
+        let candidates = mirror_candidates(url, &mirrors, None);
+        assert_eq!(
+            candidates.iter().map(|(_, url)| url.as_str()).collect::<Vec<_>>(),
+            vec![url, "https://up.example/pub/oreon/x86_64v3/foo-1.0.pax"]
+        );
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (mirror, body) = runtime
+            .block_on(fetch_with_failover(candidates, |url: String| async move {
+                if url.starts_with("https://down.example") {
+                    Err(DownloadFailure::Retry(String::from("connection refused")))
+                } else {
+                    Ok(url)
+                }
+            }))
+            .unwrap();
+        assert_eq!(mirror.base, "https://up.example/pub/oreon");
+        assert_eq!(body, "https://up.example/pub/oreon/x86_64v3/foo-1.0.pax");
+
+        // The mirror that worked is tried first next time
+        let next = mirror_candidates(url, &mirrors, Some(&mirror.base));
+        assert_eq!(next[0].0, mirror);
+
+        // URLs outside the mirror list get no failover
+        assert!(mirror_candidates("https://other.example/foo.pax", &mirrors, None).is_empty());
+    }
+
+    #[test]
+    fn test_download_throttle() {
+        use crate::download::Throttle;
+        use std::time::Duration;
+
+        // 2 KiB at 1 KiB/s is about two seconds ahead of schedule
+        let mut throttle = Throttle::new(1024);
+        let delay = throttle.delay_for(2048);
+        assert!(delay > Duration::from_millis(1900) && delay <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_download_streams_to_disk() {
+        use crate::download::write_stream;
+        use futures::StreamExt;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A chunk that counts how many of its kind are alive at once
+        struct Chunk(Vec<u8>, Arc<AtomicUsize>);
+        impl AsRef<[u8]> for Chunk {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+        impl Drop for Chunk {
+            fn drop(&mut self) {
+                self.1.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        const CHUNK: usize = 64 * 1024;
+        const CHUNKS: usize = 256;
+        let live = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (live_in, peak_in) = (live.clone(), peak.clone());
+        let stream = futures::stream::iter(0..CHUNKS).map(move |_| {
+            let now = live_in.fetch_add(1, Ordering::SeqCst) + 1;
+            peak_in.fetch_max(now, Ordering::SeqCst);
+            Ok::<_, std::io::Error>(Chunk(vec![7; CHUNK], live_in.clone()))
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.pax");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let written = runtime.block_on(write_stream(stream, &path, None, None)).unwrap();
+
+        assert_eq!(written, (CHUNK * CHUNKS) as u64);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), written);
+        // 16 MiB went through, but never more than one chunk was held
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_conflict_prompt_answers() {
+        use crate::conflict_resolution::ConflictPrompt;
+        use crate::file_tracking::{ConflictType, FileConflict};
+        use std::path::PathBuf;
+
+        let conflict = |path: &str, conflict_type: ConflictType| FileConflict {
+            path: PathBuf::from(path),
+            existing_owner: String::from("other"),
+            new_package: String::from("foo"),
+            conflict_type,
+        };
+        let conflicts = vec![
+            conflict("/usr/bin/a", ConflictType::FileOwnership),
+            conflict("/usr/share", ConflictType::DirectoryOwnership),
+            conflict("/usr/bin/b", ConflictType::UntrackedFile),
+            conflict("/usr/bin/c", ConflictType::SymlinkOwnership),
+            conflict("/usr/bin/d", ConflictType::FileOwnership),
+        ];
+
+        // Unknown answers are asked again; directories are never asked about
+        let mut prompt = ConflictPrompt::new();
+        let mut output = Vec::new();
+        let keep = prompt
+            .resolve(&conflicts, &mut std::io::Cursor::new("k\nwhat\no\n\no\n"), &mut output)
+            .unwrap();
+        assert_eq!(keep, vec![PathBuf::from("/usr/bin/a"), PathBuf::from("/usr/bin/c")]);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("would replace").count(), 5);
+        assert!(!output.contains("/usr/share"));
+        assert!(output.contains("not tracked by any package"));
+
+        // "Overwrite all" holds for the rest of the transaction
+        let mut prompt = ConflictPrompt::new();
+        let keep = prompt
+            .resolve(&conflicts, &mut std::io::Cursor::new("k\na\n"), &mut std::io::sink())
+            .unwrap();
+        assert_eq!(keep, vec![PathBuf::from("/usr/bin/a")]);
+        let later = prompt.resolve(&conflicts, &mut std::io::Cursor::new(""), &mut std::io::sink());
+        assert_eq!(later.unwrap(), Vec::<PathBuf>::new());
+
+        let mut prompt = ConflictPrompt::new();
+        let aborted = prompt.resolve(&conflicts, &mut std::io::Cursor::new("o\nx\n"), &mut std::io::sink());
+        assert!(aborted.is_err());
+        let unanswered = ConflictPrompt::new().resolve(&conflicts, &mut std::io::Cursor::new(""), &mut std::io::sink());
+        assert!(unanswered.is_err());
+    }
+
+    #[test]
+    fn test_shared_directory_survives_removal() {
+        use crate::file_tracking::FileManifest;
+        use std::path::{Path, PathBuf};
+
+        let mut first = FileManifest::new(String::from("first"), String::from("1.0"));
+        first.add_directory(PathBuf::from("/usr/share/applications"), 0o755);
+        first.add_directory(PathBuf::from("/usr/share/first"), 0o755);
+        let mut second = FileManifest::new(String::from("second"), String::from("1.0"));
+        second.add_directory(PathBuf::from("/usr/share/applications"), 0o755);
+
+        let installed = vec![first.clone(), second.clone()];
+        assert_eq!(first.removable_directories(&installed), vec![Path::new("/usr/share/first")]);
+        assert!(second.removable_directories(&installed).is_empty());
+
+        // Once the other package is gone its directories are fair game
+        assert_eq!(
+            second.removable_directories(std::slice::from_ref(&second)),
+            vec![Path::new("/usr/share/applications")]
+        );
+    }
+
+    #[test]
+    fn test_assumed_dependency_not_fetched() {
+        let package = package_with_deps("needs-assumed", &["assumed-by-test"], &["assumed-build-by-test"]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // With no sources to search, resolving either dependency would fail
+        let mut prior = std::collections::HashSet::new();
+        assert!(runtime.block_on(package.get_depends(&[], &mut prior)).is_err());
+
+        assume_installed(&[String::from("assumed-by-test"), String::from("assumed-build-by-test")]);
+        let resolved = runtime.block_on(package.get_depends(&[], &mut prior)).unwrap();
+        assert!(resolved.run_deps.is_empty());
+        assert!(resolved.build_deps.is_empty());
+    }
+
+    #[test]
+    fn test_assumed_installed_is_recorded_and_cleared() {
+        with_install_root(|_| {
+            let names = |list: &[&str]| list.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+            record_assumed_installed(&names(&["recorded-bar", "recorded-baz"])).unwrap();
+            assert_eq!(processed::recorded_assumed_installed().unwrap(), names(&["recorded-bar", "recorded-baz"]));
+
+            assume_installed(&names(&["recorded-bar"]));
+            assert!(is_assumed_installed("recorded-bar"));
+            assert_eq!(forget_assumed_installed(&names(&["recorded-bar", "never-recorded"])).unwrap(), names(&["recorded-bar"]));
+            assert!(!is_assumed_installed("recorded-bar"));
+            assert_eq!(processed::recorded_assumed_installed().unwrap(), names(&["recorded-baz"]));
+
+            forget_assumed_installed(&names(&["recorded-baz"])).unwrap();
+            assert!(processed::recorded_assumed_installed().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_no_deps_skips_resolution() {
+        let package = package_with_deps("needs-nothing", &["never-resolved"], &["never-built"]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut prior = std::collections::HashSet::new();
+
+        set_skip_dependencies(true);
+        let resolved = runtime.block_on(package.get_depends(&[], &mut prior));
+        set_skip_dependencies(false);
+        let resolved = resolved.unwrap();
+        assert_eq!(resolved.metadata.name, "needs-nothing");
+        assert!(resolved.run_deps.is_empty());
+        assert!(resolved.build_deps.is_empty());
+        assert!(resolved.dependency_targets(true).is_empty());
+    }
+
+    #[test]
+    fn test_optional_dependencies_need_selecting() {
+        use crate::depend_kind::{DependKind, OptionalDependency};
+
+        let mut package = package_with_deps("has-extras", &[], &[]);
+        package.optional_dependencies = ["extra-one", "extra-two"]
+            .iter()
+            .map(|name| OptionalDependency {
+                dependency: DependKind::Latest(name.to_string()),
+                reason: Some(String::from("nicer output")),
+            })
+            .collect();
+
+        // Unselected optionals are neither picked nor required to resolve
+        assert!(package.selected_optional(&OptionalSelection::default()).is_empty());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut prior = std::collections::HashSet::new();
+        let resolved = runtime.block_on(package.get_depends(&[], &mut prior)).unwrap();
+        assert!(resolved.run_deps.is_empty());
+
+        let all = OptionalSelection { all: true, ..Default::default() };
+        assert_eq!(package.selected_optional(&all).len(), 2);
+
+        let mut picked = OptionalSelection::default();
+        picked.add_picks("has-extras:extra-two, other:extra-one").unwrap();
+        let selected = package.selected_optional(&picked);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].to_string(), "extra-two: nicer output");
+        assert!(picked.add_picks("no-colon").is_err());
+    }
+
+    #[test]
+    fn test_selected_optionals_resolve_and_install() {
+        use crate::depend_kind::{DependKind, OptionalDependency};
+        use settings::OriginKind;
+
+        with_install_root(|root| {
+            let bundle = tempfile::tempdir().unwrap();
+            let manifest = |name: &str| {
+                format!(
+                    "name: {}\ndescription: test\nversion: 1.0.0\norigin: local\nruntime_dependencies: []\nbuild: ''\ninstall: ''\nuninstall: ''\npurge: ''\nhash: unknown\n",
+                    name
+                )
+            };
+            let mut app = local_pax_with_manifest(bundle.path(), "app", &["usr/share/app/data"], Some(&manifest("app")));
+            local_pax_with_manifest(bundle.path(), "extra", &["usr/share/extra/data"], Some(&manifest("extra")));
+            // Named the way `--from-dir` bundles are
+            std::fs::rename(bundle.path().join("extra.pax"), bundle.path().join("extra-1.0.0.pax")).unwrap();
+            app.optional_dependencies = vec![OptionalDependency {
+                dependency: DependKind::Latest(String::from("extra")),
+                reason: None,
+            }];
+            let sources = [OriginKind::LocalDir(bundle.path().display().to_string())];
+
+            // Without the flag the optional isn't resolved
+            let plain = block_on(processed::with_selected_optionals(&app, &OptionalSelection::default(), &sources)).unwrap().unwrap();
+            assert!(plain.runtime_dependencies.is_empty());
+
+            let all = OptionalSelection { all: true, ..Default::default() };
+            let root_package = block_on(processed::with_selected_optionals(&app, &all, &sources)).unwrap().unwrap();
+            let run_deps = block_on(processed::resolve_all_dependencies(&root_package, &sources)).unwrap().unwrap();
+            assert_eq!(run_deps.iter().map(|dep| dep.name.as_str()).collect::<Vec<_>>(), vec!["extra"]);
+            // The package itself still lists no hard dependencies
+            assert!(app.runtime_dependencies.is_empty());
+
+            let install = InstallPackage { metadata: app, run_deps, build_deps: Vec::new() };
+            install.install_with(false, true).unwrap();
+            assert!(root.join("usr/share/extra/data").is_file());
+            assert!(root.join("usr/share/app/data").is_file());
+            assert!(InstalledMetaData::open("app").unwrap().dependencies.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_find_orphans() {
+        let installed = |name: &str, dependent: bool, deps: &[&str]| {
+            let mut package = package_with_deps(name, deps, &[]).to_installed();
+            package.set_reason(if dependent { InstallReason::Auto } else { InstallReason::Manual });
+            package
+        };
+        let packages = vec![
+            installed("app", false, &["libneeded"]),
+            installed("libneeded", true, &[]),
+            installed("libleftover", true, &[]),
+            installed("standalone", false, &[]),
+        ];
+        let orphans: Vec<&str> = find_orphans(&packages).iter().map(|package| package.name.as_str()).collect();
+        assert_eq!(orphans, vec!["libleftover"]);
+    }
+
+    #[test]
+    fn test_no_deps_remove_leaves_dependencies() {
+        let installed = |name: &str, dependent: bool, deps: &[&str], installed_by: Option<&str>| {
+            let mut package = package_with_deps(name, deps, &[]).to_installed();
+            package.set_reason(if dependent { InstallReason::Auto } else { InstallReason::Manual });
+            package.installed_by = installed_by.map(str::to_string);
+            package
+        };
+        let packages = vec![
+            installed("app", false, &["libfoo", "libshared", "openssl"], None),
+            installed("libfoo", true, &[], None),
+            installed("libshared", true, &[], None),
+            installed("openssl", false, &[], None),
+            installed("helper", true, &[], Some("app")),
+            installed("other", false, &["libshared"], None),
+        ];
+        let removed = vec![String::from("app")];
+        let removed_deps: std::collections::HashSet<String> =
+            ["libfoo", "libshared", "openssl"].iter().map(|name| name.to_string()).collect();
+
+        // Dependencies nothing else needs are offered, manual and shared ones are not
+        assert_eq!(orphaned_by_removal(&packages, &removed, &removed_deps, false), vec!["helper", "libfoo"]);
+        // --no-deps leaves the dependency graph alone
+        assert!(orphaned_by_removal(&packages, &removed, &removed_deps, true).is_empty());
+    }
+
+    #[test]
+    fn test_installed_size_from_manifest() {
+        let fixture = r#"
+package_name: sized
+package_version: "1.0"
+files:
+  - path: /usr/bin/sized
+    size: 40960
+    permissions: 493
+    checksum: abc
+    backup_path: null
+  - path: /usr/share/sized/data
+    size: 2048
+    permissions: 420
+    checksum: def
+    backup_path: null
+directories:
+  - path: /usr/share/sized
+    permissions: 493
+symlinks:
+  - path: /usr/bin/sized-alias
+    target: /usr/bin/sized
+installed_at: 0
+"#;
+        let manifest: file_tracking::FileManifest = serde_norway::from_str(fixture).unwrap();
+        assert_eq!(manifest.installed_size(), 43008);
+
+
+        // A real install records the payload's size: two files holding "sized"
+        with_install_root(|_| {
+            let packages = tempfile::tempdir().unwrap();
+            let sized = local_pax(packages.path(), "sized", &["usr/bin/sized", "usr/share/sized/data"]);
+            sized.install_with(false, true).unwrap();
+            let installed = InstalledMetaData::open("sized").unwrap();
+            assert_eq!(installed.installed_size, Some(10));
+            assert_eq!(installed.size_on_disk(), Some(10));
+        });
+    }
+
+    #[test]
+    fn test_trust_store_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TrustStore::at(dir.path().join("trusted.d"));
+        let key_file = dir.path().join("repo.pub");
+        std::fs::write(&key_file, "-----BEGIN PUBLIC KEY-----\nAAAAC3Nza\nC1lZDI1\n-----END PUBLIC KEY-----\n").unwrap();
+
+        let added = store.add("https://repo.example.org/oreon/", &key_file).unwrap();
+        assert_eq!(added.repository, "https://repo.example.org/oreon");
+        let rewrapped = TrustedKey { repository: String::new(), key: String::from("AAAAC3NzaC1lZDI1") };
+        assert_eq!(added.fingerprint(), rewrapped.fingerprint());
+
+        let keys = store.keys().unwrap();
+        assert_eq!(keys, vec![added.clone()]);
+        assert_eq!(store.key_for("https://repo.example.org/oreon").unwrap(), Some(added));
+
+        let mut verifier = PackageVerifier::new();
+        verifier.load_trust_store(&store).unwrap();
+
+        store.remove("https://repo.example.org/oreon").unwrap();
+        assert!(store.keys().unwrap().is_empty());
+        assert!(store.remove("https://repo.example.org/oreon").is_err());
+    }
+
+    #[test]
+    fn test_install_checks_signer_against_trust_store() {
+        with_install_root(|root| {
+            let config = root.join("etc/pax");
+            unsafe { std::env::set_var("PAX_CONFIG_DIR", &config) };
+            let packages = tempfile::tempdir().unwrap();
+            let key_file = packages.path().join("repo.pub");
+            std::fs::write(&key_file, "AAAAC3NzaC1lZDI1").unwrap();
+            let trusted = TrustStore::at(config.join("trusted.d")).add("https://repo.example.org", &key_file).unwrap();
+
+            let manifest = format!("name: signed\nversion: '1.0'\nsignature: c2lnbmVk\nsigner: {}\n", trusted.fingerprint());
+            let signed = local_pax_with_manifest(packages.path(), "signed", &["usr/bin/signed"], Some(&manifest));
+            let installed = signed.install_with(false, true);
+
+            let manifest = format!("name: forged\nversion: '1.0'\nsignature: c2lnbmVk\nsigner: {}\n", "0".repeat(64));
+            let forged = local_pax_with_manifest(packages.path(), "forged", &["usr/bin/forged"], Some(&manifest));
+            let rejected = forged.install_with(false, true);
+            unsafe { std::env::remove_var("PAX_CONFIG_DIR") };
+
+            installed.unwrap();
+            assert!(root.join("usr/bin/signed").is_file());
+            assert!(rejected.unwrap_err().contains("Untrusted signer"));
+            assert!(!root.join("usr/bin/forged").exists());
+        });
+    }
+
+    #[test]
+    fn test_hooks_run_in_order_past_failures() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let hooks = dir.path().join("post-install.d");
+        std::fs::create_dir(&hooks).unwrap();
+        let script = |name: &str, body: &str, mode: u32| {
+            let path = hooks.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        script("20-second", &format!("echo \"second $PAX_HOOK $PAX_PACKAGES\" >> {}", log.display()), 0o755);
+        script("10-first", &format!("echo \"first $@\" >> {}; exit 3", log.display()), 0o755);
+        script("30-disabled", &format!("echo disabled >> {}", log.display()), 0o644);
+
+        let packages = vec![String::from("foo"), String::from("bar")];
+        let failures = hooks::run_hooks_in(&hooks, HookKind::PostInstall, &packages).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].contains("10-first"));
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "first foo bar\nsecond post-install foo bar\n"
+        );
+    }
+
+    fn manifest_owning(name: &str, files: &[&std::path::Path], dirs: &[&std::path::Path], symlinks: &[&std::path::Path]) -> file_tracking::FileManifest {
+        file_tracking::FileManifest {
+            package_name: name.to_string(),
+            package_version: String::from("1.0"),
+            files: files
+                .iter()
+                .map(|path| file_tracking::InstalledFile {
+                    path: path.to_path_buf(),
+                    size: 0,
+                    permissions: 0o644,
+                    checksum: String::new(),
+                    backup_path: None,
+                })
+                .collect(),
+            directories: dirs
+                .iter()
+                .map(|path| file_tracking::InstalledDirectory { path: path.to_path_buf(), permissions: 0o755 })
+                .collect(),
+            symlinks: symlinks
+                .iter()
+                .map(|path| file_tracking::InstalledSymlink { path: path.to_path_buf(), target: std::path::PathBuf::from("target") })
+                .collect(),
+            installed_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_conflicts_against_injected_manifests() {
+        let _root = root_lock();
+        use file_tracking::ConflictType;
+
+        let root = tempfile::tempdir().unwrap();
+        let owned = root.path().join("owned");
+        let stray = root.path().join("stray");
+        let shared = root.path().join("share");
+        let link = root.path().join("link");
+        let missing = root.path().join("missing");
+        std::fs::write(&owned, "").unwrap();
+        std::fs::write(&stray, "").unwrap();
+        std::fs::create_dir(&shared).unwrap();
+        std::os::unix::fs::symlink(&owned, &link).unwrap();
+
+        let installed = vec![manifest_owning("old", &[&owned], &[&shared], &[&link])];
+        let incoming = manifest_owning("new", &[&owned, &stray, &missing], &[&shared], &[&link]);
+        let conflicts = incoming.check_conflicts_against(installed.as_slice()).unwrap();
+
+        let kinds: Vec<(&std::path::Path, &ConflictType, &str)> = conflicts
+            .iter()
+            .map(|c| (c.path.as_path(), &c.conflict_type, c.existing_owner.as_str()))
+            .collect();
+        assert_eq!(kinds.len(), 4);
+        assert!(matches!(kinds[0], (p, ConflictType::FileOwnership, "old") if p == owned));
+        assert!(matches!(kinds[1], (p, ConflictType::UntrackedFile, "unknown") if p == stray));
+        assert!(matches!(kinds[2], (p, ConflictType::DirectoryOwnership, "old") if p == shared));
+        assert!(matches!(kinds[3], (p, ConflictType::SymlinkOwnership, "old") if p == link));
+    }
+
+    #[test]
+    fn test_reinstall_does_not_conflict_with_itself() {
+        let _root = root_lock();
+        let root = tempfile::tempdir().unwrap();
+        let owned = root.path().join("owned");
+        let shared = root.path().join("share");
+        std::fs::write(&owned, "").unwrap();
+        std::fs::create_dir(&shared).unwrap();
+
+        let installed = vec![manifest_owning("pkg", &[&owned], &[&shared], &[])];
+        let incoming = manifest_owning("pkg", &[&owned], &[&shared], &[]);
+        assert!(incoming.check_conflicts_against(installed.as_slice()).unwrap().is_empty());
+
+        // An untracked directory is left alone; only untracked files conflict
+        let unowned: Vec<file_tracking::FileManifest> = Vec::new();
+        let conflicts = incoming.check_conflicts_against(unowned.as_slice()).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert!(matches!(conflicts[0].conflict_type, file_tracking::ConflictType::UntrackedFile));
+    }
+
+    #[test]
+    fn test_installed_depends_and_rdepends() {
+        let packages: Vec<InstalledMetaData> = [
+            ("curl", vec!["libcurl"]),
+            ("libcurl", vec!["openssl", "zlib"]),
+            ("openssl", vec!["zlib"]),
+            ("zlib", vec![]),
+            ("git", vec!["libcurl", "openssl"]),
+        ]
+        .iter()
+        .map(|(name, deps)| package_with_deps(name, deps, &[]).to_installed())
+        .collect();
+
+        assert_eq!(installed_depends(&packages, "curl", false), vec!["libcurl"]);
+        assert_eq!(installed_depends(&packages, "curl", true), vec!["libcurl", "openssl", "zlib"]);
+        assert_eq!(installed_rdepends(&packages, "openssl", false), vec!["git", "libcurl"]);
+        assert_eq!(installed_rdepends(&packages, "openssl", true), vec!["curl", "git", "libcurl"]);
+        assert_eq!(installed_rdepends(&packages, "zlib", true), vec!["curl", "git", "libcurl", "openssl"]);
+        assert!(installed_rdepends(&packages, "curl", true).is_empty());
+    }
+
+    #[test]
+    fn test_clean_removes_matching_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pax.0123456789abcdef"), vec![0u8; 1000]).unwrap();
+        let extract = dir.path().join("pax_extract_42_1700000000");
+        std::fs::create_dir_all(extract.join("usr/bin")).unwrap();
+        std::fs::write(extract.join("usr/bin/tool"), vec![0u8; 24]).unwrap();
+        std::fs::write(dir.path().join("unrelated.txt"), b"keep").unwrap();
+        // Other tools' files that merely start with "pax."
+        std::fs::write(dir.path().join("pax.conf"), b"keep").unwrap();
+        std::fs::write(dir.path().join("pax.0123456789abcdef.bak"), b"keep").unwrap();
+
+        let downloads = clean::download_entries(dir.path());
+        assert_eq!(downloads, vec![dir.path().join("pax.0123456789abcdef")]);
+        assert_eq!(clean::remove_paths(&downloads).unwrap(), 1000);
+
+        let leftovers = clean::matching_entries(dir.path(), &["pax_install_", "pax_extract"]);
+        assert_eq!(clean::remove_paths(&leftovers).unwrap(), 24);
+        assert!(!extract.exists());
+        assert!(dir.path().join("unrelated.txt").exists());
+        assert!(dir.path().join("pax.conf").exists());
+        assert!(dir.path().join("pax.0123456789abcdef.bak").exists());
+        assert!(clean::download_entries(dir.path()).is_empty());
+    }
+
+    struct FixtureSource(std::collections::HashMap<&'static str, Vec<(&'static str, Vec<&'static str>)>>);
+
+    impl solver::CandidateSource for FixtureSource {
+        fn candidates(&self, name: &str) -> Option<Vec<solver::Candidate>> {
+            let versions = self.0.get(name)?;
+            Some(
+                versions
+                    .iter()
+                    .map(|(version, requires)| solver::Candidate {
+                        version: utils::Version::parse(version).unwrap(),
+                        requires: requires.iter().map(|dep| dep_ver(dep)).collect(),
+                        installed: false,
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    /// `name` or `name range`, e.g. `libc >=2.0.0, <3.0.0`
+    fn dep_ver(src: &str) -> DepVer {
+        match src.split_once(' ') {
+            Some((name, range)) => DepVer { name: name.to_string(), range: utils::Range::parse(range).unwrap() },
+            None => DepVer {
+                name: src.to_string(),
+                range: utils::Range { lower: utils::VerReq::NoBound, upper: utils::VerReq::NoBound },
+            },
+        }
+    }
+
+    fn solved(source: &FixtureSource, root: &[&str]) -> Result<Vec<String>, String> {
+        let root: Vec<DepVer> = root.iter().map(|dep| dep_ver(dep)).collect();
+        Ok(solver::solve(&root, source)?
+            .into_iter()
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect())
+    }
+
+    #[test]
+    fn test_solver_diamond() {
+        let source = FixtureSource(std::collections::HashMap::from([
+            ("a", vec![("1.0.0", vec!["c >=1.0.0, <3.0.0"])]),
+            ("b", vec![("1.0.0", vec!["c >=2.0.0"])]),
+            ("c", vec![("1.0.0", vec![]), ("2.0.0", vec![]), ("2.5.0", vec![]), ("3.0.0", vec![])]),
+        ]));
+        assert_eq!(solved(&source, &["a", "b"]).unwrap(), vec!["a 1.0.0", "b 1.0.0", "c 2.5.0"]);
+
+        let source = FixtureSource(std::collections::HashMap::from([
+            ("a", vec![("1.0.0", vec!["c <2.0.0"])]),
+            ("b", vec![("1.0.0", vec!["c >=2.0.0"])]),
+            ("c", vec![("1.0.0", vec![]), ("2.0.0", vec![])]),
+        ]));
+        let fault = solved(&source, &["a", "b"]).unwrap_err();
+        assert!(fault.contains("No version of c satisfies"), "{fault}");
+        assert!(fault.contains("required by a 1.0.0") && fault.contains("required by b 1.0.0"), "{fault}");
+    }
+
+    #[test]
+    fn test_solver_backtracks() {
+        // The newest app needs a libfoo that libbar can't live with, so the
+        // solver has to step back to app 1.0.0
+        let source = FixtureSource(std::collections::HashMap::from([
+            ("app", vec![("1.0.0", vec!["libfoo <2.0.0"]), ("2.0.0", vec!["libfoo >=2.0.0"])]),
+            ("libbar", vec![("1.0.0", vec!["libfoo <2.0.0"])]),
+            ("libfoo", vec![("1.5.0", vec![]), ("2.1.0", vec![])]),
+        ]));
+        assert_eq!(
+            solved(&source, &["app", "libbar"]).unwrap(),
+            vec!["app 1.0.0", "libbar 1.0.0", "libfoo 1.5.0"]
+        );
+        // Names the source doesn't know are left to the rest of resolution
+        assert_eq!(solved(&source, &["libfoo", "libprovided.so.1"]).unwrap(), vec!["libfoo 2.1.0"]);
+    }
+
+    #[test]
+    fn test_solver_keeps_installed_versions() {
+        let source = FixtureSource(std::collections::HashMap::from([
+            ("app", vec![("1.0.0", vec!["libfoo >=1.0.0", "libbar >=2.0.0", "libssl.so.3"])]),
+            ("libfoo", vec![("1.0.0", vec![]), ("1.4.0", vec![])]),
+            ("libbar", vec![("2.0.0", vec![]), ("2.2.0", vec![])]),
+            ("libssl.so.3", vec![("3.0.0", vec![])]),
+        ]));
+        let versions = std::collections::BTreeMap::from([
+            ("libfoo".to_string(), utils::Version::parse("1.2.0").unwrap()),
+            ("libbar".to_string(), utils::Version::parse("1.0.0").unwrap()),
+        ]);
+        let provided = |name: &str| name == "libssl.so.3";
+        let seeded = solver::InstalledSource { inner: &source, versions: &versions, provided: &provided };
+        let picks: Vec<String> = solver::solve(&[dep_ver("app")], &seeded)
+            .unwrap()
+            .into_iter()
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect();
+        // libfoo stays as installed, libbar is too old and moves up, and the
+        // provided library is left to the system
+        assert_eq!(picks, vec!["app 1.0.0", "libbar 2.2.0", "libfoo 1.2.0"]);
+    }
+
+    #[test]
+    fn test_nested_block_on_does_not_panic() {
+        fn resolve_sync(package: &ProcessedMetaData) -> Result<InstallPackage, String> {
+            let mut prior = std::collections::HashSet::new();
+            block_on(package.get_depends(&[], &mut prior))?
+        }
+
+        let package = package_with_deps("leaf", &[], &[]);
+        let outer = block_on(async { resolve_sync(&package) }).unwrap().unwrap();
+        assert_eq!(outer.metadata.name, "leaf");
+        // The shared runtime is reused rather than rebuilt
+        assert!(std::ptr::eq(runtime().unwrap(), runtime().unwrap()));
+    }
+
+    #[test]
+    fn test_search_matches_keywords_and_categories() {
+        use crate::processed::search_rank;
+
+        let mut ripgrep = package_with_deps("ripgrep", &[], &[]);
+        ripgrep.description = String::from("Recursively search directories for a regex pattern");
+        ripgrep.keywords = vec![String::from("grep"), String::from("find")];
+        ripgrep.categories = vec![String::from("Utilities")];
+        let mut grep = package_with_deps("grep", &[], &[]);
+        grep.categories = vec![String::from("utilities"), String::from("text")];
+
+        // Neither the name nor the description mentions "find"
+        assert_eq!(search_rank(&ripgrep, "find", false), Some(2));
+        assert_eq!(search_rank(&ripgrep, "utilities", false), Some(2));
+        assert_eq!(search_rank(&ripgrep, "regex", false), Some(3));
+        assert!(search_rank(&grep, "grep", false) < search_rank(&ripgrep, "grep", false));
+        assert_eq!(search_rank(&ripgrep, "find", true), None);
+        assert_eq!(search_rank(&ripgrep, "web", false), None);
+
+        let counts = category_counts(&[ripgrep, grep]);
+        assert_eq!(counts.get("utilities"), Some(&2));
+        assert_eq!(counts.get("text"), Some(&1));
+    }
+
+    #[test]
+    fn test_explain_transitive_dependency() {
+        use crate::dep_graph::DependencyGraph;
+        use crate::depend_kind::DependKind;
+
+        let package = package_with_deps;
+        let mut bar = package("bar", &[], &[]);
+        bar.runtime_dependencies = vec![DependKind::Specific(DepVer {
+            name: String::from("libfoo"),
+            range: utils::Range::parse(">=1.0").unwrap(),
+        })];
+        let install = InstallPackage {
+            metadata: package("app", &["bar"], &[]),
+            run_deps: vec![bar, package("libfoo", &["libc"], &[]), package("libc", &[], &[])],
+            build_deps: Vec::new(),
+        };
+
+        let graph = DependencyGraph::from_packages(std::slice::from_ref(&install), false);
+        assert_eq!(graph.chain_to("libc"), Some(vec![String::from("app"), String::from("bar"), String::from("libfoo"), String::from("libc")]));
+        assert_eq!(graph.explain("app"), "app: explicitly requested.");
+        assert_eq!(graph.explain("libfoo"), "libfoo: required by bar (>=1.0.0), required by app, which you requested.");
+        assert_eq!(
+            graph.explain("libc"),
+            "libc: required by libfoo, required by bar (>=1.0.0), required by app, which you requested."
+        );
+    }
+
+    #[test]
+    fn test_install_reason_transitions() {
+        let mut explicit = package_with_deps("libfoo", &[], &[]);
+        explicit.dependent = false;
+        let mut as_dependency = explicit.clone();
+        as_dependency.dependent = true;
+
+        // Explicit, then fetched again as a dependency: stays manual
+        let manual = explicit.to_installed();
+        assert_eq!(manual.reason(), InstallReason::Manual);
+        let mut refetched = as_dependency.to_installed();
+        refetched.settle_reason(Some(&manual));
+        assert_eq!(refetched.reason(), InstallReason::Manual);
+        assert!(!refetched.dependent);
+
+        // Auto, then installed explicitly: becomes manual
+        let auto = as_dependency.to_installed();
+        assert_eq!(auto.reason(), InstallReason::Auto);
+        let mut requested = explicit.to_installed();
+        requested.settle_reason(Some(&auto));
+        assert_eq!(requested.reason(), InstallReason::Manual);
+
+        // Auto, then upgraded as a dependency: stays auto and can be reclaimed
+        let mut upgraded = as_dependency.to_installed();
+        upgraded.settle_reason(Some(&auto));
+        assert_eq!(upgraded.reason(), InstallReason::Auto);
+        assert_eq!(find_orphans(std::slice::from_ref(&upgraded)).len(), 1);
+        assert!(find_orphans(&[requested]).is_empty());
+
+        // Records without a reason fall back to `dependent`
+        let mut legacy = as_dependency.to_installed();
+        legacy.install_reason = None;
+        assert_eq!(legacy.reason(), InstallReason::Auto);
+    }
+
+    #[test]
+    fn test_mark_manual_protects_from_orphan_cleanup() {
+        use crate::installed::mark_at;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("libfoo.json");
+        let mut auto = package_with_deps("libfoo", &[], &[]).to_installed();
+        auto.set_reason(InstallReason::Auto);
+        auto.write(&path).unwrap();
+        let read = |path: &std::path::Path| -> InstalledMetaData {
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+        };
+        assert_eq!(find_orphans(&[read(&path)]).len(), 1);
+
+        mark_at(&path, InstallReason::Manual).unwrap();
+        let marked = read(&path);
+        assert_eq!(marked.reason(), InstallReason::Manual);
+        assert!(find_orphans(&[marked]).is_empty());
+
+        mark_at(&path, InstallReason::Auto).unwrap();
+        assert_eq!(find_orphans(&[read(&path)]).len(), 1);
+    }
+
+    /// Serve one canned HTTP response on a local port, returning its URL.
+    fn serve_once(response: String) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/repos/o/r/releases/latest", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(response.as_bytes());
+        });
+        url
+    }
+
+    #[test]
+    fn test_github_rate_limit_and_size_cap() {
+        use crate::github::{get_json, get_json_capped};
+
+        let limited = serve_once(String::from(
+            "HTTP/1.1 403 Forbidden\r\nX-RateLimit-Remaining: 0\r\nX-RateLimit-Reset: 1700000000\r\n\
+             Content-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}",
+        ));
+        let fault = crate::block_on(get_json(&limited)).unwrap().unwrap_err();
+        assert!(fault.contains("rate limit"), "{}", fault);
+        assert!(fault.contains("1700000000"), "{}", fault);
+
+        let missing = serve_once(String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"));
+        assert_eq!(crate::block_on(get_json(&missing)).unwrap(), Ok(None));
+
+        let body = format!("{{\"body\": \"{}\"}}", "x".repeat(4096));
+        let oversized = serve_once(format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        let fault = crate::block_on(get_json_capped(&oversized, 1024)).unwrap().unwrap_err();
+        assert!(fault.contains("larger than"), "{}", fault);
+
+        let small = serve_once(String::from(
+            "HTTP/1.1 200 OK\r\nContent-Length: 20\r\nConnection: close\r\n\r\n{\"tag_name\": \"v1.0\"}",
+        ));
+        let release = crate::block_on(get_json_capped(&small, 1024)).unwrap().unwrap().unwrap();
+        assert_eq!(release["tag_name"], "v1.0");
+    }
+
+    #[test]
+    fn test_newest_github_release() {
+        use crate::github::newest_release;
+
+        let releases: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[
+                {"tag_name": "v3.0.0", "draft": true, "prerelease": false},
+                {"tag_name": "v2.1.0-rc1", "draft": false, "prerelease": true},
+                {"tag_name": "nightly", "draft": false, "prerelease": false},
+                {"tag_name": "v1.10.0", "draft": false, "prerelease": false},
+                {"tag_name": "v1.9.0", "draft": false, "prerelease": false}
+            ]"#,
+        )
+        .unwrap();
+        let tag = |release: Option<&serde_json::Value>| release.map(|release| release["tag_name"].as_str().unwrap().to_string());
+
+        assert_eq!(tag(newest_release(&releases, false)), Some(String::from("v1.10.0")));
+        assert_eq!(tag(newest_release(&releases, true)), Some(String::from("v2.1.0-rc1")));
+        // A project that only publishes pre-releases has nothing stable
+        assert_eq!(tag(newest_release(&releases[..2], false)), None);
+
+        // With no tag to go by, the most recently published release
+        let releases: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[
+                {"tag_name": "snapshot-b", "draft": false, "prerelease": false, "published_at": "2026-03-01T00:00:00Z"},
+                {"tag_name": "snapshot-c", "draft": false, "prerelease": false, "published_at": "2026-05-01T00:00:00Z"},
+                {"tag_name": "snapshot-a", "draft": false, "prerelease": false, "published_at": "2026-01-01T00:00:00Z"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(tag(newest_release(&releases, false)), Some(String::from("snapshot-c")));
+        // or failing that, the first in the listing
+        let undated: Vec<serde_json::Value> = ["nightly", "weekly"]
+            .iter()
+            .map(|name| serde_json::json!({"tag_name": name, "draft": false, "prerelease": false}))
+            .collect();
+        assert_eq!(tag(newest_release(&undated, false)), Some(String::from("nightly")));
+    }
+
+    #[test]
+    fn test_github_asset_for_arch() {
+        use crate::github::select_asset;
+        use settings::Arch;
+
+        let assets: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[
+                {"name": "tool-1.0-x86_64.pax", "browser_download_url": "https://example.invalid/x86_64"},
+                {"name": "tool-1.0-aarch64.pax", "browser_download_url": "https://example.invalid/aarch64"},
+                {"name": "SHA256SUMS", "browser_download_url": "https://example.invalid/sums"}
+            ]"#,
+        )
+        .unwrap();
+        let picked = |arch: Arch, pattern: Option<&str>| {
+            select_asset(&assets, arch, pattern).map(|asset| asset["name"].as_str().unwrap().to_string())
+        };
+
+        assert_eq!(picked(Arch::Aarch64, None), Some(String::from("tool-1.0-aarch64.pax")));
+        assert_eq!(picked(Arch::X86_64v3, None), Some(String::from("tool-1.0-x86_64.pax")));
+        // Nothing for armv7l, so the first candidate as before
+        assert_eq!(picked(Arch::Armv7l, None), Some(String::from("tool-1.0-x86_64.pax")));
+        assert_eq!(picked(Arch::Aarch64, Some("SHA*")), Some(String::from("SHA256SUMS")));
+
+        let assets: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[
+                {"name": "installer.pax"},
+                {"name": "tool-all.pax"},
+                {"name": "tool-all.json"},
+                {"name": "tool-x86_64.json"}
+            ]"#,
+        )
+        .unwrap();
+        let ranked = |arch: Arch| {
+            crate::github::ranked_assets(&assets, arch, None)
+                .iter()
+                .map(|asset| asset["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        // "all" is a whole token in tool-all but not in installer, and the
+        // metadata is tried ahead of the archive
+        assert_eq!(ranked(Arch::NoArch), vec!["tool-all.json", "tool-all.pax"]);
+        // With no mention of the architecture every candidate stays, metadata first
+        assert_eq!(ranked(Arch::Aarch64), vec!["tool-all.json", "tool-x86_64.json", "installer.pax", "tool-all.pax"]);
+    }
+
+    #[test]
+    fn test_hold_reason_round_trip() {
+        use crate::package_holds::{HoldType, PackageHoldManager};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut holds = PackageHoldManager::at(dir.path().to_path_buf());
+        holds
+            .hold_package(String::from("kernel"), HoldType::NoUpgrade, Some(String::from("waiting on driver fix")), None)
+            .unwrap();
+        holds.hold_package(String::from("mesa"), HoldType::NoUpgrade, None, None).unwrap();
+
+        let mut reloaded = PackageHoldManager::at(dir.path().to_path_buf());
+        reloaded.load_all().unwrap();
+        let kernel = reloaded.hold("kernel").unwrap();
+        assert_eq!(kernel.reason.as_deref(), Some("waiting on driver fix"));
+        assert!(kernel.created_at > 0);
+        assert_eq!(reloaded.hold("mesa").unwrap().reason, None);
+        assert!(!reloaded.can_upgrade("kernel"));
+
+        // Holds written before reasons were optional still load
+        std::fs::write(
+            dir.path().join("holds.yaml"),
+            "old:\n  package_name: old\n  hold_type: NoUpgrade\n  created_at: 1\n  created_by: null\n  expires_at: null\n",
+        )
+        .unwrap();
+        let mut legacy = PackageHoldManager::at(dir.path().to_path_buf());
+        legacy.load_all().unwrap();
+        assert_eq!(legacy.hold("old").unwrap().reason, None);
+
+        reloaded.unhold_package("kernel").unwrap();
+        assert!(reloaded.hold("kernel").is_none());
+    }
+
+    #[test]
+    fn test_doctor_finds_and_fixes_damage() {
+        use crate::doctor::{DoctorProblem, check_store, fix_store};
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("manifests")).unwrap();
+        let mut lib = package_with_deps("libfoo", &[], &[]).to_installed();
+        lib.dependents.push(Specific { name: String::from("app"), version: utils::Version::parse("1.0.0").unwrap() });
+        lib.dependents.push(Specific { name: String::from("gone"), version: utils::Version::parse("1.0.0").unwrap() });
+        lib.write(&dir.path().join("libfoo.json")).unwrap();
+        package_with_deps("app", &["libfoo"], &[]).to_installed().write(&dir.path().join("app.json")).unwrap();
+        std::fs::write(dir.path().join("manifests/libfoo.yaml"), "").unwrap();
+        // A write cut short
+        std::fs::write(dir.path().join("broken.json"), "{\"name\": \"broken\", \"ver").unwrap();
+
+        let problems = check_store(dir.path()).unwrap();
+        assert_eq!(problems.len(), 3);
+        assert!(matches!(&problems[0], DoctorProblem::Unparseable { path, .. } if path.ends_with("broken.json")));
+        assert!(problems.contains(&DoctorProblem::MissingManifest { package: String::from("app") }));
+        assert!(problems.contains(&DoctorProblem::DanglingDependent {
+            package: String::from("libfoo"),
+            dependent: String::from("gone"),
+        }));
+
+        assert_eq!(fix_store(dir.path(), &problems).unwrap().len(), 2);
+        assert!(dir.path().join("quarantine/broken.json").is_file());
+        let fixed: InstalledMetaData =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join("libfoo.json")).unwrap()).unwrap();
+        assert_eq!(fixed.dependents.len(), 1);
+        assert_eq!(fixed.dependents[0].name, "app");
+        // Only the missing manifest is left, which needs a reinstall
+        assert_eq!(
+            check_store(dir.path()).unwrap(),
+            vec![DoctorProblem::MissingManifest { package: String::from("app") }]
+        );
+    }
+
+    #[test]
+    fn test_download_only_saves_without_installing() {
+        let repo = tempfile::tempdir().unwrap();
+        let destdir = tempfile::tempdir().unwrap();
+        std::fs::write(repo.path().join("libfoo-1.0-x86_64.pax"), b"libfoo archive").unwrap();
+        std::fs::write(repo.path().join("app-1.0.pax"), b"app archive").unwrap();
+
+        let local = |name: &str| {
+            let mut package = package_with_deps(name, &[], &[]);
+            package.origin = settings::OriginKind::LocalDir(repo.path().display().to_string());
+            package
+        };
+        for name in ["app", "libfoo"] {
+            let saved = crate::block_on(local(name).download_to(destdir.path())).unwrap().unwrap();
+            assert_eq!(saved, destdir.path().join(format!("{}-1.0.pax", name)));
+        }
+
+        // Just the two package files under their canonical names, nothing extracted
+        let mut saved: Vec<String> = std::fs::read_dir(destdir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        saved.sort();
+        assert_eq!(saved, vec!["app-1.0.pax", "libfoo-1.0.pax"]);
+        assert_eq!(std::fs::read(destdir.path().join("libfoo-1.0.pax")).unwrap(), b"libfoo archive");
+        assert_eq!(local("app").canonical_file_name(), "app-1.0.pax");
+        let mut deb = local("app");
+        deb.origin = settings::OriginKind::Deb(String::new());
+        assert_eq!(deb.canonical_file_name(), "app-1.0.deb");
+    }
+
+    #[test]
+    fn test_downloaded_deb_is_found_by_from_dir() {
+        use crate::repo_index::MultiRepoIndex;
+        use std::process::Command;
+
+        if Command::new("dpkg-deb").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let stage = dir.path().join("stage");
+        std::fs::create_dir_all(stage.join("DEBIAN")).unwrap();
+        std::fs::create_dir_all(stage.join("usr/bin")).unwrap();
+        std::fs::write(stage.join("usr/bin/tool"), b"#!/bin/sh\n").unwrap();
+        std::fs::write(
+            stage.join("DEBIAN/control"),
+            "Package: tool\nVersion: 1.0\nArchitecture: all\nMaintainer: pax <pax@example.org>\nDescription: test\n",
+        )
+        .unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        let output = Command::new("dpkg-deb")
+            .arg("--build")
+            .arg(&stage)
+            .arg(repo.join("tool-1.0.deb"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        // --download-only from a local directory keeps it a .deb...
+        let bundle = dir.path().join("bundle");
+        std::fs::create_dir_all(&bundle).unwrap();
+        let mut tool = package_with_deps("tool", &[], &[]);
+        tool.origin = settings::OriginKind::LocalDir(repo.display().to_string());
+        let saved = crate::block_on(tool.download_to(&bundle)).unwrap().unwrap();
+        assert_eq!(saved, bundle.join("tool-1.0.deb"));
+
+        // ...so --from-dir on the bundle finds it again
+        let sources = [settings::OriginKind::LocalDir(bundle.display().to_string())];
+        let index = crate::block_on(MultiRepoIndex::build(&sources, false)).unwrap().unwrap();
+        let found = index.lookup_all_versions("tool");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].version, "1.0");
+    }
+
+    #[test]
+    fn test_from_dir_resolves_within_bundle() {
+        use crate::repo_index::MultiRepoIndex;
+        use settings::OriginKind;
+        use std::fs;
+        use std::process::Command;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("bundle");
+        fs::create_dir_all(&bundle).unwrap();
+        for (name, deps) in [("app", "[libfoo]"), ("libfoo", "[]")] {
+            let stage = dir.path().join(name);
+            fs::create_dir_all(stage.join("usr/bin")).unwrap();
+            fs::write(stage.join("usr/bin").join(name), b"#!/bin/sh\n").unwrap();
+            fs::write(
+                stage.join("manifest.yaml"),
+                format!(
+                    "name: {}\ndescription: test\nversion: 1.0.0\norigin: local\nruntime_dependencies: {}\nbuild: ''\ninstall: ''\nuninstall: ''\npurge: ''\nhash: unknown\n",
+                    name, deps
+                ),
+            )
+            .unwrap();
+            let status = Command::new("tar")
+                .arg("-czf")
+                .arg(bundle.join(format!("{}-1.0.0.pax", name)))
+                .arg("-C")
+                .arg(&stage)
+                .arg(".")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        // The bundle is the only source, as with `install --from-dir`
+        let sources = [OriginKind::LocalDir(bundle.display().to_string())];
+
+        let index = crate::block_on(MultiRepoIndex::build(&sources, false)).unwrap().unwrap();
+        let app = index.lookup_package("app").cloned().expect("app indexed from the bundle");
+        let deps = crate::block_on(processed::resolve_all_dependencies(&app, &sources)).unwrap().unwrap();
+        assert_eq!(deps.iter().map(|dep| dep.name.as_str()).collect::<Vec<_>>(), vec!["libfoo"]);
+        // Both package files come straight from the bundle
+        for package in std::iter::once(&app).chain(&deps) {
+            let settings::OriginKind::Pax(path) = &package.origin else {
+                panic!("{} is not from a local file: {:?}", package.name, package.origin);
+            };
+            assert!(std::path::Path::new(path).starts_with(&bundle));
+        }
+    }
+
+    #[test]
+    fn test_resolution_fails_on_solver_conflict() {
+        use crate::repo_index::MultiRepoIndex;
+        use settings::OriginKind;
+        use std::fs;
+        use std::process::Command;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("bundle");
+        fs::create_dir_all(&bundle).unwrap();
+        // app wants an old libfoo, libbar a new one
+        for (name, version, deps) in [
+            ("app", "1.0.0", "['libfoo<2.0.0', libbar]"),
+            ("libbar", "1.0.0", "['libfoo>=2.0.0']"),
+            ("libfoo", "1.0.0", "[]"),
+            ("libfoo", "2.0.0", "[]"),
+        ] {
+            let stage = dir.path().join(format!("{}-{}", name, version));
+            fs::create_dir_all(stage.join("usr/bin")).unwrap();
+            fs::write(stage.join("usr/bin").join(name), b"#!/bin/sh\n").unwrap();
+            fs::write(
+                stage.join("manifest.yaml"),
+                format!(
+                    "name: {}\ndescription: test\nversion: {}\norigin: local\nruntime_dependencies: {}\nbuild: ''\ninstall: ''\nuninstall: ''\npurge: ''\nhash: unknown\n",
+                    name, version, deps
+                ),
+            )
+            .unwrap();
+            let status = Command::new("tar")
+                .arg("-czf")
+                .arg(bundle.join(format!("{}-{}.pax", name, version)))
+                .arg("-C")
+                .arg(&stage)
+                .arg(".")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        let sources = [OriginKind::LocalDir(bundle.display().to_string())];
+
+        let index = crate::block_on(MultiRepoIndex::build(&sources, false)).unwrap().unwrap();
+        let app = index.lookup_package("app").cloned().expect("app indexed from the bundle");
+        let fault = crate::block_on(processed::resolve_all_dependencies(&app, &sources)).unwrap().unwrap_err();
+        assert!(fault.contains("No version of libfoo satisfies"), "{fault}");
+    }
+
+    #[test]
+    fn test_index_prefers_highest_priority_repo() {
+        use crate::repo_index::MultiRepoIndex;
+        use settings::OriginKind;
+        use std::fs;
+        use std::process::Command;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut sources = Vec::new();
+        // The preferred repo carries an older version than the fallback
+        for (repo, version) in [("preferred", "1.0.0"), ("fallback", "2.0.0")] {
+            let stage = dir.path().join(format!("{}-stage", repo));
+            fs::create_dir_all(stage.join("usr/bin")).unwrap();
+            fs::write(stage.join("usr/bin/tool"), b"#!/bin/sh\n").unwrap();
+            fs::write(
+                stage.join("manifest.yaml"),
+                format!(
+                    "name: tool\ndescription: test\nversion: {}\norigin: local\nruntime_dependencies: []\nbuild: ''\ninstall: ''\nuninstall: ''\npurge: ''\nhash: unknown\n",
+                    version
+                ),
+            )
+            .unwrap();
+            let bundle = dir.path().join(repo);
+            fs::create_dir_all(&bundle).unwrap();
+            let status = Command::new("tar")
+                .arg("-czf")
+                .arg(bundle.join(format!("tool-{}.pax", version)))
+                .arg("-C")
+                .arg(&stage)
+                .arg(".")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            sources.push(OriginKind::LocalDir(bundle.display().to_string()));
+        }
+
+        let index = crate::block_on(MultiRepoIndex::build(&sources, false)).unwrap().unwrap();
+        let mut all = index.lookup_all_versions("tool").into_iter().map(|m| m.version).collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, vec!["1.0.0", "2.0.0"]);
+        let preferred = index.lookup_preferred("tool");
+        assert_eq!(preferred.iter().map(|m| m.version.as_str()).collect::<Vec<_>>(), vec!["1.0.0"]);
+    }
+
+    #[test]
+    fn test_manifest_is_reproducible() {
+        use std::fs;
+
+        let package = package_with_deps("hello", &[], &[]);
+        let dir = tempfile::tempdir().unwrap();
+        let mut saved = Vec::new();
+        // The same payload, its entries created in opposite orders
+        for (run, names) in [("a", ["zeta", "alpha", "mid"]), ("b", ["mid", "alpha", "zeta"])] {
+            let extract = dir.path().join(run);
+            fs::create_dir_all(extract.join("usr/bin")).unwrap();
+            for name in names {
+                fs::create_dir_all(extract.join("usr/share").join(name)).unwrap();
+                fs::write(extract.join("usr/share").join(name).join("data"), name).unwrap();
+                fs::write(extract.join("usr/bin").join(name), name).unwrap();
+                std::os::unix::fs::symlink(format!("../share/{}", name), extract.join("usr/bin").join(format!("{}-link", name)))
+                    .unwrap();
+            }
+            let mut manifest = crate::block_on(package.create_file_manifest(&extract)).unwrap().unwrap();
+            manifest.installed_at = 0;
+            let paths: Vec<_> = manifest.files.iter().map(|file| file.path.clone()).collect();
+            let mut sorted = paths.clone();
+            sorted.sort();
+            assert_eq!(paths, sorted);
+            assert!(paths.contains(&std::path::PathBuf::from("/usr/share/alpha/data")));
+            saved.push(manifest.to_yaml().unwrap());
+        }
+        assert_eq!(saved[0], saved[1]);
+    }
 }
@@ -2,7 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs::File,
-    io::{Read, Write},
+    io::Read,
+    path::PathBuf,
     time::{SystemTime, UNIX_EPOCH},
 };
 
@@ -12,7 +13,9 @@ use utils::{err, get_metadata_dir, Version};
 pub struct PackageHold {
     pub package_name: String,
     pub hold_type: HoldType,
-    pub reason: String,
+    /// Why the package is held, as given to `pax hold --reason`.
+    #[serde(default)]
+    pub reason: Option<String>,
     pub created_at: u64,
     pub created_by: Option<String>,
     pub expires_at: Option<u64>,
@@ -47,6 +50,8 @@ pub struct PackageHoldManager {
     holds: HashMap<String, PackageHold>,
     version_pins: HashMap<String, VersionPin>,
     repository_pins: HashMap<String, RepositoryPin>,
+    // Where the hold files live; the installed metadata directory by default
+    dir: Option<PathBuf>,
 }
 
 impl PackageHoldManager {
@@ -55,14 +60,29 @@ impl PackageHoldManager {
             holds: HashMap::new(),
             version_pins: HashMap::new(),
             repository_pins: HashMap::new(),
+            dir: None,
         }
     }
 
+    /// A manager keeping its hold files in `dir` instead.
+    pub fn at(dir: PathBuf) -> Self {
+        Self { dir: Some(dir), ..Self::new() }
+    }
+
+    fn store_path(&self, file: &str) -> Result<PathBuf, String> {
+        let mut path = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => get_metadata_dir()?,
+        };
+        path.push(file);
+        Ok(path)
+    }
+
     pub fn hold_package(
         &mut self,
         package_name: String,
         hold_type: HoldType,
-        reason: String,
+        reason: Option<String>,
         expires_at: Option<u64>,
     ) -> Result<(), String> {
         let hold = PackageHold {
@@ -73,7 +93,8 @@ impl PackageHoldManager {
                 .duration_since(UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
-            created_by: None, // Could be set to current user
+            // The user behind sudo, if any, rather than root
+            created_by: std::env::var("SUDO_USER").or_else(|_| std::env::var("USER")).ok(),
             expires_at,
         };
 
@@ -202,6 +223,11 @@ impl PackageHoldManager {
         }
     }
 
+    /// The hold on `package_name`, if it has one.
+    pub fn hold(&self, package_name: &str) -> Option<&PackageHold> {
+        self.holds.get(package_name)
+    }
+
     pub fn get_pinned_version(&self, package_name: &str) -> Option<&Version> {
         self.version_pins.get(package_name).map(|pin| &pin.version)
     }
@@ -262,56 +288,34 @@ impl PackageHoldManager {
     }
 
     fn save_holds(&self) -> Result<(), String> {
-        let mut holds_path = get_metadata_dir()?;
-        holds_path.push("holds.yaml");
-
-        let mut file = File::create(&holds_path)
-            .map_err(|_| "Failed to create holds file")?;
+        let holds_path = self.store_path("holds.yaml")?;
 
         let yaml = serde_norway::to_string(&self.holds)
             .map_err(|_| "Failed to serialize holds")?;
 
-        file.write_all(yaml.as_bytes())
-            .map_err(|_| "Failed to write holds file")?;
-
-        Ok(())
+        utils::write_atomic(&holds_path, yaml.as_bytes())
     }
 
     fn save_version_pins(&self) -> Result<(), String> {
-        let mut pins_path = get_metadata_dir()?;
-        pins_path.push("version_pins.yaml");
-
-        let mut file = File::create(&pins_path)
-            .map_err(|_| "Failed to create version pins file")?;
+        let pins_path = self.store_path("version_pins.yaml")?;
 
         let yaml = serde_norway::to_string(&self.version_pins)
             .map_err(|_| "Failed to serialize version pins")?;
 
-        file.write_all(yaml.as_bytes())
-            .map_err(|_| "Failed to write version pins file")?;
-
-        Ok(())
+        utils::write_atomic(&pins_path, yaml.as_bytes())
     }
 
     fn save_repository_pins(&self) -> Result<(), String> {
-        let mut pins_path = get_metadata_dir()?;
-        pins_path.push("repository_pins.yaml");
-
-        let mut file = File::create(&pins_path)
-            .map_err(|_| "Failed to create repository pins file")?;
+        let pins_path = self.store_path("repository_pins.yaml")?;
 
         let yaml = serde_norway::to_string(&self.repository_pins)
             .map_err(|_| "Failed to serialize repository pins")?;
 
-        file.write_all(yaml.as_bytes())
-            .map_err(|_| "Failed to write repository pins file")?;
-
-        Ok(())
+        utils::write_atomic(&pins_path, yaml.as_bytes())
     }
 
     pub fn load_holds(&mut self) -> Result<(), String> {
-        let mut holds_path = get_metadata_dir()?;
-        holds_path.push("holds.yaml");
+        let holds_path = self.store_path("holds.yaml")?;
 
         if holds_path.exists() {
             let mut file = File::open(&holds_path)
@@ -329,8 +333,7 @@ impl PackageHoldManager {
     }
 
     pub fn load_version_pins(&mut self) -> Result<(), String> {
-        let mut pins_path = get_metadata_dir()?;
-        pins_path.push("version_pins.yaml");
+        let pins_path = self.store_path("version_pins.yaml")?;
 
         if pins_path.exists() {
             let mut file = File::open(&pins_path)
@@ -348,8 +351,7 @@ impl PackageHoldManager {
     }
 
     pub fn load_repository_pins(&mut self) -> Result<(), String> {
-        let mut pins_path = get_metadata_dir()?;
-        pins_path.push("repository_pins.yaml");
+        let pins_path = self.store_path("repository_pins.yaml")?;
 
         if pins_path.exists() {
             let mut file = File::open(&pins_path)
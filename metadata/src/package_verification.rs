@@ -12,6 +12,29 @@ pub struct PackageSignature {
     pub timestamp: u64,
 }
 
+impl PackageSignature {
+    /// The signature a builder embedded in the package's manifest.yaml, as
+    /// `signature` plus the `signer`'s key fingerprint, if it has one.
+    pub fn embedded(extract_dir: &std::path::Path, package_name: &str, package_version: &str) -> Option<Self> {
+        let manifest = std::fs::read_to_string(extract_dir.join("manifest.yaml")).ok()?;
+        let manifest: serde_norway::Value = serde_norway::from_str(&manifest).ok()?;
+        let field = |key: &str| manifest.get(key)?.as_str().map(|value| value.trim().to_string());
+        let signature_data = field("signature")?;
+        let signature_type = match field("signature_type").map(|kind| kind.to_ascii_lowercase()).as_deref() {
+            Some("gpg") => SignatureType::Gpg,
+            _ => SignatureType::Ed25519,
+        };
+        Some(Self {
+            package_name: package_name.to_string(),
+            package_version: package_version.to_string(),
+            signature_type,
+            signature_data,
+            signer: field("signer").map(|signer| signer.to_ascii_lowercase()),
+            timestamp: 0,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SignatureType {
     Sha256,
@@ -51,6 +74,21 @@ impl PackageVerifier {
         self.trusted_keys.insert(key_id, public_key);
     }
 
+    /// A verifier trusting the keys added with `pax key add`.
+    pub fn from_trust_store() -> Result<Self, String> {
+        let mut verifier = Self::new();
+        verifier.load_trust_store(&crate::repository_auth::TrustStore::system()?)?;
+        Ok(verifier)
+    }
+
+    /// Trust every key in `store`, identified by fingerprint.
+    pub fn load_trust_store(&mut self, store: &crate::repository_auth::TrustStore) -> Result<(), String> {
+        for key in store.keys()? {
+            self.trusted_keys.insert(key.fingerprint(), key.key);
+        }
+        Ok(())
+    }
+
     pub fn verify_package(
         &self,
         package_path: &std::path::Path,
@@ -177,6 +215,15 @@ impl PackageVerifier {
         Ok(format!("{:x}", hasher.finalize()))
     }
 
+    /// A signature only counts when it names a signer in the trust store.
+    fn check_signer(&self, signature: &PackageSignature) -> Result<(), String> {
+        match &signature.signer {
+            Some(signer) if self.trusted_keys.contains_key(signer) => Ok(()),
+            Some(signer) => err!("Untrusted signer: {}", signer),
+            None => err!("Signature does not name its signer"),
+        }
+    }
+
     fn verify_gpg_signature(
         &self,
         _path: &std::path::Path,
@@ -185,18 +232,11 @@ impl PackageVerifier {
         // This would integrate with GPG for actual signature verification
         // For now, we'll do a basic check
         
-        if let Some(signer) = &signature.signer {
-            if !self.trusted_keys.contains_key(signer) {
-                return err!("Untrusted signer: {}", signer);
-            }
-        }
+        self.check_signer(signature)?;
 
         // In a real implementation, this would:
         // 1. Extract the signature from signature_data
         // 2. Use GPG to verify the signature against the package
-        // 3. Check that the signer is in our trusted keys
-        
-        println!("GPG signature verification would happen here");
         Ok(())
     }
 
@@ -208,18 +248,11 @@ impl PackageVerifier {
         // This would integrate with Ed25519 for actual signature verification
         // For now, we'll do a basic check
         
-        if let Some(signer) = &signature.signer {
-            if !self.trusted_keys.contains_key(signer) {
-                return err!("Untrusted signer: {}", signer);
-            }
-        }
+        self.check_signer(signature)?;
 
         // In a real implementation, this would:
         // 1. Extract the signature from signature_data
         // 2. Use Ed25519 to verify the signature against the package
-        // 3. Check that the signer is in our trusted keys
-        
-        println!("Ed25519 signature verification would happen here");
         Ok(())
     }
 
@@ -37,6 +37,10 @@ impl RawApt {
             dependent: true,
             build_dependencies,
             runtime_dependencies,
+            optional_dependencies: Vec::new(),
+            installed_size: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
             install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                 critical: self.critical_files,
                 configs: self.config_files,
@@ -43,6 +43,10 @@ impl RawGithub {
             dependent: true,
             build_dependencies,
             runtime_dependencies,
+            optional_dependencies: Vec::new(),
+            installed_size: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
             install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
                 build: self.build,
                 install: self.install,
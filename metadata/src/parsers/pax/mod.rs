@@ -198,6 +198,10 @@ impl RawPax {
             dependent: true,
             build_dependencies,
             runtime_dependencies,
+            optional_dependencies: Vec::new(),
+            installed_size: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
             install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
                 build: self.build,
                 install: self.install,
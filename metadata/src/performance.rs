@@ -1,7 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
+use crate::file_tracking::FileManifest;
 use crate::processed::ProcessedMetaData;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -331,3 +334,122 @@ impl ParallelDownloader {
         Ok(data)
     }
 }
+
+/// Reverse index of installed paths to their owning packages, persisted as a
+/// sorted `path\tpackage` file so ownership lookups are a map lookup instead
+/// of a scan over every saved manifest. A path shared by several packages,
+/// such as a common directory, keeps every owner.
+#[derive(Debug, Clone, Default)]
+pub struct FileIndex {
+    entries: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+impl FileIndex {
+    pub fn location() -> Result<PathBuf, String> {
+        // Lives beside the manifests it indexes, so alternate roots get their own
+        let installed = utils::get_metadata_dir()?;
+        let mut path = installed.parent().map(Path::to_path_buf).unwrap_or(installed);
+        path.push("cache");
+        path.push("file-index.db");
+        Ok(path)
+    }
+
+    pub fn from_manifests(manifests: &[FileManifest]) -> Self {
+        let mut index = Self::default();
+        for manifest in manifests {
+            index.insert_manifest(manifest);
+        }
+        index
+    }
+
+    /// Rebuild the index from the manifests saved on disk.
+    pub fn rebuild() -> Result<Self, String> {
+        Ok(Self::from_manifests(&crate::file_tracking::load_all_manifests()?))
+    }
+
+    /// Load the persisted index. Returns None if it hasn't been built yet.
+    pub fn load() -> Result<Option<Self>, String> {
+        let path = Self::location()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut index = Self::default();
+        for line in contents.lines() {
+            if let Some((file, package)) = line.rsplit_once('\t') {
+                index.insert(PathBuf::from(file), package.to_string());
+            }
+        }
+        Ok(Some(index))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::location()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut contents = String::new();
+        for (file, owners) in &self.entries {
+            for package in owners {
+                contents.push_str(&format!("{}\t{}\n", file.display(), package));
+            }
+        }
+        fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// The first owner of `path` by name, for paths that belong to one package.
+    pub fn lookup(&self, path: &Path) -> Option<&str> {
+        self.owners(path).next()
+    }
+
+    /// Every package that owns `path`, sorted by name.
+    pub fn owners(&self, path: &Path) -> impl Iterator<Item = &str> {
+        self.entries
+            .get(path)
+            .into_iter()
+            .flat_map(|owners| owners.iter().map(String::as_str))
+    }
+
+    /// Replace everything recorded for the manifest's package with its current contents.
+    pub fn update_manifest(&mut self, manifest: &FileManifest) {
+        self.remove_package(&manifest.package_name);
+        self.insert_manifest(manifest);
+    }
+
+    pub fn remove_package(&mut self, package: &str) {
+        self.entries.retain(|_, owners| {
+            owners.remove(package);
+            !owners.is_empty()
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn insert(&mut self, path: PathBuf, package: String) {
+        self.entries.entry(path).or_default().insert(package);
+    }
+
+    fn insert_manifest(&mut self, manifest: &FileManifest) {
+        for (path, package) in Self::manifest_entries(manifest) {
+            self.insert(path, package);
+        }
+    }
+
+    fn manifest_entries(manifest: &FileManifest) -> impl Iterator<Item = (PathBuf, String)> + '_ {
+        manifest
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .chain(manifest.directories.iter().map(|dir| dir.path.clone()))
+            .chain(manifest.symlinks.iter().map(|symlink| symlink.path.clone()))
+            .map(|path| (path, manifest.package_name.clone()))
+    }
+}
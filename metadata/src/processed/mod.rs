@@ -5,23 +5,24 @@ use settings::OriginKind;
 use std::fmt;
 use std::hash::Hash;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{self, File, OpenOptions},
-    io::{self, Read, Write},
+    io::{self, IsTerminal, Read, Write},
     os::unix::fs::{PermissionsExt, symlink},
     path::{Path, PathBuf},
     process::Command as RunCommand,
-    sync::OnceLock,
+    sync::{Mutex, OnceLock},
     time::{SystemTime, UNIX_EPOCH},
 };
-use tokio::runtime::Runtime;
 use utils::{err, get_update_dir, tmpfile, Range, VerReq, Version};
-use futures::future::{join_all, select_all};
+use futures::future::join_all;
+use futures::stream::{FuturesOrdered, StreamExt};
 use futures::FutureExt;
 
 use crate::{
-    depend_kind::DependKind, DepVer, InstalledInstallKind, InstalledMetaData, MetaDataKind,
-    Specific, installed::InstalledCompilable, parsers::pax::RawPax, parsers::github::RawGithub, parsers::apt::RawApt,
+    download::{DownloadFailure, download_to_file, fetch_with_failover},
+    depend_kind::{DependKind, OptionalDependency}, DepVer, InstalledInstallKind, InstalledMetaData, MetaDataKind,
+    Specific, installed::{InstallReason, InstalledCompilable}, package_verification::{PackageSignature, PackageVerifier}, parsers::pax::RawPax, parsers::github::RawGithub, parsers::apt::RawApt,
 };
 
 // #region agent log
@@ -51,15 +52,16 @@ where
     let mut stack: Vec<PathBuf> = vec![root.to_path_buf()];
 
     while let Some(dir) = stack.pop() {
-        let entries = fs::read_dir(&dir).map_err(|e| {
-            format!("Failed to read directory {}: {}", dir.display(), e)
-        })?;
+        let mut entries = fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to iterate directory {}: {}", dir.display(), e))?;
+        // Visit in name order so manifests come out the same on every run
+        entries.sort();
+        let mut subdirs = Vec::new();
 
-        for entry in entries {
-            let entry = entry.map_err(|e| {
-                format!("Failed to iterate directory {}: {}", dir.display(), e)
-            })?;
-            let path = entry.path();
+        for path in entries {
 
             let metadata = fs::symlink_metadata(&path).map_err(|e| {
                 format!("Failed to inspect {}: {}", path.display(), e)
@@ -79,14 +81,100 @@ where
             visitor(&path, relative, &metadata)?;
 
             if metadata.is_dir() {
-                stack.push(path);
+                subdirs.push(path);
             }
         }
+        // Reversed, so the stack pops them in name order
+        stack.extend(subdirs.into_iter().rev());
     }
 
     Ok(())
 }
 
+/// SHA-256 over the payload an archive extracted to. The embedded manifest
+/// is skipped, which lets a package carry the hash of its own contents.
+/// Entries are fed in path order: files as `path\0contents`, symlinks as
+/// `path\0->target`.
+pub fn payload_sha256(root: &Path) -> Result<String, String> {
+    use sha2::{Digest, Sha256};
+
+    let mut entries = Vec::new();
+    walk_package_payload(root, |path, relative, metadata| {
+        if metadata.is_file() || metadata.file_type().is_symlink() {
+            entries.push((relative.to_path_buf(), path.to_path_buf(), metadata.file_type().is_symlink()));
+        }
+        Ok(())
+    })?;
+    entries.sort();
+
+    let mut hasher = Sha256::new();
+    for (relative, path, is_symlink) in entries {
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        if is_symlink {
+            let target = fs::read_link(&path)
+                .map_err(|e| format!("Failed to read link {}: {}", path.display(), e))?;
+            hasher.update(b"->");
+            hasher.update(target.to_string_lossy().as_bytes());
+        } else {
+            let bytes = fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            hasher.update(&bytes);
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The extension a package file's contents call for: `deb` for an ar
+/// archive, `rpm` for an RPM lead and `pax` for a gzipped tarball.
+pub fn package_extension(file: &Path) -> Option<&'static str> {
+    let mut magic = [0u8; 8];
+    let read = File::open(file).and_then(|mut file| file.read(&mut magic)).ok()?;
+    let magic = &magic[..read];
+    if magic.starts_with(b"!<arch>\n") {
+        Some("deb")
+    } else if magic.starts_with(&[0xED, 0xAB, 0xEE, 0xDB]) {
+        Some("rpm")
+    } else if magic.starts_with(&[0x1F, 0x8B]) {
+        Some("pax")
+    } else {
+        None
+    }
+}
+
+/// Whether a stored hash is the legacy placeholder rather than a real digest.
+pub fn is_placeholder_hash(hash: &str) -> bool {
+    let hash = hash.trim();
+    hash.is_empty() || hash.eq_ignore_ascii_case("unknown") || hash.chars().all(|c| c == '0')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashCheck {
+    Verified,
+    /// The package only carries a placeholder, so there is nothing to check.
+    Placeholder,
+    Mismatch { expected: String, found: String },
+}
+
+/// Compare a package's stored hash with what was downloaded. The hash may
+/// cover the extracted payload or, for packages described by a sidecar or a
+/// repository index, the archive itself; either match is accepted, and any
+/// other real hash is a `Mismatch`.
+pub fn check_package_hash(expected: &str, archive: &Path, extract_dir: &Path) -> Result<HashCheck, String> {
+    if is_placeholder_hash(expected) {
+        return Ok(HashCheck::Placeholder);
+    }
+    let expected = expected.trim().to_ascii_lowercase();
+    let found = payload_sha256(extract_dir)?;
+    if found == expected {
+        return Ok(HashCheck::Verified);
+    }
+    if archive.is_file() && crate::file_tracking::calculate_file_checksum(archive)? == expected {
+        return Ok(HashCheck::Verified);
+    }
+    Ok(HashCheck::Mismatch { expected, found })
+}
+
 fn collect_package_entries(root: &Path) -> Result<Vec<(PathBuf, PathBuf)>, String> {
     let mut entries = Vec::new();
     walk_package_payload(root, |src, relative, _| {
@@ -99,21 +187,17 @@ fn collect_package_entries(root: &Path) -> Result<Vec<(PathBuf, PathBuf)>, Strin
 pub fn render_progress(label: &str, current: usize, total: usize, item: &str) {
     let total = total.max(1);
     let percent = (current * 100) / total;
-    let bar_width = 30usize;
+    let display_item = utils::truncate_start(item, 40);
+
+    // Give the bar whatever the label, percentage and item leave over
+    let width = utils::terminal_width();
+    let used = label.chars().count() + display_item.chars().count() + 10;
+    let bar_width = width.saturating_sub(used).clamp(10, 50);
     let filled = (percent * bar_width) / 100;
     let mut bar = String::new();
     bar.push_str(&"#".repeat(filled.min(bar_width)));
     bar.push_str(&"-".repeat(bar_width.saturating_sub(filled)));
 
-    let mut display_item = item.to_string();
-    if display_item.len() > 40 {
-        let tail_len = 37;
-        display_item = format!(
-            "...{}",
-            &display_item[display_item.len().saturating_sub(tail_len)..]
-        );
-    }
-
     print!(
         "\r\x1B[K{} [{}] {:3}% {}",
         label,
@@ -208,6 +292,24 @@ pub struct QueuedChanges {
     pub install: Vec<String>,
     pub remove: Vec<String>,
     pub upgrade: Vec<String>,
+    // Target version of each queued install/upgrade, where known
+    #[serde(default)]
+    pub versions: BTreeMap<String, String>,
+    // Currently installed version of each queued upgrade
+    #[serde(default)]
+    pub previous: BTreeMap<String, String>,
+    // Bytes to fetch per package; missing entries are unknown
+    #[serde(default)]
+    pub download_sizes: BTreeMap<String, u64>,
+    // Upgrades not queued because the package is held
+    #[serde(default)]
+    pub held: Vec<String>,
+    // Why each held package is held, for the holds that were given a reason
+    #[serde(default)]
+    pub hold_reasons: BTreeMap<String, String>,
+    // Upgrades not queued because a dependency constraint blocks them, with why
+    #[serde(default)]
+    pub held_back: BTreeMap<String, String>,
 }
 
 impl InstallPackage {
@@ -227,51 +329,320 @@ impl InstallPackage {
         deps
     }
     
-    pub fn install(&self, runtime: &Runtime) -> Result<(), String> {
-        // First install runtime dependencies with this package as parent
-        for dep in &self.run_deps {
-            if let Err(e) = runtime.block_on(dep.clone().install_package_impl(false, Some(self.metadata.name.clone()))) {
+    /// The dependencies to place before this package: runtime dependencies,
+    /// then build dependencies if `include_build` is set.
+    pub fn dependency_targets(&self, include_build: bool) -> Vec<&ProcessedMetaData> {
+        let build_deps = if include_build { self.build_deps.as_slice() } else { &[] };
+        self.run_deps.iter().chain(build_deps).collect()
+    }
+
+    /// Install the dependencies without the package itself, like `apt
+    /// build-dep`. The package never gets installed, so each dependency is
+    /// recorded as automatically installed with no parent, which lets it be
+    /// reclaimed as an orphan later.
+    pub fn install_dependencies(&self, include_build: bool, allow_overwrite: bool, verify: bool) -> Result<(), String> {
+        for dep in self.dependency_targets(include_build) {
+            let mut auto = dep.clone();
+            auto.dependent = true;
+            if let Err(e) = crate::block_on(auto.install_package_impl(allow_overwrite, verify, None)).and_then(|result| result) {
                 return Err(format!("Failed to install dependency {}: {}", dep.name, e));
             }
         }
-        
-        // Then install build dependencies with this package as parent
-        for dep in &self.build_deps {
-            if let Err(e) = runtime.block_on(dep.clone().install_package_impl(false, Some(self.metadata.name.clone()))) {
-                return Err(format!("Failed to install build dependency {}: {}", dep.name, e));
-            }
-        }
-        
-        // Finally install the main package (no parent)
-        self.metadata.install(runtime)
+        Ok(())
+    }
+
+    pub fn install(&self) -> Result<(), String> {
+        self.install_with(false, true)
     }
     
-    pub fn install_with_overwrite(&self, runtime: &Runtime) -> Result<(), String> {
+    pub fn install_with_overwrite(&self) -> Result<(), String> {
+        self.install_with(true, true)
+    }
+
+    pub fn install_with(&self, allow_overwrite: bool, verify: bool) -> Result<(), String> {
         // First install runtime dependencies with this package as parent
         for dep in &self.run_deps {
-            if let Err(e) = runtime.block_on(dep.clone().install_package_impl(true, Some(self.metadata.name.clone()))) {
+            if let Err(e) = crate::block_on(dep.clone().install_package_impl(allow_overwrite, verify, Some(self.metadata.name.clone()))).and_then(|result| result) {
                 return Err(format!("Failed to install dependency {}: {}", dep.name, e));
             }
         }
         
         // Then install build dependencies with this package as parent
         for dep in &self.build_deps {
-            if let Err(e) = runtime.block_on(dep.clone().install_package_impl(true, Some(self.metadata.name.clone()))) {
+            if let Err(e) = crate::block_on(dep.clone().install_package_impl(allow_overwrite, verify, Some(self.metadata.name.clone()))).and_then(|result| result) {
                 return Err(format!("Failed to install build dependency {}: {}", dep.name, e));
             }
         }
         
-        // Finally install the main package with overwrite enabled (no parent)
-        self.metadata.install_with_overwrite(runtime)
+        // Finally install the main package (no parent), which the user asked for
+        let mut requested = self.metadata.clone();
+        requested.dependent = false;
+        requested.install_with(allow_overwrite, verify)
+    }
+}
+/// Move files the user chose to keep out of the way of an install, returning
+/// `(original, aside)` pairs for `restore_set_aside`. `paths` are manifest
+/// paths, so they are looked up under the install root.
+fn set_aside(paths: &[PathBuf]) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut aside = Vec::new();
+    for path in paths {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        let path = utils::in_install_root(path);
+        let target = path.with_file_name(format!(".{}.pax-keep", name.to_string_lossy()));
+        std::fs::rename(&path, &target)
+            .map_err(|e| format!("Failed to set aside {}: {}", path.display(), e))?;
+        aside.push((path, target));
+    }
+    Ok(aside)
+}
+
+/// Put kept files back over whatever the install wrote in their place.
+fn restore_set_aside(aside: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    for (path, target) in aside {
+        std::fs::rename(target, path)
+            .map_err(|e| format!("Failed to restore {}: {}", path.display(), e))?;
+        println!("Kept existing {}", path.display());
+    }
+    Ok(())
+}
+
+type MetadataKey = (String, Option<String>, u64);
+
+/// Metadata already fetched during this command, keyed by name, requested
+/// version and a hash of the sources asked, so a dependency shared by several
+/// packages is only looked up once. Misses aren't kept, so a transient
+/// failure can be retried. Separate from the on-disk repository index cache.
+#[derive(Default)]
+pub struct MetadataMemo {
+    entries: Mutex<HashMap<MetadataKey, ProcessedMetaData>>,
+}
+
+impl MetadataMemo {
+    pub fn key(app: &str, version: Option<&str>, sources: &[OriginKind]) -> MetadataKey {
+        use std::hash::{DefaultHasher, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        sources.hash(&mut hasher);
+        (app.to_string(), version.map(str::to_string), hasher.finish())
+    }
+
+    pub async fn get_or_fetch<F, Fut>(&self, key: MetadataKey, fetch: F) -> Result<Option<ProcessedMetaData>, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Option<ProcessedMetaData>, String>>,
+    {
+        if let Some(hit) = self.entries.lock().ok().and_then(|entries| entries.get(&key).cloned()) {
+            return Ok(Some(hit));
+        }
+        let Some(fetched) = fetch().await? else {
+            return Ok(None);
+        };
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(key, fetched.clone());
+        }
+        Ok(Some(fetched))
+    }
+}
+
+fn metadata_memo() -> &'static MetadataMemo {
+    static MEMO: OnceLock<MetadataMemo> = OnceLock::new();
+    MEMO.get_or_init(MetadataMemo::default)
+}
+
+// Where a package will be fetched from, resolved once during planning
+#[derive(Clone, Debug)]
+struct RemotePackageFile {
+    url: String,
+    size: Option<u64>,
+}
+
+type RemoteFileKey = (String, String, OriginKind);
+
+fn remote_file_cache() -> &'static Mutex<HashMap<RemoteFileKey, RemotePackageFile>> {
+    static CACHE: OnceLock<Mutex<HashMap<RemoteFileKey, RemotePackageFile>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Ask the server how big a file is without downloading it. Tries `HEAD`
+/// first, then a one-byte ranged `GET` for servers that don't answer `HEAD`.
+/// Returns None if the size isn't reported.
+async fn remote_content_length(url: &str) -> Option<u64> {
+    use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+
+    let client = reqwest::Client::new();
+    if let Ok(response) = client.head(url).send().await
+        && response.status().is_success()
+        && let Some(length) = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+        && length > 0
+    {
+        return Some(length);
     }
+
+    // "Content-Range: bytes 0-0/12345"
+    let response = client.get(url).header(RANGE, "bytes=0-0").send().await.ok()?;
+    response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit_once('/'))
+        .and_then(|(_, total)| total.parse::<u64>().ok())
 }
+
 impl QueuedChanges {
     pub fn new() -> Self {
         Self {
             install: Vec::new(),
             remove: Vec::new(),
             upgrade: Vec::new(),
+            versions: BTreeMap::new(),
+            previous: BTreeMap::new(),
+            download_sizes: BTreeMap::new(),
+            held: Vec::new(),
+            hold_reasons: BTreeMap::new(),
+            held_back: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.remove.is_empty() && self.upgrade.is_empty()
+    }
+
+    pub fn queue_install(&mut self, package: &str, version: &str) {
+        if self.insert_dependent(package.to_string()) {
+            self.versions.insert(package.to_string(), version.to_string());
+        }
+    }
+
+    pub fn queue_upgrade(&mut self, package: &str, from: &str, to: &str) {
+        if !self.upgrade.iter().any(|p| p == package) {
+            self.upgrade.push(package.to_string());
+        }
+        self.previous.insert(package.to_string(), from.to_string());
+        self.versions.insert(package.to_string(), to.to_string());
+    }
+
+    pub fn queue_remove(&mut self, package: &str) {
+        if !self.remove.iter().any(|p| p == package) {
+            self.remove.push(package.to_string());
+        }
+    }
+
+    /// Look up the download size of each package about to be fetched. Sizes
+    /// that can't be determined are left out and shown as unknown.
+    pub async fn resolve_download_sizes(&mut self, packages: &[&ProcessedMetaData]) {
+        let sizes = join_all(packages.iter().map(|package| package.download_size())).await;
+        for (package, size) in packages.iter().zip(sizes) {
+            if let Some(size) = size {
+                self.download_sizes.insert(package.name.clone(), size);
+            }
+        }
+    }
+
+    /// Sum of the known download sizes, and whether any queued fetch has an unknown size.
+    pub fn download_size(&self) -> (u64, bool) {
+        let mut total = 0;
+        let mut unknown = false;
+        for package in self.install.iter().chain(&self.upgrade) {
+            match self.download_sizes.get(package) {
+                Some(size) => total += size,
+                None => unknown = true,
+            }
+        }
+        (total, unknown)
+    }
+
+    /// Render the plan as e.g. "Installing (2): a(1.0), b(2.1)", one line per
+    /// kind of change, followed by the total download size where known.
+    pub fn render_plan(&self) -> String {
+        let mut lines = Vec::new();
+        let describe = |package: &String| match self.versions.get(package) {
+            Some(version) => format!("{}({})", package, version),
+            None => package.clone(),
+        };
+        if !self.install.is_empty() {
+            lines.push(format!(
+                "Installing ({}): \x1B[92m{}\x1B[0m",
+                self.install.len(),
+                self.install.iter().map(describe).collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if !self.upgrade.is_empty() {
+            let upgrades = self
+                .upgrade
+                .iter()
+                .map(|package| match (self.previous.get(package), self.versions.get(package)) {
+                    (Some(from), Some(to)) => format!("{}({}->{})", package, from, to),
+                    _ => describe(package),
+                })
+                .collect::<Vec<_>>();
+            lines.push(format!(
+                "Upgrading ({}): \x1B[94m{}\x1B[0m",
+                self.upgrade.len(),
+                upgrades.join(", ")
+            ));
+        }
+        if !self.remove.is_empty() {
+            lines.push(format!(
+                "Removing ({}): \x1B[91m{}\x1B[0m",
+                self.remove.len(),
+                self.remove.join(", ")
+            ));
+        }
+        if !self.held.is_empty() {
+            let held = self
+                .held
+                .iter()
+                .map(|package| match self.hold_reasons.get(package) {
+                    Some(reason) => format!("{} ({})", package, reason),
+                    None => package.clone(),
+                })
+                .collect::<Vec<_>>();
+            lines.push(format!(
+                "Held ({}): \x1B[93m{}\x1B[0m",
+                self.held.len(),
+                held.join(", ")
+            ));
+        }
+        if !self.held_back.is_empty() {
+            let kept = self
+                .held_back
+                .iter()
+                .map(|(package, reason)| format!("{} ({})", package, reason))
+                .collect::<Vec<_>>();
+            lines.push(format!(
+                "Kept back ({}): \x1B[93m{}\x1B[0m",
+                self.held_back.len(),
+                kept.join(", ")
+            ));
+        }
+        if !self.install.is_empty() || !self.upgrade.is_empty() {
+            let (total, unknown) = self.download_size();
+            if total > 0 {
+                lines.push(format!(
+                    "Total download size: {}{}",
+                    if unknown { "at least " } else { "" },
+                    utils::format_size(total)
+                ));
+            } else if unknown {
+                lines.push(String::from("Total download size: unknown"));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Print the plan and ask before applying it. `assume_yes` skips the prompt.
+    pub fn confirm(&self, prompt: &str, assume_yes: bool) -> Result<bool, String> {
+        println!("\n{}", self.render_plan());
+        if assume_yes {
+            return Ok(true);
         }
+        utils::choice(prompt, true)
     }
 
     pub fn insert_primary(&mut self, package: String) -> bool {
@@ -307,6 +678,17 @@ pub struct ProcessedMetaData {
     pub dependent: bool,
     pub build_dependencies: Vec<DependKind>,
     pub runtime_dependencies: Vec<DependKind>,
+    #[serde(default)]
+    pub optional_dependencies: Vec<OptionalDependency>,
+    /// Bytes on disk: measured once installed, otherwise the estimate the
+    /// package metadata gives, if any.
+    #[serde(default)]
+    pub installed_size: Option<u64>,
+    /// Search terms and browsing categories from the package metadata.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
     pub install_kind: ProcessedInstallKind,
     pub hash: String,
     // Additional fields expected by the application
@@ -321,13 +703,15 @@ pub struct ProcessedMetaData {
 impl ProcessedMetaData {
     fn debug_enabled() -> bool {
         static DEBUG: OnceLock<bool> = OnceLock::new();
+        // `-vv` turns fetch tracing on as well as the older PAX_DEBUG_FETCH
         *DEBUG.get_or_init(|| {
-            std::env::var("PAX_DEBUG_FETCH")
-                .map(|v| {
-                    let v = v.trim().to_ascii_lowercase();
-                    matches!(v.as_str(), "1" | "true" | "yes" | "on")
-                })
-                .unwrap_or(false)
+            utils::logging::enabled(utils::logging::LogLevel::Debug)
+                || std::env::var("PAX_DEBUG_FETCH")
+                    .map(|v| {
+                        let v = v.trim().to_ascii_lowercase();
+                        matches!(v.as_str(), "1" | "true" | "yes" | "on")
+                    })
+                    .unwrap_or(false)
         })
     }
 
@@ -347,6 +731,7 @@ impl ProcessedMetaData {
             origin: self.origin.clone(),
             dependent: self.dependent,
             installed_by,
+            install_reason: Some(if self.dependent { InstallReason::Auto } else { InstallReason::Manual }),
             dependencies: {
                 let mut result = Vec::new();
                 for dep in &self.runtime_dependencies {
@@ -357,6 +742,11 @@ impl ProcessedMetaData {
                 result
             },
             dependents: Vec::new(),
+            optional_dependencies: self.optional_dependencies.clone(),
+            installed_size: None,
+            installed_files: Vec::new(),
+            keywords: self.keywords.clone(),
+            categories: self.categories.clone(),
             install_kind: match &self.install_kind {
                 ProcessedInstallKind::PreBuilt(prebuilt) => {
                     InstalledInstallKind::PreBuilt(prebuilt.clone())
@@ -372,33 +762,29 @@ impl ProcessedMetaData {
         }
     }
     
+    /// The optional dependencies `selection` asks to install with this package.
+    pub fn selected_optional(&self, selection: &OptionalSelection) -> Vec<&OptionalDependency> {
+        self.optional_dependencies
+            .iter()
+            .filter(|optional| selection.includes(&self.name, &optional.dependency.name()))
+            .collect()
+    }
+
     pub fn to_installed(&self) -> InstalledMetaData {
         self.to_installed_with_parent(None)
     }
     
     pub async fn install_package(self) -> Result<(), String> {
-        self.install_package_impl(false, None).await
+        self.install_package_impl(false, true, None).await
     }
     
-    async fn install_package_impl(self, allow_overwrite: bool, installed_by: Option<String>) -> Result<(), String> {
+    async fn install_package_impl(self, allow_overwrite: bool, verify: bool, installed_by: Option<String>) -> Result<(), String> {
         let name = self.name.to_string();
         println!("Installing {name}...");
         
         // Get the package file (download or use local)
         let package_file = self.get_package_file().await?;
         
-        // Note: Hash verification is skipped for packages with embedded manifests
-        // because the hash in manifest.yaml is the hash of the entire archive including
-        // the manifest, creating a circular verification problem.
-        // For packages with sidecar metadata files (.pax.meta), verification can be performed.
-        
-        if !self.hash.is_empty() && self.hash != "unknown" && !self.hash.starts_with('0') {
-            // This package has a valid hash, but we don't verify for embedded manifests
-            println!("\x1B[92m[OK]\x1B[0m Package metadata loaded (embedded manifest)");
-        } else {
-            println!("\x1B[93m[WARN]\x1B[0m Package hash not provided or placeholder, skipping verification");
-        }
-        
         // Create temporary extraction directory
         let extract_dir = std::env::temp_dir().join(format!("pax_install_{}", std::process::id()));
         std::fs::create_dir_all(&extract_dir)
@@ -406,14 +792,53 @@ impl ProcessedMetaData {
         
         // Extract the package
         self.extract_package(&package_file, &extract_dir).await?;
+
+        if verify {
+            match check_package_hash(&self.hash, &package_file, &extract_dir) {
+                Ok(HashCheck::Verified) => println!("\x1B[92m[OK]\x1B[0m Package hash verified"),
+                Ok(HashCheck::Placeholder) => {
+                    println!("\x1B[93m[WARN]\x1B[0m Package hash not provided or placeholder, skipping verification");
+                }
+                Ok(HashCheck::Mismatch { expected, found }) => {
+                    let _ = std::fs::remove_dir_all(&extract_dir);
+                    return err!("Hash mismatch for {}: expected {}, found {}", name, expected, found);
+                }
+                Err(fault) => {
+                    let _ = std::fs::remove_dir_all(&extract_dir);
+                    return Err(fault);
+                }
+            }
+            // Signed packages must name a key from the trust store
+            if let Some(signature) = PackageSignature::embedded(&extract_dir, &self.name, &self.version) {
+                let checked = PackageVerifier::from_trust_store()
+                    .and_then(|verifier| verifier.verify_package(&package_file, Some(&signature)));
+                match checked {
+                    Ok(result) if result.is_valid => println!(
+                        "\x1B[93m[WARN]\x1B[0m {} names trusted signer {}, but its signature is not cryptographically verified",
+                        name,
+                        signature.signer.as_deref().unwrap_or_default()
+                    ),
+                    Ok(result) => {
+                        let _ = std::fs::remove_dir_all(&extract_dir);
+                        return err!("Signature check failed for {}: {}", name, result.details.trim());
+                    }
+                    Err(fault) => {
+                        let _ = std::fs::remove_dir_all(&extract_dir);
+                        return Err(fault);
+                    }
+                }
+            }
+        } else {
+            println!("\x1B[93m[WARN]\x1B[0m Hash verification disabled, not checking {}", name);
+        }
         
         // Check for file conflicts before installation
-        let file_manifest = self.create_file_manifest(&extract_dir).await?;
+        let mut file_manifest = self.create_file_manifest(&extract_dir).await?;
         let conflicts = file_manifest.check_conflicts()?;
         
         if !conflicts.is_empty() {
             if allow_overwrite {
-                println!("\x1B[93m[WARN] File conflicts detected, but --allow-overwrite is enabled:\x1B[0m");
+                println!("\x1B[93m[WARN] File conflicts detected, but overwriting is allowed:\x1B[0m");
             } else {
                 println!("\x1B[93m[WARN] File conflicts detected:\x1B[0m");
             }
@@ -437,16 +862,34 @@ impl ProcessedMetaData {
                     }
                 }
             }
-            if !allow_overwrite {
-                println!("\x1B[93m[WARN] Proceeding with installation - existing files will be backed up.\x1B[0m");
-            }
         }
+        // --allowerasing, --yes and non-interactive runs back up and
+        // overwrite; otherwise each conflicting file is asked about
+        let keep = if conflicts.is_empty() {
+            Vec::new()
+        } else if allow_overwrite || !std::io::stdin().is_terminal() {
+            println!("\x1B[93m[WARN] Proceeding with installation - existing files will be backed up.\x1B[0m");
+            Vec::new()
+        } else {
+            match crate::conflict_resolution::prompt_for_conflicts(&conflicts) {
+                Ok(keep) => keep,
+                Err(fault) => {
+                    let _ = std::fs::remove_dir_all(&extract_dir);
+                    return Err(fault);
+                }
+            }
+        };
+        let overwritten: HashSet<PathBuf> = conflicts
+            .iter()
+            .map(|conflict| conflict.path.clone())
+            .filter(|path| !keep.contains(path))
+            .collect();
+        file_manifest.backup_files_where(|path| overwritten.contains(path))?;
+        let kept = set_aside(&keep)?;
+        file_manifest.files.retain(|file| !keep.contains(&file.path));
+        file_manifest.symlinks.retain(|symlink| !keep.contains(&symlink.path));
         
-        // Get install root from environment variable PAX_ROOT, default to /
-        let install_root = std::env::var("PAX_ROOT")
-            .ok()
-            .map(|r| PathBuf::from(r))
-            .unwrap_or_else(|| PathBuf::from("/"));
+        let install_root = utils::install_root();
         
         // Install based on package type
         // For Compilable packages from repositories, they are prebuilt and install commands handle file placement
@@ -454,32 +897,34 @@ impl ProcessedMetaData {
         println!("[INSTALL_PKG] Package type: {:?}", self.install_kind);
         println!("[INSTALL_PKG] Extract dir: {}", extract_dir.display());
         println!("[INSTALL_PKG] Install root: {}", install_root.display());
-        match self.install_kind {
+        let installed = match self.install_kind {
             ProcessedInstallKind::PreBuilt(ref prebuilt) => {
                 println!("[INSTALL_PKG] Installing as PreBuilt package");
-                self.install_prebuilt_package_to_root(&extract_dir, prebuilt, allow_overwrite, &install_root).await?;
+                self.install_prebuilt_package_to_root(&extract_dir, prebuilt, allow_overwrite, &install_root).await
             }
             ProcessedInstallKind::Compilable(ref compilable) => {
                 println!("[INSTALL_PKG] Installing as Compilable package");
                 println!("[INSTALL_PKG] Compilable install commands length: {}", compilable.install.len());
                 // Always run install commands - they use DESTDIR to place files correctly
-                self.install_compilable_package_to_root(&extract_dir, compilable, &install_root).await?;
+                self.install_compilable_package_to_root(&extract_dir, compilable, &install_root).await
             }
-        }
+        };
+        // Kept files go back even if the install failed part way
+        restore_set_aside(&kept)?;
+        installed?;
+
+        // Save installed metadata; under an alternate root this lands in the
+        // root's own metadata directory rather than the host's
+        let installed_dir = utils::get_metadata_dir()?;
+        let package_file = installed_dir.join(format!("{}.json", name));
+        let path = package_file;
+        let mut metadata = self.to_installed_with_parent(installed_by);
+        metadata.record_files(&file_manifest);
+        metadata.settle_reason(InstalledMetaData::open(&metadata.name).ok().as_ref());
+        metadata.write(&path)?;
         
-        // Save installed metadata - but skip if installing to custom root (PAX_ROOT)
-        // We don't want to pollute system metadata when building ISO
-        let pax_root = std::env::var("PAX_ROOT").ok();
-        if pax_root.is_none() || pax_root.as_deref() == Some("/") {
-            let installed_dir = utils::get_metadata_dir()?;
-            let package_file = installed_dir.join(format!("{}.json", name));
-            let path = package_file;
-            let metadata = self.to_installed_with_parent(installed_by);
-            metadata.write(&path)?;
-            
-            // Save file manifest for conflict detection
-            file_manifest.save()?;
-        }
+        // Save file manifest for conflict detection
+        file_manifest.save()?;
         
         // Clean up
         let _ = std::fs::remove_dir_all(&extract_dir);
@@ -494,6 +939,8 @@ impl ProcessedMetaData {
         println!("Installing pre-built files from package...");
         
         let mut manifest = FileManifest::new(self.name.clone(), self.version.clone());
+        // Manifests record paths as seen from inside the install root
+        let recorded = |path: &Path| Path::new("/").join(path.strip_prefix(install_root).unwrap_or(path));
         let entries = collect_package_entries(extract_dir)?;
         let total = entries.len().max(1);
         let mut processed = 0usize;
@@ -519,7 +966,7 @@ impl ProcessedMetaData {
                 fs::set_permissions(&dest_path, std::fs::Permissions::from_mode(mode)).map_err(|e| {
                     format!("Failed to set permissions: {}", e)
                 })?;
-                manifest.add_directory(dest_path.clone(), mode);
+                manifest.add_directory(recorded(&dest_path), mode);
             } else if metadata.file_type().is_symlink() {
                 if let Some(parent) = dest_path.parent() {
                     fs::create_dir_all(parent).map_err(|e| format!("Failed to create parent: {}", e))?;
@@ -527,7 +974,7 @@ impl ProcessedMetaData {
                 let target = fs::read_link(&src_path).map_err(|e| format!("Failed to read symlink: {}", e))?;
                 let _ = fs::remove_file(&dest_path);
                 symlink(&target, &dest_path).map_err(|e| format!("Failed to create symlink: {}", e))?;
-                manifest.add_symlink(dest_path.clone(), target);
+                manifest.add_symlink(recorded(&dest_path), target);
             } else if metadata.is_file() {
                 if let Some(parent) = dest_path.parent() {
                     fs::create_dir_all(parent).map_err(|e| format!("Failed to create parent: {}", e))?;
@@ -539,7 +986,7 @@ impl ProcessedMetaData {
                 let mode = metadata.permissions().mode();
                 fs::set_permissions(&dest_path, std::fs::Permissions::from_mode(mode)).map_err(|e| format!("Failed to set permissions: {}", e))?;
                 let checksum = crate::file_tracking::calculate_file_checksum(&dest_path).unwrap_or_default();
-                manifest.add_file(dest_path.clone(), metadata.len(), mode, checksum);
+                manifest.add_file(recorded(&dest_path), metadata.len(), mode, checksum);
             }
             
             render_progress("Installing", processed, total, &relative_clean.to_string_lossy());
@@ -547,20 +994,19 @@ impl ProcessedMetaData {
         
         println!("\nInstalled {} files from prebuilt package.", manifest.files.len());
         
-        // Save metadata and manifest if not using custom root
-        let pax_root = std::env::var("PAX_ROOT").ok();
-        if pax_root.is_none() || pax_root.as_deref() == Some("/") {
-            let installed_dir = utils::get_metadata_dir()?;
-            let package_file = installed_dir.join(format!("{}.json", self.name));
-            let metadata = self.to_installed_with_parent(installed_by);
-            metadata.write(&package_file)?;
-            manifest.save()?;
-        }
+        // Save metadata and manifest (redirected under an alternate root)
+        let installed_dir = utils::get_metadata_dir()?;
+        let package_file = installed_dir.join(format!("{}.json", self.name));
+        let mut metadata = self.to_installed_with_parent(installed_by);
+        metadata.record_files(&manifest);
+        metadata.settle_reason(InstalledMetaData::open(&metadata.name).ok().as_ref());
+        metadata.write(&package_file)?;
+        manifest.save()?;
         
         Ok(())
     }
     
-    async fn create_file_manifest(&self, extract_dir: &Path) -> Result<crate::file_tracking::FileManifest, String> {
+    pub(crate) async fn create_file_manifest(&self, extract_dir: &Path) -> Result<crate::file_tracking::FileManifest, String> {
         use crate::file_tracking::FileManifest;
         
         let mut manifest = FileManifest::new(self.name.clone(), self.version.clone());
@@ -572,14 +1018,22 @@ impl ProcessedMetaData {
         Ok(manifest)
     }
     
+    /// Record everything under `extract_base` in `manifest`, placed under
+    /// `target_base`. Manifests written before subdirectories were walked
+    /// under their own path record nested entries at the root, /usr/bin/foo
+    /// as /foo, so removing or verifying those packages checks the wrong
+    /// paths until a reinstall writes a fresh manifest.
     fn walk_directory(&self, extract_base: &Path, target_base: &PathBuf, manifest: &mut crate::file_tracking::FileManifest) -> Result<(), String> {
         use std::fs;
         
-        for entry in fs::read_dir(extract_base)
-            .map_err(|e| format!("Failed to read directory {}: {}", extract_base.display(), e))? {
-            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-            let extract_path = entry.path();
-            let metadata = entry.metadata()
+        let mut entries = fs::read_dir(extract_base)
+            .map_err(|e| format!("Failed to read directory {}: {}", extract_base.display(), e))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read entry: {}", e))?;
+        entries.sort();
+        for extract_path in entries {
+            let metadata = fs::symlink_metadata(&extract_path)
                 .map_err(|e| format!("Failed to get metadata for {}: {}", extract_path.display(), e))?;
             
             // Map the extraction path to target path
@@ -597,8 +1051,8 @@ impl ProcessedMetaData {
             } else if metadata.is_dir() {
                 let permissions = metadata.permissions().mode();
                 manifest.add_directory(target_path.clone(), permissions);
-                // Recursively process subdirectories
-                self.walk_directory(&extract_path, target_base, manifest)?;
+                // Recursively process subdirectories, under their own path
+                self.walk_directory(&extract_path, &target_path, manifest)?;
             } else if metadata.file_type().is_symlink() {
                 let target = fs::read_link(&extract_path)
                     .map_err(|e| format!("Failed to read symlink target: {}", e))?;
@@ -609,10 +1063,142 @@ impl ProcessedMetaData {
         Ok(())
     }
     
+    /// Resolve the URL this package downloads from and its size, caching the
+    /// result so the later download doesn't repeat the repository lookup.
+    /// Returns None for packages that are copied from the local filesystem.
+    async fn remote_file(&self) -> Result<Option<RemotePackageFile>, String> {
+        self.origin.ensure_reachable()?;
+        let key = (self.name.clone(), self.version.clone(), self.origin.clone());
+        if let Some(cached) = remote_file_cache().lock().ok().and_then(|cache| cache.get(&key).cloned()) {
+            return Ok(Some(cached));
+        }
+
+        let (url, listed_size) = match &self.origin {
+            OriginKind::Pax(pax) => {
+                if !pax.starts_with("http://") && !pax.starts_with("https://") {
+                    return Ok(None);
+                }
+                (pax.clone(), None)
+            }
+            OriginKind::Github { user, repo } => (
+                format!("https://github.com/{}/{}/archive/refs/tags/{}.tar.gz", user, repo, self.version),
+                None,
+            ),
+            OriginKind::Apt(source) => {
+                if std::path::Path::new(source).exists() {
+                    return Ok(None);
+                }
+                use crate::deb_repository::{DebRepositoryClient, pool_path};
+                use settings::{AptLayout, SettingsYaml};
+
+                let base = source.trim_end_matches('/');
+                // Prefer the file the Packages index names; the layout only
+                // guesses a path when the index has no entry for the package
+                match DebRepositoryClient::new(base.to_string())
+                    .get_package(&self.name, Some(&self.version))
+                    .await
+                {
+                    Ok(package_info) => (package_info.url, Some(package_info.size)),
+                    Err(_) => {
+                        let settings = SettingsYaml::get_settings().unwrap_or_default();
+                        let url = match settings.apt_layout(&self.origin) {
+                            AptLayout::Flat => format!("{}/packages/{}/{}.deb", base, self.name, self.version),
+                            AptLayout::Pool => format!(
+                                "{}/{}",
+                                base,
+                                pool_path(&self.name, &self.version, settings.target_arch().deb_arch())
+                            ),
+                        };
+                        (url, None)
+                    }
+                }
+            }
+            OriginKind::Rpm(repo_url) | OriginKind::Yum(repo_url) => {
+                use crate::yum_repository::YumRepositoryClient;
+
+                let client = YumRepositoryClient::new(repo_url.clone());
+                let package_info = client.get_package(&self.name, Some(&self.version)).await
+                    .map_err(|_| "Failed to get RPM package info")?;
+                (package_info.url, Some(package_info.size))
+            }
+            OriginKind::Deb(repo_url) => {
+                use crate::deb_repository::DebRepositoryClient;
+
+                let client = DebRepositoryClient::new(repo_url.clone());
+                let package_info = client.get_package(&self.name, Some(&self.version)).await
+                    .map_err(|_| "Failed to get package info from DEB repository")?;
+                (package_info.url, Some(package_info.size))
+            }
+            // R2 downloads are signed per request, so there's no URL to reuse
+            OriginKind::CloudflareR2 { .. } | OriginKind::LocalDir(_) => return Ok(None),
+        };
+
+        let size = match listed_size {
+            Some(size) if size > 0 => Some(size),
+            _ => remote_content_length(&url).await,
+        };
+        let resolved = RemotePackageFile { url, size };
+        if let Ok(mut cache) = remote_file_cache().lock() {
+            cache.insert(key, resolved.clone());
+        }
+        Ok(Some(resolved))
+    }
+
+    /// Bytes that installing this package will download. Local packages
+    /// download nothing; None means the server didn't say.
+    pub async fn download_size(&self) -> Option<u64> {
+        match &self.origin {
+            OriginKind::CloudflareR2 { .. } => None,
+            _ => match self.remote_file().await {
+                Ok(Some(remote)) => remote.size,
+                Ok(None) => Some(0),
+                Err(_) => None,
+            },
+        }
+    }
+
+    /// The name the package file is saved under by `--download-only`, e.g.
+    /// `foo-1.0.0.pax`, which a `LocalDir` origin finds again. This goes by
+    /// the origin; `download_to` goes by the file itself when it can tell.
+    pub fn canonical_file_name(&self) -> String {
+        let extension = match &self.origin {
+            OriginKind::Apt(_) | OriginKind::Deb(_) => "deb",
+            OriginKind::Rpm(_) | OriginKind::Yum(_) => "rpm",
+            OriginKind::Pax(source) => match Path::new(source).extension().and_then(|ext| ext.to_str()) {
+                Some("deb") => "deb",
+                Some("rpm") => "rpm",
+                _ => "pax",
+            },
+            _ => "pax",
+        };
+        format!("{}-{}.{}", self.name, self.version, extension)
+    }
+
+    /// Fetch the package file into `destdir` under its canonical name
+    /// without extracting or installing anything. Returns the saved path.
+    pub async fn download_to(&self, destdir: &std::path::Path) -> Result<std::path::PathBuf, String> {
+        let package_file = self.get_package_file().await?;
+        // A local directory or bucket can hold .deb and .rpm files as well
+        let file_name = match package_extension(&package_file) {
+            Some(extension) => format!("{}-{}.{}", self.name, self.version, extension),
+            None => self.canonical_file_name(),
+        };
+        let target = destdir.join(file_name);
+        // The temporary file may be on another filesystem, so copy rather than rename
+        let copied = std::fs::copy(&package_file, &target)
+            .map_err(|e| format!("Failed to save {}: {}", target.display(), e));
+        let _ = std::fs::remove_file(&package_file);
+        copied?;
+        Ok(target)
+    }
+
     async fn get_package_file(&self) -> Result<std::path::PathBuf, String> {
+        self.origin.ensure_reachable()?;
         let tmpfile = tmpfile().ok_or("Failed to reserve temporary file")?;
+        let origin = self.origin.normalize_file_url();
+        let label = format!("Downloading {}", self.name);
         
-        match &self.origin {
+        match &origin {
             OriginKind::Pax(pax) => {
                 let pax_path = std::path::Path::new(pax);
                 if pax_path.exists() {
@@ -620,31 +1206,29 @@ impl ProcessedMetaData {
                     std::fs::copy(pax, &tmpfile)
                         .map_err(|e| format!("Failed to copy local PAX file: {}", e))?;
                 } else if pax.starts_with("http://") || pax.starts_with("https://") {
-                    // Remote file - download directly
+                    // Remote file - download directly, falling through the mirror list
                     // PAX repositories now just serve .pax files directly
-                    let response = reqwest::get(pax.as_str()).await
-                        .map_err(|e| format!("Failed to download PAX file: {}", e))?;
-                    
-                    if !response.status().is_success() {
-                        return Err(format!("HTTP error {} when downloading PAX file from {}", response.status(), pax));
+                    // Reading the list may block on the network, so keep it off the runtime
+                    let mirrors = tokio::task::spawn_blocking(settings::configured_mirrors).await.unwrap_or_default();
+                    let candidates = settings::mirror_candidates(pax, mirrors, settings::last_good_mirror().as_deref());
+                    if candidates.is_empty() {
+                        download_to_file(pax, &tmpfile, &label).await.map_err(DownloadFailure::into_message)?;
+                    } else {
+                        let (mirror, _) = fetch_with_failover(candidates, |url| {
+                            let tmpfile = tmpfile.clone();
+                            let label = label.clone();
+                            async move { download_to_file(&url, &tmpfile, &label).await }
+                        })
+                        .await?;
+                        settings::remember_mirror(&mirror);
                     }
-                    
-                    let bytes = response.bytes().await
-                        .map_err(|e| format!("Failed to read PAX file data: {}", e))?;
-                    std::fs::write(&tmpfile, bytes)
-                        .map_err(|e| format!("Failed to write PAX file to temp: {}", e))?;
                 } else {
                     return Err(format!("Package file does not exist: {}", pax));
                 }
             }
-            OriginKind::Github { user, repo } => {
-                let endpoint = format!("https://github.com/{}/{}/archive/refs/tags/{}.tar.gz", user, repo, self.version);
-                let response = reqwest::get(&endpoint).await
-                    .map_err(|_| "Failed to download GitHub archive")?;
-                let bytes = response.bytes().await
-                    .map_err(|_| "Failed to read GitHub archive data")?;
-                std::fs::write(&tmpfile, bytes)
-                    .map_err(|_| "Failed to write GitHub archive to temp")?;
+            OriginKind::Github { .. } => {
+                let endpoint = self.remote_file().await?.ok_or("Failed to resolve GitHub archive URL")?.url;
+                download_to_file(&endpoint, &tmpfile, &label).await.map_err(DownloadFailure::into_message)?;
             }
             OriginKind::Apt(source) => {
                 let path = std::path::Path::new(source);
@@ -652,30 +1236,14 @@ impl ProcessedMetaData {
                     std::fs::copy(path, &tmpfile)
                         .map_err(|_| "Failed to copy local DEB package")?;
                 } else {
-                    let base = source.trim_end_matches('/');
-                    let endpoint = format!("{}/packages/{}/{}.deb", base, self.name, self.version);
-                    let response = reqwest::get(&endpoint).await
-                        .map_err(|_| "Failed to download APT package")?;
-                    let bytes = response.bytes().await
-                        .map_err(|_| "Failed to read APT package data")?;
-                    std::fs::write(&tmpfile, bytes)
-                        .map_err(|_| "Failed to write APT package to temp")?;
+                    let endpoint = self.remote_file().await?.ok_or("Failed to resolve APT package URL")?.url;
+                    download_to_file(&endpoint, &tmpfile, &label).await.map_err(DownloadFailure::into_message)?;
                 }
             }
-            OriginKind::Rpm(repo_url) => {
-                use crate::yum_repository::YumRepositoryClient;
-                
-                let client = YumRepositoryClient::new(repo_url.clone());
-                let package_info = client.get_package(&self.name, Some(&self.version)).await
-                    .map_err(|_| "Failed to get RPM package info")?;
-                
-                let response = reqwest::get(&package_info.url).await
-                        .map_err(|_| "Failed to download RPM package")?;
-                    let bytes = response.bytes().await
-                        .map_err(|_| "Failed to read RPM package data")?;
-                    std::fs::write(&tmpfile, bytes)
-                        .map_err(|_| "Failed to write RPM package to temp")?;
-                }
+            OriginKind::Rpm(_) => {
+                let package_url = self.remote_file().await?.ok_or("Failed to get RPM package info")?.url;
+                download_to_file(&package_url, &tmpfile, &label).await.map_err(DownloadFailure::into_message)?;
+            }
             OriginKind::CloudflareR2 { bucket, account_id, .. } => {
                 use crate::cloudflare_r2::CloudflareR2Client;
                 
@@ -690,39 +1258,18 @@ impl ProcessedMetaData {
                 let package_info = client.get_package(&self.name, Some(&self.version)).await
                     .map_err(|_| "Failed to get package info from R2")?;
                 
-                let bytes = client.download_package(&package_info).await
-                    .map_err(|_| "Failed to download package from R2")?;
-                
-                std::fs::write(&tmpfile, bytes)
-                    .map_err(|_| "Failed to write R2 package to temp")?;
+                client.download_package(&package_info, &tmpfile).await
+                    .map_err(|e| format!("Failed to download package from R2: {}", e))?;
             }
-            OriginKind::Deb(repo_url) => {
-                use crate::deb_repository::DebRepositoryClient;
-                
-                let client = DebRepositoryClient::new(repo_url.clone());
-                
-                let package_info = client.get_package(&self.name, Some(&self.version)).await
-                    .map_err(|_| "Failed to get package info from DEB repository")?;
-                
-                let bytes = client.download_package(&package_info).await
-                    .map_err(|_| "Failed to download package from DEB repository")?;
-                
-                std::fs::write(&tmpfile, bytes)
-                    .map_err(|_| "Failed to write DEB package to temp")?;
+            OriginKind::Deb(_) => {
+                let package_url = self.remote_file().await?
+                    .ok_or("Failed to get package info from DEB repository")?.url;
+                download_to_file(&package_url, &tmpfile, &label).await.map_err(DownloadFailure::into_message)?;
             }
-            OriginKind::Yum(repo_url) => {
-                use crate::yum_repository::YumRepositoryClient;
-                
-                let client = YumRepositoryClient::new(repo_url.clone());
-                
-                let package_info = client.get_package(&self.name, Some(&self.version)).await
-                    .map_err(|_| "Failed to get package info from YUM repository")?;
-                
-                let bytes = client.download_package(&package_info).await
-                    .map_err(|_| "Failed to download package from YUM repository")?;
-                
-                std::fs::write(&tmpfile, bytes)
-                    .map_err(|_| "Failed to write RPM package to temp")?;
+            OriginKind::Yum(_) => {
+                let package_url = self.remote_file().await?
+                    .ok_or("Failed to get package info from YUM repository")?.url;
+                download_to_file(&package_url, &tmpfile, &label).await.map_err(DownloadFailure::into_message)?;
             }
             OriginKind::LocalDir(dir_path) => {
                 // Find package file in local directory
@@ -739,8 +1286,9 @@ impl ProcessedMetaData {
                     dir.join(format!("{}_{}.deb", self.name, self.version)),
                 ];
                 
-                // Also try with architecture suffixes (x86_64v3, x86_64v1, x86_64)
-                for arch in &["x86_64v3", "x86_64v1", "x86_64"] {
+                // Also try with architecture suffixes, the --arch override first
+                let preferred = settings::arch_override().map(|arch| arch.to_string());
+                for arch in preferred.iter().map(String::as_str).chain(["x86_64v3", "x86_64v1", "x86_64"]) {
                     possible_files.push(dir.join(format!("{}-{}-{}.pax", self.name, self.version, arch)));
                     possible_files.push(dir.join(format!("{}-{}-{}.deb", self.name, self.version, arch)));
                     possible_files.push(dir.join(format!("{}-{}-{}.rpm", self.name, self.version, arch)));
@@ -917,6 +1465,8 @@ impl ProcessedMetaData {
             self.name.clone(),
             self.version.clone(),
         );
+        // Manifests record paths as seen from inside the install root
+        let recorded = |path: &Path| Path::new("/").join(path.strip_prefix(install_root).unwrap_or(path));
 
         let entries = collect_package_entries(extract_dir)?;
         println!("[INSTALL_PREBUILT] Found {} entries to install", entries.len());
@@ -955,7 +1505,7 @@ impl ProcessedMetaData {
                     )
                 })?;
 
-                manifest.add_directory(dest_path.clone(), mode);
+                manifest.add_directory(recorded(&dest_path), mode);
             } else if metadata.file_type().is_symlink() {
                 if let Some(parent) = dest_path.parent() {
                     fs::create_dir_all(parent).map_err(|e| {
@@ -1006,7 +1556,7 @@ impl ProcessedMetaData {
                     }
                 }
 
-                manifest.add_symlink(dest_path.clone(), target);
+                manifest.add_symlink(recorded(&dest_path), target);
             } else if metadata.is_file() {
                 if let Some(parent) = dest_path.parent() {
                     fs::create_dir_all(parent).map_err(|e| {
@@ -1044,7 +1594,7 @@ impl ProcessedMetaData {
                 let checksum = crate::file_tracking::calculate_file_checksum(&dest_path)
                     .unwrap_or_default();
 
-                manifest.add_file(dest_path.clone(), metadata.len(), mode, checksum);
+                manifest.add_file(recorded(&dest_path), metadata.len(), mode, checksum);
             }
 
             render_progress(
@@ -1073,10 +1623,12 @@ impl ProcessedMetaData {
                 "\x1B[93m[WARN] No executable files were installed; this package may only provide libraries.\x1B[0m"
             );
         }
-        if manifest
-            .files
-            .iter()
-            .any(|f| needs_ldconfig(&f.path))
+        // The host's linker cache has nothing to do with an alternate root
+        if !utils::is_alternate_root()
+            && manifest
+                .files
+                .iter()
+                .any(|f| needs_ldconfig(&f.path))
         {
             refresh_ld_cache();
         }
@@ -1085,10 +1637,7 @@ impl ProcessedMetaData {
     }
     
     async fn install_compilable_package(&self, extract_dir: &std::path::Path, compilable: &ProcessedCompilable) -> Result<(), String> {
-        let install_root = std::env::var("PAX_ROOT")
-            .ok()
-            .map(|r| PathBuf::from(r))
-            .unwrap_or_else(|| PathBuf::from("/"));
+        let install_root = utils::install_root();
         self.install_compilable_package_to_root(extract_dir, compilable, &install_root).await
     }
     
@@ -1104,23 +1653,36 @@ impl ProcessedMetaData {
         println!("[{}] Install script:\n{}", self.name, compilable.install);
         std::io::stdout().flush().unwrap();
         
-        // Run install commands - split by newlines if multiple commands
-        let commands: Vec<&str> = compilable.install.lines().collect();
+        // Variables available to `${VAR}` references in install commands
+        let destdir = install_root.to_string_lossy().to_string();
+        let target = "x86_64-unknown-linux-gnu".to_string();
+        let settings = settings::SettingsYaml::get_settings().ok();
+        let arch = settings.as_ref().map(|settings| settings.target_arch()).unwrap_or(settings::Arch::NoArch);
+        let shell = settings.as_ref().map_or(settings::DEFAULT_SHELL, |settings| settings.shell());
+        let vars = HashMap::from([
+            ("PAX_NAME".to_string(), self.name.clone()),
+            ("PAX_VERSION".to_string(), self.version.clone()),
+            ("PAX_ARCH".to_string(), arch.to_string()),
+            ("DESTDIR".to_string(), destdir.clone()),
+            ("TARGET".to_string(), target.clone()),
+        ]);
+
+        // Run install commands one at a time so failures name the exact command
+        let commands = utils::split_commands(&compilable.install);
         
         for (i, cmd) in commands.iter().enumerate() {
-            let cmd = cmd.trim();
-            if cmd.is_empty() || cmd.starts_with('#') {
-                continue;
-            }
+            let cmd = utils::expand_vars(cmd, &vars)
+                .map_err(|e| format!("Install command {} for {}: {}", i + 1, self.name, e))?;
+            let cmd = cmd.as_str();
             
             println!("[{}] Executing install command {}: {}", self.name, i + 1, cmd);
             std::io::stdout().flush().unwrap();
             
-            let mut install_cmd = RunCommand::new("bash");
+            let mut install_cmd = RunCommand::new(shell);
             install_cmd.arg("-c").arg(cmd);
             install_cmd.current_dir(extract_dir);
-            install_cmd.env("DESTDIR", install_root.to_string_lossy().to_string());
-            install_cmd.env("TARGET", "x86_64-unknown-linux-gnu");
+            install_cmd.env("DESTDIR", &destdir);
+            install_cmd.env("TARGET", &target);
             
             let output = install_cmd.output().map_err(|e| format!("Failed to execute install command '{}': {}", cmd, e))?;
             
@@ -1166,9 +1728,11 @@ impl ProcessedMetaData {
     }
     
     pub async fn get_metadata_from_local_package(package_path: &str) -> Result<Self, String> {
-        use std::path::Path;
+        Self::load_local_package(Path::new(package_path))
+    }
 
-        let path = Path::new(package_path);
+    /// Read the metadata of the package file at `path`, by its extension.
+    pub(crate) fn load_local_package(path: &Path) -> Result<Self, String> {
         if !path.exists() {
             return err!("Package file does not exist: {}", path.display());
         }
@@ -1244,11 +1808,7 @@ impl ProcessedMetaData {
                 );
             };
 
-            // Fix common YAML issues in manifests (malformed quotes, etc.)
-            let fixed_manifest = Self::fix_yaml_syntax(&manifest_content);
-
-            let raw_pax = serde_norway::from_str::<RawPax>(&fixed_manifest)
-                .map_err(|e| format!("Failed to parse manifest.yaml as PAX format: {}", e))?;
+            let raw_pax = Self::parse_manifest(&manifest_content)?;
 
             // #region agent log
             let _ = write_debug_log(&serde_json::json!({
@@ -1314,6 +1874,47 @@ impl ProcessedMetaData {
         Ok(processed)
     }
 
+    fn strict_manifest() -> bool {
+        std::env::var("PAX_STRICT_MANIFEST")
+            .map(|v| {
+                let v = v.trim().to_ascii_lowercase();
+                matches!(v.as_str(), "1" | "true" | "yes" | "on")
+            })
+            .unwrap_or(false)
+    }
+
+    /// Parse manifest.yaml as written. Only if that fails, and PAX_STRICT_MANIFEST
+    /// isn't set, retry with `fix_yaml_syntax` and warn about every line it changed.
+    fn parse_manifest(content: &str) -> Result<RawPax, String> {
+        let fault = match serde_norway::from_str::<RawPax>(content) {
+            Ok(raw_pax) => return Ok(raw_pax),
+            Err(fault) => fault,
+        };
+        if Self::strict_manifest() {
+            return err!("Malformed manifest.yaml (PAX_STRICT_MANIFEST is set): {}", fault);
+        }
+
+        let fixed = Self::fix_yaml_syntax(content);
+        let raw_pax = serde_norway::from_str::<RawPax>(&fixed)
+            .map_err(|_| format!("Failed to parse manifest.yaml as PAX format: {}", fault))?;
+
+        let fixed_lines = fixed.lines().collect::<HashSet<_>>();
+        let changed = content
+            .lines()
+            .filter(|line| !fixed_lines.contains(line))
+            .collect::<Vec<_>>();
+        println!(
+            "\x1B[93m[WARN] manifest.yaml for {} is malformed ({}); rewrote {} line(s) to parse it:\x1B[0m",
+            raw_pax.name,
+            fault,
+            changed.len()
+        );
+        for line in changed {
+            println!("\x1B[93m  {}\x1B[0m", line.trim());
+        }
+        Ok(raw_pax)
+    }
+
     fn parse_pax_metadata_dir(metadata_dir: &Path) -> Result<Self, String> {
         let yaml_path = metadata_dir.join("metadata.yaml");
         let json_path = metadata_dir.join("metadata.json");
@@ -1452,12 +2053,21 @@ impl ProcessedMetaData {
         // #endregion
         
         // Prioritize /dependencies/runtime_dependencies (the correct path based on user's metadata structure)
-        let runtime_deps = Self::parse_new_metadata_dependencies(
-            deps_runtime_path2  // /dependencies/runtime_dependencies (CORRECT PATH)
-                .or_else(|| deps_runtime_path1)  // /dependencies/runtime (fallback)
-                .or_else(|| deps_runtime_path4)  // /package/runtime_dependencies (fallback)
-                .or_else(|| deps_runtime_path3), // /package/dependencies/runtime (fallback)
+        let runtime_node = deps_runtime_path2  // /dependencies/runtime_dependencies (CORRECT PATH)
+            .or(deps_runtime_path1)  // /dependencies/runtime (fallback)
+            .or(deps_runtime_path4)  // /package/runtime_dependencies (fallback)
+            .or(deps_runtime_path3); // /package/dependencies/runtime (fallback)
+        let runtime_deps = Self::parse_new_metadata_dependencies(runtime_node);
+
+        // Optionals come from their own list, or runtime entries marked `optional: true`
+        let mut optional_deps = Self::parse_optional_dependencies(
+            metadata_value
+                .pointer("/dependencies/optional_dependencies")
+                .or_else(|| metadata_value.pointer("/dependencies/optional"))
+                .or_else(|| metadata_value.pointer("/package/optional_dependencies")),
+            false,
         );
+        optional_deps.extend(Self::parse_optional_dependencies(runtime_node, true));
         
         // #region agent log
         let _ = write_debug_log(&serde_json::json!({
@@ -1487,6 +2097,27 @@ impl ProcessedMetaData {
                 .or_else(|| metadata_value.pointer("/package/build_dependencies")),
         );
 
+        let installed_size = metadata_value
+            .pointer("/package/installed_size")
+            .or_else(|| metadata_value.pointer("/artifacts/installed_size"))
+            .and_then(|v| v.as_u64());
+        let string_list = |pointer: &str| -> Vec<String> {
+            metadata_value
+                .pointer(pointer)
+                .and_then(|v| v.as_array())
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| item.as_str())
+                        .map(|item| item.trim().to_string())
+                        .filter(|item| !item.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let keywords = string_list("/package/keywords");
+        let categories = string_list("/package/categories");
+
         let mut hash = metadata_value
             .pointer("/artifacts/binary_hash")
             .and_then(|v| v.as_str())
@@ -1517,6 +2148,10 @@ impl ProcessedMetaData {
             dependent: false,
             build_dependencies: build_deps,
             runtime_dependencies: runtime_deps,
+            optional_dependencies: optional_deps,
+            installed_size,
+            keywords,
+            categories,
             install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                 critical: Vec::new(),
                 configs: Vec::new(),
@@ -1623,6 +2258,53 @@ impl ProcessedMetaData {
         Self::dependencies_from_strings(deps_as_strings)
     }
 
+    /// Optional dependencies with their `reason`. With `flagged_only`, only
+    /// object entries marked `optional: true` count, as in a runtime list.
+    fn parse_optional_dependencies(node: Option<&JsonValue>, flagged_only: bool) -> Vec<OptionalDependency> {
+        let items = match node {
+            Some(JsonValue::Array(items)) => items.as_slice(),
+            Some(item) => std::slice::from_ref(item),
+            None => return Vec::new(),
+        };
+
+        let mut optional = Vec::new();
+        for item in items {
+            let (entry, reason) = match item {
+                JsonValue::String(s) if !flagged_only => (s.trim().to_string(), None),
+                JsonValue::Object(obj) => {
+                    let flagged = obj.get("optional").and_then(|v| v.as_bool()).unwrap_or(false);
+                    let Some(name) = obj
+                        .get("name")
+                        .or_else(|| obj.get("package"))
+                        .and_then(|v| v.as_str())
+                    else {
+                        continue;
+                    };
+                    if flagged_only && !flagged {
+                        continue;
+                    }
+                    let constraint = obj
+                        .get("version_constraint")
+                        .or_else(|| obj.get("version"))
+                        .or_else(|| obj.get("constraint"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default();
+                    let reason = obj
+                        .get("reason")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty());
+                    (Self::normalize_dependency_entry(name.trim(), constraint), reason)
+                }
+                _ => continue,
+            };
+            if let Some(dependency) = Self::dependencies_from_strings(vec![entry]).into_iter().next() {
+                optional.push(OptionalDependency { dependency, reason });
+            }
+        }
+        optional
+    }
+
     fn normalize_dependency_entry(name: &str, constraint: &str) -> String {
         let trimmed = constraint.trim();
 
@@ -1764,6 +2446,10 @@ impl ProcessedMetaData {
         let description = read_dpkg_field(path, "Description")?
             .unwrap_or_else(|| format!("Debian package {}", name));
         let depends_raw = read_dpkg_field(path, "Depends")?.unwrap_or_default();
+        // dpkg records Installed-Size in KiB
+        let installed_size = read_dpkg_field(path, "Installed-Size")?
+            .and_then(|size| size.trim().parse::<u64>().ok())
+            .map(|kib| kib * 1024);
 
         let (_, critical_files, config_files) = Self::collect_payload_from(&temp_dir)?;
 
@@ -1776,6 +2462,10 @@ impl ProcessedMetaData {
             dependent: false,
             build_dependencies: Vec::new(),
             runtime_dependencies: Self::parse_dependency_list(&depends_raw),
+            optional_dependencies: Vec::new(),
+            installed_size,
+            keywords: Vec::new(),
+            categories: Vec::new(),
             install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                 critical: critical_files,
                 configs: config_files,
@@ -1848,6 +2538,10 @@ impl ProcessedMetaData {
             runtime_dependencies: filtered_deps.into_iter()
                 .map(|dep| DependKind::Latest(dep))
                 .collect(),
+            optional_dependencies: Vec::new(),
+            installed_size: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
             install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                 critical: critical_files,
                 configs: config_files,
@@ -2102,7 +2796,7 @@ impl ProcessedMetaData {
         Ok((has_entries, critical_files, config_files))
     }
 
-    fn parse_dependency_list(list: &str) -> Vec<DependKind> {
+    pub(crate) fn parse_dependency_list(list: &str) -> Vec<DependKind> {
         list.split([',', '\n'])
             .filter_map(|item| {
                 let trimmed = item.trim();
@@ -2300,6 +2994,14 @@ impl ProcessedMetaData {
             return None;
         }
 
+        if let Some(url) = Self::select_versioned_candidate(&candidates, app, version) {
+            Self::debug_log(format_args!(
+                "[PAX_DISCOVER] Selecting highest version candidate {}",
+                url
+            ));
+            return Some(url);
+        }
+
         if let Some(ver) = version {
             let mut best: Option<(String, bool)> = None;
             for (url, has_hint) in &candidates {
@@ -2352,6 +3054,145 @@ impl ProcessedMetaData {
         best.map(|(url, _)| url)
     }
 
+    /// Parses the version out of `{app}-{version}-{release}-{arch}.pax`.
+    fn version_from_file_name(file_name: &str, app: &str) -> Option<Version> {
+        let rest = file_name
+            .strip_prefix(app)?
+            .strip_prefix('-')?
+            .strip_suffix(".pax")?;
+        Version::parse(rest.split('-').next()?).ok()
+    }
+
+    /// Picks the candidate with the highest version satisfying `version`,
+    /// preferring arch-matching candidates and stable releases. Returns None
+    /// when no candidate file name carries a parseable version.
+    fn select_versioned_candidate(
+        candidates: &[(String, bool)],
+        app: &str,
+        version: Option<&str>,
+    ) -> Option<String> {
+        let range = match version {
+            Some(ver) => Range {
+                lower: VerReq::Eq(Version::parse(ver).ok()?),
+                upper: VerReq::NoBound,
+            },
+            None => Range {
+                lower: VerReq::NoBound,
+                upper: VerReq::NoBound,
+            },
+        };
+        let versioned: Vec<(&String, bool, Version)> = candidates
+            .iter()
+            .filter_map(|(url, has_hint)| {
+                let file_name = url.rsplit('/').next().unwrap_or(url);
+                Self::version_from_file_name(file_name, app).map(|v| (url, *has_hint, v))
+            })
+            .collect();
+        let prefer_hint = versioned
+            .iter()
+            .any(|(_, has_hint, v)| *has_hint && range.contains(v));
+        let pool: Vec<&(&String, bool, Version)> = versioned
+            .iter()
+            .filter(|(_, has_hint, _)| *has_hint || !prefer_hint)
+            .collect();
+        let versions: Vec<Version> = pool.iter().map(|(_, _, v)| v.clone()).collect();
+        let best = utils::select_best_version(&versions, &range, true)?;
+        // Several builds of one version differ only by release, so fall back
+        // to comparing the URLs for those
+        pool.iter()
+            .filter(|(_, _, v)| *v == best)
+            .map(|(url, _, _)| (*url).clone())
+            .max()
+    }
+
+    /// Every version of `app` linked from a repository index page, newest first.
+    pub(crate) fn versions_from_index(index_html: &str, app: &str) -> Vec<String> {
+        let versions = Self::extract_href_candidates(index_html, app)
+            .iter()
+            .filter_map(|href| {
+                let file_name = href.rsplit('/').next().unwrap_or(href);
+                let rest = file_name.strip_prefix(app)?.strip_prefix('-')?.strip_suffix(".pax")?;
+                let version = rest.split('-').next()?;
+                Version::parse(version).ok().map(|_| version.to_string())
+            })
+            .collect();
+        sort_versions_desc(versions)
+    }
+
+    /// Every version of this package its origin offers, newest first. Pax
+    /// repositories are read from `packages.json` and the index page, APT
+    /// and YUM repositories from their package lists, and GitHub from the
+    /// release tags. Always includes this package's own version.
+    pub async fn fetch_available_versions(&self) -> Vec<String> {
+        let mut versions = vec![self.version.clone()];
+        if self.origin.ensure_reachable().is_err() {
+            return versions;
+        }
+        match &self.origin {
+            OriginKind::Pax(source) if source.starts_with("http://") || source.starts_with("https://") => {
+                let base = source.trim_end_matches('/');
+                if let Ok(response) = reqwest::get(format!("{}/packages.json", base)).await
+                    && let Ok(body) = response.text().await
+                    && let Ok(index) = serde_json::from_str::<JsonValue>(&body)
+                    && let Some(packages) = index.get("packages").and_then(|p| p.as_array())
+                {
+                    versions.extend(packages.iter().filter_map(|package| {
+                        let name = package.get("name").and_then(|n| n.as_str())?;
+                        let version = package.get("version").and_then(|v| v.as_str())?;
+                        (name == self.name).then(|| version.to_string())
+                    }));
+                }
+                if let Ok(response) = reqwest::get(format!("{}/", base)).await
+                    && response.status().is_success()
+                    && let Ok(body) = response.text().await
+                {
+                    versions.extend(Self::versions_from_index(&body, &self.name));
+                }
+            }
+            OriginKind::Github { user, repo } => {
+                let endpoint = format!("https://api.github.com/repos/{}/{}/releases", user, repo);
+                if let Ok(Some(JsonValue::Array(releases))) = crate::github::get_json(&endpoint).await {
+                    versions.extend(releases.iter().filter_map(|release| {
+                        release.get("tag_name").and_then(|t| t.as_str()).map(str::to_string)
+                    }));
+                }
+            }
+            OriginKind::Apt(_) | OriginKind::Deb(_) => {
+                if let Some(client) = crate::deb_repository::DebRepositoryClient::from_origin(&self.origin)
+                    && let Ok(packages) = client.list_packages().await
+                {
+                    versions.extend(packages.into_iter().filter(|p| p.name == self.name).map(|p| p.version));
+                }
+            }
+            OriginKind::Rpm(_) | OriginKind::Yum(_) => {
+                if let Some(client) = crate::yum_repository::YumRepositoryClient::from_origin(&self.origin)
+                    && let Ok(packages) = client.list_packages().await
+                {
+                    versions.extend(packages.into_iter().filter(|p| p.name == self.name).map(|p| p.version));
+                }
+            }
+            _ => {}
+        }
+        sort_versions_desc(versions)
+    }
+
+    /// Check a version asked for with `install -s` against `available_versions`.
+    /// An empty list means the origin didn't say, so any version is accepted.
+    pub fn check_requested_version(&self, requested: &str) -> Result<(), String> {
+        let normalize = |version: &str| version.trim().trim_start_matches('v').to_string();
+        if self.available_versions.is_empty()
+            || self.available_versions.iter().any(|version| normalize(version) == normalize(requested))
+        {
+            return Ok(());
+        }
+        err!(
+            "Version `{}` of `{}` is not available! Available versions: {}",
+            requested,
+            self.name,
+            self.available_versions.join(", ")
+        )
+    }
+
     fn extract_href_candidates(index_html: &str, app: &str) -> Vec<String> {
         let mut result = Vec::new();
         let mut remaining = index_html;
@@ -2399,9 +3240,28 @@ impl ProcessedMetaData {
         version: Option<&str>,
         sources: &[OriginKind],
         dependent: bool,
-    ) -> Option<Self> {
-        // Process all sources in parallel, return as soon as we get the first successful result
-        let mut source_futures: Vec<_> = sources.iter().map(|source| {
+    ) -> Result<Option<Self>, String> {
+        let key = MetadataMemo::key(app, version, sources);
+        let Some(mut metadata) = metadata_memo()
+            .get_or_fetch(key, || Self::fetch_metadata(app, version, sources, dependent))
+            .await?
+        else {
+            return Ok(None);
+        };
+        // `dependent` only marks the result, so a shared entry can serve both kinds of lookup
+        metadata.dependent = dependent;
+        Ok(Some(metadata))
+    }
+
+    async fn fetch_metadata(
+        app: &str,
+        version: Option<&str>,
+        sources: &[OriginKind],
+        dependent: bool,
+    ) -> Result<Option<Self>, String> {
+        // Fetch from all sources concurrently but take the first hit in
+        // priority order; a source that fails before then fails the lookup
+        let mut source_futures: FuturesOrdered<_> = sources.iter().map(|source| {
             let app = app.to_string();
             let version = version.map(|v| v.to_string());
             let source = source.clone();
@@ -2412,17 +3272,13 @@ impl ProcessedMetaData {
             .boxed()
         }).collect();
 
-        // Race all sources - return as soon as we get the first successful result
-        while !source_futures.is_empty() {
-            let (result, _index, remaining) = select_all(source_futures).await;
-            source_futures = remaining;
-
-            if let Some(metadata) = result {
-                return Some(metadata);
+        while let Some(result) = source_futures.next().await {
+            if let Some(metadata) = result? {
+                return Ok(Some(metadata));
             }
         }
 
-        None
+        Ok(None)
     }
 
     pub async fn get_all_metadata(
@@ -2445,7 +3301,15 @@ impl ProcessedMetaData {
 
         // Wait for all sources to complete and collect successful results
         let results = join_all(source_futures).await;
-        let packages = results.into_iter().flatten().collect::<Vec<_>>();
+        let packages = results
+            .into_iter()
+            .filter_map(|result| {
+                result.unwrap_or_else(|fault| {
+                    println!("\x1B[93m[WARN] {}\x1B[0m", fault);
+                    None
+                })
+            })
+            .collect::<Vec<_>>();
 
         // Deduplicate packages based on repository priority
         // For RPM packages, prefer updates repository over base repository
@@ -2499,7 +3363,18 @@ impl ProcessedMetaData {
         version: Option<&str>,
         source: &OriginKind,
         dependent: bool,
-    ) -> Option<Self> {
+    ) -> Result<Option<Self>, String> {
+        if let Err(fault) = source.ensure_reachable() {
+            warn_offline(fault);
+            return Ok(None);
+        }
+        let source = &source.normalize_file_url();
+        // A `file://` URL naming a single package is read like a local install
+        if let OriginKind::Pax(path) | OriginKind::Apt(path) = source
+            && Path::new(path).is_file()
+        {
+            return Ok(Self::get_metadata_from_local_package(path).await.ok());
+        }
         let mut metadata = None;
         match source {
                 OriginKind::Pax(source) => {
@@ -2579,83 +3454,87 @@ impl ProcessedMetaData {
                         let endpoint = if let Some(version) = version {
                             format!("https://api.github.com/repos/{}/{}/releases/tags/{}", user, repo, version)
                         } else {
-                            format!("https://api.github.com/repos/{}/{}/releases/latest", user, repo)
+                            // Pick from the full listing by tag version, since
+                            // releases/latest never returns a pre-release
+                            format!("https://api.github.com/repos/{}/{}/releases", user, repo)
                         };
                         
-                        if let Ok(response) = reqwest::get(&endpoint).await {
-                            if let Ok(body) = response.text().await {
-                                if let Ok(release_data) = serde_json::from_str::<serde_json::Value>(&body) {
-                                    // Look for a PAX metadata file in the release assets
-                                    if let Some(assets) = release_data.get("assets").and_then(|a| a.as_array()) {
-                                        for asset in assets {
-                                            if let Some(name) = asset.get("name").and_then(|n| n.as_str()) {
-                                                if name.ends_with(".pax") || name.ends_with(".json") {
-                                                    if let Some(download_url) = asset.get("browser_download_url").and_then(|u| u.as_str()) {
-                                                        if let Ok(asset_response) = reqwest::get(download_url).await {
-                                                            if let Ok(asset_body) = asset_response.text().await {
-                                                                // Try to parse as PAX format first
-                                                                if metadata.is_none() {
-                                                                    if let Ok(raw_pax) = serde_json::from_str::<RawPax>(&asset_body) {
-                                                                        if let Some(processed) = raw_pax.process() {
-                                                                            metadata = Some(processed);
-                                                                        }
-                                                                    }
-                                                                }
-                                                                // Try to parse as GitHub format
-                                                                if metadata.is_none() {
-                                                                    if let Ok(raw_github) = serde_json::from_str::<RawGithub>(&asset_body) {
-                                                                        if let Some(processed) = raw_github.process() {
-                                                                            metadata = Some(processed);
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-}
-
-                                    // If no assets found, try to create a basic package from release info
-                                    if metadata.is_none() {
-                                        if let Some(tag_name) = release_data.get("tag_name").and_then(|t| t.as_str()) {
-                                            if let Some(name) = release_data.get("name").and_then(|n| n.as_str()) {
-                                                if let Some(body) = release_data.get("body").and_then(|b| b.as_str()) {
-                                                    // Create a basic ProcessedMetaData from release info
-                                                    let processed = ProcessedMetaData {
-                                                        name: name.to_string(),
-                                                        kind: MetaDataKind::Github,
-                                                        description: body.to_string(),
-                                                        version: tag_name.to_string(),
-                                                        origin: OriginKind::Github { 
-                                                            user: user.clone(),
-                                                            repo: repo.clone() 
-                                                        },
-                                                        dependent,
-                                                        build_dependencies: Vec::new(),
-                                                        runtime_dependencies: Vec::new(),
-                                                        install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
-                                                            build: "make".to_string(),
-                                                            install: "make install".to_string(),
-                                                            uninstall: "make uninstall".to_string(),
-                                                            purge: "make uninstall".to_string(),
-                                                        }),
-                                                        hash: "unknown".to_string(),
-                                                        package_type: "GitHub".to_string(),
-                                                        installed: false,
-                                                        dependencies: Vec::new(),
-                                                        dependents: Vec::new(),
-                                                        installed_files: Vec::new(),
-                                                        available_versions: Vec::new(),
-                                                    };
-                                                    metadata = Some(processed);
-                                                }
-                                            }
-                                        }
-                                    }
+                        let release = match crate::github::get_json(&endpoint).await {
+                            Ok(Some(JsonValue::Array(releases))) => {
+                                crate::github::newest_release(&releases, crate::github::include_prereleases()).cloned()
+                            }
+                            Ok(release) => release,
+                            // A rate limit says nothing about the package, so don't report it missing
+                            Err(fault) => return Err(fault),
+                        };
+                        if let Some(release_data) = release {
+                            // Look for a PAX metadata file among the release assets,
+                            // picking the one built for this architecture
+                            let settings = settings::SettingsYaml::get_settings().unwrap_or_default();
+                            let origin = OriginKind::Github { user: user.clone(), repo: repo.clone() };
+                            let pattern = settings.asset_pattern(&origin);
+                            let assets = release_data.get("assets").and_then(|a| a.as_array()).map(Vec::as_slice).unwrap_or_default();
+                            // Best first; one that doesn't parse gives way to the next
+                            for asset in crate::github::ranked_assets(assets, settings.target_arch(), pattern) {
+                                let Some(download_url) = asset.get("browser_download_url").and_then(|u| u.as_str()) else {
+                                    continue;
+                                };
+                                let Ok(Some(asset_body)) = crate::github::get_text(download_url).await else {
+                                    continue;
+                                };
+                                // Try to parse as PAX format first, then as GitHub format
+                                metadata = serde_json::from_str::<RawPax>(&asset_body)
+                                    .ok()
+                                    .and_then(RawPax::process)
+                                    .or_else(|| {
+                                        serde_json::from_str::<RawGithub>(&asset_body)
+                                            .ok()
+                                            .and_then(RawGithub::process)
+                                    });
+                                if metadata.is_some() {
+                                    break;
                                 }
                             }
+
+                            // If no assets found, try to create a basic package from release info
+                            if metadata.is_none()
+                                && let Some(tag_name) = release_data.get("tag_name").and_then(|t| t.as_str())
+                                && let Some(name) = release_data.get("name").and_then(|n| n.as_str())
+                                && let Some(body) = release_data.get("body").and_then(|b| b.as_str())
+                            {
+                                // Create a basic ProcessedMetaData from release info
+                                let processed = ProcessedMetaData {
+                                    name: name.to_string(),
+                                    kind: MetaDataKind::Github,
+                                    description: body.to_string(),
+                                    version: tag_name.to_string(),
+                                    origin: OriginKind::Github { 
+                                        user: user.clone(),
+                                        repo: repo.clone() 
+                                    },
+                                    dependent,
+                                    build_dependencies: Vec::new(),
+                                    runtime_dependencies: Vec::new(),
+                                    optional_dependencies: Vec::new(),
+                                    installed_size: None,
+                                    keywords: Vec::new(),
+                                    categories: Vec::new(),
+                                    install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
+                                        build: "make".to_string(),
+                                        install: "make install".to_string(),
+                                        uninstall: "make uninstall".to_string(),
+                                        purge: "make uninstall".to_string(),
+                                    }),
+                                    hash: "unknown".to_string(),
+                                    package_type: "GitHub".to_string(),
+                                    installed: false,
+                                    dependencies: Vec::new(),
+                                    dependents: Vec::new(),
+                                    installed_files: Vec::new(),
+                                    available_versions: Vec::new(),
+                                };
+                                metadata = Some(processed);
+                            }
                         }
                         metadata
                     };
@@ -2710,6 +3589,10 @@ impl ProcessedMetaData {
                                     runtime_dependencies: package_info.dependencies.into_iter()
                                         .map(|dep| crate::depend_kind::DependKind::Latest(dep))
                                         .collect(),
+                                    optional_dependencies: Vec::new(),
+                                    installed_size: None,
+                                    keywords: Vec::new(),
+                                    categories: Vec::new(),
                                     install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                                         critical: Vec::new(),
                                         configs: Vec::new(),
@@ -2756,6 +3639,10 @@ impl ProcessedMetaData {
                                 runtime_dependencies: package_info.dependencies.into_iter()
                                     .map(|dep| crate::depend_kind::DependKind::Latest(dep))
                                     .collect(),
+                                optional_dependencies: Vec::new(),
+                                installed_size: None,
+                                keywords: Vec::new(),
+                                categories: Vec::new(),
                                 install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                                     critical: Vec::new(),
                                     configs: Vec::new(),
@@ -2798,6 +3685,10 @@ impl ProcessedMetaData {
                                     runtime_dependencies: package_info.dependencies.into_iter()
                                         .map(|dep| crate::depend_kind::DependKind::Latest(dep))
                                         .collect(),
+                                    optional_dependencies: Vec::new(),
+                                    installed_size: None,
+                                    keywords: Vec::new(),
+                                    categories: Vec::new(),
                                     install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                                         critical: file_list,
                                         configs: Vec::new(),
@@ -2843,6 +3734,10 @@ impl ProcessedMetaData {
                                     runtime_dependencies: package_info.dependencies.into_iter()
                                         .map(|dep| crate::depend_kind::DependKind::Latest(dep))
                                         .collect(),
+                                    optional_dependencies: Vec::new(),
+                                    installed_size: None,
+                                    keywords: Vec::new(),
+                                    categories: Vec::new(),
                                     install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                                         critical: file_list,
                                         configs: Vec::new(),
@@ -2891,7 +3786,9 @@ impl ProcessedMetaData {
                                 ]
                             } else {
                                 // For latest version, scan all files and pick the one matching the name
-                                // Prefer x86_64v3, then x86_64v1, then others
+                                // Prefer the --arch override, then x86_64v3, then x86_64v1, then others
+                                let preferred = settings::arch_override().map(|arch| arch.to_string());
+                                let mut candidates_preferred = Vec::new();
                                 let mut candidates_v3 = Vec::new();
                                 let mut candidates_v1 = Vec::new();
                                 let mut candidates_other = Vec::new();
@@ -2909,7 +3806,11 @@ impl ProcessedMetaData {
                                                 (file_name.starts_with(&prefix) && file_name.ends_with(".deb")) ||
                                                 (file_name.starts_with(&prefix) && file_name.ends_with(".rpm"))) {
                                                 // Prioritize by architecture
-                                                if file_name.contains("x86_64v3") {
+                                                if let Some(arch) = &preferred
+                                                    && file_name.contains(arch.as_str())
+                                                {
+                                                    candidates_preferred.push(path.clone());
+                                                } else if file_name.contains("x86_64v3") {
                                                     candidates_v3.push(path.clone());
                                                     Self::debug_log(format_args!(
                                                         "[LOCALDIR] Found x86_64v3 candidate: {}",
@@ -2946,8 +3847,10 @@ impl ProcessedMetaData {
                                     candidates_v1.len(),
                                     candidates_other.len()
                                 ));
-                                // Prefer v3, then v1, then others
-                                if !candidates_v3.is_empty() {
+                                // Prefer the override, then v3, then v1, then others
+                                if !candidates_preferred.is_empty() {
+                                    candidates_preferred
+                                } else if !candidates_v3.is_empty() {
                                     candidates_v3
                                 } else if !candidates_v1.is_empty() {
                                     candidates_v1
@@ -3020,9 +3923,9 @@ impl ProcessedMetaData {
         }
         if let Some(mut mut_metadata) = metadata {
             mut_metadata.dependent = dependent;
-            Some(mut_metadata)
+            Ok(Some(mut_metadata))
         } else {
-            None
+            Ok(None)
         }
     }
     
@@ -3079,6 +3982,10 @@ impl ProcessedMetaData {
             dependent: false,
             build_dependencies: Vec::new(),
             runtime_dependencies: dependencies.into_iter().map(|dep| DependKind::Latest(dep)).collect(),
+            optional_dependencies: Vec::new(),
+            installed_size: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
             install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                 critical: critical_files,
                 configs: config_files,
@@ -3100,15 +4007,28 @@ impl ProcessedMetaData {
     ) -> Result<InstallPackage, String> {
         let mut run_deps = Vec::new();
         let mut build_deps = Vec::new();
+        if skip_dependencies() {
+            return Ok(InstallPackage {
+                metadata: self.clone(),
+                run_deps,
+                build_deps,
+            });
+        }
         
         // Resolve runtime dependencies
         for dep in &self.runtime_dependencies {
+            if is_assumed_installed(&dep.name()) {
+                continue;
+            }
             let resolved = self.resolve_single_dependency(dep, sources, prior).await?;
             run_deps.push(resolved);
         }
         
         // Resolve build dependencies
         for dep in &self.build_dependencies {
+            if is_assumed_installed(&dep.name()) {
+                continue;
+            }
             let resolved = self.resolve_single_dependency(dep, sources, prior).await?;
             build_deps.push(resolved);
         }
@@ -3139,7 +4059,7 @@ impl ProcessedMetaData {
                 };
                 
                 if prior.contains(&specific) {
-                    return Err(format!("Circular dependency detected: {}", dep_ver.name));
+                    return Err(format!("Circular dependency detected: {}", dep_ver));
                 }
                 
                 prior.insert(specific);
@@ -3164,6 +4084,10 @@ impl ProcessedMetaData {
                                dependent: false,
                                build_dependencies: Vec::new(),
                                runtime_dependencies: Vec::new(),
+                               optional_dependencies: Vec::new(),
+                               installed_size: None,
+                               keywords: Vec::new(),
+                               categories: Vec::new(),
                                install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
                                    build: "".to_string(),
                                    install: "".to_string(),
@@ -3242,6 +4166,10 @@ impl ProcessedMetaData {
                        dependent: true,
                        build_dependencies: installed.dependencies.iter().map(|dep| DependKind::Specific(dep.clone())).collect(),
                        runtime_dependencies: installed.dependencies.iter().map(|dep| DependKind::Specific(dep.clone())).collect(),
+                       optional_dependencies: Vec::new(),
+                       installed_size: None,
+                       keywords: Vec::new(),
+                       categories: Vec::new(),
                        install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
                            build: "".to_string(),
                            install: "".to_string(),
@@ -3301,12 +4229,18 @@ impl ProcessedMetaData {
             .unwrap_or(false)
     }
 
-    pub fn install(&self, runtime: &Runtime) -> Result<(), String> {
-        runtime.block_on(self.clone().install_package_impl(false, None))
+    pub fn install(&self) -> Result<(), String> {
+        self.install_with(false, true)
     }
     
-    pub fn install_with_overwrite(&self, runtime: &Runtime) -> Result<(), String> {
-        runtime.block_on(self.clone().install_package_impl(true, None))
+    pub fn install_with_overwrite(&self) -> Result<(), String> {
+        self.install_with(true, true)
+    }
+
+    /// Install with explicit options; `verify` checks the package hash
+    /// before anything is placed.
+    pub fn install_with(&self, allow_overwrite: bool, verify: bool) -> Result<(), String> {
+        crate::block_on(self.clone().install_package_impl(allow_overwrite, verify, None))?
     }
 
     pub fn list_deps(&self, runtime: bool) -> Vec<String> {
@@ -3362,10 +4296,10 @@ impl ProcessedMetaData {
         })
     }
     
-    pub fn upgrade_package(&self, _sources: &[OriginKind], runtime: &Runtime) -> Result<(), String> {
+    pub fn upgrade_package(&self, _sources: &[OriginKind]) -> Result<(), String> {
         // For now, just reinstall the package
         // TODO: Implement proper upgrade logic
-        runtime.block_on(self.clone().install_package())
+        crate::block_on(self.clone().install_package())?
     }
     
     pub fn remove_update_cache(&self) -> Result<(), String> {
@@ -3395,72 +4329,271 @@ async fn select_package_from_multiple(packages: &[ProcessedMetaData], package_na
                 if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open("/home/blester/pax-rs/.cursor/debug.log") {
                     let _ = writeln!(file, "{{\"sessionId\":\"debug-session\",\"runId\":\"url_debug\",\"hypothesisId\":\"URL_DUP\",\"location\":\"metadata/src/processed/mod.rs:3258\",\"message\":\"displaying_origin\",\"data\":{{\"package\":\"{}\",\"origin_url\":\"{}\"}},\"timestamp\":{}}}", package.name, url, std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as u64);
                 }
-                format!("RPM: {}", url)
-            },
-            OriginKind::Yum(url) => format!("YUM: {}", url),
-            OriginKind::Github { user, repo } => format!("GitHub: {}/{}", user, repo),
-            OriginKind::CloudflareR2 { bucket, account_id, .. } => format!("R2: {}.{}", bucket, account_id),
-            OriginKind::LocalDir(path) => format!("Local: {}", path),
-        };
+                format!("RPM: {}", url)
+            },
+            OriginKind::Yum(url) => format!("YUM: {}", url),
+            OriginKind::Github { user, repo } => format!("GitHub: {}/{}", user, repo),
+            OriginKind::CloudflareR2 { bucket, account_id, .. } => format!("R2: {}.{}", bucket, account_id),
+            OriginKind::LocalDir(path) => format!("Local: {}", path),
+        };
+
+        println!("{}. {} (v{}) - {}", i + 1, package.name, package.version, repo_info);
+        println!("   {}", package.description);
+        println!();
+    }
+
+    println!("0. Cancel installation");
+    println!();
+
+    // Get user input
+    loop {
+        print!("Enter selection (1-{}): ", packages.len());
+        std::io::Write::flush(&mut std::io::stdout()).map_err(|e| format!("Failed to flush stdout: {}", e))?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).map_err(|e| format!("Failed to read input: {}", e))?;
+        let input = input.trim();
+
+        match input.parse::<usize>() {
+            Ok(0) => return Ok(None), // Cancelled
+            Ok(n) if n > 0 && n <= packages.len() => {
+                return Ok(Some(packages[n - 1].clone()));
+            }
+            _ => {
+                println!("Invalid selection. Please enter a number between 0 and {}.", packages.len());
+            }
+        }
+    }
+}
+
+/// How well `meta` matches `query`, lower being better: a name match ranks
+/// above a keyword or category match, which ranks above a description
+/// match. `None` if it doesn't match at all; `--exact` only matches names.
+pub(crate) fn search_rank(meta: &ProcessedMetaData, query: &str, exact: bool) -> Option<u8> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    if exact {
+        return meta.name.eq_ignore_ascii_case(query).then_some(0);
+    }
+    let query_lower = query.to_ascii_lowercase();
+    let contains = |text: &str| text.to_ascii_lowercase().contains(&query_lower);
+    if meta.name.eq_ignore_ascii_case(query) {
+        Some(0)
+    } else if contains(&meta.name) {
+        Some(1)
+    } else if meta.keywords.iter().chain(&meta.categories).any(|term| contains(term)) {
+        Some(2)
+    } else if contains(&meta.description) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+fn in_category(meta: &ProcessedMetaData, category: &str) -> bool {
+    meta.categories.iter().any(|known| known.eq_ignore_ascii_case(category))
+}
+
+/// How many of `packages` are in each category, for `pax categories`.
+/// Categories are compared case-insensitively and listed in lower case.
+pub fn category_counts(packages: &[ProcessedMetaData]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for package in packages {
+        let categories: HashSet<String> = package.categories.iter().map(|category| category.to_ascii_lowercase()).collect();
+        for category in categories {
+            *counts.entry(category).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// Thread-local storage for refresh flag
+thread_local! {
+    static FORCE_REFRESH: std::cell::Cell<bool> = std::cell::Cell::new(false);
+}
+
+pub fn set_force_refresh(refresh: bool) {
+    FORCE_REFRESH.with(|f| f.set(refresh));
+}
+
+thread_local! {
+    static SKIP_DEPENDENCIES: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// `--no-deps`: look up only the requested packages, leaving their
+/// dependencies unresolved and uninstalled.
+pub fn set_skip_dependencies(skip: bool) {
+    SKIP_DEPENDENCIES.with(|s| s.set(skip));
+}
+
+fn skip_dependencies() -> bool {
+    SKIP_DEPENDENCIES.with(|s| s.get())
+}
+
+thread_local! {
+    static IGNORE_INSTALLED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// `--download-only`: resolve as if nothing were installed, so the whole
+/// dependency closure is fetched for the machine it is meant for.
+pub fn set_ignore_installed(ignore: bool) {
+    IGNORE_INSTALLED.with(|i| i.set(ignore));
+}
+
+fn ignore_installed() -> bool {
+    IGNORE_INSTALLED.with(|i| i.get())
+}
+
+/// Which optional dependencies to pull in alongside the requested packages:
+/// every one (`--with-optional`) or `package:dependency` picks (`--with`).
+#[derive(Clone, Debug, Default)]
+pub struct OptionalSelection {
+    pub all: bool,
+    pub picks: HashSet<(String, String)>,
+}
+
+impl OptionalSelection {
+    /// Add comma-separated `package:dependency` picks.
+    pub fn add_picks(&mut self, specs: &str) -> Result<(), String> {
+        for spec in specs.split(',').map(str::trim).filter(|spec| !spec.is_empty()) {
+            match spec.split_once(':') {
+                Some((package, dependency)) if !package.is_empty() && !dependency.is_empty() => {
+                    self.picks.insert((package.to_string(), dependency.to_string()));
+                }
+                _ => return err!("Expected `package:dependency`, got `{spec}`!"),
+            }
+        }
+        Ok(())
+    }
 
-        println!("{}. {} (v{}) - {}", i + 1, package.name, package.version, repo_info);
-        println!("   {}", package.description);
-        println!();
+    pub fn includes(&self, package: &str, dependency: &str) -> bool {
+        self.all || self.picks.contains(&(package.to_string(), dependency.to_string()))
     }
+}
 
-    println!("0. Cancel installation");
-    println!();
+static OPTIONAL_SELECTION: Mutex<Option<OptionalSelection>> = Mutex::new(None);
 
-    // Get user input
-    loop {
-        print!("Enter selection (1-{}): ", packages.len());
-        std::io::Write::flush(&mut std::io::stdout()).map_err(|e| format!("Failed to flush stdout: {}", e))?;
+/// Pull the optional dependencies `selection` names into the rest of this
+/// run's resolution. Without it, optionals are never installed.
+pub fn select_optional(selection: OptionalSelection) {
+    if let Ok(mut selected) = OPTIONAL_SELECTION.lock() {
+        *selected = Some(selection);
+    }
+}
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).map_err(|e| format!("Failed to read input: {}", e))?;
-        let input = input.trim();
+fn optional_selection() -> OptionalSelection {
+    OPTIONAL_SELECTION
+        .lock()
+        .ok()
+        .and_then(|selected| selected.clone())
+        .unwrap_or_default()
+}
 
-        match input.parse::<usize>() {
-            Ok(0) => return Ok(None), // Cancelled
-            Ok(n) if n > 0 && n <= packages.len() => {
-                return Ok(Some(packages[n - 1].clone()));
-            }
-            _ => {
-                println!("Invalid selection. Please enter a number between 0 and {}.", packages.len());
-            }
+/// `metadata` with the optionals `selection` picks added to its runtime
+/// dependencies, to resolve them like any other. An optional that can't be
+/// found is dropped rather than failing the install. Only the copy carries
+/// them, so `metadata` itself never records them as hard dependencies.
+pub(crate) async fn with_selected_optionals(
+    metadata: &ProcessedMetaData,
+    selection: &OptionalSelection,
+    sources: &[OriginKind],
+) -> Result<ProcessedMetaData, String> {
+    let mut with_optionals = metadata.clone();
+    for optional in metadata.selected_optional(selection) {
+        let dep_name = optional.dependency.name();
+        if ProcessedMetaData::get_metadata(&dep_name, None, sources, true).await?.is_some() {
+            with_optionals.runtime_dependencies.push(optional.dependency.clone());
+        } else {
+            println!(
+                "\x1B[93m[WARN] Optional dependency `{}` of `{}` is unavailable; skipping it.\x1B[0m",
+                dep_name, metadata.name
+            );
         }
     }
+    Ok(with_optionals)
 }
 
-fn matches_search(meta: &ProcessedMetaData, query: &str, exact: bool) -> bool {
-    if query.is_empty() {
-        return true;
+// Dependencies provided outside pax (`--assume-installed`), plus those
+// recorded by earlier runs
+static ASSUMED_INSTALLED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+fn assumed_installed_file() -> Result<PathBuf, String> {
+    let installed = utils::get_metadata_dir()?;
+    let mut path = installed.parent().map(Path::to_path_buf).unwrap_or(installed);
+    path.push("assumed-installed");
+    Ok(path)
+}
+
+/// Names recorded with `record_assumed_installed`, one per line.
+pub fn recorded_assumed_installed() -> Result<Vec<String>, String> {
+    let path = assumed_installed_file()?;
+    if !path.exists() {
+        return Ok(Vec::new());
     }
-    if exact {
-        meta.name.eq_ignore_ascii_case(query)
+    let contents = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(contents.lines().map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect())
+}
+
+/// Remember `names` as provided outside pax, so later runs don't try to
+/// resolve them either.
+pub fn record_assumed_installed(names: &[String]) -> Result<(), String> {
+    let mut recorded = recorded_assumed_installed()?;
+    recorded.extend(names.iter().cloned());
+    recorded.sort();
+    recorded.dedup();
+    let path = assumed_installed_file()?;
+    let mut contents = recorded.join("\n");
+    contents.push('\n');
+    fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Forget `names` as provided outside pax, both for this run and for later
+/// ones. Returns the names that had been recorded.
+pub fn forget_assumed_installed(names: &[String]) -> Result<Vec<String>, String> {
+    let (forgotten, kept): (Vec<String>, Vec<String>) =
+        recorded_assumed_installed()?.into_iter().partition(|name| names.contains(name));
+    let path = assumed_installed_file()?;
+    if kept.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
     } else {
-        let query_lower = query.to_ascii_lowercase();
-        meta.name.to_ascii_lowercase().contains(&query_lower)
-            || meta
-                .description
-                .to_ascii_lowercase()
-                .contains(&query_lower)
+        let mut contents = kept.join("\n");
+        contents.push('\n');
+        fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
     }
+    if let Ok(mut assumed) = ASSUMED_INSTALLED.lock() {
+        loaded_assumed(&mut assumed).retain(|name| !names.contains(name));
+    }
+    Ok(forgotten)
 }
 
-// Thread-local storage for refresh flag
-thread_local! {
-    static FORCE_REFRESH: std::cell::Cell<bool> = std::cell::Cell::new(false);
+/// The assumed set, filled from what earlier runs recorded on first use.
+fn loaded_assumed(assumed: &mut Option<HashSet<String>>) -> &mut HashSet<String> {
+    assumed.get_or_insert_with(|| recorded_assumed_installed().unwrap_or_default().into_iter().collect())
 }
 
-pub fn set_force_refresh(refresh: bool) {
-    FORCE_REFRESH.with(|f| f.set(refresh));
+/// Treat `names` as already installed for the rest of this run: resolution
+/// prunes them, and their own dependencies, without fetching anything.
+/// Names recorded by earlier runs stay assumed too.
+pub fn assume_installed(names: &[String]) {
+    if let Ok(mut assumed) = ASSUMED_INSTALLED.lock() {
+        loaded_assumed(&mut assumed).extend(names.iter().cloned());
+    }
+}
+
+pub fn is_assumed_installed(name: &str) -> bool {
+    let Ok(mut assumed) = ASSUMED_INSTALLED.lock() else {
+        return false;
+    };
+    loaded_assumed(&mut assumed).contains(name)
 }
 
 /// Recursively resolve all dependencies for a package
 /// NEW ARCHITECTURE: Uses repo index (no HTTP during resolution)
 /// Returns error if any dependencies are missing from repositories
-async fn resolve_all_dependencies(
+pub(crate) async fn resolve_all_dependencies(
     package: &ProcessedMetaData,
     sources: &[OriginKind],
 ) -> Result<Vec<ProcessedMetaData>, String> {
@@ -3527,7 +4660,7 @@ async fn resolve_all_dependencies(
         Err(e) => {
             eprintln!("Warning: Failed to build repo index: {}. Falling back to old method.", e);
             // Fallback to old method if index building fails
-            return Ok(resolve_all_dependencies_old(package, sources).await);
+            return resolve_all_dependencies_old(package, sources).await;
         }
     };
     
@@ -3544,8 +4677,8 @@ async fn resolve_all_dependencies(
 
     // PHASE 1: Load installed packages and build provides lookup (ONLY from local database)
     let installed_packages = match list_installed_packages(false, false, None) {
-        Ok(packages) => packages,
-        Err(_) => Vec::new(),
+        Ok(packages) if !ignore_installed() => packages,
+        _ => Vec::new(),
     };
     
     // Track missing dependencies (packages not found in repositories and not installed)
@@ -3556,6 +4689,27 @@ async fn resolve_all_dependencies(
     // Build provides lookup from installed packages (cross-format compatible)
     let installed_provides = InstalledPackageProvides::from_installed_packages(&installed_packages);
 
+    // Choose versions up front, so differing ranges on a shared dependency
+    // are settled by backtracking rather than by whichever version is listed first
+    let requirements: Vec<utils::DepVer> = package
+        .runtime_dependencies
+        .iter()
+        .filter_map(crate::solver::requirement)
+        .collect();
+    let installed_versions: BTreeMap<String, Version> = installed_packages
+        .iter()
+        .filter_map(|installed| Some((installed.name.clone(), Version::parse(&installed.version).ok()?)))
+        .collect();
+    let provided = |name: &str| installed_provides.is_dependency_satisfied(name).is_some();
+    let source = crate::solver::InstalledSource {
+        inner: &crate::solver::IndexSource { index: &repo_index, pax_only: is_pax_package },
+        versions: &installed_versions,
+        provided: &provided,
+    };
+    // A conflict the solver can't get around fails the resolution, with the
+    // requirements that clashed in the message
+    let solved = crate::solver::solve(&requirements, &source)?;
+
     // PHASE 2: Resolve dependencies using INDEX ONLY (no HTTP)
     let resolution_start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
     
@@ -3584,6 +4738,11 @@ async fn resolve_all_dependencies(
     while queue_index < to_process.len() {
         let dep_name = to_process[queue_index].clone();
         queue_index += 1;
+        crate::cancel::check_cancelled()?;
+        render_progress("Resolving", queue_index, to_process.len(), &dep_name);
+        if is_assumed_installed(&dep_name) {
+            continue;
+        }
         
         // #region agent log
         let _ = write_debug_log(&serde_json::json!({
@@ -3608,7 +4767,14 @@ async fn resolve_all_dependencies(
         } else {
             repo_index.lookup_all_versions(&dep_name)
         };
-        let dep_metadata = all_versions.first();
+        let dep_metadata = match solved.get(&dep_name) {
+            Some(version) => all_versions
+                .iter()
+                .find(|metadata| Version::parse(&metadata.version).is_ok_and(|parsed| parsed == *version)),
+            // The solver leaves out names it can't reason about (unparseable
+            // versions, provides), so those take the first listed version
+            None => all_versions.first(),
+        };
         
         // #region agent log
         let _ = write_debug_log(&serde_json::json!({
@@ -4202,7 +5368,7 @@ async fn resolve_all_dependencies(
 async fn resolve_all_dependencies_old(
     package: &ProcessedMetaData,
     sources: &[OriginKind],
-) -> Vec<ProcessedMetaData> {
+) -> Result<Vec<ProcessedMetaData>, String> {
     let main_package_name = &package.name;
     use std::collections::{HashMap, HashSet};
     
@@ -4211,8 +5377,8 @@ async fn resolve_all_dependencies_old(
     // PHASE 1: Load installed packages DB
     let phase1_start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
     let installed_packages = match list_installed_packages(false, false, None) {
-        Ok(packages) => packages,
-        Err(_) => Vec::new(),
+        Ok(packages) if !ignore_installed() => packages,
+        _ => Vec::new(),
     };
     let phase1_end = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
     let _ = write_debug_log(&serde_json::json!({
@@ -4271,7 +5437,7 @@ async fn resolve_all_dependencies_old(
     
     while !to_process.is_empty() {
         iteration += 1;
-        let all_deps: Vec<String> = to_process.drain(..).collect();
+        let all_deps: Vec<String> = to_process.drain(..).filter(|name| !is_assumed_installed(name)).collect();
         
         if all_deps.is_empty() {
             break;
@@ -4303,7 +5469,7 @@ async fn resolve_all_dependencies_old(
                 }
             }).collect();
             
-            let chunk_results = join_all(chunk_futures).await;
+            let chunk_results = join_all(chunk_futures).await.into_iter().collect::<Result<Vec<_>, _>>()?;
             
             // Update memo and results
             for (dep_name, result) in chunk.iter().zip(chunk_results.iter()) {
@@ -4390,7 +5556,7 @@ async fn resolve_all_dependencies_old(
         "timestamp": total_end
     }));
 
-    result
+    Ok(result)
 }
 
 
@@ -4957,7 +6123,7 @@ fn map_library_dependency_to_package(dep_name: &str) -> Option<String> {
 
 pub async fn get_packages(
     package_names: Vec<String>,
-    _preferred_source: Option<&str>,
+    preferred_source: Option<&str>,
     force_refresh: bool,
 ) -> Result<Vec<InstallPackage>, String> {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -4984,7 +6150,16 @@ pub async fn get_packages(
     if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/blester/pax-rs/.cursor/debug.log") {
         let _ = writeln!(file, "{{\"sessionId\":\"debug-session\",\"runId\":\"timing\",\"hypothesisId\":\"DELAY\",\"location\":\"metadata/src/processed/mod.rs:4188\",\"message\":\"after_get_settings\",\"data\":{{\"timestamp\":{},\"duration_ms\":{}}},\"timestamp\":{}}}", after_get_settings, after_get_settings.saturating_sub(before_get_settings), after_get_settings);
     }
-    let sources: Vec<OriginKind> = settings.sources.clone();
+    let mut sources: Vec<OriginKind> = settings.target_sources();
+    // A local directory given with `--from`/`--from-dir` is the only source,
+    // dependencies included, so an offline bundle never reaches the network
+    let confined = preferred_source.is_some_and(|from| settings::file_url_path(from).is_some());
+    // `--from file://...` searches only that local repository
+    if let Some(from) = preferred_source
+        && settings::file_url_path(from).is_some()
+    {
+        sources = vec![OriginKind::Pax(from.to_string()).normalize_file_url()];
+    }
     
     // Build repo index FIRST to avoid per-package HTTP fetches (this eliminates the ~15s delay!)
     use crate::repo_index::MultiRepoIndex;
@@ -5013,30 +6188,53 @@ pub async fn get_packages(
         let name = name.clone();
         let sources_clone = sources.clone();
         let repo_index_clone = repo_index.as_ref();
+        let settings = &settings;
         async move {
-            // Try to use repo index first (fast path - no HTTP calls!)
-            let all_matches: Vec<ProcessedMetaData> = if let Some(index) = repo_index_clone {
-                // Use index for fast lookup - get all versions from all repos
-                index.lookup_all_versions(&name)
+            if crate::cancel::is_cancelled() {
+                return None;
+            }
+            // A pinned package is only looked up in its pinned origin
+            let pinned = !confined && settings.pins.iter().any(|pin| pin.matches(&name));
+            // Candidates come from the highest-priority repo that has the package;
+            // the other repos only contribute to `available_versions`
+            let (all_matches, preferred): (Vec<ProcessedMetaData>, Vec<ProcessedMetaData>) = if pinned {
+                let package_sources = settings.sources_for(&name);
+                match MultiRepoIndex::build(&package_sources, force_refresh).await {
+                    Ok(index) => (index.lookup_all_versions(&name), index.lookup_preferred(&name)),
+                    Err(_) => {
+                        let all = ProcessedMetaData::get_all_metadata(&name, None, &package_sources, true).await;
+                        let first = all.iter().take(1).cloned().collect();
+                        (all, first)
+                    }
+                }
+            } else if let Some(index) = repo_index_clone {
+                // Try to use repo index first (fast path - no HTTP calls!)
+                (index.lookup_all_versions(&name), index.lookup_preferred(&name))
             } else {
-                // Fallback to per-source fetches if index failed
-                ProcessedMetaData::get_all_metadata(&name, None, &sources_clone, true).await
+                // Fallback to per-source fetches if index failed; results follow source order
+                let all = ProcessedMetaData::get_all_metadata(&name, None, &sources_clone, true).await;
+                let first = all.iter().take(1).cloned().collect();
+                (all, first)
             };
 
             // If no matches found, return None
-            if all_matches.is_empty() {
+            if preferred.is_empty() {
                 return None;
             }
 
+            // Every version the indexes offer, for `pax info` and `install -s`
+            let available_versions = sort_versions_desc(all_matches.iter().map(|m| m.version.clone()).collect());
+
             // Select package (either automatically or via user choice)
-            let metadata = if all_matches.len() == 1 {
-                all_matches.into_iter().next().unwrap()
+            let mut metadata = if preferred.len() == 1 {
+                preferred.into_iter().next().unwrap()
             } else {
-                match select_package_from_multiple(&all_matches, &name).await {
+                match select_package_from_multiple(&preferred, &name).await {
                     Ok(Some(selected)) => selected,
                     _ => return None, // User cancelled or error
                 }
             };
+            metadata.available_versions = available_versions;
 
             // #region agent log
             let _ = write_debug_log(&serde_json::json!({
@@ -5060,6 +6258,22 @@ pub async fn get_packages(
             }));
             // #endregion
 
+            if skip_dependencies() {
+                return Some(InstallPackage {
+                    metadata,
+                    run_deps: Vec::new(),
+                    build_deps: Vec::new(),
+                });
+            }
+
+            let with_optionals = match with_selected_optionals(&metadata, &optional_selection(), &sources_clone).await {
+                Ok(with_optionals) => with_optionals,
+                Err(fault) => {
+                    eprintln!("{}", fault);
+                    return None;
+                }
+            };
+
             // Resolve all dependencies recursively
             // #region agent log
             let _ = write_debug_log(&serde_json::json!({
@@ -5077,7 +6291,7 @@ pub async fn get_packages(
             }));
             // #endregion
             
-            let mut run_deps = match resolve_all_dependencies(&metadata, &sources_clone).await {
+            let mut run_deps = match resolve_all_dependencies(&with_optionals, &sources_clone).await {
                 Ok(deps) => {
                     // #region agent log
                     let _ = write_debug_log(&serde_json::json!({
@@ -5113,7 +6327,9 @@ pub async fn get_packages(
                     // #endregion
                     // Error message already formatted nicely - return None to skip this package
                     // We'll collect the error and fail after all packages are processed
-                    eprintln!("{}", e);
+                    if !crate::cancel::is_cancelled() {
+                        eprintln!("{}", e);
+                    }
                     return None;
                 }
             };
@@ -5137,7 +6353,8 @@ pub async fn get_packages(
                 ];
                 let libs_results = join_all(libs_futures).await;
 
-                for libs_metadata in libs_results.into_iter().flatten() {
+                // A best-effort extra, so a lookup that fails just doesn't add it
+                for libs_metadata in libs_results.into_iter().filter_map(Result::ok).flatten() {
                     if !run_deps.iter().any(|dep| dep.name == libs_metadata.name) {
                         run_deps.push(libs_metadata);
                         break; // Found one, no need to try others
@@ -5146,7 +6363,7 @@ pub async fn get_packages(
             }
 
             // Resolve build dependencies in parallel
-            let build_dep_futures: Vec<_> = metadata.build_dependencies.iter().map(|dep| {
+            let build_dep_futures: Vec<_> = metadata.build_dependencies.iter().filter(|dep| !is_assumed_installed(&dep.name())).map(|dep| {
                 let dep_name = match dep {
                     DependKind::Latest(name) => name.clone(),
                     DependKind::Specific(dep_ver) => dep_ver.name.clone(),
@@ -5158,7 +6375,13 @@ pub async fn get_packages(
                 }
             }).collect();
 
-            let build_deps: Vec<_> = join_all(build_dep_futures).await.into_iter().flatten().collect();
+            let build_deps: Vec<_> = match join_all(build_dep_futures).await.into_iter().collect::<Result<Vec<_>, _>>() {
+                Ok(build_deps) => build_deps.into_iter().flatten().collect(),
+                Err(fault) => {
+                    eprintln!("{}", fault);
+                    return None;
+                }
+            };
 
             // Convert ProcessedMetaData to InstallPackage
             let install_package = InstallPackage {
@@ -5171,20 +6394,114 @@ pub async fn get_packages(
     }).collect();
     
     let results = join_all(package_futures).await;
+    crate::cancel::check_cancelled()?;
     let packages: Vec<_> = results.into_iter().flatten().collect();
     Ok(packages)
 }
 
+fn processed_from_installed(installed: InstalledMetaData) -> ProcessedMetaData {
+    let installed_size = installed.size_on_disk();
+    ProcessedMetaData {
+        name: installed.name,
+        kind: installed.kind,
+        description: installed.description,
+        version: installed.version,
+        origin: installed.origin,
+        dependent: installed.dependent,
+        build_dependencies: Vec::new(),
+        runtime_dependencies: installed.dependencies.iter().map(|dep| DependKind::Specific(dep.clone())).collect(),
+        optional_dependencies: installed.optional_dependencies,
+        installed_size,
+        keywords: installed.keywords,
+        categories: installed.categories,
+        install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
+            build: "".to_string(),
+            install: "".to_string(),
+            uninstall: "".to_string(),
+            purge: "".to_string(),
+        }),
+        hash: installed.hash,
+        package_type: format!("{:?}", installed.kind),
+        installed: true,
+        dependencies: installed.dependencies.iter().map(|dep| dep.name.clone()).collect(),
+        dependents: installed.dependents.iter().map(|dep| dep.name.clone()).collect(),
+        installed_files: installed.installed_files,
+        available_versions: Vec::new(),
+    }
+}
+
+/// Print an offline-mode refusal once per origin rather than once per lookup.
+fn warn_offline(fault: String) {
+    static WARNED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+    if let Ok(mut warned) = WARNED.lock()
+        && warned.get_or_insert_with(HashSet::new).insert(fault.clone())
+    {
+        println!("\x1B[93m[WARN] {}\x1B[0m", fault);
+    }
+}
+
+/// Newest first by `utils::Version`, without duplicates.
+fn sort_versions_desc(mut versions: Vec<String>) -> Vec<String> {
+    versions.sort_by(|a, b| {
+        let parse = |v: &str| Version::parse(v.trim_start_matches('v')).unwrap_or_default();
+        parse(b).cmp(&parse(a)).then_with(|| b.cmp(a))
+    });
+    versions.dedup();
+    versions
+}
+
 pub async fn get_package_info(
     package_name: &str,
-    _show_files: bool,
+    show_files: bool,
     _show_deps: bool,
-    _show_versions: bool,
-    _settings: Option<&settings::SettingsYaml>,
+    show_versions: bool,
+    settings: Option<&settings::SettingsYaml>,
 ) -> Result<ProcessedMetaData, String> {
-    let sources = vec![settings::OriginKind::Pax("local".to_string())];
-    ProcessedMetaData::get_metadata(package_name, None, &sources, true).await
-        .ok_or_else(|| format!("Package {} not found", package_name))
+    // Installed packages are described by their local metadata and manifest
+    if let Ok(installed) = InstalledMetaData::open(package_name) {
+        let mut info = processed_from_installed(installed);
+        // Records from before the file list was kept fall back to the manifest
+        if show_files
+            && info.installed_files.is_empty()
+            && let Ok(manifest) = crate::file_tracking::FileManifest::load(package_name)
+        {
+            info.installed_files = manifest
+                .file_paths()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+        }
+        if show_versions {
+            info.available_versions = info.fetch_available_versions().await;
+        }
+        return Ok(info);
+    }
+
+    let sources = settings
+        .map(|settings| settings.sources_for(package_name))
+        .unwrap_or_default();
+    let mut info = ProcessedMetaData::get_metadata(package_name, None, &sources, false)
+        .await?
+        .ok_or_else(|| format!("Package {} not found", package_name))?;
+    info.installed = false;
+    // Not installed yet, so list what the package metadata says it ships
+    if show_files
+        && let ProcessedInstallKind::PreBuilt(prebuilt) = &info.install_kind
+    {
+        let mut files: Vec<String> = prebuilt
+            .critical
+            .iter()
+            .chain(prebuilt.configs.iter())
+            .cloned()
+            .collect();
+        files.sort();
+        files.dedup();
+        info.installed_files = files;
+    }
+    if show_versions {
+        info.available_versions = info.fetch_available_versions().await;
+    }
+    Ok(info)
 }
 
 pub fn list_installed_packages(
@@ -5204,8 +6521,9 @@ pub fn list_installed_packages(
         if path.extension().and_then(|s| s.to_str()) == Some("json") {
             let content = std::fs::read_to_string(&path)
                 .map_err(|e| format!("Failed to read file: {}", e))?;
-            let installed: InstalledMetaData = serde_json::from_str(&content)
-                .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+            let installed: InstalledMetaData = serde_json::from_str(&content).map_err(|e| {
+                format!("Failed to parse {}: {} (run `pax doctor` to check the installed metadata)", path.display(), e)
+            })?;
 
             // Apply filter if provided
             if let Some(pattern) = filter_pattern {
@@ -5255,6 +6573,99 @@ pub fn list_installed_packages(
     Ok(all_packages)
 }
 
+/// Packages installed automatically as dependencies that nothing installed
+/// depends on any more.
+pub fn find_orphans(packages: &[InstalledMetaData]) -> Vec<&InstalledMetaData> {
+    let needed: HashSet<&str> = packages
+        .iter()
+        .flat_map(|package| package.dependencies.iter().map(|dep| dep.name.as_str()))
+        .collect();
+    packages
+        .iter()
+        .filter(|package| package.reason() == InstallReason::Auto && !needed.contains(package.name.as_str()))
+        .collect()
+}
+
+/// What removing `removed` leaves unneeded: automatically installed packages
+/// that the removed ones depended on (`removed_deps`) or installed, and that
+/// nothing in `packages` still depends on. With `no_deps` the dependency
+/// graph is left alone, so there are none.
+pub fn orphaned_by_removal(
+    packages: &[InstalledMetaData],
+    removed: &[String],
+    removed_deps: &HashSet<String>,
+    no_deps: bool,
+) -> Vec<String> {
+    if no_deps {
+        return Vec::new();
+    }
+    let remaining: Vec<&InstalledMetaData> = packages
+        .iter()
+        .filter(|package| !removed.contains(&package.name))
+        .collect();
+    let needed: HashSet<&str> = remaining
+        .iter()
+        .flat_map(|package| package.dependencies.iter().map(|dep| dep.name.as_str()))
+        .collect();
+    let mut orphans: Vec<String> = remaining
+        .iter()
+        .filter(|package| {
+            removed_deps.contains(&package.name)
+                || package.installed_by.as_ref().is_some_and(|parent| removed.contains(parent))
+        })
+        .filter(|package| package.reason() == InstallReason::Auto && !needed.contains(package.name.as_str()))
+        .map(|package| package.name.clone())
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+/// What `name` depends on according to the installed records: its direct
+/// dependencies, or with `recursive` everything reachable through them.
+pub fn installed_depends(packages: &[InstalledMetaData], name: &str, recursive: bool) -> Vec<String> {
+    walk_installed(name, recursive, |current| {
+        packages
+            .iter()
+            .filter(|package| package.name == current)
+            .flat_map(|package| package.dependencies.iter().map(|dep| dep.name.clone()))
+            .collect()
+    })
+}
+
+/// Installed packages that depend on `name`, directly or with `recursive`
+/// through other installed packages.
+pub fn installed_rdepends(packages: &[InstalledMetaData], name: &str, recursive: bool) -> Vec<String> {
+    walk_installed(name, recursive, |current| {
+        let declared = packages
+            .iter()
+            .filter(|package| package.dependencies.iter().any(|dep| dep.name == current))
+            .map(|package| package.name.clone());
+        let recorded = packages
+            .iter()
+            .filter(|package| package.name == current)
+            .flat_map(|package| package.dependents.iter().map(|dependent| dependent.name.clone()));
+        declared.chain(recorded).collect()
+    })
+}
+
+fn walk_installed(name: &str, recursive: bool, next: impl Fn(&str) -> Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::from([name.to_string()]);
+    let mut queue = vec![name.to_string()];
+    let mut found = Vec::new();
+    while let Some(current) = queue.pop() {
+        for neighbour in next(&current) {
+            if seen.insert(neighbour.clone()) {
+                found.push(neighbour.clone());
+                if recursive {
+                    queue.push(neighbour);
+                }
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
 pub fn get_local_deps(package_name: &str) -> Result<Vec<String>, String> {
     let installed_dir = utils::get_metadata_dir()?;
     let package_file = installed_dir.join(format!("{}.json", package_name));
@@ -5275,10 +6686,19 @@ pub async fn search_packages(
     exact_match: bool,
     installed_only: bool,
     _show_deps: bool,
+    category: Option<&str>,
     settings: Option<&settings::SettingsYaml>,
 ) -> Result<Vec<ProcessedMetaData>, String> {
-    let mut results = Vec::new();
+    use crate::repo_index::MultiRepoIndex;
+
+    let mut ranked = Vec::new();
     let mut seen = HashSet::new();
+    let wanted = |meta: &ProcessedMetaData| {
+        if category.is_some_and(|category| !in_category(meta, category)) {
+            return None;
+        }
+        search_rank(meta, query, exact_match)
+    };
     let installed_dir = utils::get_metadata_dir()?;
     
     for entry in std::fs::read_dir(&installed_dir)
@@ -5291,128 +6711,362 @@ pub async fn search_packages(
                 .map_err(|e| format!("Failed to read file: {}", e))?;
             let installed: InstalledMetaData = serde_json::from_str(&content)
                 .map_err(|e| format!("Failed to parse JSON: {}", e))?;
-            
-            if installed.name.contains(query) || installed.description.contains(query) {
-                let processed = ProcessedMetaData {
-                    name: installed.name,
-                    kind: installed.kind,
-                    description: installed.description,
-                    version: installed.version,
-                    origin: installed.origin,
-                    dependent: true,
-                    build_dependencies: installed.dependencies.iter().map(|dep| DependKind::Specific(dep.clone())).collect(),
-                    runtime_dependencies: installed.dependencies.iter().map(|dep| DependKind::Specific(dep.clone())).collect(),
-                    install_kind: ProcessedInstallKind::Compilable(ProcessedCompilable {
-                        build: "".to_string(),
-                        install: "".to_string(),
-                        uninstall: "".to_string(),
-                        purge: "".to_string(),
-                    }),
-                    hash: installed.hash,
-                    package_type: format!("{:?}", installed.kind.clone()),
-                    installed: true,
-                    dependencies: installed.dependencies.iter().map(|dep| dep.name.clone()).collect(),
-                    dependents: installed.dependents.iter().map(|dep| dep.name.clone()).collect(),
-                    installed_files: Vec::new(), // TODO: implement file tracking
-                    available_versions: Vec::new(), // TODO: implement version discovery
-                };
+            let processed = processed_from_installed(installed);
+            if let Some(rank) = wanted(&processed) {
                 seen.insert(processed.name.clone());
-                results.push(processed);
+                ranked.push((rank, processed));
             }
         }
     }
     
     if !installed_only {
         if let Some(settings) = settings {
-            let sources = settings.sources.clone();
-            let remote_matches = ProcessedMetaData::get_all_metadata(query, None, &sources, true).await;
+            let sources = settings.target_sources();
+            let mut remote_matches: Vec<ProcessedMetaData> = match MultiRepoIndex::build(&sources, false).await {
+                Ok(index) => index.latest_packages().into_iter().cloned().collect(),
+                Err(_) => Vec::new(),
+            };
+            if !query.is_empty() {
+                remote_matches.extend(ProcessedMetaData::get_all_metadata(query, None, &sources, true).await);
+            }
 
             for mut remote in remote_matches {
-                if !seen.contains(&remote.name) && matches_search(&remote, query, exact_match) {
+                if seen.contains(&remote.name) {
+                    continue;
+                }
+                if let Some(rank) = wanted(&remote) {
                     remote.installed = false;
                     seen.insert(remote.name.clone());
-                    results.push(remote);
+                    ranked.push((rank, remote));
                 }
             }
         }
     }
     
-    Ok(results)
+    // Stable, so installed packages stay ahead of remote ones of equal rank
+    ranked.sort_by_key(|(rank, _)| *rank);
+    Ok(ranked.into_iter().map(|(_, package)| package).collect())
+}
+
+/// How an available upgrade is treated by `pax upgrade`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpdateClass {
+    Upgradable,
+    /// Held by `PackageHoldManager`.
+    Held,
+    /// Blocked by a dependency constraint, with why.
+    HeldBack(String),
+}
+
+/// Available upgrades, split the way `pax upgrade` reports them.
+#[derive(Clone, Debug, Default)]
+pub struct UpdateCheck {
+    pub upgradable: Vec<ProcessedMetaData>,
+    pub held: Vec<ProcessedMetaData>,
+    /// The reason given for each hold that has one.
+    pub hold_reasons: BTreeMap<String, String>,
+    pub held_back: Vec<(ProcessedMetaData, String)>,
+}
+
+impl UpdateCheck {
+    /// Queue the upgradable packages, noting the held and held-back ones.
+    pub fn to_plan(&self) -> QueuedChanges {
+        let mut plan = QueuedChanges::new();
+        for update in &self.upgradable {
+            let from = InstalledMetaData::open(&update.name)
+                .map(|installed| installed.version)
+                .unwrap_or_default();
+            plan.queue_upgrade(&update.name, &from, &update.version);
+        }
+        plan.held = self.held.iter().map(|update| update.name.clone()).collect();
+        plan.hold_reasons = self.hold_reasons.clone();
+        plan.held_back = self
+            .held_back
+            .iter()
+            .map(|(update, reason)| (update.name.clone(), reason.clone()))
+            .collect();
+        plan
+    }
+}
+
+/// Classify every newer version in `available` as upgradable, held, or held
+/// back. An upgrade is held back when the intersection of what installed
+/// packages require of it excludes the new version, or when the new version
+/// needs an installed dependency at a version that will not be there.
+/// Holding one back can block others, so this repeats until nothing changes.
+pub fn classify_updates(
+    installed: &BTreeMap<String, UpgradeCandidate>,
+    available: &BTreeMap<String, UpgradeCandidate>,
+    held: &HashSet<String>,
+) -> BTreeMap<String, UpdateClass> {
+    let parse = |version: &str| Version::parse(version).unwrap_or_default();
+    let mut classes = BTreeMap::new();
+    for (name, latest) in available {
+        let Some(current) = installed.get(name) else {
+            continue;
+        };
+        if parse(&latest.version) > parse(&current.version) {
+            let class = if held.contains(name) { UpdateClass::Held } else { UpdateClass::Upgradable };
+            classes.insert(name.clone(), class);
+        }
+    }
+
+    loop {
+        let upgrading: Vec<&String> = classes
+            .iter()
+            .filter(|(_, class)| **class == UpdateClass::Upgradable)
+            .map(|(name, _)| name)
+            .collect();
+        let wanted = |name: &String| match available.get(name) {
+            Some(latest) if upgrading.contains(&name) => latest,
+            _ => &installed[name],
+        };
+
+        let mut blocked = None;
+        for &name in &upgrading {
+            let new = parse(&available[name].version);
+            let mut constraint = Some(Range { lower: VerReq::NoBound, upper: VerReq::NoBound });
+            let mut culprit = None;
+            for other in installed.keys() {
+                for dep in wanted(other).dependencies.iter().filter(|dep| dep.name == *name) {
+                    constraint = dep.range.negotiate(constraint);
+                    if culprit.is_none() && !dep.range.contains(&new) {
+                        culprit = Some(other);
+                    }
+                }
+            }
+            if !constraint.is_some_and(|range| range.contains(&new)) {
+                let reason = match culprit {
+                    Some(other) => format!("{} requires the installed version", other),
+                    None => String::from("conflicting version constraints"),
+                };
+                blocked = Some((name.clone(), reason));
+                break;
+            }
+            if let Some(dep) = available[name].dependencies.iter().find(|dep| {
+                installed.contains_key(&dep.name) && !dep.range.contains(&parse(&wanted(&dep.name).version))
+            }) {
+                blocked = Some((name.clone(), format!("needs a different version of {}", dep.name)));
+                break;
+            }
+        }
+
+        match blocked {
+            Some((name, reason)) => {
+                classes.insert(name, UpdateClass::HeldBack(reason));
+            }
+            None => return classes,
+        }
+    }
 }
 
-pub async fn collect_updates(force_refresh: bool) -> Result<Vec<ProcessedMetaData>, String> {
+pub async fn collect_updates(force_refresh: bool) -> Result<UpdateCheck, String> {
     // Set thread-local refresh flag for dependency resolution
     set_force_refresh(force_refresh);
     // Check for updates from repositories
-    let installed_dir = utils::get_metadata_dir()?;
     let settings = settings::SettingsYaml::get_settings()
         .map_err(|e| format!("Failed to load settings: {}", e))?;
-    let sources = settings.sources;
-    let mut updates = Vec::new();
-    
-    for entry in std::fs::read_dir(&installed_dir)
-        .map_err(|e| format!("Failed to read directory: {}", e))? {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let path = entry.path();
-        
-        if path.extension().and_then(|s| s.to_str()) == Some("json") {
-            let content = std::fs::read_to_string(&path)
-                .map_err(|e| format!("Failed to read file: {}", e))?;
-            let installed: InstalledMetaData = serde_json::from_str(&content)
-                .map_err(|e| format!("Failed to parse JSON: {}", e))?;
-            
-            // Check if newer version is available
-            if let Some(latest) = ProcessedMetaData::get_metadata(&installed.name, None, &sources, true).await {
-                let installed_version = utils::Version::parse(&installed.version)
-                    .unwrap_or_default();
-                let latest_version = utils::Version::parse(&latest.version)
-                    .unwrap_or_default();
-                
-                if latest_version > installed_version {
-                    updates.push(latest);
-                }
-            }
+    let mut holds = crate::PackageHoldManager::new();
+    holds.load_all()?;
+
+    let mut installed = BTreeMap::new();
+    let mut fetched = BTreeMap::new();
+    let mut held = HashSet::new();
+    for package in list_installed_packages(false, false, None)? {
+        let sources = settings.sources_for(&package.name);
+        if let Some(latest) = ProcessedMetaData::get_metadata(&package.name, None, &sources, true).await? {
+            fetched.insert(package.name.clone(), latest);
+        }
+        if !holds.can_upgrade(&package.name) {
+            held.insert(package.name.clone());
         }
+        installed.insert(package.name.clone(), UpgradeCandidate::from_installed(&package));
     }
-    
-    Ok(updates)
+    let available = fetched
+        .iter()
+        .map(|(name, latest)| (name.clone(), UpgradeCandidate::from_processed(latest)))
+        .collect();
+
+    let mut check = UpdateCheck { hold_reasons: hold_reasons(&holds, &held), ..Default::default() };
+    for (name, class) in classify_updates(&installed, &available, &held) {
+        let Some(latest) = fetched.remove(&name) else {
+            continue;
+        };
+        match class {
+            UpdateClass::Upgradable => check.upgradable.push(latest),
+            UpdateClass::Held => check.held.push(latest),
+            UpdateClass::HeldBack(reason) => check.held_back.push((latest, reason)),
+        }
+    }
+    Ok(check)
+}
+
+/// The reasons recorded for the holds on `held`, where one was given.
+fn hold_reasons<'a>(holds: &crate::PackageHoldManager, held: impl IntoIterator<Item = &'a String>) -> BTreeMap<String, String> {
+    held.into_iter()
+        .filter_map(|name| {
+            let reason = holds.hold(name)?.reason.clone()?;
+            Some((name.clone(), reason))
+        })
+        .collect()
 }
 
-pub async fn upgrade_all(force_refresh: bool) -> Result<Vec<String>, String> {
+pub async fn upgrade_all(force_refresh: bool) -> Result<QueuedChanges, String> {
     // Check for updates on all installed packages
-    let updates = collect_updates(force_refresh).await?;
-    Ok(updates.iter().map(|u| u.name.clone()).collect())
+    let check = collect_updates(force_refresh).await?;
+    let mut plan = check.to_plan();
+    plan.resolve_download_sizes(&check.upgradable.iter().collect::<Vec<_>>()).await;
+    Ok(plan)
+}
+
+/// The parts of a package version that partial-upgrade planning looks at.
+#[derive(Clone, Debug)]
+pub struct UpgradeCandidate {
+    pub version: String,
+    pub dependencies: Vec<DepVer>,
+}
+
+impl UpgradeCandidate {
+    fn from_processed(metadata: &ProcessedMetaData) -> Self {
+        Self {
+            version: metadata.version.clone(),
+            dependencies: metadata
+                .runtime_dependencies
+                .iter()
+                .filter_map(|dep| match dep {
+                    DependKind::Specific(dep) => Some(dep.clone()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn from_installed(metadata: &InstalledMetaData) -> Self {
+        Self {
+            version: metadata.version.clone(),
+            dependencies: metadata.dependencies.clone(),
+        }
+    }
+}
+
+/// Decide which packages to upgrade when only `targets` were asked for.
+/// Installed dependencies are pulled in only when a target's new version no
+/// longer accepts them, and the plan is refused if an upgraded version would
+/// fall outside the constraint of any installed package that depends on it.
+pub fn plan_partial_upgrade(
+    targets: &[String],
+    installed: &BTreeMap<String, UpgradeCandidate>,
+    available: &BTreeMap<String, UpgradeCandidate>,
+) -> Result<QueuedChanges, String> {
+    let parse = |version: &str| Version::parse(version).unwrap_or_default();
+    let mut selected: BTreeMap<String, &UpgradeCandidate> = BTreeMap::new();
+    let mut pending: Vec<String> = targets.iter().rev().cloned().collect();
+
+    while let Some(name) = pending.pop() {
+        if selected.contains_key(&name) {
+            continue;
+        }
+        let (Some(current), Some(latest)) = (installed.get(&name), available.get(&name)) else {
+            continue;
+        };
+        if parse(&latest.version) <= parse(&current.version) {
+            continue;
+        }
+        for dep in &latest.dependencies {
+            let Some(dep_current) = installed.get(&dep.name) else {
+                continue; // Not installed; the install step resolves it
+            };
+            if dep.range.contains(&parse(&dep_current.version)) {
+                continue;
+            }
+            match available.get(&dep.name) {
+                Some(dep_latest) if dep.range.contains(&parse(&dep_latest.version)) => {
+                    pending.push(dep.name.clone());
+                }
+                _ => {
+                    return err!(
+                        "`{}` {} needs a version of `{}` that is neither installed nor available!",
+                        name,
+                        latest.version,
+                        dep.name
+                    );
+                }
+            }
+        }
+        selected.insert(name, latest);
+    }
+
+    // Every installed package must still accept what it depends on
+    for (name, current) in installed {
+        let wanted = selected.get(name).copied().unwrap_or(current);
+        for dep in &wanted.dependencies {
+            if let Some(upgraded) = selected.get(&dep.name)
+                && !dep.range.contains(&parse(&upgraded.version))
+            {
+                return err!(
+                    "Upgrading `{}` to {} would break `{}`, which requires an older version!",
+                    dep.name,
+                    upgraded.version,
+                    name
+                );
+            }
+        }
+    }
+
+    let mut plan = QueuedChanges::new();
+    for (name, latest) in selected {
+        plan.queue_upgrade(&name, &installed[&name].version, &latest.version);
+    }
+    Ok(plan)
 }
 
-pub async fn upgrade_only(package_names: Vec<String>, force_refresh: bool) -> Result<Vec<String>, String> {
+pub async fn upgrade_only(package_names: Vec<String>, force_refresh: bool) -> Result<QueuedChanges, String> {
     // Set thread-local refresh flag for dependency resolution
     set_force_refresh(force_refresh);
-    // Check for updates on specific packages
     let settings = settings::SettingsYaml::get_settings()
         .map_err(|e| format!("Failed to load settings: {}", e))?;
-    let sources = settings.sources;
-    let mut to_upgrade = Vec::new();
-    
-    for name in package_names {
-        // Check installed version
-        let installed = match InstalledMetaData::open(&name) {
-            Ok(installed) => installed,
-            Err(_) => continue, // Not installed
-        };
-        
-        // Check latest version
-        if let Some(latest) = ProcessedMetaData::get_metadata(&name, None, &sources, true).await {
-            let installed_version = utils::Version::parse(&installed.version)
-                .unwrap_or_default();
-            let latest_version = utils::Version::parse(&latest.version)
-                .unwrap_or_default();
-            
-            if latest_version > installed_version {
-                to_upgrade.push(name);
+    let installed: BTreeMap<String, UpgradeCandidate> = list_installed_packages(false, false, None)?
+        .iter()
+        .map(|package| (package.name.clone(), UpgradeCandidate::from_installed(package)))
+        .collect();
+
+    // Fetch the named packages, then any installed dependency their new
+    // versions mention, so planning can see everything it may pull in
+    let mut fetched: BTreeMap<String, ProcessedMetaData> = BTreeMap::new();
+    let mut pending = package_names.clone();
+    let mut seen: HashSet<String> = HashSet::new();
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name.clone()) || !installed.contains_key(&name) {
+            continue;
+        }
+        let sources = settings.sources_for(&name);
+        if let Some(latest) = ProcessedMetaData::get_metadata(&name, None, &sources, true).await? {
+            for dep in UpgradeCandidate::from_processed(&latest).dependencies {
+                pending.push(dep.name);
             }
+            fetched.insert(name, latest);
         }
     }
-    
+    let available: BTreeMap<String, UpgradeCandidate> = fetched
+        .iter()
+        .map(|(name, latest)| (name.clone(), UpgradeCandidate::from_processed(latest)))
+        .collect();
+
+    let mut holds = crate::PackageHoldManager::new();
+    holds.load_all()?;
+    let (held, targets): (Vec<String>, Vec<String>) = package_names
+        .into_iter()
+        .partition(|name| installed.contains_key(name) && !holds.can_upgrade(name));
+
+    let mut to_upgrade = plan_partial_upgrade(&targets, &installed, &available)?;
+    to_upgrade.hold_reasons = hold_reasons(&holds, &held);
+    to_upgrade.held = held;
+    let upgrading: Vec<&ProcessedMetaData> = to_upgrade
+        .upgrade
+        .iter()
+        .filter_map(|name| fetched.get(name))
+        .collect();
+    to_upgrade.resolve_download_sizes(&upgrading).await;
     Ok(to_upgrade)
 }
 
@@ -5423,23 +7077,26 @@ pub async fn upgrade_packages(package_names: Vec<String>, force_refresh: bool) -
     // Upgrade specific packages
     let settings = settings::SettingsYaml::get_settings()
         .map_err(|e| format!("Failed to load settings: {}", e))?;
-    let sources = settings.sources;
-    let runtime = Runtime::new()
-        .map_err(|_| "Failed to create runtime".to_string())?;
-    
     for name in package_names {
+        crate::cancel::check_cancelled()?;
         // Get latest version
-        let latest = ProcessedMetaData::get_metadata(&name, None, &sources, true).await
+        let sources = settings.sources_for(&name);
+        let latest = ProcessedMetaData::get_metadata(&name, None, &sources, true).await?
             .ok_or_else(|| format!("Package {} not found", name))?;
         
-        // Install the latest version (this will handle upgrades)
-        latest.install(&runtime)?;
+        // Install the latest version (this will handle upgrades). Already on
+        // the runtime, so await rather than blocking on it again
+        latest.install_package_impl(false, true, None).await?;
     }
     
     Ok(())
 }
 
-pub async fn emancipate(_package_name: &str) -> Result<(), String> {
+pub async fn emancipate(package_name: &str) -> Result<(), String> {
+    if is_assumed_installed(package_name) {
+        println!("`{}` is assumed installed (provided outside pax); nothing to emancipate.", package_name);
+        return Ok(());
+    }
     // This would typically remove a package and its dependencies
     // For now, just return success
     Ok(())
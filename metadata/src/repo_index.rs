@@ -26,7 +26,8 @@ fn get_cached_mirror_url() -> Result<String, String> {
     }
     
     // Cache miss or expired - fetch new mirror
-    let mirror_url = settings::get_best_mirror_url()?;
+    // The lookup uses blocking HTTP, which panics if run straight on the runtime
+    let mirror_url = tokio::task::block_in_place(settings::get_best_mirror_url)?;
     *guard = (Some(mirror_url.clone()), now);
     Ok(mirror_url)
 }
@@ -94,6 +95,11 @@ impl RepoIndex {
             let _ = writeln!(file, "{{\"sessionId\":\"debug-session\",\"runId\":\"timing\",\"hypothesisId\":\"DELAY\",\"location\":\"metadata/src/repo_index.rs:35\",\"message\":\"load_or_build_start\",\"data\":{{\"origin\":\"{:?}\",\"force_refresh\":{},\"timestamp\":{}}},\"timestamp\":{}}}", origin, force_refresh, load_start, load_start);
         }
         
+        // A local directory is cheap to rescan and changes under us, so it is never cached
+        if let OriginKind::LocalDir(dir) = origin {
+            return Self::build_local_dir_index(Path::new(dir));
+        }
+
         let cache_key = Self::cache_key_for_origin(origin);
         
         let before_cache_check = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
@@ -108,8 +114,9 @@ impl RepoIndex {
                 if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/blester/pax-rs/.cursor/debug.log") {
                     let _ = writeln!(file, "{{\"sessionId\":\"debug-session\",\"runId\":\"timing\",\"hypothesisId\":\"DELAY\",\"location\":\"metadata/src/repo_index.rs:42\",\"message\":\"cache_hit\",\"data\":{{\"timestamp\":{},\"duration_ms\":{}}},\"timestamp\":{}}}", after_cache_check, after_cache_check.saturating_sub(before_cache_check), after_cache_check);
                 }
-                let display_origin = Self::resolve_display_origin(origin);
-                eprintln!("Using cached index for {:?}", display_origin);
+                if utils::logging::enabled(utils::logging::LogLevel::Info) {
+                    eprintln!("Using cached index for {:?}", Self::resolve_display_origin(origin));
+                }
                 return Ok(cached);
             }
         } else {
@@ -135,6 +142,7 @@ impl RepoIndex {
     
     /// Build index by fetching all metadata from repo
     async fn build_index(origin: &OriginKind) -> Result<Self, String> {
+        origin.ensure_reachable()?;
         match origin {
             OriginKind::Rpm(url) | OriginKind::Yum(url) => {
                 Self::build_rpm_index(url).await
@@ -145,7 +153,8 @@ impl RepoIndex {
             OriginKind::Deb(url) => {
                 Self::build_deb_index(url).await
             }
-            OriginKind::Github { .. } | OriginKind::Apt(_) | OriginKind::CloudflareR2 { .. } | OriginKind::LocalDir(_) => {
+            OriginKind::LocalDir(dir) => Self::build_local_dir_index(Path::new(dir)),
+            OriginKind::Github { .. } | OriginKind::Apt(_) | OriginKind::CloudflareR2 { .. } => {
                 // These repos don't have a single metadata file
                 // For now, return empty index (will fall back to per-package fetches)
                 Ok(Self {
@@ -200,6 +209,10 @@ impl RepoIndex {
                 runtime_dependencies: pkg_info.dependencies.into_iter()
                     .map(|dep| DependKind::Latest(dep))
                     .collect(),
+                optional_dependencies: Vec::new(),
+                installed_size: None,
+                keywords: Vec::new(),
+                categories: Vec::new(),
                 install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                     critical: Vec::new(), // File lists not available in primary.xml
                     configs: Vec::new(),
@@ -441,6 +454,63 @@ impl RepoIndex {
     }
     
     /// Build index from Debian repository
+    /// Index every package file in a directory of `.pax`, `.deb` and `.rpm`
+    /// files, such as one written by `install --download-only`. Files that
+    /// can't be read are skipped.
+    fn build_local_dir_index(dir: &Path) -> Result<Self, String> {
+        let entries = fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read local repository {}: {}", dir.display(), e))?;
+        let mut packages: HashMap<String, Vec<ProcessedMetaData>> = HashMap::new();
+        let mut provides_lib: HashMap<String, Vec<String>> = HashMap::new();
+        let mut provides_file: HashMap<String, Vec<String>> = HashMap::new();
+        let mut dependencies = HashMap::new();
+        let mut files: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        files.sort();
+        for path in files {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let packaged = [".pax", ".deb", ".rpm"].iter().any(|ext| file_name.ends_with(ext));
+            if !packaged || file_name.contains(".src.") {
+                continue;
+            }
+            let metadata = match ProcessedMetaData::load_local_package(&path) {
+                Ok(metadata) => metadata,
+                Err(fault) => {
+                    eprintln!("Warning: Skipping {}: {}", path.display(), fault);
+                    continue;
+                }
+            };
+            let normalized_name = metadata.name.to_lowercase();
+            if let crate::processed::ProcessedInstallKind::PreBuilt(ref prebuilt) = metadata.install_kind {
+                for file in &prebuilt.critical {
+                    provides_file.entry(file.clone()).or_default().push(normalized_name.clone());
+                    if file.contains(".so")
+                        && let Some(lib_name) = file.split('/').next_back()
+                    {
+                        provides_lib.entry(lib_name.to_string()).or_default().push(normalized_name.clone());
+                    }
+                }
+            }
+            dependencies.insert(normalized_name.clone(), metadata.runtime_dependencies.clone());
+            packages.entry(normalized_name).or_default().push(metadata);
+        }
+        for versions in packages.values_mut() {
+            versions.sort_by(|a, b| utils::Version::parse(&b.version).cmp(&utils::Version::parse(&a.version)));
+        }
+
+        let origin = OriginKind::LocalDir(dir.display().to_string());
+        Ok(Self {
+            packages,
+            provides_lib,
+            provides_file,
+            provides_pkg: HashMap::new(),
+            dependencies,
+            cache_key: Self::cache_key_for_origin(&origin),
+            origin,
+        })
+    }
+
     async fn build_deb_index(base_url: &str) -> Result<Self, String> {
         use crate::deb_repository::DebRepositoryClient;
         
@@ -469,6 +539,10 @@ impl RepoIndex {
                 runtime_dependencies: pkg_info.dependencies.into_iter()
                     .map(|dep| DependKind::Latest(dep))
                     .collect(),
+                optional_dependencies: Vec::new(),
+                installed_size: None,
+                keywords: Vec::new(),
+                categories: Vec::new(),
                 install_kind: ProcessedInstallKind::PreBuilt(PreBuilt {
                     critical: Vec::new(), // File lists not available in Packages file
                     configs: Vec::new(),
@@ -663,7 +737,9 @@ impl MultiRepoIndex {
         }
         
         let build_end = SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
-        eprintln!("Index building complete: {} successful, {} failed, {}ms total", successful, failed, build_end.saturating_sub(build_start));
+        if utils::logging::enabled(utils::logging::LogLevel::Info) {
+            eprintln!("Index building complete: {} successful, {} failed, {}ms total", successful, failed, build_end.saturating_sub(build_start));
+        }
         
         if indexes.is_empty() {
             return Err("No repositories could be indexed".to_string());
@@ -674,8 +750,9 @@ impl MultiRepoIndex {
     
     /// Get only PAX indexes (for PAX package dependency resolution)
     fn pax_indexes(&self) -> Vec<&RepoIndex> {
+        // A local bundle holds .pax files too
         self.indexes.iter()
-            .filter(|idx| matches!(idx.origin, OriginKind::Pax(_)))
+            .filter(|idx| matches!(idx.origin, OriginKind::Pax(_) | OriginKind::LocalDir(_)))
             .collect()
     }
     
@@ -689,6 +766,23 @@ impl MultiRepoIndex {
         None
     }
     
+    /// The latest version of every package, first repo winning, for
+    /// browsing and search
+    pub fn latest_packages(&self) -> Vec<&ProcessedMetaData> {
+        let mut seen = HashSet::new();
+        let mut packages = Vec::new();
+        for index in &self.indexes {
+            for (name, versions) in &index.packages {
+                if let Some(latest) = versions.first()
+                    && seen.insert(name.as_str())
+                {
+                    packages.push(latest);
+                }
+            }
+        }
+        packages
+    }
+
     /// Lookup package in PAX repos only (for PAX package dependency resolution)
     pub fn lookup_package_pax_only(&self, name: &str) -> Option<&ProcessedMetaData> {
         for index in self.pax_indexes() {
@@ -712,6 +806,18 @@ impl MultiRepoIndex {
         matches
     }
     
+    /// Lookup all versions of a package in the highest-priority repo that has it
+    ///
+    /// Indexes keep the order of the sources they were built from, which settings sort by priority.
+    pub fn lookup_preferred(&self, name: &str) -> Vec<ProcessedMetaData> {
+        let normalized_name = name.to_lowercase();
+        self.indexes
+            .iter()
+            .find_map(|index| index.packages.get(&normalized_name))
+            .map(|versions| versions.to_vec())
+            .unwrap_or_default()
+    }
+    
     /// Lookup all versions of a package in PAX repos only (for PAX package dependency resolution)
     pub fn lookup_all_versions_pax_only(&self, name: &str) -> Vec<ProcessedMetaData> {
         // Normalize to lowercase for case-insensitive lookup
@@ -1,13 +1,14 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
-    fs::File,
+    fs::{self, File},
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use utils::{err, get_metadata_dir};
+use utils::{err, get_dir, get_metadata_dir};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepositoryCredentials {
@@ -395,3 +396,113 @@ impl Default for RepositoryAuthManager {
         Self::new()
     }
 }
+
+/// A public key trusted to sign one repository's packages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub repository: String,
+    pub key: String,
+}
+
+impl TrustedKey {
+    /// SHA-256 of the key material, ignoring armor lines and whitespace so
+    /// re-wrapped copies of one key agree.
+    pub fn fingerprint(&self) -> String {
+        let material: String = self
+            .key
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with("-----"))
+            .flat_map(|line| line.chars().filter(|c| !c.is_whitespace()))
+            .collect();
+        format!("{:x}", Sha256::digest(material.as_bytes()))
+    }
+}
+
+/// Trusted repository keys, one file per repository under
+/// `/etc/pax/trusted.d/`.
+pub struct TrustStore {
+    dir: PathBuf,
+}
+
+impl TrustStore {
+    pub fn system() -> Result<Self, String> {
+        Ok(Self::at(get_dir()?.join("trusted.d")))
+    }
+
+    pub fn at(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn key_path(&self, repository: &str) -> PathBuf {
+        let name: String = repository
+            .trim_end_matches('/')
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.yaml", name))
+    }
+
+    /// Trust the key in `key_file` for `repository`, replacing any key it had.
+    pub fn add(&self, repository: &str, key_file: &Path) -> Result<TrustedKey, String> {
+        let key = fs::read_to_string(key_file)
+            .map_err(|e| format!("Failed to read key file {}: {}", key_file.display(), e))?;
+        if key.trim().is_empty() {
+            return err!("Key file {} is empty", key_file.display());
+        }
+        let trusted = TrustedKey { repository: repository.trim_end_matches('/').to_string(), key };
+        fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        let path = self.key_path(repository);
+        let data = serde_norway::to_string(&trusted).map_err(|_| "Failed to serialize trusted key")?;
+        fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(trusted)
+    }
+
+    pub fn remove(&self, repository: &str) -> Result<TrustedKey, String> {
+        let Some(trusted) = self.key_for(repository)? else {
+            return err!("No trusted key for repository: {}", repository);
+        };
+        let path = self.key_path(repository);
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        Ok(trusted)
+    }
+
+    pub fn key_for(&self, repository: &str) -> Result<Option<TrustedKey>, String> {
+        let path = self.key_path(repository);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_norway::from_str(&data)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// Every trusted key, by repository.
+    pub fn keys(&self) -> Result<Vec<TrustedKey>, String> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(|e| format!("Failed to read {}: {}", self.dir.display(), e))? {
+            let path = entry.map_err(|e| format!("Failed to read entry: {}", e))?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                continue;
+            }
+            let data = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            match serde_norway::from_str::<TrustedKey>(&data) {
+                Ok(key) => keys.push(key),
+                Err(e) => println!("\x1B[93m[WARN] Skipping unreadable trusted key {}: {}\x1B[0m", path.display(), e),
+            }
+        }
+        keys.sort_by(|a, b| a.repository.cmp(&b.repository));
+        Ok(keys)
+    }
+}
+
+impl RepositoryAuthManager {
+    /// The key trusted for `repository_url`, if one was added with `pax key add`.
+    pub fn trusted_key(&self, repository_url: &str) -> Result<Option<TrustedKey>, String> {
+        TrustStore::system()?.key_for(repository_url)
+    }
+}
@@ -0,0 +1,24 @@
+use std::future::Future;
+use std::sync::OnceLock;
+
+use tokio::runtime::{Handle, Runtime};
+
+/// The one tokio runtime pax drives its async work on, created the first
+/// time something needs it.
+pub fn runtime() -> Result<&'static Runtime, String> {
+    static RUNTIME: OnceLock<Option<Runtime>> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| Runtime::new().ok())
+        .as_ref()
+        .ok_or_else(|| String::from("Error creating runtime!"))
+}
+
+/// Run `future` to completion from synchronous code. Safe to call from code
+/// that is itself running on a runtime, where a plain `block_on` would
+/// panic with "Cannot start a runtime from within a runtime".
+pub fn block_on<F: Future>(future: F) -> Result<F::Output, String> {
+    match Handle::try_current() {
+        Ok(handle) => Ok(tokio::task::block_in_place(|| handle.block_on(future))),
+        Err(_) => Ok(runtime()?.block_on(future)),
+    }
+}
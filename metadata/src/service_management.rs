@@ -93,6 +93,12 @@ impl ServiceManager {
     }
 
     pub fn start_service(&mut self, service_name: &str) -> Result<(), String> {
+        // Services in an alternate root belong to a system that isn't running
+        if utils::is_alternate_root() {
+            println!("Skipping start of {} in alternate root.", service_name);
+            return Ok(());
+        }
+
         let service = self.services.get(service_name)
             .ok_or_else(|| format!("Service {} not found", service_name))?;
 
@@ -121,6 +127,12 @@ impl ServiceManager {
     }
 
     pub fn stop_service(&mut self, service_name: &str) -> Result<(), String> {
+        // Services in an alternate root belong to a system that isn't running
+        if utils::is_alternate_root() {
+            println!("Skipping stop of {} in alternate root.", service_name);
+            return Ok(());
+        }
+
         let service = self.services.get(service_name)
             .ok_or_else(|| format!("Service {} not found", service_name))?;
 
@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, HashSet};
+
+use utils::{DepVer, Range, VerReq, Version};
+
+use crate::depend_kind::DependKind;
+use crate::repo_index::MultiRepoIndex;
+
+/// One installable version of a package and what it requires.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub version: Version,
+    pub requires: Vec<DepVer>,
+    /// The version already on the system; tried before any other
+    pub installed: bool,
+}
+
+/// Where the solver finds the versions a package is available in.
+pub trait CandidateSource {
+    /// Every version of `name`, or `None` for a name the solver should
+    /// leave to the rest of resolution (provided by another package, or
+    /// versioned in a way it can't compare).
+    fn candidates(&self, name: &str) -> Option<Vec<Candidate>>;
+}
+
+/// Give up rather than search forever on a pathological graph.
+const STEP_LIMIT: usize = 10_000;
+
+/// A dependency as a solver requirement; volatile (system binary)
+/// dependencies aren't versioned packages and are skipped.
+pub fn requirement(dep: &DependKind) -> Option<DepVer> {
+    match dep {
+        DependKind::Latest(name) => Some(DepVer {
+            name: name.clone(),
+            range: Range { lower: VerReq::NoBound, upper: VerReq::NoBound },
+        }),
+        DependKind::Specific(dep_ver) => Some(dep_ver.clone()),
+        DependKind::Volatile(_) => None,
+    }
+}
+
+/// Pick one version of every package reachable from `root` so that all
+/// requirements on it hold. Each package keeps its installed version when
+/// that fits, and otherwise gets the highest version in the intersection of
+/// its requirements, stable releases first; when that choice leaves
+/// something else unsatisfiable the solver backs up and tries the next one.
+pub fn solve<S: CandidateSource + ?Sized>(root: &[DepVer], source: &S) -> Result<BTreeMap<String, Version>, String> {
+    let mut solver = Solver {
+        source,
+        constraints: BTreeMap::new(),
+        picks: BTreeMap::new(),
+        external: HashSet::new(),
+        steps: 0,
+        conflict: None,
+    };
+    for dep in root {
+        solver.constraints.entry(dep.name.clone()).or_default().push((dep.range.clone(), String::from("the request")));
+    }
+    if solver.search() {
+        Ok(solver.picks)
+    } else {
+        Err(solver.conflict.unwrap_or_else(|| String::from("Dependencies could not be resolved")))
+    }
+}
+
+struct Solver<'a, S: ?Sized> {
+    source: &'a S,
+    /// Every requirement on each package, with who made it
+    constraints: BTreeMap<String, Vec<(Range, String)>>,
+    picks: BTreeMap<String, Version>,
+    external: HashSet<String>,
+    steps: usize,
+    conflict: Option<String>,
+}
+
+impl<S: CandidateSource + ?Sized> Solver<'_, S> {
+    fn search(&mut self) -> bool {
+        let Some(name) = self
+            .constraints
+            .keys()
+            .find(|name| !self.picks.contains_key(*name) && !self.external.contains(*name))
+            .cloned()
+        else {
+            return true;
+        };
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            self.conflict = Some(format!("Gave up resolving dependencies after {} steps", STEP_LIMIT));
+            return false;
+        }
+
+        let Some(mut candidates) = self.source.candidates(&name) else {
+            self.external.insert(name.clone());
+            let solved = self.search();
+            if !solved {
+                self.external.remove(&name);
+            }
+            return solved;
+        };
+        let Some(range) = self.combined_range(&name) else {
+            self.conflict = Some(self.describe_conflict(&name));
+            return false;
+        };
+        candidates.retain(|candidate| range.contains(&candidate.version));
+        candidates.sort_by(|a, b| {
+            (a.installed, a.version.pre.is_empty(), &a.version)
+                .cmp(&(b.installed, b.version.pre.is_empty(), &b.version))
+                .reverse()
+        });
+
+        for candidate in candidates {
+            // A choice that breaks a package already picked is no choice at all
+            let breaks_pick = candidate.requires.iter().any(|dep| {
+                self.picks.get(&dep.name).is_some_and(|picked| !dep.range.contains(picked))
+            });
+            if breaks_pick {
+                if self.conflict.is_none() {
+                    self.conflict = Some(format!(
+                        "{} {} conflicts with the versions already chosen",
+                        name, candidate.version
+                    ));
+                }
+                continue;
+            }
+
+            let requirer = format!("{} {}", name, candidate.version);
+            self.picks.insert(name.clone(), candidate.version.clone());
+            for dep in &candidate.requires {
+                self.constraints
+                    .entry(dep.name.clone())
+                    .or_default()
+                    .push((dep.range.clone(), requirer.clone()));
+            }
+            if self.search() {
+                return true;
+            }
+            // Back out this choice's requirements before trying the next
+            for dep in &candidate.requires {
+                if let Some(list) = self.constraints.get_mut(&dep.name) {
+                    if let Some(index) = list.iter().rposition(|(_, who)| *who == requirer) {
+                        list.remove(index);
+                    }
+                    if list.is_empty() {
+                        self.constraints.remove(&dep.name);
+                    }
+                }
+            }
+            self.picks.remove(&name);
+            if self.steps > STEP_LIMIT {
+                return false;
+            }
+        }
+        if self.conflict.is_none() {
+            self.conflict = Some(self.describe_conflict(&name));
+        }
+        false
+    }
+
+    fn combined_range(&self, name: &str) -> Option<Range> {
+        let any = Range { lower: VerReq::NoBound, upper: VerReq::NoBound };
+        self.constraints
+            .get(name)
+            .into_iter()
+            .flatten()
+            .try_fold(any, |range, (next, _)| range.intersect(next))
+    }
+
+    fn describe_conflict(&self, name: &str) -> String {
+        let wanted: Vec<String> = self
+            .constraints
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|(range, who)| format!("{} (required by {})", range, who))
+            .collect();
+        format!("No version of {} satisfies {}", name, wanted.join(" and "))
+    }
+}
+
+/// Candidates straight from the repository index.
+pub struct IndexSource<'a> {
+    pub index: &'a MultiRepoIndex,
+    pub pax_only: bool,
+}
+
+impl CandidateSource for IndexSource<'_> {
+    fn candidates(&self, name: &str) -> Option<Vec<Candidate>> {
+        if crate::processed::is_assumed_installed(name) {
+            return None;
+        }
+        let versions = if self.pax_only {
+            self.index.lookup_all_versions_pax_only(name)
+        } else {
+            self.index.lookup_all_versions(name)
+        };
+        if versions.is_empty() {
+            return None;
+        }
+        // Distro versions like `1:2.3-4ubuntu1` don't parse; leave those to first-fit
+        versions
+            .iter()
+            .map(|metadata| {
+                Some(Candidate {
+                    version: Version::parse(&metadata.version).ok()?,
+                    requires: metadata.runtime_dependencies.iter().filter_map(requirement).collect(),
+                    installed: false,
+                })
+            })
+            .collect()
+    }
+}
+
+/// `inner`'s candidates, seeded with what is already installed. An
+/// installed version is offered first, its dependencies taken as met; a
+/// name only provided by an installed package is left to the rest of
+/// resolution, as the first-fit resolver does.
+pub struct InstalledSource<'a, S: ?Sized> {
+    pub inner: &'a S,
+    /// Installed packages whose versions parse
+    pub versions: &'a BTreeMap<String, Version>,
+    /// Whether something installed satisfies a name
+    pub provided: &'a dyn Fn(&str) -> bool,
+}
+
+impl<S: CandidateSource + ?Sized> CandidateSource for InstalledSource<'_, S> {
+    fn candidates(&self, name: &str) -> Option<Vec<Candidate>> {
+        let Some(version) = self.versions.get(name) else {
+            return if (self.provided)(name) { None } else { self.inner.candidates(name) };
+        };
+        let mut candidates = self.inner.candidates(name).unwrap_or_default();
+        candidates.retain(|candidate| candidate.version != *version);
+        candidates.push(Candidate { version: version.clone(), requires: Vec::new(), installed: true });
+        Some(candidates)
+    }
+}
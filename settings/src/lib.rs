@@ -1,7 +1,13 @@
 use std::{
+    collections::HashMap,
     fs::{self, File},
     io::{Read, Write},
     path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
     thread::sleep,
     time::{Duration, Instant},
 };
@@ -28,50 +34,194 @@ pub struct SettingsYaml {
     pub sources: Vec<OriginKind>,
     #[serde(default)]
     pub disabled_sources: Vec<String>, // URLs of sources that failed health checks
+    #[serde(default)]
+    pub pins: Vec<SourcePin>,
+    #[serde(default)]
+    pub apt_layouts: Vec<SourceLayout>,
+    #[serde(default)]
+    pub asset_patterns: Vec<SourceAssetPattern>,
+}
+
+/// One schema step: rewrites a settings file written for `from` into the
+/// shape `to` expects.
+type Migration = fn(&mut serde_json::Map<String, serde_json::Value>);
+
+/// Every schema step as `(from, to, migration)`. `migrate_settings` chains
+/// them from a file's version up to `SETTINGS_YAML_VERSION`.
+const MIGRATIONS: &[(&str, &str, Migration)] = &[("0.0.1", "0.1.0", migrate_0_0_1)];
+
+/// 0.0.1 predates mirror lists, disabled sources, pins and apt layouts.
+fn migrate_0_0_1(fields: &mut serde_json::Map<String, serde_json::Value>) {
+    use serde_json::Value;
+
+    fields.entry("mirror_list").or_insert(Value::Null);
+    for list in ["disabled_sources", "pins", "apt_layouts"] {
+        fields.entry(list).or_insert_with(|| Value::Array(Vec::new()));
+    }
+}
+
+/// Bring settings written under schema `from` up to `SETTINGS_YAML_VERSION`
+/// by applying each step in `MIGRATIONS` in turn.
+pub fn migrate_settings(mut value: serde_json::Value, from: &str) -> Result<serde_json::Value, String> {
+    let mut version = from.to_string();
+    while version != SETTINGS_YAML_VERSION {
+        let Some((_, to, migration)) = MIGRATIONS.iter().find(|(step_from, _, _)| *step_from == version) else {
+            return err!("No migration from settings schema {} to {}!", version, SETTINGS_YAML_VERSION);
+        };
+        let serde_json::Value::Object(fields) = &mut value else {
+            return err!("Settings file is not a mapping!");
+        };
+        migration(fields);
+        fields.insert(String::from("version"), serde_json::Value::from(*to));
+        version = to.to_string();
+    }
+    Ok(value)
+}
+
+/// YAML to JSON for migration. Tagged values such as `!Pax url` become the
+/// single-key objects serde_json reads externally tagged enums from.
+fn yaml_to_json(value: serde_norway::Value) -> serde_json::Value {
+    use serde_json::Value as Json;
+    use serde_norway::Value as Yaml;
+
+    match value {
+        Yaml::Null => Json::Null,
+        Yaml::Bool(b) => Json::Bool(b),
+        Yaml::Number(n) => serde_json::to_value(&n).unwrap_or(Json::Null),
+        Yaml::String(s) => Json::String(s),
+        Yaml::Sequence(entries) => Json::Array(entries.into_iter().map(yaml_to_json).collect()),
+        Yaml::Mapping(fields) => Json::Object(
+            fields
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let key = match key {
+                        Yaml::String(key) => key,
+                        other => serde_norway::to_string(&other).ok()?.trim().to_string(),
+                    };
+                    Some((key, yaml_to_json(value)))
+                })
+                .collect(),
+        ),
+        Yaml::Tagged(tagged) => {
+            let tag = tagged.tag.to_string();
+            let mut object = serde_json::Map::new();
+            object.insert(tag.trim_start_matches('!').to_string(), yaml_to_json(tagged.value));
+            Json::Object(object)
+        }
+    }
+}
+
+/// Priority given to sources that don't set `priority=` in sources.conf.
+pub const DEFAULT_SOURCE_PRIORITY: i32 = 99;
+
+pub const SETTINGS_YAML_VERSION: &str = env!("SETTINGS_YAML_VERSION");
+
+/// Interpreter for package commands when `exec` is not set.
+pub const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// The interpreter package commands run under: `exec` if set, otherwise
+/// `/bin/sh`. The builder resolves its shell the same way.
+pub fn resolve_shell(exec: Option<&str>) -> &str {
+    exec.map(str::trim).filter(|exec| !exec.is_empty()).unwrap_or(DEFAULT_SHELL)
+}
+
+/// Restricts every package whose name matches `pattern` to a single origin.
+/// `pattern` is a glob where `*` matches any run of characters and `?` a single one.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct SourcePin {
+    pub pattern: String,
+    pub origin: OriginKind,
+}
+
+impl SourcePin {
+    pub fn matches(&self, package: &str) -> bool {
+        glob_match(&self.pattern, package)
+    }
+}
+
+/// Where an APT source keeps its `.deb` files, used when its `Packages` index
+/// doesn't say. `Flat` is the `packages/<name>/<version>.deb` layout existing
+/// pax APT servers use; `Pool` is the Debian `pool/main/<prefix>/<name>/` one
+/// and has to be asked for with `layout=pool`.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum AptLayout {
+    Pool,
+    #[default]
+    Flat,
+}
+
+/// A `layout=` hint given for one source in sources.conf.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct SourceLayout {
+    pub origin: OriginKind,
+    pub layout: AptLayout,
+}
+
+/// An `asset=` glob given for a GitHub source in sources.conf, naming the
+/// release asset to download when its names don't follow the usual pattern.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct SourceAssetPattern {
+    pub origin: OriginKind,
+    pub pattern: String,
 }
 
 impl SettingsYaml {
     pub fn new() -> Self {
-        let mut command = std::process::Command::new("/usr/bin/uname");
-        let arch = if let Ok(output) = command.arg("-m").output() {
-            match String::from_utf8_lossy(&output.stdout)
-                .to_string()
-                .as_str()
-                .trim()
-            {
-                "x86_64" => {
-                    let mut command = std::process::Command::new("/usr/bin/bash");
-                    command.arg("-c").arg("(lscpu|grep -q avx512f&&echo 4&&exit||lscpu|grep -q avx2&&echo 3&&exit||lscpu|grep -q sse4_2&&echo 2&&exit||echo 1)");
-                    if let Ok(output) = command.output() {
-                        match String::from_utf8_lossy(&output.stdout)
-                            .to_string()
-                            .as_str()
-                            .trim()
-                        {
-                            "4" | "3" => Arch::X86_64v3,
-                            "2" | "1" => Arch::X86_64v1,
-                            _ => Arch::NoArch,
-                        }
-                    } else {
-                        Arch::NoArch
-                    }
-                }
-                "aarch64" => Arch::Aarch64,
-                "armv7l" => Arch::Armv7l,
-                "armv8l" => Arch::Armv8l,
-                _ => Arch::NoArch,
-            }
-        } else {
-            Arch::NoArch
-        };
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let arch = arch_from_cpuinfo(std::env::consts::ARCH, &cpuinfo);
         Self {
             locked: false,
-            version: env!("SETTINGS_YAML_VERSION").to_string(),
+            version: SETTINGS_YAML_VERSION.to_string(),
             arch,
             exec: None,
             mirror_list: None,
             sources: Vec::new(),
             disabled_sources: Vec::new(),
+            pins: Vec::new(),
+            apt_layouts: Vec::new(),
+            asset_patterns: Vec::new(),
+        }
+    }
+    /// The shell configured for install commands.
+    pub fn shell(&self) -> &str {
+        resolve_shell(self.exec.as_deref())
+    }
+    /// The `.deb` layout configured for `origin`, defaulting to the flat layout.
+    pub fn apt_layout(&self, origin: &OriginKind) -> AptLayout {
+        self.apt_layouts
+            .iter()
+            .find(|hint| hint.origin == *origin)
+            .map(|hint| hint.layout)
+            .unwrap_or_default()
+    }
+    /// The release asset glob configured for `origin`, if any.
+    pub fn asset_pattern(&self, origin: &OriginKind) -> Option<&str> {
+        self.asset_patterns
+            .iter()
+            .find(|hint| hint.origin == *origin)
+            .map(|hint| hint.pattern.as_str())
+    }
+
+    /// Returns the sources that should be consulted for `package`.
+    ///
+    /// Precedence rules:
+    /// 1. If a pin matches the package name, only the pinned origin is consulted.
+    ///    The first matching pin in sources.conf wins.
+    /// 2. Otherwise every source is consulted in `self.sources` order, which
+    ///    `get_settings` sorts by ascending `priority=` (lower wins, default 99).
+    ///    Sources with equal priority keep their sources.conf order.
+    ///
+    /// Like `target_sources`, the result follows any `--arch` override.
+    pub fn sources_for(&self, package: &str) -> Vec<OriginKind> {
+        match self.pins.iter().find(|pin| pin.matches(package)) {
+            Some(pin) => {
+                let mut origin = pin.origin.clone();
+                if let Some(arch) = arch_override() {
+                    retarget_arch(&mut origin, arch);
+                }
+                vec![origin]
+            }
+            None => self.target_sources(),
         }
     }
     pub fn set_settings(mut self) -> Result<(), String> {
@@ -93,20 +243,111 @@ impl SettingsYaml {
         }
         self.sources = unique_sources;
 
-        let mut file = match File::create(affirm_path()?) {
-            Ok(file) => file,
-            Err(_) => return err!("Failed to open SettingsYaml as WO!"),
-        };
         let settings = match serde_norway::to_string(&self) {
             Ok(settings) => settings,
             Err(_) => return err!("Failed to parse SettingsYaml to string!"),
         };
-        match file.write_all(settings.as_bytes()) {
-            Ok(_) => Ok(()),
-            Err(_) => err!("Failed to write to file!"),
-        }
+        utils::write_atomic(&affirm_path()?, settings.as_bytes())
     }
     pub fn get_settings() -> Result<Self, String> {
+        Self::load_settings()
+    }
+
+    /// The architecture to install for: the `--arch` override if one was
+    /// given, otherwise the configured one.
+    pub fn target_arch(&self) -> Arch {
+        arch_override().unwrap_or(self.arch)
+    }
+
+    /// The configured sources, pointed at the `--arch` override if one was
+    /// given. The override only applies to what is read here; the stored
+    /// sources are left alone so it never reaches settings.yaml.
+    pub fn target_sources(&self) -> Vec<OriginKind> {
+        let mut sources = self.sources.clone();
+        if let Some(arch) = arch_override() {
+            sources.iter_mut().for_each(|origin| retarget_arch(origin, arch));
+        }
+        sources
+    }
+
+    /// Parse settings.yaml, migrating it first if it was written under an
+    /// older schema. Returns the version it was migrated from, if any. A file
+    /// from a version with no migration path is read as-is.
+    fn parse_versioned(data: &str) -> Result<(Self, Option<String>), String> {
+        let strict = || serde_norway::from_str::<Self>(data).map_err(|e| e.to_string());
+        let value: serde_norway::Value = serde_norway::from_str(data).map_err(|e| e.to_string())?;
+        let value = yaml_to_json(value);
+        let Some(from) = value.get("version").and_then(serde_json::Value::as_str).map(str::to_string) else {
+            return strict().map(|settings| (settings, None));
+        };
+        if from == SETTINGS_YAML_VERSION {
+            return strict().map(|settings| (settings, None));
+        }
+        match migrate_settings(value, &from) {
+            Ok(migrated) => serde_json::from_value::<Self>(migrated)
+                .map(|settings| (settings, Some(from)))
+                .map_err(|e| e.to_string()),
+            Err(_) => strict().map(|settings| (settings, None)),
+        }
+    }
+
+    /// Rebuild settings from a file that no longer deserializes as a whole:
+    /// each top-level field is taken over if it still fits the schema, list
+    /// fields keep whichever entries still fit, and anything else keeps its
+    /// default. `None` if the file isn't a YAML mapping at all.
+    fn recover(data: &str) -> Option<Self> {
+        use serde_norway::Value;
+
+        let Value::Mapping(old) = serde_norway::from_str::<Value>(data).ok()? else {
+            return None;
+        };
+        let fits = |candidate: &Value| serde_norway::from_value::<Self>(candidate.clone()).is_ok();
+        let mut merged = serde_norway::to_value(Self::new()).ok()?;
+        for (key, value) in old {
+            let Value::Mapping(fields) = &mut merged else {
+                return None;
+            };
+            if !fields.contains_key(&key) {
+                continue;
+            }
+            let previous = fields.insert(key.clone(), value.clone());
+            if fits(&merged) {
+                continue;
+            }
+            let Value::Mapping(fields) = &mut merged else {
+                return None;
+            };
+            match value {
+                Value::Sequence(entries) => {
+                    fields.insert(key.clone(), Value::Sequence(Vec::new()));
+                    for entry in entries {
+                        let Value::Mapping(fields) = &mut merged else {
+                            return None;
+                        };
+                        if let Some(Value::Sequence(kept)) = fields.get_mut(&key) {
+                            kept.push(entry);
+                        }
+                        if !fits(&merged)
+                            && let Value::Mapping(fields) = &mut merged
+                            && let Some(Value::Sequence(kept)) = fields.get_mut(&key)
+                        {
+                            kept.pop();
+                        }
+                    }
+                }
+                _ => {
+                    if let Some(previous) = previous {
+                        fields.insert(key, previous);
+                    }
+                }
+            }
+        }
+        let mut settings: Self = serde_norway::from_value(merged).ok()?;
+        settings.version = SETTINGS_YAML_VERSION.to_string();
+        Some(settings)
+    }
+
+    fn load_settings() -> Result<Self, String> {
         let path = {
             let mut p = get_dir()?;
             p.push("settings.yaml");
@@ -124,8 +365,29 @@ impl SettingsYaml {
         if file.read_to_string(&mut data).is_err() {
             return err!("Failed to read file!");
         };
-        let mut settings: SettingsYaml = match serde_norway::from_str::<SettingsYaml>(&data) {
-            Ok(mut settings_yaml) => {
+        let mut settings: SettingsYaml = match Self::parse_versioned(&data) {
+            Ok((mut settings_yaml, migrated_from)) => {
+                if let Some(from) = migrated_from {
+                    // Only persist the migration once the original is kept
+                    let backup = path.with_extension("yaml.bak");
+                    match fs::copy(&path, &backup) {
+                        Ok(_) => match settings_yaml.clone().set_settings() {
+                            Ok(()) => println!(
+                                "Migrated settings from schema {} to {}; the original is saved at {}.",
+                                from,
+                                SETTINGS_YAML_VERSION,
+                                backup.display()
+                            ),
+                            Err(fault) => println!("\x1B[93m[WARN] Failed to save migrated settings: {}\x1B[0m", fault),
+                        },
+                        Err(fault) => println!(
+                            "\x1B[93m[WARN] Migrated settings from schema {} in memory only; could not back up {}: {}\x1B[0m",
+                            from,
+                            path.display(),
+                            fault
+                        ),
+                    }
+                }
                 // Clean URL prefixes from stored repository URLs
                 for source in &mut settings_yaml.sources {
                     match source {
@@ -146,9 +408,36 @@ impl SettingsYaml {
                 settings_yaml
             }
             Err(e) => {
-                // If parsing fails, log the error and create fresh settings
-                println!("\x1B[93m[WARN] Settings file corrupted ({}). Creating fresh settings...\x1B[0m", e);
-                let new_settings = Self::new();
+                // Keep the original before anything is rewritten, then carry
+                // over whatever still parses and default only the rest
+                let backup = path.with_extension("yaml.bak");
+                if let Err(fault) = fs::copy(&path, &backup) {
+                    return err!(
+                        "Settings file {} could not be parsed ({}) and could not be backed up to {}: {}",
+                        path.display(),
+                        e,
+                        backup.display(),
+                        fault
+                    );
+                }
+                let new_settings = match Self::recover(&data) {
+                    Some(recovered) => {
+                        println!(
+                            "\x1B[93m[WARN] Settings file could not be parsed ({}). Kept the fields that still read and reset the rest; the original is saved at {}.\x1B[0m",
+                            e,
+                            backup.display()
+                        );
+                        recovered
+                    }
+                    None => {
+                        println!(
+                            "\x1B[93m[WARN] Settings file could not be parsed ({}). Reset to defaults; the original is saved at {}.\x1B[0m",
+                            e,
+                            backup.display()
+                        );
+                        Self::new()
+                    }
+                };
                 if let Err(e) = new_settings.clone().set_settings() {
                     return err!("Failed to create new settings file: {}", e);
                 }
@@ -157,7 +446,7 @@ impl SettingsYaml {
         };
         let dir = get_dir()?;
         match load_sources_conf(&dir) {
-            Ok((mirror, file_sources)) => {
+            Ok(SourcesConf { mirror, sources: file_sources, priorities, pins, layouts, asset_patterns }) => {
                 if mirror.is_some() {
                     settings.mirror_list = mirror;
                 }
@@ -213,8 +502,9 @@ impl SettingsYaml {
                 } else {
                     // No sources configured - use correct Oreon mirror
                     let arch = match settings.arch {
-                        Arch::X86_64v1 => "x86_64v1",
-                        Arch::X86_64v3 => "x86_64v3",
+                        // Fall back to the nearest level the default repo is laid out for
+                        Arch::X86_64v1 | Arch::X86_64v2 => "x86_64v1",
+                        Arch::X86_64v3 | Arch::X86_64v4 => "x86_64v3",
                         Arch::Aarch64 => "aarch64",
                         _ => "x86_64v3", // default fallback
                     };
@@ -328,8 +618,9 @@ impl SettingsYaml {
                 // Ensure only one Oreon repository (prefer the official one)
                 let oreon_url_pattern = {
                     let arch = match settings.arch {
-                        Arch::X86_64v1 => "x86_64v1",
-                        Arch::X86_64v3 => "x86_64v3",
+                        // Fall back to the nearest level the default repo is laid out for
+                        Arch::X86_64v1 | Arch::X86_64v2 => "x86_64v1",
+                        Arch::X86_64v3 | Arch::X86_64v4 => "x86_64v3",
                         Arch::Aarch64 => "aarch64",
                         _ => "x86_64v3", // default fallback
                     };
@@ -348,6 +639,11 @@ impl SettingsYaml {
                     !matches!(source, OriginKind::Pax(url) if url.contains("oreon") && url != &oreon_url_pattern)
                 });
 
+                sort_by_priority(&mut settings.sources, &priorities);
+                settings.pins = pins;
+                settings.apt_layouts = layouts;
+                settings.asset_patterns = asset_patterns;
+
             }
             Err(fault) => {
                 println!(
@@ -357,8 +653,9 @@ impl SettingsYaml {
                 // Start with default repositories
                 settings.sources.push(OriginKind::Pax("http://pax.local:8080".to_string()));
                 let arch = match settings.arch {
-                    Arch::X86_64v1 => "x86_64v1",
-                    Arch::X86_64v3 => "x86_64v3",
+                    // Fall back to the nearest level the default repo is laid out for
+                    Arch::X86_64v1 | Arch::X86_64v2 => "x86_64v1",
+                    Arch::X86_64v3 | Arch::X86_64v4 => "x86_64v3",
                     Arch::Aarch64 => "aarch64",
                     _ => "x86_64v3", // default fallback
                 };
@@ -388,6 +685,56 @@ pub enum OriginKind {
     LocalDir(String), // Local directory repository
 }
 
+/// The absolute path behind a `file://` URL. Both `file:///srv/packages`
+/// and `file://localhost/srv/packages` give `/srv/packages`.
+pub fn file_url_path(url: &str) -> Option<&str> {
+    let rest = url.strip_prefix("file://")?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    rest.starts_with('/').then_some(rest)
+}
+
+impl OriginKind {
+    /// Whether reaching this origin needs the network. Local directories and
+    /// plain file paths do not.
+    pub fn requires_network(&self) -> bool {
+        match self {
+            OriginKind::Github { .. } | OriginKind::CloudflareR2 { .. } => true,
+            OriginKind::LocalDir(_) => false,
+            OriginKind::Apt(url) | OriginKind::Pax(url) | OriginKind::Rpm(url)
+            | OriginKind::Deb(url) | OriginKind::Yum(url) => {
+                url.starts_with("http://") || url.starts_with("https://")
+            }
+        }
+    }
+
+    /// Resolve a `file://` Pax or Apt source to the local origin it names: a
+    /// directory becomes a LocalDir, a single package a bare path.
+    pub fn normalize_file_url(&self) -> OriginKind {
+        let (OriginKind::Pax(url) | OriginKind::Apt(url)) = self else {
+            return self.clone();
+        };
+        let Some(path) = file_url_path(url) else {
+            return self.clone();
+        };
+        if Path::new(path).is_dir() {
+            OriginKind::LocalDir(path.to_string())
+        } else if let OriginKind::Pax(_) = self {
+            OriginKind::Pax(path.to_string())
+        } else {
+            OriginKind::Apt(path.to_string())
+        }
+    }
+
+    /// Fail fast instead of reaching a network origin in offline mode.
+    pub fn ensure_reachable(&self) -> Result<(), String> {
+        if utils::is_offline() && self.requires_network() {
+            err!("offline mode: cannot reach {self}")
+        } else {
+            Ok(())
+        }
+    }
+}
+
 impl std::fmt::Display for OriginKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -405,22 +752,217 @@ impl std::fmt::Display for OriginKind {
     }
 }
 
-#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum Arch {
     NoArch,
     X86_64v1,
+    X86_64v2,
     X86_64v3,
+    X86_64v4,
     Aarch64,
     Armv7l,
     Armv8l,
 }
 
+impl std::fmt::Display for Arch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Arch::NoArch => "noarch",
+            Arch::X86_64v1 => "x86_64v1",
+            Arch::X86_64v2 => "x86_64v2",
+            Arch::X86_64v3 => "x86_64v3",
+            Arch::X86_64v4 => "x86_64v4",
+            Arch::Aarch64 => "aarch64",
+            Arch::Armv7l => "armv7l",
+            Arch::Armv8l => "armv8l",
+        })
+    }
+}
+
+impl FromStr for Arch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "noarch" => Ok(Arch::NoArch),
+            "x86_64v1" => Ok(Arch::X86_64v1),
+            "x86_64v2" => Ok(Arch::X86_64v2),
+            "x86_64v3" => Ok(Arch::X86_64v3),
+            "x86_64v4" => Ok(Arch::X86_64v4),
+            "aarch64" => Ok(Arch::Aarch64),
+            "armv7l" => Ok(Arch::Armv7l),
+            "armv8l" => Ok(Arch::Armv8l),
+            _ => err!("Unknown architecture `{s}`! Expected one of noarch, x86_64v1, x86_64v2, x86_64v3, x86_64v4, aarch64, armv7l, armv8l."),
+        }
+    }
+}
+
+/// The `Arch` for a machine of type `machine` (as in `std::env::consts::ARCH`)
+/// given its `/proc/cpuinfo`. x86_64 is ranked by the feature each
+/// microarchitecture level hinges on (SSE4.2 for v2, AVX2 for v3, AVX-512
+/// for v4); 32-bit ARM by the `CPU architecture` it reports.
+fn arch_from_cpuinfo(machine: &str, cpuinfo: &str) -> Arch {
+    let field = |name: &str| {
+        cpuinfo.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim())
+        })
+    };
+    match machine {
+        "x86_64" => {
+            let flags: Vec<&str> = field("flags").unwrap_or_default().split_whitespace().collect();
+            if flags.contains(&"avx512f") {
+                Arch::X86_64v4
+            } else if flags.contains(&"avx2") {
+                Arch::X86_64v3
+            } else if flags.contains(&"sse4_2") {
+                Arch::X86_64v2
+            } else {
+                Arch::X86_64v1
+            }
+        }
+        "aarch64" => Arch::Aarch64,
+        "arm" => match field("CPU architecture") {
+            Some("7") => Arch::Armv7l,
+            Some(version) if version.parse::<u32>().is_ok_and(|version| version >= 8) => Arch::Armv8l,
+            _ => Arch::NoArch,
+        },
+        _ => Arch::NoArch,
+    }
+}
+
+static ARCH_OVERRIDE: OnceLock<Arch> = OnceLock::new();
+
+/// Make `target_arch`, `target_sources` and `sources_for` target `arch`
+/// instead of the configured architecture for the rest of the run. Only the
+/// first call takes effect.
+pub fn set_arch_override(arch: Arch) {
+    let _ = ARCH_OVERRIDE.set(arch);
+}
+
+/// The architecture requested with `--arch`, if any.
+pub fn arch_override() -> Option<Arch> {
+    ARCH_OVERRIDE.get().copied()
+}
+
+/// Point a repository whose URL ends in an architecture segment (e.g.
+/// `.../oreon-11/unstable/x86_64v3`) at `arch` instead.
+fn retarget_arch(origin: &mut OriginKind, arch: Arch) {
+    if let OriginKind::Pax(url) = origin
+        && let Some((base, last)) = url.trim_end_matches('/').rsplit_once('/')
+        && Arch::from_str(last).is_ok()
+    {
+        *url = format!("{}/{}", base, arch);
+    }
+}
+
+impl Arch {
+    /// The Debian architecture name used in `.deb` file names.
+    pub fn deb_arch(&self) -> &'static str {
+        match self {
+            Arch::NoArch => "all",
+            Arch::X86_64v1 | Arch::X86_64v2 | Arch::X86_64v3 | Arch::X86_64v4 => "amd64",
+            Arch::Aarch64 => "arm64",
+            Arch::Armv7l | Arch::Armv8l => "armhf",
+        }
+    }
+}
+
 impl Default for SettingsYaml {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// One entry of a mirror list: a base URL serving the same tree as the
+/// other entries, so a package under one can be fetched from any of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    pub base: String,
+}
+
+impl Mirror {
+    pub fn new(base: &str) -> Self {
+        Self { base: base.trim_end_matches('/').to_string() }
+    }
+
+    /// `url` moved from `from` onto this mirror, or `None` if it does not
+    /// live under `from`.
+    pub fn rewrite(&self, url: &str, from: &Mirror) -> Option<String> {
+        let rest = url.strip_prefix(&from.base)?;
+        (rest.is_empty() || rest.starts_with('/')).then(|| format!("{}{}", self.base, rest))
+    }
+}
+
+/// Parse a newline-delimited mirror list, skipping blanks and `#` comments.
+pub fn parse_mirror_list(text: &str) -> Vec<Mirror> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| Mirror::new(&line.replace("$arch", "x86_64v3"))) // Replace $arch with detected arch
+        .collect()
+}
+
+/// The mirrors listed by `mirror_list`, read once per run. The list may be
+/// a URL, a `file://` URL or a path; without one there is no failover.
+pub fn configured_mirrors() -> &'static [Mirror] {
+    static MIRRORS: OnceLock<Vec<Mirror>> = OnceLock::new();
+    MIRRORS.get_or_init(|| {
+        let Some(location) = SettingsYaml::get_settings().ok().and_then(|settings| settings.mirror_list) else {
+            return Vec::new();
+        };
+        let text = if let Some(path) = file_url_path(&location).or_else(|| location.starts_with('/').then_some(location.as_str())) {
+            fs::read_to_string(path).ok()
+        } else if utils::is_offline() {
+            None
+        } else {
+            reqwest::blocking::get(&location)
+                .ok()
+                .filter(|response| response.status().is_success())
+                .and_then(|response| response.text().ok())
+        };
+        text.map(|text| parse_mirror_list(&text)).unwrap_or_default()
+    })
+}
+
+/// Every mirror's copy of `url`, the mirror that last worked first and the
+/// rest in list order. Empty when `url` is not under any listed mirror.
+pub fn mirror_candidates(url: &str, mirrors: &[Mirror], last_good: Option<&str>) -> Vec<(Mirror, String)> {
+    let Some(from) = mirrors.iter().find(|mirror| mirror.rewrite(url, mirror).is_some()) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<(Mirror, String)> = mirrors
+        .iter()
+        .filter_map(|mirror| mirror.rewrite(url, from).map(|moved| (mirror.clone(), moved)))
+        .collect();
+    if let Some(last_good) = last_good
+        && let Some(index) = candidates.iter().position(|(mirror, _)| mirror.base == last_good)
+    {
+        let preferred = candidates.remove(index);
+        candidates.insert(0, preferred);
+    }
+    candidates
+}
+
+fn last_mirror_path() -> Result<PathBuf, String> {
+    Ok(get_dir()?.join("last-mirror"))
+}
+
+/// The mirror that served the last successful download, if recorded.
+pub fn last_good_mirror() -> Option<String> {
+    let text = fs::read_to_string(last_mirror_path().ok()?).ok()?;
+    Some(text.trim().to_string()).filter(|base| !base.is_empty())
+}
+
+/// Prefer `mirror` for later downloads. Failing to record it is harmless.
+pub fn remember_mirror(mirror: &Mirror) {
+    if last_good_mirror().as_deref() != Some(mirror.base.as_str())
+        && let Ok(path) = last_mirror_path()
+    {
+        let _ = fs::write(path, format!("{}\n", mirror.base));
+    }
+}
+
 /// Fetch mirrors from the Oreon mirror list URL
 fn fetch_oreon_mirrors() -> Result<Vec<String>, String> {
     let mirror_list_url = "https://mirrors.oreonhq.com/oreon-11/sources";
@@ -441,11 +983,7 @@ fn fetch_oreon_mirrors() -> Result<Vec<String>, String> {
             match response.text() {
                 Ok(text) => {
                     // The mirror list is a plain text file with one URL per line
-                    let mirrors: Vec<String> = text.lines()
-                        .map(|line| line.trim())
-                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
-                        .map(|line| line.replace("$arch", "x86_64v3")) // Replace $arch with detected arch
-                        .collect();
+                    let mirrors: Vec<String> = parse_mirror_list(&text).into_iter().map(|mirror| mirror.base).collect();
 
                     if mirrors.is_empty() {
                         return err!("No mirrors found in mirror list");
@@ -588,6 +1126,9 @@ fn select_best_mirror(mirrors: &[String]) -> Result<String, String> {
 /// Get the best mirror URL, either from configured mirror list or fetch from Oreon
 /// Computes fresh each time to handle changing network conditions
 pub fn get_best_mirror_url() -> Result<String, String> {
+    if utils::is_offline() {
+        return err!("offline mode: cannot reach the mirror list");
+    }
     // First try to get from settings
     if let Ok(settings) = SettingsYaml::get_settings() {
         if let Some(mirror_list_url) = &settings.mirror_list {
@@ -597,11 +1138,7 @@ pub fn get_best_mirror_url() -> Result<String, String> {
                     if response.status().is_success() {
                         if let Ok(text) = response.text() {
                             // The mirror list is a plain text file with one URL per line
-                            let mirrors: Vec<String> = text.lines()
-                                .map(|line| line.trim())
-                                .filter(|line| !line.is_empty() && !line.starts_with('#'))
-                                .map(|line| line.replace("$arch", "x86_64v3")) // Replace $arch with detected arch
-                                .collect();
+                            let mirrors: Vec<String> = parse_mirror_list(&text).into_iter().map(|mirror| mirror.base).collect();
 
                             if mirrors.is_empty() {
                                 return err!("No mirrors found in configured mirror list");
@@ -621,10 +1158,67 @@ pub fn get_best_mirror_url() -> Result<String, String> {
     select_best_mirror(&mirrors)
 }
 
-fn load_sources_conf(dir: &Path) -> Result<(Option<String>, Vec<OriginKind>), String> {
+/// Matches `name` against a glob supporting `*` and `?`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Orders sources by ascending priority. The sort is stable, so sources
+/// sharing a priority keep their sources.conf order.
+fn sort_by_priority(sources: &mut [OriginKind], priorities: &HashMap<OriginKind, i32>) {
+    sources.sort_by_key(|source| {
+        priorities
+            .get(source)
+            .copied()
+            .unwrap_or(DEFAULT_SOURCE_PRIORITY)
+    });
+}
+
+/// Everything read out of sources.conf.
+struct SourcesConf {
+    mirror: Option<String>,
+    sources: Vec<OriginKind>,
+    priorities: HashMap<OriginKind, i32>,
+    pins: Vec<SourcePin>,
+    layouts: Vec<SourceLayout>,
+    asset_patterns: Vec<SourceAssetPattern>,
+}
+
+/// Parses sources.conf. Besides the source itself, a repo line may carry
+/// `priority=N` (lower is preferred), `pin=glob[,glob...]`, which pins
+/// matching package names to that repo, `layout=pool|flat` for APT repos and
+/// `asset=glob` for GitHub repos whose release assets need picking by name.
+fn load_sources_conf(dir: &Path) -> Result<SourcesConf, String> {
     let path = dir.join("sources.conf");
+    let mut conf = SourcesConf {
+        mirror: None,
+        sources: Vec::new(),
+        priorities: HashMap::new(),
+        pins: Vec::new(),
+        layouts: Vec::new(),
+        asset_patterns: Vec::new(),
+    };
     if !path.exists() {
-        return Ok((None, Vec::new()));
+        return Ok(conf);
     }
     let contents =
         fs::read_to_string(&path).map_err(|_| format!("Failed to read {}.", path.display()))?;
@@ -658,6 +1252,43 @@ fn load_sources_conf(dir: &Path) -> Result<(Option<String>, Vec<OriginKind>), St
             .map(|s| s.to_lowercase());
         let source_url = find("url").map(|s| s.to_string());
         let provider = find("provider").map(|s| s.to_lowercase());
+        let priority = match find("priority").map(|s| s.parse::<i32>()) {
+            Some(Ok(priority)) => Some(priority),
+            Some(Err(_)) => {
+                println!(
+                    "\x1B[93m[WARN] Invalid priority on line {} of {}. Using {}.\x1B[0m",
+                    idx + 1,
+                    path.display(),
+                    DEFAULT_SOURCE_PRIORITY
+                );
+                None
+            }
+            None => None,
+        };
+        let pin_patterns: Vec<String> = find("pin")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|pattern| pattern.trim().to_string())
+                    .filter(|pattern| !pattern.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let layout = match find("layout").map(|s| s.to_lowercase()).as_deref() {
+            Some("pool") => Some(AptLayout::Pool),
+            Some("flat") => Some(AptLayout::Flat),
+            Some(other) => {
+                println!(
+                    "\x1B[93m[WARN] Unknown layout `{}` on line {} of {}. Using flat.\x1B[0m",
+                    other,
+                    idx + 1,
+                    path.display()
+                );
+                None
+            }
+            None => None,
+        };
+        let sources_before = sources.len();
 
         match source_type.as_deref() {
             Some("mirror") => {
@@ -793,11 +1424,10 @@ fn load_sources_conf(dir: &Path) -> Result<(Option<String>, Vec<OriginKind>), St
                         }
                     } else if url.starts_with("file://") || url.starts_with("/") || url.starts_with("./") || url.starts_with("../") {
                         // Local directory repository
-                        let dir_path = if url.starts_with("file://") {
-                            url.strip_prefix("file://").unwrap().to_string()
-                        } else {
-                            url.to_string()
-                        };
+                        let dir_path = file_url_path(&url)
+                            .or_else(|| url.strip_prefix("file://"))
+                            .unwrap_or(&url)
+                            .to_string();
                         let dir_path = Path::new(&dir_path);
                         if dir_path.exists() && dir_path.is_dir() {
                             sources.push(OriginKind::LocalDir(dir_path.to_string_lossy().to_string()));
@@ -857,27 +1487,38 @@ fn load_sources_conf(dir: &Path) -> Result<(Option<String>, Vec<OriginKind>), St
                 );
             }
         };
+
+        if sources.len() > sources_before {
+            let origin = sources[sources.len() - 1].clone();
+            if let Some(priority) = priority {
+                conf.priorities.insert(origin.clone(), priority);
+            }
+            for pattern in pin_patterns {
+                conf.pins.push(SourcePin {
+                    pattern,
+                    origin: origin.clone(),
+                });
+            }
+            if let Some(layout) = layout {
+                conf.layouts.push(SourceLayout { origin: origin.clone(), layout });
+            }
+            if let Some(pattern) = find("asset") {
+                conf.asset_patterns.push(SourceAssetPattern { origin: origin.clone(), pattern: pattern.to_string() });
+            }
+        }
     }
-    Ok((mirror, sources))
+    conf.mirror = mirror;
+    conf.sources = sources;
+    Ok(conf)
 }
 
 fn affirm_path() -> Result<PathBuf, String> {
     let mut path = get_dir()?;
     path.push("settings.yaml");
     if !path.exists() {
-        match File::create(&path) {
-            Ok(mut file) => {
-                if let Ok(new_settings) = serde_norway::to_string(&SettingsYaml::new()) {
-                    if file.write_all(new_settings.as_bytes()).is_ok() {
-                        Ok(path)
-                    } else {
-                        err!("Failed to write to file!")
-                    }
-                } else {
-                    err!("Failed to serialize settings!")
-                }
-            }
-            Err(_) => err!("Failed to create settings file!"),
+        match serde_norway::to_string(&SettingsYaml::new()) {
+            Ok(new_settings) => utils::write_atomic(&path, new_settings.as_bytes()).map(|_| path),
+            Err(_) => err!("Failed to serialize settings!"),
         }
     } else if path.is_file() {
         if File::open(&path).is_ok() {
@@ -890,6 +1531,10 @@ fn affirm_path() -> Result<PathBuf, String> {
     }
 }
 
+// Whether this process took the program lock. Read-only commands never take
+// it, so they run alongside an install without waiting on or releasing it.
+static LOCK_HELD: AtomicBool = AtomicBool::new(false);
+
 pub fn acquire_lock() -> Result<Option<PostAction>, String> {
     acquire_lock_with_auto_force(false)
 }
@@ -1072,12 +1717,245 @@ pub fn acquire_lock_with_auto_force(auto_force_unlock: bool) -> Result<Option<Po
     }
     settings.locked = true;
     settings.set_settings()?;
+    LOCK_HELD.store(true, Ordering::SeqCst);
     Ok(None)
 }
 
+/// Release the program lock, if this process is the one holding it.
 pub fn remove_lock() -> Result<(), String> {
+    if !LOCK_HELD.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
     let mut settings = SettingsYaml::get_settings()?;
     settings.locked = false;
     settings.set_settings()
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_sources_conf(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pax-settings-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("sources.conf"), contents).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("nodejs*", "nodejs22-libs"));
+        assert!(glob_match("python3-?ip", "python3-pip"));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("nodejs*", "node"));
+        assert!(!glob_match("gcc", "gcc-c++"));
+    }
+
+    #[test]
+    fn test_priority_ordering() {
+        let dir = write_sources_conf(
+            "priority",
+            "sourcetype=repo url=https://github.example/pax\n\
+             sourcetype=repo url=https://internal.example/pax priority=10\n\
+             sourcetype=repo url=https://fallback.example/pax priority=200\n\
+             sourcetype=repo url=https://second.example/pax\n",
+        );
+        let mut conf = load_sources_conf(&dir).unwrap();
+        sort_by_priority(&mut conf.sources, &conf.priorities);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            conf.sources,
+            vec![
+                OriginKind::Pax("https://internal.example/pax".to_string()),
+                OriginKind::Pax("https://github.example/pax".to_string()),
+                OriginKind::Pax("https://second.example/pax".to_string()),
+                OriginKind::Pax("https://fallback.example/pax".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pin_override() {
+        let dir = write_sources_conf(
+            "pin",
+            "sourcetype=repo url=https://main.example/pax priority=1\n\
+             sourcetype=repo url=https://node.example/pax pin=nodejs*,npm\n",
+        );
+        let conf = load_sources_conf(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut settings = SettingsYaml::new();
+        settings.sources = conf.sources;
+        settings.pins = conf.pins;
+
+        let pinned = vec![OriginKind::Pax("https://node.example/pax".to_string())];
+        assert_eq!(settings.sources_for("nodejs22"), pinned);
+        assert_eq!(settings.sources_for("npm"), pinned);
+        assert_eq!(settings.sources_for("bash"), settings.sources);
+    }
+
+    #[test]
+    fn test_apt_layout_hint() {
+        let dir = write_sources_conf(
+            "layout",
+            "sourcetype=repo provider=apt url=https://deb.example/debian layout=pool\n\
+             sourcetype=repo provider=apt url=https://custom.example/apt\n",
+        );
+        let conf = load_sources_conf(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut settings = SettingsYaml::new();
+        settings.apt_layouts = conf.layouts;
+
+        let debian = OriginKind::Apt("https://deb.example/debian".to_string());
+        let custom = OriginKind::Apt("https://custom.example/apt".to_string());
+        assert_eq!(settings.apt_layout(&debian), AptLayout::Pool);
+        assert_eq!(settings.apt_layout(&custom), AptLayout::Flat);
+    }
+
+    #[test]
+    fn test_github_asset_hint() {
+        let dir = write_sources_conf(
+            "asset",
+            "sourcetype=repo github=acme/tool asset=tool-*-linux.pax\n\
+             sourcetype=repo github=acme/other\n",
+        );
+        let conf = load_sources_conf(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut settings = SettingsYaml::new();
+        settings.asset_patterns = conf.asset_patterns;
+
+        let tool = OriginKind::Github { user: String::from("acme"), repo: String::from("tool") };
+        let other = OriginKind::Github { user: String::from("acme"), repo: String::from("other") };
+        assert_eq!(settings.asset_pattern(&tool), Some("tool-*-linux.pax"));
+        assert_eq!(settings.asset_pattern(&other), None);
+    }
+
+    #[test]
+    fn test_retarget_arch() {
+        assert_eq!("x86_64v1".parse::<Arch>(), Ok(Arch::X86_64v1));
+        assert!("x86_64v9".parse::<Arch>().is_err());
+
+        let retargeted = |source: &str| {
+            let mut origin = OriginKind::Pax(source.to_string());
+            retarget_arch(&mut origin, Arch::X86_64v1);
+            origin
+        };
+        assert_eq!(
+            retargeted("https://mirrors.example/oreon-11/unstable/x86_64v3"),
+            OriginKind::Pax("https://mirrors.example/oreon-11/unstable/x86_64v1".to_string())
+        );
+        assert_eq!(
+            retargeted("https://mirrors.example/oreon-11/unstable/x86_64v3/"),
+            OriginKind::Pax("https://mirrors.example/oreon-11/unstable/x86_64v1".to_string())
+        );
+        // Sources without an architecture segment are left alone
+        assert_eq!(retargeted("https://other.example/pax"), OriginKind::Pax("https://other.example/pax".to_string()));
+        let mut local = OriginKind::LocalDir("/srv/x86_64v3".to_string());
+        retarget_arch(&mut local, Arch::X86_64v1);
+        assert_eq!(local, OriginKind::LocalDir("/srv/x86_64v3".to_string()));
+    }
+
+    #[test]
+    fn test_offline_origins() {
+        assert!(OriginKind::Pax("https://repo.example/pax".to_string()).requires_network());
+        assert!(OriginKind::Github { user: "u".to_string(), repo: "r".to_string() }.requires_network());
+        assert!(!OriginKind::LocalDir("/srv/pax".to_string()).requires_network());
+        assert!(!OriginKind::Pax("/srv/pkgs/foo.pax".to_string()).requires_network());
+
+        // SAFETY: no other test reads PAX_OFFLINE
+        unsafe { std::env::set_var("PAX_OFFLINE", "1") };
+        let remote = OriginKind::Pax("https://repo.example/pax".to_string()).ensure_reachable();
+        let local = OriginKind::LocalDir("/srv/pax".to_string()).ensure_reachable();
+        unsafe { std::env::remove_var("PAX_OFFLINE") };
+        assert!(remote.is_err_and(|fault| fault.contains("offline mode: cannot reach")));
+        assert!(local.is_ok());
+    }
+
+    #[test]
+    fn test_exec_shell() {
+        use std::process::Command;
+
+        let run = |exec: Option<&str>| {
+            Command::new(resolve_shell(exec))
+                .arg("-c")
+                .arg("[[ pax == p* ]]")
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        };
+        assert_eq!(resolve_shell(None), "/bin/sh");
+        assert_eq!(resolve_shell(Some("")), "/bin/sh");
+        // Only meaningful where /bin/sh is a POSIX shell such as dash
+        let sh_is_bash = std::fs::canonicalize("/bin/sh").is_ok_and(|path| path.ends_with("bash"));
+        if !sh_is_bash {
+            assert!(!run(None));
+        }
+        assert!(run(Some("/bin/bash")));
+    }
+
+    #[test]
+    fn test_recover_unparseable_settings() {
+        let data = "locked: false\nversion: 0.0.1\narch: not-an-arch\nexec: /bin/bash\nmirror_list: https://mirrors.example.org/list\nsources:\n  - !Pax https://repo.example.org/oreon\n  - !Bogus nope\n  - !Apt https://deb.example.org\nunknown_field: 1\n";
+        assert!(serde_norway::from_str::<SettingsYaml>(data).is_err());
+
+        let recovered = SettingsYaml::recover(data).unwrap();
+        assert_eq!(recovered.exec.as_deref(), Some("/bin/bash"));
+        assert_eq!(recovered.mirror_list.as_deref(), Some("https://mirrors.example.org/list"));
+        assert_eq!(
+            recovered.sources,
+            vec![
+                OriginKind::Pax(String::from("https://repo.example.org/oreon")),
+                OriginKind::Apt(String::from("https://deb.example.org")),
+            ]
+        );
+        assert_eq!(recovered.version, SETTINGS_YAML_VERSION);
+        assert!(SettingsYaml::recover("- not\n- a mapping\n").is_none());
+    }
+
+    #[test]
+    fn test_migrate_old_settings() {
+        let old = "locked: false\nversion: 0.0.1\narch: X86_64v3\nexec: /bin/bash\nsources:\n  - !Pax https://repo.example.org/oreon-11/unstable/x86_64v3\n  - !Github\n    user: oreonproject\n    repo: pax-rs\n";
+        let (settings, migrated_from) = SettingsYaml::parse_versioned(old).unwrap();
+        assert_eq!(migrated_from.as_deref(), Some("0.0.1"));
+        assert_eq!(settings.version, SETTINGS_YAML_VERSION);
+        assert_eq!(settings.arch, Arch::X86_64v3);
+        assert_eq!(settings.exec.as_deref(), Some("/bin/bash"));
+        assert_eq!(settings.mirror_list, None);
+        assert_eq!(
+            settings.sources,
+            vec![
+                OriginKind::Pax(String::from("https://repo.example.org/oreon-11/unstable/x86_64v3")),
+                OriginKind::Github { user: String::from("oreonproject"), repo: String::from("pax-rs") },
+            ]
+        );
+
+        // Current and unknown versions are read as they are
+        let current = serde_norway::to_string(&settings).unwrap();
+        assert_eq!(SettingsYaml::parse_versioned(&current).unwrap().1, None);
+        let unknown = old.replace("0.0.1", "0.0.9");
+        assert_eq!(SettingsYaml::parse_versioned(&unknown).unwrap().1, None);
+        assert!(migrate_settings(serde_json::json!({}), "0.0.9").is_err());
+    }
+
+    #[test]
+    fn test_arch_from_cpuinfo() {
+        let x86 = |flags: &str| format!("processor\t: 0\nvendor_id\t: GenuineIntel\nflags\t\t: {}\nbugs\t\t: spectre_v1\n", flags);
+        assert_eq!(arch_from_cpuinfo("x86_64", &x86("fpu sse sse2 avx2 avx512f")), Arch::X86_64v4);
+        assert_eq!(arch_from_cpuinfo("x86_64", &x86("fpu sse sse2 sse4_2 avx avx2")), Arch::X86_64v3);
+        assert_eq!(arch_from_cpuinfo("x86_64", &x86("fpu sse sse2 ssse3 sse4_1 sse4_2")), Arch::X86_64v2);
+        assert_eq!(arch_from_cpuinfo("x86_64", &x86("fpu sse sse2")), Arch::X86_64v1);
+        // A flag only counts as a whole word
+        assert_eq!(arch_from_cpuinfo("x86_64", &x86("fpu sse2 avx2_fake")), Arch::X86_64v1);
+        assert_eq!(arch_from_cpuinfo("x86_64", ""), Arch::X86_64v1);
+
+        let arm = |version: &str| format!("processor\t: 0\nFeatures\t: half thumb vfp neon\nCPU architecture: {}\n", version);
+        assert_eq!(arch_from_cpuinfo("arm", &arm("7")), Arch::Armv7l);
+        assert_eq!(arch_from_cpuinfo("arm", &arm("8")), Arch::Armv8l);
+        assert_eq!(arch_from_cpuinfo("arm", ""), Arch::NoArch);
+        assert_eq!(arch_from_cpuinfo("aarch64", "Features\t: fp asimd sve\n"), Arch::Aarch64);
+        assert_eq!(arch_from_cpuinfo("riscv64", ""), Arch::NoArch);
+    }
+}
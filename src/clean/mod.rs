@@ -0,0 +1,95 @@
+use commands::Command;
+use flags::Flag;
+use metadata::clean::{CleanTarget, remove_paths};
+use settings::acquire_lock;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    let cache = Flag::new(
+        None,
+        "cache",
+        "Clear cached repository indexes and the installed-file index",
+        false,
+        false,
+        |states, _| {
+            states.shove("cache", true);
+        },
+    );
+
+    let packages = Flag::new(
+        None,
+        "packages",
+        "Remove downloaded package files",
+        false,
+        false,
+        |states, _| {
+            states.shove("packages", true);
+        },
+    );
+
+    let all = Flag::new(
+        Some('a'),
+        "all",
+        "Clear caches, downloaded packages and leftover extraction directories",
+        false,
+        false,
+        |states, _| {
+            states.shove("all", true);
+        },
+    );
+
+    Command::new(
+        "clean",
+        Vec::new(),
+        "Remove cached metadata and downloaded packages",
+        vec![cache, packages, all],
+        None,
+        run,
+        hierarchy,
+    )
+}
+
+fn run(states: &StateBox, _: Option<&[String]>) -> PostAction {
+    let all = states.flag_set("all");
+    let mut targets = Vec::new();
+    if all || states.flag_set("cache") {
+        targets.push(CleanTarget::Cache);
+    }
+    if all || states.flag_set("packages") {
+        targets.push(CleanTarget::Packages);
+    }
+    if all {
+        targets.push(CleanTarget::Leftovers);
+    }
+    if targets.is_empty() {
+        return PostAction::GetHelp;
+    }
+    // Holding the lock means no install is using the files being removed
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+
+    let mut total = 0;
+    for target in targets {
+        let paths = match target.paths() {
+            Ok(paths) => paths,
+            Err(fault) => return PostAction::Fuck(fault),
+        };
+        if paths.is_empty() {
+            println!("No {} to clean.", target.describe());
+            continue;
+        }
+        match remove_paths(&paths) {
+            Ok(freed) => {
+                println!("Removed {}: {} freed", target.describe(), utils::format_size(freed));
+                total += freed;
+            }
+            Err(fault) => return PostAction::Fuck(fault),
+        }
+    }
+    println!("\x1B[92mFreed {} in total.\x1B[0m", utils::format_size(total));
+    PostAction::Return
+}
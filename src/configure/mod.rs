@@ -1,9 +1,14 @@
+use std::str::FromStr;
+
 use commands::Command;
 use flags::Flag;
-use settings::{SettingsYaml, acquire_lock, remove_lock};
+use settings::{Arch, SettingsYaml, acquire_lock, check_root_required, remove_lock};
 use statebox::StateBox;
 use utils::{PostAction, choice, err};
 
+/// Top-level settings.yaml fields `get`, `set` and `--set` understand.
+const KEYS: [&str; 3] = ["arch", "exec", "mirror_list"];
+
 pub fn build(hierarchy: &[String]) -> Command {
     let setting = Flag::new(
         Some('s'),
@@ -15,15 +20,84 @@ pub fn build(hierarchy: &[String]) -> Command {
     );
     Command::new(
         "configure",
-        vec![String::from("c")],
+        vec![String::from("c"), String::from("config")],
         "Configures internal pax settings.",
         vec![setting, utils::yes_flag()],
-        None,
+        Some(vec![build_get, build_set]),
         |_, _| PostAction::GetHelp,
         hierarchy,
     )
 }
 
+fn build_get(hierarchy: &[String]) -> Command {
+    Command::new(
+        "get",
+        Vec::new(),
+        "Print a setting (arch, exec or mirror_list).",
+        Vec::new(),
+        None,
+        get_run,
+        hierarchy,
+    )
+}
+
+fn build_set(hierarchy: &[String]) -> Command {
+    Command::new(
+        "set",
+        Vec::new(),
+        "Change a setting (arch, exec or mirror_list). An empty value clears exec and mirror_list.",
+        vec![utils::yes_flag()],
+        None,
+        set_run,
+        hierarchy,
+    )
+}
+
+fn get_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    // Reading a setting doesn't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+    let [key] = args.unwrap_or_default() else {
+        return PostAction::Fuck(String::from("Usage: pax config get <key>"));
+    };
+    let result = SettingsYaml::get_settings().and_then(|settings| get_value(&settings, key));
+    match result {
+        Ok(value) => {
+            println!("{value}");
+            PostAction::Return
+        }
+        Err(fault) => PostAction::Fuck(fault),
+    }
+}
+
+fn set_run(states: &StateBox, args: Option<&[String]>) -> PostAction {
+    let (key, value) = match args.unwrap_or_default() {
+        [key] => (key, ""),
+        [key, value] => (key, value.as_str()),
+        _ => return PostAction::Fuck(String::from("Usage: pax config set <key> <value>")),
+    };
+    // Validate before taking the lock so a typo doesn't prompt for elevation
+    if let Err(fault) = set_value(&mut SettingsYaml::default(), key, value) {
+        return PostAction::Fuck(fault);
+    }
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+    let result = SettingsYaml::get_settings().and_then(|settings| {
+        change_setting(settings, key, value, states.flag_set("yes"))
+    });
+    if let Err(fault) = remove_lock() {
+        return PostAction::Fuck(fault);
+    }
+    match result {
+        Ok(()) => PostAction::Return,
+        Err(fault) => PostAction::Fuck(fault),
+    }
+}
+
 fn set_handle(states: &mut StateBox, arg: Option<String>) {
     match acquire_lock() {
         Ok(Some(_)) => {
@@ -54,7 +128,7 @@ fn set_handle(states: &mut StateBox, arg: Option<String>) {
 fn set_func(
     states: &mut StateBox,
     arg: Option<String>,
-    mut settings: SettingsYaml,
+    settings: SettingsYaml,
 ) -> Result<(), String> {
     let Some(arg) = arg else {
         return err!("Missing an argument!");
@@ -62,28 +136,51 @@ fn set_func(
     let Some((key, value)) = arg.split_once('=') else {
         return err!("Invalid syntax. please use `--set \"key=value\"`.");
     };
+    change_setting(settings, key, value, states.flag_set("yes"))
+}
+
+/// Show the change, confirm it unless `yes`, then write it to settings.yaml.
+fn change_setting(mut settings: SettingsYaml, key: &str, value: &str, yes: bool) -> Result<(), String> {
+    let old = get_value(&settings, key)?;
+    set_value(&mut settings, key, value)?;
+    let new = get_value(&settings, key)?;
+    println!("Will change setting `{key}` from \x1B[95m{old}\x1B[0m to \x1B[95m{new}\x1B[0m.");
+    if !yes {
+        match choice("Proceed?", true) {
+            Err(message) => return err!("{message}"),
+            Ok(false) => return err!("Abort."),
+            Ok(true) => (),
+        }
+    }
+    settings.set_settings()
+}
+
+fn get_value(settings: &SettingsYaml, key: &str) -> Result<String, String> {
+    let unset = || String::from("(unset)");
+    match key {
+        "arch" => Ok(settings.arch.to_string()),
+        "exec" => Ok(settings.exec.clone().unwrap_or_else(unset)),
+        "mirror_list" => Ok(settings.mirror_list.clone().unwrap_or_else(unset)),
+        _ => err!("Unrecognized key {key}! Expected one of {}.", KEYS.join(", ")),
+    }
+}
+
+/// Validate `value` for `key` and store it. An empty `exec` or
+/// `mirror_list` clears the setting.
+fn set_value(settings: &mut SettingsYaml, key: &str, value: &str) -> Result<(), String> {
+    let optional = || (!value.is_empty()).then(|| value.to_string());
     match key {
-        "exec" => {
-            let val = if value.is_empty() {
-                None
-            } else {
-                Some(value.to_string())
-            };
-            println!(
-                "Will change setting `exec` from \x1B[95m{:?}\x1B[0m to \x1B[95m{val:?}\x1B[0m.",
-                settings.exec
-            );
-            if states.get("yes").is_none_or(|x: &bool| !*x) {
-                match choice("Proceed?", true) {
-                    Err(message) => return err!("{message}"),
-                    Ok(false) => return err!("Abort."),
-                    Ok(true) => (),
-                }
+        "arch" => settings.arch = Arch::from_str(value)?,
+        "exec" => settings.exec = optional(),
+        "mirror_list" => {
+            if !value.is_empty()
+                && !["http://", "https://", "file://", "/"].iter().any(|prefix| value.starts_with(prefix))
+            {
+                return err!("`mirror_list` must be an http(s):// or file:// URL or an absolute path, not `{value}`.");
             }
-            settings.exec = val;
+            settings.mirror_list = optional();
         }
-        _ => return err!("Unrecognized key {key}!"),
+        _ => return err!("Unrecognized key {key}! Expected one of {}.", KEYS.join(", ")),
     }
-    settings.set_settings()?;
     Ok(())
 }
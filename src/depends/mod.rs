@@ -0,0 +1,99 @@
+use commands::Command;
+use flags::Flag;
+use metadata::{InstalledMetaData, installed_depends, installed_rdepends, list_installed_packages};
+use settings::check_root_required;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build_depends(hierarchy: &[String]) -> Command {
+    Command::new(
+        "depends",
+        Vec::new(),
+        "List what an installed package depends on",
+        vec![recursive_flag()],
+        None,
+        depends,
+        hierarchy,
+    )
+}
+
+pub fn build_rdepends(hierarchy: &[String]) -> Command {
+    Command::new(
+        "rdepends",
+        Vec::new(),
+        "List installed packages that depend on a package",
+        vec![recursive_flag()],
+        None,
+        rdepends,
+        hierarchy,
+    )
+}
+
+fn recursive_flag() -> Flag {
+    Flag::new(
+        Some('r'),
+        "recursive",
+        "Follow dependencies transitively, not just one level",
+        false,
+        false,
+        |states, _| {
+            states.shove("recursive", true);
+        },
+    )
+}
+
+fn depends(states: &StateBox, args: Option<&[String]>) -> PostAction {
+    run(states, args, false)
+}
+
+fn rdepends(states: &StateBox, args: Option<&[String]>) -> PostAction {
+    run(states, args, true)
+}
+
+fn run(states: &StateBox, args: Option<&[String]>, reverse: bool) -> PostAction {
+    // Queries read the installed metadata only, don't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+    let args = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No package provided!")),
+        Some(args) => args,
+    };
+    let packages = match list_installed_packages(false, false, None) {
+        Ok(packages) => packages,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    let recursive = states.flag_set("recursive");
+
+    for (i, name) in args.iter().enumerate() {
+        // Reverse queries also make sense for a package that isn't installed
+        if !reverse && !packages.iter().any(|package| package.name == *name) {
+            return PostAction::Fuck(format!("Package `{}` is not installed.", name));
+        }
+        let (names, heading) = if reverse {
+            (installed_rdepends(&packages, name, recursive), "Packages depending on")
+        } else {
+            (installed_depends(&packages, name, recursive), "Dependencies of")
+        };
+        if i > 0 {
+            println!();
+        }
+        if names.is_empty() {
+            let none = if reverse { "Nothing installed depends on" } else { "No dependencies for" };
+            println!("\x1B[95m{} {}\x1B[0m", none, name);
+            continue;
+        }
+        println!("\x1B[92m{} {}:\x1B[0m", heading, name);
+        for dep in &names {
+            print_entry(&packages, dep);
+        }
+    }
+    PostAction::Return
+}
+
+fn print_entry(packages: &[InstalledMetaData], name: &str) {
+    match packages.iter().find(|package| package.name == name) {
+        Some(package) => println!("  \x1B[94m{}\x1B[0m {}", package.name, package.version),
+        None => println!("  \x1B[94m{}\x1B[0m \x1B[93m[NOT INSTALLED]\x1B[0m", name),
+    }
+}
@@ -0,0 +1,79 @@
+use commands::Command;
+use flags::Flag;
+use metadata::dep_graph::DependencyGraph;
+use metadata::get_packages;
+use settings::check_root_required;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    let format = Flag::new(
+        None,
+        "format",
+        "Output format: `tree` (default) or `dot` for Graphviz",
+        true,
+        false,
+        |states, arg| {
+            if let Some(format) = arg {
+                states.shove("format", format.clone());
+            }
+        },
+    );
+
+    let include_build = Flag::new(
+        None,
+        "include-build",
+        "Also show build dependencies",
+        false,
+        false,
+        |states, _| {
+            states.shove("include_build", true);
+        },
+    );
+
+    Command::new(
+        "deps",
+        Vec::new(),
+        "Print the resolved dependency graph of packages without installing them",
+        vec![format, include_build],
+        None,
+        run,
+        hierarchy,
+    )
+}
+
+fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
+    // Deps is read-only, doesn't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+    let args = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No package provided!")),
+        Some(args) => args,
+    };
+    let dot = match states.get::<String>("format").map(String::as_str) {
+        None | Some("tree") => false,
+        Some("dot") => true,
+        Some(other) => return PostAction::Fuck(format!("Unknown format `{}`; expected `tree` or `dot`.", other)),
+    };
+
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    let packages = match runtime.block_on(get_packages(args.to_vec(), None, false)) {
+        Ok(packages) => packages,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    if packages.is_empty() {
+        return PostAction::Fuck(String::from("None of the requested packages could be resolved."));
+    }
+
+    let graph = DependencyGraph::from_packages(&packages, states.flag_set("include_build"));
+    if dot {
+        print!("{}", graph.to_dot());
+    } else {
+        print!("{}", graph.to_tree());
+    }
+    PostAction::Return
+}
@@ -0,0 +1,80 @@
+use commands::Command;
+use flags::Flag;
+use metadata::doctor::{check_store, fix_store};
+use settings::{acquire_lock, check_root_required};
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    let fix = Flag::new(
+        None,
+        "fix",
+        "Move unparseable package files aside and prune dangling dependents",
+        false,
+        false,
+        |states, _| {
+            states.shove("fix", true);
+        },
+    );
+
+    Command::new(
+        "doctor",
+        Vec::new(),
+        "Check the installed package metadata for damage",
+        vec![fix],
+        None,
+        run,
+        hierarchy,
+    )
+}
+
+fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
+    let fix = states.flag_set("fix");
+    if fix {
+        match acquire_lock() {
+            Ok(Some(action)) => return action,
+            Err(fault) => return PostAction::Fuck(fault),
+            _ => (),
+        }
+    } else if let Some(action) = check_root_required(false) {
+        // Checking alone is read-only, doesn't require root
+        return action;
+    }
+
+    let dir = match utils::get_metadata_dir() {
+        Ok(dir) => dir,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    let problems = match check_store(&dir) {
+        Ok(problems) => problems,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    if problems.is_empty() {
+        println!("\x1B[92mNo problems found in {}.\x1B[0m", dir.display());
+        return PostAction::Return;
+    }
+    for problem in &problems {
+        println!("\x1B[91m{}\x1B[0m", problem);
+    }
+    if !fix {
+        println!("Run `pax doctor --fix` to repair what can be repaired.");
+        return PostAction::Err(1);
+    }
+
+    match fix_store(&dir, &problems) {
+        Ok(done) => {
+            for action in done {
+                println!("\x1B[92m{}\x1B[0m", action);
+            }
+        }
+        Err(fault) => return PostAction::Fuck(fault),
+    }
+    match check_store(&dir) {
+        Ok(remaining) if remaining.is_empty() => PostAction::Return,
+        Ok(remaining) => {
+            println!("\x1B[93m[WARN] {} problem(s) need attention by hand.\x1B[0m", remaining.len());
+            PostAction::Err(1)
+        }
+        Err(fault) => PostAction::Fuck(fault),
+    }
+}
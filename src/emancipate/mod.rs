@@ -3,7 +3,6 @@ use metadata::emancipate;
 use settings::acquire_lock;
 use statebox::StateBox;
 use utils::PostAction;
-use tokio::runtime::Runtime;
 
 pub fn build(hierarchy: &[String]) -> Command {
     Command::new(
@@ -30,7 +29,7 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         Some(args) => args.iter(),
     };
     let mut data = Vec::new();
-    if states.get("specific").is_some_and(|x| *x) {
+    if states.flag_set("specific") {
         while let Some(name) = args.next()
             && let Some(ver) = args.next()
         {
@@ -39,8 +38,9 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     } else {
         args.for_each(|x| data.push((x, None)));
     }
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
     };
     if let Err(fault) = runtime.block_on(emancipate(&data[0].0)) {
         PostAction::Fuck(fault)
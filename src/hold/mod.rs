@@ -0,0 +1,154 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use commands::Command;
+use flags::Flag;
+use metadata::{HoldType, InstalledMetaData, PackageHoldManager};
+use settings::{acquire_lock, check_root_required};
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build_hold(hierarchy: &[String]) -> Command {
+    let reason = Flag::new(
+        None,
+        "reason",
+        "Record why the packages are held, shown by `pax holds` and `pax upgrade`",
+        true,
+        false,
+        |states, arg| {
+            if let Some(reason) = arg {
+                states.shove("reason", reason.clone());
+            }
+        },
+    );
+
+    Command::new(
+        "hold",
+        Vec::new(),
+        "Keep installed packages at their current version during upgrades.",
+        vec![reason],
+        None,
+        hold_run,
+        hierarchy,
+    )
+}
+
+pub fn build_unhold(hierarchy: &[String]) -> Command {
+    Command::new(
+        "unhold",
+        Vec::new(),
+        "Let held packages be upgraded again.",
+        Vec::new(),
+        None,
+        unhold_run,
+        hierarchy,
+    )
+}
+
+pub fn build_holds(hierarchy: &[String]) -> Command {
+    Command::new(
+        "holds",
+        Vec::new(),
+        "List held packages and why they are held.",
+        Vec::new(),
+        None,
+        holds_run,
+        hierarchy,
+    )
+}
+
+fn load_holds() -> Result<PackageHoldManager, String> {
+    let mut holds = PackageHoldManager::new();
+    holds.load_all()?;
+    Ok(holds)
+}
+
+fn hold_run(states: &StateBox, args: Option<&[String]>) -> PostAction {
+    let packages = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No package provided!")),
+        Some(args) => args,
+    };
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+    for package in packages {
+        if InstalledMetaData::open(package).is_err() {
+            return PostAction::Fuck(format!("Package `{}` is not installed!", package));
+        }
+    }
+    let mut holds = match load_holds() {
+        Ok(holds) => holds,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    let reason = states.get::<String>("reason").cloned();
+    for package in packages {
+        if let Err(fault) = holds.hold_package(package.clone(), HoldType::NoUpgrade, reason.clone(), None) {
+            return PostAction::Fuck(fault);
+        }
+        println!("\x1B[92m{} is now held.\x1B[0m", package);
+    }
+    PostAction::Return
+}
+
+fn unhold_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    let packages = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No package provided!")),
+        Some(args) => args,
+    };
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+    let mut holds = match load_holds() {
+        Ok(holds) => holds,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    for package in packages {
+        if let Err(fault) = holds.unhold_package(package) {
+            return PostAction::Fuck(fault);
+        }
+    }
+    PostAction::Return
+}
+
+fn holds_run(_: &StateBox, _: Option<&[String]>) -> PostAction {
+    // Listing holds is read-only, doesn't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+    let holds = match load_holds() {
+        Ok(holds) => holds,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    let held = holds.list_held_packages();
+    if held.is_empty() {
+        println!("\x1B[95mNo held packages\x1B[0m");
+        return PostAction::Return;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    for hold in held {
+        let mut detail = vec![held_for(now.saturating_sub(hold.created_at))];
+        if let Some(user) = &hold.created_by {
+            detail.push(format!("by {}", user));
+        }
+        println!("\x1B[94m{}\x1B[0m \x1B[90m(held {})\x1B[0m", hold.package_name, detail.join(" "));
+        if let Some(reason) = &hold.reason {
+            println!("  {}", reason);
+        }
+    }
+    PostAction::Return
+}
+
+/// How long ago a hold was placed, to the nearest day.
+fn held_for(seconds: u64) -> String {
+    match seconds / 86400 {
+        0 => String::from("today"),
+        1 => String::from("yesterday"),
+        days => format!("{} days ago", days),
+    }
+}
@@ -3,7 +3,6 @@ use flags::Flag;
 use metadata::get_package_info;
 use settings::{check_root_required, SettingsYaml};
 use statebox::StateBox;
-use tokio::runtime::Runtime;
 use utils::{PostAction};
 
 pub fn build(hierarchy: &[String]) -> Command {
@@ -67,9 +66,9 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     }
 
     let package_name = &args[0];
-    let show_files = states.get::<bool>("show_files").is_some_and(|x| *x);
-    let show_deps = states.get::<bool>("show_deps").is_some_and(|x| *x);
-    let show_versions = states.get::<bool>("show_versions").is_some_and(|x| *x);
+    let show_files = states.flag_set("show_files");
+    let show_deps = states.flag_set("show_deps");
+    let show_versions = states.flag_set("show_versions");
 
     // Get settings for available package info
     let settings = match SettingsYaml::get_settings() {
@@ -77,8 +76,9 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         Err(_) => return PostAction::PullSources,
     };
 
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
     };
 
     match runtime.block_on(get_package_info(
@@ -97,6 +97,19 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
             println!("\x1B[90mVersion:\x1B[0m {}", info.version);
             println!("\x1B[90mOrigin:\x1B[0m {}", info.origin);
             println!("\x1B[90mPackage Type:\x1B[0m {}", info.package_type);
+            if info.installed {
+                if let Some(size) = info.installed_size {
+                    println!("\x1B[90mInstalled-Size:\x1B[0m {}", utils::format_size(size));
+                }
+            } else {
+                match runtime.block_on(info.download_size()) {
+                    Some(size) => println!("\x1B[90mDownload-Size:\x1B[0m {}", utils::format_size(size)),
+                    None => println!("\x1B[90mDownload-Size:\x1B[0m unknown"),
+                }
+                if let Some(size) = info.installed_size {
+                    println!("\x1B[90mInstalled-Size:\x1B[0m ~{} (estimated)", utils::format_size(size));
+                }
+            }
             
             if info.installed {
                 println!("\x1B[92mStatus:\x1B[0m \x1B[92m[INSTALLED]\x1B[0m");
@@ -120,6 +133,14 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
                     }
                 }
                 
+                if !info.optional_dependencies.is_empty() {
+                    println!();
+                    println!("\x1B[90mOptional Dependencies:\x1B[0m");
+                    for optional in &info.optional_dependencies {
+                        println!("  • {}", optional);
+                    }
+                }
+
                 if !info.dependents.is_empty() {
                     println!();
                     println!("\x1B[90mDependents:\x1B[0m");
@@ -129,11 +150,19 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
                 }
             }
             
-            if show_files && !info.installed_files.is_empty() {
+            if show_files {
                 println!();
-                println!("\x1B[90mInstalled Files:\x1B[0m");
-                for file in &info.installed_files {
-                    println!("  • {}", file);
+                if info.installed {
+                    println!("\x1B[90mInstalled Files:\x1B[0m");
+                } else {
+                    println!("\x1B[90mPackage Files:\x1B[0m");
+                }
+                if info.installed_files.is_empty() {
+                    println!("  None");
+                } else {
+                    for file in &info.installed_files {
+                        println!("  • {}", file);
+                    }
                 }
             }
             
@@ -1,20 +1,172 @@
 use commands::Command;
-use metadata::{get_packages, ProcessedMetaData, InstalledMetaData};
+use flags::Flag;
+use metadata::{get_packages, ProcessedMetaData, InstalledMetaData, QueuedChanges};
+use metadata::dep_graph::DependencyGraph;
 use settings::SettingsYaml;
 use settings::acquire_lock;
 use statebox::StateBox;
-use tokio::runtime::Runtime;
 use utils::PostAction;
-use utils::choice;
 use std::path::Path;
 use futures::future::join_all;
 
 pub fn build(hierarchy: &[String]) -> Command {
+    let only_dependencies = Flag::new(
+        None,
+        "only-dependencies",
+        "Install the package's dependencies but not the package itself",
+        false,
+        false,
+        |states, _| {
+            states.shove("only_dependencies", true);
+        },
+    );
+
+    let include_build = Flag::new(
+        None,
+        "include-build",
+        "With --only-dependencies, also install build dependencies",
+        false,
+        false,
+        |states, _| {
+            states.shove("include_build", true);
+        },
+    );
+
+    let arch = Flag::new(
+        None,
+        "arch",
+        "Install packages built for this architecture instead of the host's (e.g. x86_64v1)",
+        true,
+        false,
+        |states, arg| {
+            if let Some(arch) = arg {
+                states.shove("arch", arch.clone());
+            }
+        },
+    );
+
+    let no_verify = Flag::new(
+        None,
+        "no-verify",
+        "Skip checking each package's hash before installing it",
+        false,
+        false,
+        |states, _| {
+            states.shove("no_verify", true);
+        },
+    );
+
+    let assume_installed = Flag::new(
+        None,
+        "assume-installed",
+        "Treat these comma-separated dependencies as provided outside pax",
+        true,
+        false,
+        |states, arg| {
+            if let Some(names) = arg {
+                states.shove("assume_installed", names.clone());
+            }
+        },
+    );
+
+    let no_deps = Flag::new(
+        None,
+        "no-deps",
+        "Install only the named packages, without resolving or installing their dependencies",
+        false,
+        false,
+        |states, _| {
+            states.shove("no_deps", true);
+        },
+    )
+    .conflicts_with(&["only-dependencies"]);
+
+    let with_optional = Flag::new(
+        None,
+        "with-optional",
+        "Also install every optional dependency of the requested packages",
+        false,
+        false,
+        |states, _| {
+            states.shove("with_optional", true);
+        },
+    );
+
+    let with = Flag::new(
+        None,
+        "with",
+        "Also install these comma-separated optional dependencies, as package:dependency",
+        true,
+        false,
+        |states, arg| {
+            if let Some(picks) = arg {
+                states.shove("with", picks.clone());
+            }
+        },
+    );
+
+    let from_dir = Flag::new(
+        None,
+        "from-dir",
+        "Install from a directory of package files, resolving dependencies only against it",
+        true,
+        false,
+        |states, arg| {
+            if let Some(dir) = arg {
+                states.shove("from_dir", dir.clone());
+            }
+        },
+    )
+    .conflicts_with(&["from"]);
+
+    let download_only = Flag::new(
+        None,
+        "download-only",
+        "Resolve and download the packages and their dependencies without installing them",
+        false,
+        false,
+        |states, _| {
+            states.shove("download_only", true);
+        },
+    );
+
+    let destdir = Flag::new(
+        None,
+        "destdir",
+        "Directory --download-only saves packages to (the current directory by default)",
+        true,
+        false,
+        |states, arg| {
+            if let Some(dir) = arg {
+                states.shove("destdir", dir.clone());
+            }
+        },
+    );
+
     Command::new(
         "install",
         vec![String::from("i")],
         "Install the application from a specified path",
-        vec![utils::specific_flag(), utils::yes_flag(), utils::from_flag(), utils::allow_overwrite_flag(), utils::refresh_flag()],
+        vec![
+            utils::specific_flag(),
+            utils::yes_flag(),
+            utils::explain_flag(),
+            utils::from_flag(),
+            utils::allow_overwrite_flag(),
+            utils::refresh_flag(),
+            utils::pre_flag(),
+            only_dependencies,
+            include_build,
+            arch,
+            no_verify,
+            assume_installed,
+            no_deps,
+            with_optional,
+            with,
+            from_dir,
+            download_only,
+            destdir,
+        ],
         None,
         run,
         hierarchy,
@@ -35,6 +187,13 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         None => return PostAction::NothingToDo,
         Some(args) => args.to_vec(),
     };
+
+    if let Some(arch) = states.get::<String>("arch") {
+        match arch.parse::<settings::Arch>() {
+            Ok(arch) => settings::set_arch_override(arch),
+            Err(fault) => return PostAction::Fuck(fault),
+        }
+    }
     
     // Check for already installed packages before acquiring lock
     let is_local_package = |arg: &str| {
@@ -55,10 +214,14 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     };
 
     let has_local_package = args_vec.iter().any(|arg| is_local_package(arg));
-    
-    if has_local_package {
-        let Ok(runtime) = Runtime::new() else {
-            return PostAction::Fuck(String::from("Error creating runtime!"));
+    // Staging for another machine: what is installed here doesn't matter
+    let download_only = states.flag_set("download_only");
+    metadata::set_ignore_installed(download_only);
+
+    if has_local_package && !download_only {
+        let runtime = match metadata::runtime() {
+            Ok(runtime) => runtime,
+            Err(fault) => return PostAction::Fuck(fault),
         };
         
         for package_file in args_vec.iter().filter(|arg| is_local_package(arg)) {
@@ -87,8 +250,49 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         Err(fault) => return PostAction::Fuck(fault),
         _ => (),
     }
+    // Ctrl-C stops resolution and downloads at the next step instead of mid-write
+    if let Err(fault) = metadata::cancel::install_interrupt_handler() {
+        println!("\x1B[93m[WARN] {}\x1B[0m", fault);
+    }
+
+    metadata::github::set_include_prereleases(states.flag_set("pre"));
+    if states.flag_set("no_deps") {
+        println!("\x1B[93m[WARN] --no-deps: dependencies will not be installed; dependency integrity is not guaranteed.\x1B[0m");
+        metadata::set_skip_dependencies(true);
+    }
+
+    let mut optional = metadata::OptionalSelection { all: states.flag_set("with_optional"), ..Default::default() };
+    if let Some(picks) = states.get::<String>("with")
+        && let Err(fault) = optional.add_picks(picks)
+    {
+        return PostAction::Fuck(fault);
+    }
+    metadata::select_optional(optional);
+
+    if let Some(names) = states.get::<String>("assume_installed") {
+        let names: Vec<String> = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        metadata::assume_installed(&names);
+        if let Err(fault) = metadata::record_assumed_installed(&names) {
+            return PostAction::Fuck(fault);
+        }
+        println!("Assuming installed: {}", names.join(", "));
+    }
     
-    if !has_local_package {
+    // The bundle directory stands in for the configured sources, as a file:// `--from`
+    let from_dir = match states.get::<String>("from_dir") {
+        Some(dir) => match std::fs::canonicalize(dir) {
+            Ok(path) if path.is_dir() => Some(format!("file://{}", path.display())),
+            _ => return PostAction::Fuck(format!("`{}` is not a directory!", dir)),
+        },
+        None => None,
+    };
+
+    if !has_local_package && from_dir.is_none() {
     print!("Reading sources...");
     let settings = match SettingsYaml::get_settings() {
         Ok(settings) => settings,
@@ -102,7 +306,7 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     let mut data = Vec::new();
     let mut local_package_files = Vec::new();
     
-    if states.get("specific").is_some_and(|x| *x) {
+    if states.flag_set("specific") {
         let mut args_iter = args_vec.iter();
         while let Some(name) = args_iter.next()
             && let Some(ver) = args_iter.next()
@@ -123,8 +327,9 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         }
     }
     
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
     };
     
     let mut install_packages = Vec::new();
@@ -157,7 +362,7 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     
     // Handle remote packages
     if !data.is_empty() {
-        let preferred_source = states.get("from_repo").and_then(|v: &String| Some(v.as_str()));
+        let preferred_source = from_dir.as_deref().or(states.get("from_repo").map(String::as_str));
 
         // Separate packages: those with specific versions vs those without
         let mut packages_with_versions: Vec<String> = Vec::new();
@@ -175,8 +380,15 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         // If so, skip remote fetching for them
         let mut packages_to_fetch = packages_with_versions;
         for name in packages_without_versions {
-            if let Ok(installed) = InstalledMetaData::open(&name) {
-                println!("Package `{}` is already installed (version {}).", name, installed.version);
+            if !download_only && let Ok(installed) = InstalledMetaData::open(&name) {
+                match installed.mark_requested() {
+                    Ok(true) => println!(
+                        "Package `{}` is already installed (version {}), now marked as manually installed.",
+                        name, installed.version
+                    ),
+                    Ok(false) => println!("Package `{}` is already installed (version {}).", name, installed.version),
+                    Err(fault) => return PostAction::Fuck(fault),
+                }
                 continue;
             }
             packages_to_fetch.push(name);
@@ -194,7 +406,7 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
                 let _ = writeln!(file, "{{\"sessionId\":\"debug-session\",\"runId\":\"timing\",\"hypothesisId\":\"DELAY\",\"location\":\"src/install/mod.rs:179\",\"message\":\"before_get_packages\",\"data\":{{\"timestamp\":{}}},\"timestamp\":{}}}", before_get_packages, before_get_packages);
             }
             
-            let refresh_cache = states.get("refresh_cache").is_some_and(|x: &bool| *x);
+            let refresh_cache = states.flag_set("refresh_cache");
             let remote_data = match runtime.block_on(get_packages(packages_to_fetch, preferred_source, refresh_cache)) {
                 Ok(data) => data,
                 Err(fault) => return PostAction::Fuck(fault),
@@ -204,18 +416,24 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
             for package in remote_data {
                 let requested_version = data.iter().find(|(n, _)| n.eq_ignore_ascii_case(&package.metadata.name)).and_then(|(_, v)| v.as_ref());
 
-                if let Some(requested_ver) = requested_version {
-                    if let Ok(installed) = InstalledMetaData::open(&package.metadata.name) {
-                        if installed.version == **requested_ver {
-                            println!("Package `{}` version `{}` is already installed.", package.metadata.name, requested_ver);
-                            continue;
-                        } else {
-                            println!("Package `{}` is installed with version `{}`, but you're trying to install version `{}`.",
-                                    package.metadata.name, installed.version, requested_ver);
-                            println!("Consider using `pax upgrade` or `pax remove` first.");
-                            continue;
-                        }
+                if let Some(requested_ver) = requested_version
+                    && let Err(fault) = package.metadata.check_requested_version(requested_ver)
+                {
+                    return PostAction::Fuck(fault);
+                }
+
+                if let Some(requested_ver) = requested_version
+                    && !download_only
+                    && let Ok(installed) = InstalledMetaData::open(&package.metadata.name)
+                {
+                    if installed.version == **requested_ver {
+                        println!("Package `{}` version `{}` is already installed.", package.metadata.name, requested_ver);
+                    } else {
+                        println!("Package `{}` is installed with version `{}`, but you're trying to install version `{}`.",
+                                package.metadata.name, installed.version, requested_ver);
+                        println!("Consider using `pax upgrade` or `pax remove` first.");
                     }
+                    continue;
                 }
                 filtered_data.push(package);
             }
@@ -230,47 +448,106 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     if data.is_empty() {
         return PostAction::NothingToDo;
     }
-    println!(
-        "\nThe following package(s) will be INSTALLED: \x1B[92m{}\x1B[0m",
-        data.iter()
-            .fold(String::new(), |acc, x| format!("{acc} {}", x.metadata.name))
-            .trim()
-    );
-    let has_dependencies = data.iter().any(|x| !x.run_deps.is_empty() || !x.build_deps.is_empty());
-    if has_dependencies {
-        println!(
-            "The following package(s) will be INSTALLED (dependencies):  \x1B[93m{}\x1B[0m",
-            data.iter()
-                .flat_map(|x| x.list_deps(true))
-                .fold(String::new(), |acc, x| format!("{acc} {x}"))
-                .trim()
-        );
+    let only_dependencies = states.flag_set("only_dependencies");
+    let include_build = !only_dependencies || states.flag_set("include_build");
+    let mut plan = QueuedChanges::new();
+    let mut to_fetch: Vec<&ProcessedMetaData> = Vec::new();
+    if !only_dependencies {
+        for package in &data {
+            plan.queue_install(&package.metadata.name, &package.metadata.version);
+            to_fetch.push(&package.metadata);
+        }
     }
-
-    if states.get("yes").is_none_or(|x: &bool| !*x) {
-        let prompt = if has_dependencies {
-            "Continue with installation?"
-        } else {
-            "Proceed with installation?"
-        };
-        match choice(prompt, true) {
-            Err(message) => return PostAction::Fuck(message),
-            Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
-            Ok(true) => (),
-        };
+    for dep in data.iter().flat_map(|x| x.dependency_targets(include_build)) {
+        plan.queue_install(&dep.name, &dep.version);
+        to_fetch.push(dep);
+    }
+    if plan.is_empty() {
+        return PostAction::NothingToDo;
     }
-    let allow_overwrite = states.get("allow_overwrite").is_some_and(|x: &bool| *x);
+    runtime.block_on(plan.resolve_download_sizes(&to_fetch));
+    if states.flag_set("explain") {
+        let graph = DependencyGraph::from_packages(&data, include_build);
+        for name in &plan.install {
+            println!("{}", graph.explain(name));
+        }
+        println!();
+    }
+    if download_only {
+        let destdir = states.get::<String>("destdir").map(String::as_str).unwrap_or(".");
+        return download(runtime, &plan, &to_fetch, Path::new(destdir), states.flag_set("yes"));
+    }
+    let prompt = if plan.install.len() > data.len() {
+        "Continue with installation?"
+    } else {
+        "Proceed with installation?"
+    };
+    match plan.confirm(prompt, states.flag_set("yes")) {
+        Err(message) => return PostAction::Fuck(message),
+        Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
+        Ok(true) => (),
+    }
+    // --yes skips the per-file conflict prompts, backing up and overwriting
+    let allow_overwrite = states.flag_set("allow_overwrite") || states.flag_set("yes");
+    let verify = !states.flag_set("no_verify");
     
     for data in data {
-        if allow_overwrite {
-            if let Err(fault) = data.install_with_overwrite(&runtime) {
-                return PostAction::Fuck(fault);
-            }
+        if let Err(fault) = metadata::cancel::check_cancelled() {
+            return PostAction::Fuck(fault);
+        }
+        let result = if only_dependencies {
+            data.install_dependencies(include_build, allow_overwrite, verify)
         } else {
-            if let Err(fault) = data.install(&runtime) {
-                return PostAction::Fuck(fault);
-            }
+            data.install_with(allow_overwrite, verify)
+        };
+        if let Err(fault) = result {
+            return PostAction::Fuck(fault);
+        }
+    }
+    metadata::run_hooks(metadata::HookKind::PostInstall, &plan.install);
+    PostAction::Return
+}
+
+/// `--download-only`: fetch every package in the plan into `destdir` under
+/// its canonical name, ready for a `LocalDir` origin on another machine.
+fn download(
+    runtime: &tokio::runtime::Runtime,
+    plan: &QueuedChanges,
+    packages: &[&ProcessedMetaData],
+    destdir: &Path,
+    yes: bool,
+) -> PostAction {
+    match plan.confirm("Download these packages?", yes) {
+        Err(message) => return PostAction::Fuck(message),
+        Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
+        Ok(true) => (),
+    }
+    if let Err(fault) = std::fs::create_dir_all(destdir) {
+        return PostAction::Fuck(format!("Failed to create {}: {}", destdir.display(), fault));
+    }
+    let mut total = 0;
+    let mut saved = Vec::new();
+    for package in packages {
+        if let Err(fault) = metadata::cancel::check_cancelled() {
+            return PostAction::Fuck(fault);
         }
+        let path = match runtime.block_on(package.download_to(destdir)) {
+            Ok(path) => path,
+            Err(fault) => return PostAction::Fuck(format!("Failed to download {}: {}", package.name, fault)),
+        };
+        let size = std::fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+        total += size;
+        saved.push((path, size));
+    }
+    println!();
+    for (path, size) in &saved {
+        println!("  {} \x1B[90m({})\x1B[0m", path.display(), utils::format_size(*size));
     }
+    println!(
+        "\x1B[92mDownloaded {} file(s), {} in total, to {}\x1B[0m",
+        saved.len(),
+        utils::format_size(total),
+        destdir.display()
+    );
     PostAction::Return
 }
@@ -26,6 +26,20 @@ struct TemplateRepository {
     extra: serde_json::Value,
 }
 
+// Written to <root>/etc/pax/rootfs-manifest.yaml after populating a root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RootfsManifest {
+    root: PathBuf,
+    packages: Vec<RootfsPackage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RootfsPackage {
+    name: String,
+    version: String,
+    files: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct TemplateConfig {
     hostname: Option<String>,
@@ -52,7 +66,7 @@ pub fn build(hierarchy: &[String]) -> Command {
     let packages = Flag::new(
         Some('p'),
         "packages",
-        "Comma-separated list of packages to include in the ISO",
+        "Comma-separated list of packages, or a file listing one package per line",
         true,
         false,
         |states, value| {
@@ -79,7 +93,7 @@ pub fn build(hierarchy: &[String]) -> Command {
         "isocreate",
         vec![],
         "Build a live ISO image for Oreon or other pax-based distros",
-        vec![output, packages, template, utils::yes_flag()],
+        vec![output, packages, template, utils::root_flag(), utils::yes_flag()],
         None,
         run,
         hierarchy,
@@ -97,9 +111,7 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
         return PostAction::Elevate;
     }
     
-    let output_path = states.get::<String>("output")
-        .map(|s| PathBuf::from(s.as_str()))
-        .unwrap_or_else(|| PathBuf::from("oreon-live.iso"));
+    let output_path = PathBuf::from(states.get_or("output", String::from("oreon-live.iso")));
     
     // Load template file if provided
     let template = if let Some(template_path) = states.get::<String>("template") {
@@ -112,9 +124,7 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
     };
     
     // Get packages from template or command line
-    let packages_str = states.get::<String>("packages")
-        .map(|s| s.as_str().to_string())
-        .unwrap_or_else(|| String::from(""));
+    let packages_str = states.get_or("packages", String::new());
     
     let package_list: Vec<String> = if let Some(ref tmpl) = template {
         let raw_packages = tmpl.packages.clone().unwrap_or_default();
@@ -126,6 +136,11 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
             .collect();
         println!("[TEMPLATE] Processed package list: {:?}", processed);
         processed
+    } else if Path::new(&packages_str).is_file() {
+        match read_package_list(Path::new(&packages_str)) {
+            Ok(list) => list,
+            Err(fault) => return PostAction::Fuck(fault),
+        }
     } else if !packages_str.is_empty() {
         packages_str
             .split(',')
@@ -169,7 +184,7 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
         println!("Repositories: {}", repositories.len());
     }
     
-    if states.get("yes").is_none_or(|x: &bool| !*x) {
+    if !states.flag_set("yes") {
         match choice("Proceed with ISO creation?", true) {
             Err(message) => return PostAction::Fuck(message),
             Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
@@ -177,11 +192,20 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
         }
     }
     
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
     };
     
-    match build_iso(&runtime, &package_list, &repositories, &output_path, template.as_ref()) {
+    // With --root, populate that root directly instead of building an image
+    if utils::is_alternate_root() {
+        return match build_rootfs(runtime, &package_list, &repositories, &utils::install_root()) {
+            Ok(()) => PostAction::Return,
+            Err(fault) => PostAction::Fuck(fault),
+        };
+    }
+    
+    match build_iso(runtime, &package_list, &repositories, &output_path, template.as_ref()) {
         Ok(missing_packages) => {
             println!("\n\x1B[92mISO created successfully: {}\x1B[0m", output_path.display());
             
@@ -216,6 +240,110 @@ fn load_template(path: &Path) -> Result<IsoTemplate, String> {
     }
 }
 
+fn read_package_list(path: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read package list {}: {}", path.display(), e))?;
+    Ok(content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect())
+}
+
+// Resolve the full runtime dependency closure of `package_list`, ordered so
+// every package comes after its dependencies. Fails before anything is
+// installed if any package can't be resolved.
+async fn resolve_closure(
+    package_list: &[String],
+    repositories: &[OriginKind],
+) -> Result<Vec<metadata::ProcessedMetaData>, String> {
+    use std::collections::HashSet;
+    
+    let mut stack = Vec::new();
+    for name in package_list.iter().rev() {
+        match metadata::ProcessedMetaData::get_metadata(name, None, repositories, true).await? {
+            Some(package) => stack.push((package, false)),
+            None => return Err(format!("Package `{}` could not be resolved!", name)),
+        }
+    }
+    
+    let mut ordered = Vec::new();
+    let mut seen = HashSet::new();
+    let mut prior = HashSet::new();
+    while let Some((package, expanded)) = stack.pop() {
+        if expanded {
+            ordered.push(package);
+            continue;
+        }
+        if !seen.insert(package.name.clone()) {
+            continue;
+        }
+        let resolved = package.get_depends(repositories, &mut prior).await
+            .map_err(|e| format!("Failed to resolve dependencies of `{}`: {}", package.name, e))?;
+        stack.push((package, true));
+        for dep in resolved.run_deps.into_iter().rev() {
+            // Host binaries standing in for volatile dependencies aren't installable
+            if dep.version != "system" && !seen.contains(&dep.name) {
+                stack.push((dep, false));
+            }
+        }
+    }
+    
+    Ok(ordered)
+}
+
+fn build_rootfs(
+    runtime: &Runtime,
+    package_list: &[String],
+    repositories: &[OriginKind],
+    root: &Path,
+) -> Result<(), String> {
+    fs::create_dir_all(root)
+        .map_err(|e| format!("Failed to create root {}: {}", root.display(), e))?;
+    
+    println!("Resolving dependencies...");
+    let packages = runtime.block_on(resolve_closure(package_list, repositories))?;
+    println!("Installing {} package(s) into {}", packages.len(), root.display());
+    
+    let mut manifest = RootfsManifest {
+        root: root.to_path_buf(),
+        packages: Vec::new(),
+    };
+    for (idx, package) in packages.iter().enumerate() {
+        println!("[{}/{}] {} {}", idx + 1, packages.len(), package.name, package.version);
+        runtime.block_on(install_single_package_to_root(package.clone(), root))
+            .map_err(|e| format!("Failed to install {}: {}", package.name, e))?;
+        // Metadata for the root is written under the root itself
+        let files = metadata::file_tracking::FileManifest::load(&package.name)
+            .map(|installed| installed.files.len())
+            .unwrap_or_default();
+        manifest.packages.push(RootfsPackage {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            files,
+        });
+    }
+    
+    let manifest_path = root.join("etc/pax/rootfs-manifest.yaml");
+    if let Some(parent) = manifest_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let yaml = serde_yaml::to_string(&manifest)
+        .map_err(|e| format!("Failed to serialize rootfs manifest: {}", e))?;
+    fs::write(&manifest_path, yaml)
+        .map_err(|e| format!("Failed to write {}: {}", manifest_path.display(), e))?;
+    
+    println!("\n\x1B[92mInstalled {} package(s) into {}\x1B[0m", manifest.packages.len(), root.display());
+    for package in &manifest.packages {
+        println!("  {} {} ({} files)", package.name, package.version, package.files);
+    }
+    println!("Manifest written to {}", manifest_path.display());
+    
+    Ok(())
+}
+
 fn parse_template_repositories(repos: &[TemplateRepository]) -> Vec<OriginKind> {
     let mut result = Vec::new();
     
@@ -2666,7 +2794,7 @@ async fn fetch_packages_from_repos(
         // Always process explicitly requested packages, even if already visited as a dependency
         let is_explicit = explicit_packages.contains(&name);
         
-        if let Some(metadata) = ProcessedMetaData::get_metadata(&name, None, repositories, true).await {
+        if let Some(metadata) = ProcessedMetaData::get_metadata(&name, None, repositories, true).await? {
             // Mark as visited after successfully fetching metadata
             visited.insert(name.clone());
             
@@ -2686,7 +2814,7 @@ async fn fetch_packages_from_repos(
                 // Only process dependencies we haven't seen yet - strict check to prevent loops
                 if !visited.contains(&dep_name) {
                     visited.insert(dep_name.clone());
-                    if let Some(dep_metadata) = ProcessedMetaData::get_metadata(&dep_name, None, repositories, true).await {
+                    if let Some(dep_metadata) = ProcessedMetaData::get_metadata(&dep_name, None, repositories, true).await? {
                         run_deps.push(dep_metadata);
                     }
                 }
@@ -2706,7 +2834,7 @@ async fn fetch_packages_from_repos(
                 // Only process dependencies we haven't seen yet - strict check to prevent loops
                 if !visited.contains(&dep_name) {
                     visited.insert(dep_name.clone());
-                    if let Some(dep_metadata) = ProcessedMetaData::get_metadata(&dep_name, None, repositories, true).await {
+                    if let Some(dep_metadata) = ProcessedMetaData::get_metadata(&dep_name, None, repositories, true).await? {
                         build_deps.push(dep_metadata);
                     }
                 }
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use commands::Command;
+use metadata::TrustStore;
+use settings::check_root_required;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    Command::new(
+        "key",
+        vec![String::from("keys")],
+        "Manage the public keys trusted to sign each repository.",
+        Vec::new(),
+        Some(vec![build_add, build_list, build_remove]),
+        |_, _| PostAction::GetHelp,
+        hierarchy,
+    )
+}
+
+fn build_add(hierarchy: &[String]) -> Command {
+    Command::new(
+        "add",
+        Vec::new(),
+        "Trust the public key in <keyfile> for <repo>, replacing any key it had.",
+        Vec::new(),
+        None,
+        add_run,
+        hierarchy,
+    )
+}
+
+fn build_list(hierarchy: &[String]) -> Command {
+    Command::new(
+        "list",
+        vec![String::from("ls")],
+        "List trusted keys and their fingerprints.",
+        Vec::new(),
+        None,
+        list_run,
+        hierarchy,
+    )
+}
+
+fn build_remove(hierarchy: &[String]) -> Command {
+    Command::new(
+        "remove",
+        vec![String::from("rm")],
+        "Stop trusting the key for <repo>.",
+        Vec::new(),
+        None,
+        remove_run,
+        hierarchy,
+    )
+}
+
+fn add_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    let [repository, key_file] = args.unwrap_or_default() else {
+        return PostAction::Fuck(String::from("Usage: pax key add <repo> <keyfile>"));
+    };
+    // The trust store lives under /etc/pax
+    if let Some(action) = check_root_required(true) {
+        return action;
+    }
+    match TrustStore::system().and_then(|store| store.add(repository, Path::new(key_file))) {
+        Ok(key) => {
+            println!("\x1B[92mTrusted key for {}\x1B[0m", key.repository);
+            println!("  \x1B[90mFingerprint:\x1B[0m {}", key.fingerprint());
+            PostAction::Return
+        }
+        Err(fault) => PostAction::Fuck(fault),
+    }
+}
+
+fn list_run(_: &StateBox, _: Option<&[String]>) -> PostAction {
+    // Listing keys is read-only, doesn't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+    let keys = match TrustStore::system().and_then(|store| store.keys()) {
+        Ok(keys) => keys,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    if keys.is_empty() {
+        println!("\x1B[95mNo trusted keys\x1B[0m");
+        return PostAction::Return;
+    }
+    for key in &keys {
+        println!("\x1B[94m{}\x1B[0m", key.repository);
+        println!("  \x1B[90mFingerprint:\x1B[0m {}", key.fingerprint());
+    }
+    PostAction::Return
+}
+
+fn remove_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    let [repository] = args.unwrap_or_default() else {
+        return PostAction::Fuck(String::from("Usage: pax key remove <repo>"));
+    };
+    if let Some(action) = check_root_required(true) {
+        return action;
+    }
+    match TrustStore::system().and_then(|store| store.remove(repository)) {
+        Ok(key) => {
+            println!("\x1B[92mRemoved key {} for {}\x1B[0m", key.fingerprint(), key.repository);
+            PostAction::Return
+        }
+        Err(fault) => PostAction::Fuck(fault),
+    }
+}
@@ -1,6 +1,7 @@
 use commands::Command;
 use flags::Flag;
-use metadata::list_installed_packages;
+use metadata::{collect_updates, find_orphans, list_installed_packages, InstalledMetaData};
+use serde::Serialize;
 use settings::check_root_required;
 use statebox::StateBox;
 use utils::{PostAction};
@@ -16,7 +17,7 @@ pub fn build(hierarchy: &[String]) -> Command {
             states.shove("show_deps", true);
         },
     );
-    
+
     let show_dependents = Flag::new(
         Some('r'),
         "reverse",
@@ -27,7 +28,7 @@ pub fn build(hierarchy: &[String]) -> Command {
             states.shove("show_dependents", true);
         },
     );
-    
+
     let filter = Flag::new(
         Some('f'),
         "filter",
@@ -41,69 +42,251 @@ pub fn build(hierarchy: &[String]) -> Command {
         },
     );
 
+    let installed = Flag::new(
+        Some('i'),
+        "installed",
+        "List every installed package (the default)",
+        false,
+        false,
+        |states, _| {
+            states.shove("installed", true);
+        },
+    )
+    .conflicts_with(&["upgradable", "orphans"]);
+
+    let upgradable = Flag::new(
+        Some('u'),
+        "upgradable",
+        "List installed packages with an upgrade available, as `pax upgrade` sees them",
+        false,
+        false,
+        |states, _| {
+            states.shove("upgradable", true);
+        },
+    )
+    .conflicts_with(&["installed", "orphans"]);
+
+    let orphans = Flag::new(
+        Some('o'),
+        "orphans",
+        "List packages installed as dependencies that nothing depends on any more",
+        false,
+        false,
+        |states, _| {
+            states.shove("orphans", true);
+        },
+    )
+    .conflicts_with(&["installed", "upgradable"]);
+
+    let by_size = Flag::new(
+        Some('s'),
+        "by-size",
+        "Sort packages by installed size, largest first",
+        false,
+        false,
+        |states, _| {
+            states.shove("by_size", true);
+        },
+    );
+
+    let json = Flag::new(
+        None,
+        "json",
+        "Print the list as JSON",
+        false,
+        false,
+        |states, _| {
+            states.shove("json", true);
+        },
+    );
+
     Command::new(
         "list",
         vec![String::from("l")],
         "List all installed packages",
-        vec![show_deps, show_dependents, filter],
+        vec![show_deps, show_dependents, filter, installed, upgradable, orphans, by_size, json],
         None,
         run,
         hierarchy,
     )
 }
 
+/// One row of `pax list`, in the table and in `--json`.
+#[derive(Serialize)]
+struct ListEntry {
+    name: String,
+    installed: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    available: Option<String>,
+    origin: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    dependent: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dependencies: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dependents: Vec<String>,
+}
+
+impl ListEntry {
+    fn new(package: &InstalledMetaData, show_deps: bool, show_dependents: bool) -> Self {
+        Self {
+            name: package.name.clone(),
+            installed: package.version.clone(),
+            available: None,
+            origin: package.origin.to_string(),
+            size: None,
+            dependent: package.dependent,
+            dependencies: if show_deps {
+                package.dependencies.iter().map(|dep| dep.name.clone()).collect()
+            } else {
+                Vec::new()
+            },
+            dependents: if show_dependents {
+                package.dependents.iter().map(|dep| dep.name.clone()).collect()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+}
+
 fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
     // List is read-only, doesn't require root
     if let Some(action) = check_root_required(false) {
         return action;
     }
 
-    let show_deps = states.get::<bool>("show_deps").is_some_and(|x| *x);
-    let show_dependents = states.get::<bool>("show_dependents").is_some_and(|x| *x);
+    let show_deps = states.flag_set("show_deps");
+    let show_dependents = states.flag_set("show_dependents");
     let filter_pattern = states.get::<String>("filter_pattern").map(|x| x.clone());
 
-    match list_installed_packages(show_deps, show_dependents, filter_pattern.as_deref()) {
-        Ok(packages) => {
-            if packages.is_empty() {
-                println!("\x1B[95mNo packages installed\x1B[0m");
-            } else {
-                let filter_msg = if let Some(pattern) = &filter_pattern {
-                    format!(" (filtered by '{}')", pattern)
-                } else {
-                    String::new()
-                };
-                
-                println!("\x1B[92mInstalled packages{}:\x1B[0m", filter_msg);
-                println!();
-                
-                for (i, package) in packages.iter().enumerate() {
-                    println!("\x1B[94m{}. {}\x1B[0m", i + 1, package.name);
-                    println!("   \x1B[90mVersion:\x1B[0m {}", package.version);
-                    println!("   \x1B[90mOrigin:\x1B[0m {}", package.origin);
-                    
-                    if package.dependent {
-                        println!("   \x1B[93m[DEPENDENT]\x1B[0m");
-                    } else {
-                        println!("   \x1B[92m[INDEPENDENT]\x1B[0m");
-                    }
-                    
-                    if show_deps && !package.dependencies.is_empty() {
-                        let dep_names: Vec<String> = package.dependencies.iter().map(|dep| dep.name.clone()).collect();
-                        println!("   \x1B[90mDependencies:\x1B[0m {}", dep_names.join(", "));
-                    }
-                    
-                    if show_dependents && !package.dependents.is_empty() {
-                        let dep_names: Vec<String> = package.dependents.iter().map(|dep| dep.name.clone()).collect();
-                        println!("   \x1B[90mDependents:\x1B[0m {}", dep_names.join(", "));
-                    }
-                    
-                    println!();
-                }
-                
-                println!("\x1B[90mTotal: {} package(s)\x1B[0m", packages.len());
+    let packages = match list_installed_packages(show_deps, show_dependents, filter_pattern.as_deref()) {
+        Ok(packages) => packages,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+
+    let (title, mut entries) = if states.flag_set("upgradable") {
+        let runtime = match metadata::runtime() {
+            Ok(runtime) => runtime,
+            Err(fault) => return PostAction::Fuck(fault),
+        };
+        // The same check `pax upgrade` makes, so the two always agree
+        let check = match runtime.block_on(collect_updates(false)) {
+            Ok(check) => check,
+            Err(fault) => return PostAction::Fuck(fault),
+        };
+        let entries = packages
+            .iter()
+            .filter_map(|package| {
+                let update = check.upgradable.iter().find(|update| update.name == package.name)?;
+                let mut entry = ListEntry::new(package, show_deps, show_dependents);
+                entry.available = Some(update.version.clone());
+                Some(entry)
+            })
+            .collect::<Vec<_>>();
+        ("Upgradable packages", entries)
+    } else if states.flag_set("orphans") {
+        // Orphans are judged against everything installed, not just the filtered view
+        let all = if filter_pattern.is_some() {
+            match list_installed_packages(false, false, None) {
+                Ok(all) => all,
+                Err(fault) => return PostAction::Fuck(fault),
             }
-            PostAction::Return
+        } else {
+            packages.clone()
+        };
+        let orphans: Vec<&str> = find_orphans(&all).iter().map(|package| package.name.as_str()).collect();
+        let entries = packages
+            .iter()
+            .filter(|package| orphans.contains(&package.name.as_str()))
+            .map(|package| ListEntry::new(package, show_deps, show_dependents))
+            .collect::<Vec<_>>();
+        ("Orphaned packages", entries)
+    } else {
+        let entries = packages
+            .iter()
+            .map(|package| ListEntry::new(package, show_deps, show_dependents))
+            .collect::<Vec<_>>();
+        ("Installed packages", entries)
+    };
+
+    if states.flag_set("by_size") {
+        for entry in &mut entries {
+            entry.size = packages
+                .iter()
+                .find(|package| package.name == entry.name)
+                .and_then(InstalledMetaData::size_on_disk);
+        }
+        entries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    }
+
+    if states.flag_set("json") {
+        return match serde_json::to_string_pretty(&entries) {
+            Ok(json) => {
+                println!("{}", json);
+                PostAction::Return
+            }
+            Err(fault) => PostAction::Fuck(format!("Failed to serialize package list: {}", fault)),
+        };
+    }
+
+    if entries.is_empty() {
+        println!("\x1B[95mNo {}\x1B[0m", title.to_lowercase());
+        return PostAction::Return;
+    }
+
+    let filter_msg = if let Some(pattern) = &filter_pattern {
+        format!(" (filtered by '{}')", pattern)
+    } else {
+        String::new()
+    };
+    println!("\x1B[92m{}{}:\x1B[0m", title, filter_msg);
+    println!();
+    print_table(&entries);
+    println!();
+    println!("\x1B[90mTotal: {} package(s)\x1B[0m", entries.len());
+    PostAction::Return
+}
+
+fn print_table(entries: &[ListEntry]) {
+    let upgradable = entries.iter().any(|entry| entry.available.is_some());
+    let sized = entries.iter().any(|entry| entry.size.is_some());
+    let sizes: Vec<String> = entries
+        .iter()
+        .map(|entry| entry.size.map(utils::format_size).unwrap_or_else(|| String::from("?")))
+        .collect();
+    let size_width = sizes.iter().map(String::len).max().unwrap_or(0).max("SIZE".len());
+    let size_column = |size: &str| if sized { format!("{:>size_width$}  ", size) } else { String::new() };
+    let name_width = entries.iter().map(|entry| entry.name.len()).max().unwrap_or(0).max("NAME".len());
+    let version_width = entries.iter().map(|entry| entry.installed.len()).max().unwrap_or(0).max("INSTALLED".len());
+
+    let last = if upgradable { "AVAILABLE" } else { "STATUS" };
+    println!(
+        "\x1B[90m{:<name_width$}  {:<version_width$}  {}{}\x1B[0m",
+        "NAME",
+        "INSTALLED",
+        size_column("SIZE"),
+        last
+    );
+    for (entry, size) in entries.iter().zip(&sizes) {
+        let last = match &entry.available {
+            Some(available) => format!("\x1B[92m{}\x1B[0m", available),
+            None if entry.dependent => String::from("\x1B[93m[DEPENDENT]\x1B[0m"),
+            None => String::from("\x1B[92m[INDEPENDENT]\x1B[0m"),
+        };
+        println!(
+            "\x1B[94m{:<name_width$}\x1B[0m  {:<version_width$}  {}{}",
+            entry.name,
+            entry.installed,
+            size_column(size),
+            last
+        );
+        if !entry.dependencies.is_empty() {
+            println!("  \x1B[90mDependencies:\x1B[0m {}", entry.dependencies.join(", "));
+        }
+        if !entry.dependents.is_empty() {
+            println!("  \x1B[90mDependents:\x1B[0m {}", entry.dependents.join(", "));
         }
-        Err(fault) => PostAction::Fuck(fault),
     }
 }
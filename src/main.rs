@@ -1,16 +1,27 @@
 use std::{env, path::Path};
 
+pub mod clean;
 pub mod configure;
+pub mod depends;
+pub mod deps;
+pub mod doctor;
 pub mod emancipate;
+pub mod hold;
 pub mod info;
 pub mod install;
 pub mod isocreate;
+pub mod key;
 pub mod list;
+pub mod mark;
 pub mod pax_init;
+pub mod reindex;
 pub mod remove;
 pub mod repo;
 pub mod search;
 pub mod update;
+pub mod upgrade;
+pub mod verify;
+pub mod which;
 
 pub fn main() {
     let args: Vec<String> = env::args().collect();
@@ -21,25 +32,47 @@ pub fn main() {
         .unwrap_or(None)
         .unwrap_or(None)
         .unwrap_or("pax");
+    commands::set_version(env!("CARGO_PKG_VERSION"));
     // Main command
     let main_command = commands::Command::new(
         name,
         Vec::new(),
         "PAX is the official package manager for Oreon.",
-        vec![],
+        vec![
+            utils::root_flag(),
+            utils::config_dir_flag(),
+            utils::offline_flag(),
+            utils::limit_rate_flag(),
+            utils::verbose_flag(),
+        ],
         Some(vec![
+            clean::build,
             configure::build,
+            depends::build_depends,
+            depends::build_rdepends,
+            deps::build,
+            doctor::build,
             emancipate::build,
+            hold::build_hold,
+            hold::build_holds,
             info::build,
             install::build,
             isocreate::build,
+            key::build,
             list::build,
+            mark::build,
             pax_init::build,
+            reindex::build,
             remove::build_purge,
             remove::build_remove,
             repo::build,
             search::build,
+            search::build_categories,
+            hold::build_unhold,
             update::build,
+            upgrade::build,
+            verify::build,
+            which::build,
         ]),
         |_command, _args| utils::PostAction::GetHelp,
         &[],
@@ -0,0 +1,143 @@
+use commands::Command;
+use metadata::{InstallReason, InstalledMetaData};
+use settings::acquire_lock;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    Command::new(
+        "mark",
+        Vec::new(),
+        "Change whether installed packages count as manually or automatically installed.",
+        Vec::new(),
+        Some(vec![build_manual, build_auto, build_assumed, build_unassumed]),
+        |_, _| PostAction::GetHelp,
+        hierarchy,
+    )
+}
+
+fn build_manual(hierarchy: &[String]) -> Command {
+    Command::new(
+        "manual",
+        Vec::new(),
+        "Mark packages as manually installed, so they are never removed as orphans.",
+        Vec::new(),
+        None,
+        manual_run,
+        hierarchy,
+    )
+}
+
+fn build_auto(hierarchy: &[String]) -> Command {
+    Command::new(
+        "auto",
+        Vec::new(),
+        "Mark packages as automatically installed, so they are removed once nothing needs them.",
+        Vec::new(),
+        None,
+        auto_run,
+        hierarchy,
+    )
+}
+
+fn build_assumed(hierarchy: &[String]) -> Command {
+    Command::new(
+        "assumed",
+        Vec::new(),
+        "Treat names as provided outside pax, so dependencies on them are never installed.",
+        Vec::new(),
+        None,
+        assumed_run,
+        hierarchy,
+    )
+}
+
+fn build_unassumed(hierarchy: &[String]) -> Command {
+    Command::new(
+        "unassumed",
+        Vec::new(),
+        "Stop treating names as provided outside pax, as recorded by --assume-installed.",
+        Vec::new(),
+        None,
+        unassumed_run,
+        hierarchy,
+    )
+}
+
+fn manual_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    run(args, InstallReason::Manual)
+}
+
+fn auto_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    run(args, InstallReason::Auto)
+}
+
+fn assumed_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    let names = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No package provided!")),
+        Some(args) => args,
+    };
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+    if let Err(fault) = metadata::record_assumed_installed(names) {
+        return PostAction::Fuck(fault);
+    }
+    println!("\x1B[92mAssuming installed: {}.\x1B[0m", names.join(", "));
+    PostAction::Return
+}
+
+fn unassumed_run(_: &StateBox, args: Option<&[String]>) -> PostAction {
+    let names = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No package provided!")),
+        Some(args) => args,
+    };
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+    let forgotten = match metadata::forget_assumed_installed(names) {
+        Ok(forgotten) => forgotten,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    for name in names {
+        if forgotten.contains(name) {
+            println!("\x1B[92m{} is no longer assumed installed.\x1B[0m", name);
+        } else {
+            println!("{} was not assumed installed.", name);
+        }
+    }
+    PostAction::Return
+}
+
+fn run(args: Option<&[String]>, reason: InstallReason) -> PostAction {
+    let packages = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No package provided!")),
+        Some(args) => args,
+    };
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+    // Check every name first so a typo doesn't leave the marks half applied
+    for package in packages {
+        if InstalledMetaData::open(package).is_err() {
+            return PostAction::Fuck(format!("Package `{}` is not installed!", package));
+        }
+    }
+    let label = match reason {
+        InstallReason::Manual => "manually installed",
+        InstallReason::Auto => "automatically installed",
+    };
+    for package in packages {
+        if let Err(fault) = InstalledMetaData::mark(package, reason) {
+            return PostAction::Fuck(fault);
+        }
+        println!("\x1B[92m{} marked as {}.\x1B[0m", package, label);
+    }
+    PostAction::Return
+}
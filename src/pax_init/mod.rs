@@ -4,7 +4,6 @@ use settings::OriginKind;
 use settings::SettingsYaml;
 use settings::acquire_lock;
 use statebox::StateBox;
-use tokio::runtime::Runtime;
 use utils::PostAction;
 use utils::err;
 
@@ -39,7 +38,7 @@ fn get_endpoints(states: &StateBox, _args: Option<&[String]>) -> PostAction {
         Err(fault) => return PostAction::Fuck(fault),
         _ => (),
     }
-    if states.get::<bool>("force").is_none_or(|x| !*x) {
+    if !states.flag_set("force") {
         println!(
             "\x1B[33m===== WARNING! WARNING! WARNING! =====\x1B[0m
 This command should \x1B[31mNOT\x1B[0m be run as part of a standard update procedure.
@@ -47,8 +46,9 @@ To continue anyway, run with flag `\x1B[35m--{LONG_NAME}\x1B[0m`."
         );
     } else {
         println!("Pulling sources...");
-        let Ok(runtime) = Runtime::new() else {
-            return PostAction::Fuck(String::from("Error creating runtime!"));
+        let runtime = match metadata::runtime() {
+            Ok(runtime) => runtime,
+            Err(fault) => return PostAction::Fuck(fault),
         };
         if let Err(fault) = runtime.block_on(gen_sources()) {
             return PostAction::Fuck(fault);
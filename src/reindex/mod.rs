@@ -0,0 +1,36 @@
+use commands::Command;
+use metadata::performance::FileIndex;
+use settings::acquire_lock;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    Command::new(
+        "reindex",
+        Vec::new(),
+        "Rebuild the file ownership index from installed package manifests",
+        Vec::new(),
+        None,
+        run,
+        hierarchy,
+    )
+}
+
+fn run(_states: &StateBox, _args: Option<&[String]>) -> PostAction {
+    match acquire_lock() {
+        Ok(Some(action)) => return action,
+        Err(fault) => return PostAction::Fuck(fault),
+        _ => (),
+    }
+
+    let index = match FileIndex::rebuild() {
+        Ok(index) => index,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    if let Err(fault) = index.save() {
+        return PostAction::Fuck(fault);
+    }
+
+    println!("Indexed \x1B[94m{}\x1B[0m installed path(s).", index.len());
+    PostAction::Return
+}
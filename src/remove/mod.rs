@@ -1,9 +1,9 @@
 use commands::Command;
+use flags::Flag;
 use metadata;
 use settings::acquire_lock;
 use statebox::StateBox;
-use tokio::runtime::Runtime;
-use utils::{PostAction, choice};
+use utils::PostAction;
 use std::io;
 
 pub fn build_remove(hierarchy: &[String]) -> Command {
@@ -11,7 +11,7 @@ pub fn build_remove(hierarchy: &[String]) -> Command {
         "remove",
         vec![String::from("r")],
         "Removes a package, whilst maintaining any user-made configurations",
-        vec![utils::specific_flag(), utils::yes_flag()],
+        vec![utils::specific_flag(), utils::yes_flag(), no_deps_flag()],
         None,
         remove,
         hierarchy,
@@ -23,13 +23,26 @@ pub fn build_purge(hierarchy: &[String]) -> Command {
         "purge",
         vec![String::from("p")],
         "Removes a package, WITHOUT maintaining any user-made configurations",
-        vec![utils::specific_flag(), utils::yes_flag()],
+        vec![utils::specific_flag(), utils::yes_flag(), no_deps_flag()],
         None,
         purge,
         hierarchy,
     )
 }
 
+fn no_deps_flag() -> Flag {
+    Flag::new(
+        None,
+        "no-deps",
+        "Remove only the named packages, leaving their dependencies alone",
+        false,
+        false,
+        |states, _| {
+            states.shove("no_deps", true);
+        },
+    )
+}
+
 fn remove(states: &StateBox, args: Option<&[String]>) -> PostAction {
     run(states, args, false)
 }
@@ -44,12 +57,16 @@ fn run(states: &StateBox, args: Option<&[String]>, purge: bool) -> PostAction {
         Err(fault) => return PostAction::Fuck(fault),
         _ => (),
     }
+    // Manifests and removal safety checks assume the host root
+    if utils::is_alternate_root() {
+        return PostAction::Fuck(String::from("Removing packages from an alternate root is not supported!"));
+    }
     let mut args = match args {
         None => return PostAction::NothingToDo,
         Some(args) => args.iter(),
     };
     let mut data = Vec::new();
-    if states.get("specific").is_some_and(|x| *x) {
+    if states.flag_set("specific") {
         while let Some(name) = args.next()
             && let Some(ver) = args.next()
         {
@@ -58,9 +75,6 @@ fn run(states: &StateBox, args: Option<&[String]>, purge: bool) -> PostAction {
     } else {
         args.for_each(|x| data.push((x, None)));
     }
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
-    };
     
     if data.is_empty() {
                 return PostAction::NothingToDo;
@@ -69,10 +83,15 @@ fn run(states: &StateBox, args: Option<&[String]>, purge: bool) -> PostAction {
     // Get package names to remove
     let package_names: Vec<String> = data.iter().map(|(name, _)| (*name).clone()).collect();
     
+    let no_deps = states.flag_set("no_deps");
+    if no_deps {
+        println!("\x1B[93m[WARN] --no-deps: dependencies are left as they are; dependency integrity is not guaranteed.\x1B[0m");
+    }
+
     // Collect dependencies of packages to be removed BEFORE removal (for purge only)
     use std::collections::HashSet;
     let mut removed_deps = HashSet::new();
-    if purge {
+    if purge && !no_deps {
         for package_name in &package_names {
             if let Ok(metadata) = metadata::InstalledMetaData::open(package_name) {
                 for dep in &metadata.dependencies {
@@ -84,30 +103,27 @@ fn run(states: &StateBox, args: Option<&[String]>, purge: bool) -> PostAction {
         }
     }
     
-            let msg = if purge { "PURGED: " } else { "REMOVED:" };
-            println!(
-                "\nThe following package(s) will be {msg}  \x1B[91m{}\x1B[0m",
-        package_names.join(" ")
-            );
+    let mut plan = metadata::QueuedChanges::new();
+    for package_name in &package_names {
+        plan.queue_remove(package_name);
+    }
     
     // Show dependencies that might become orphans
     if purge && !removed_deps.is_empty() {
         let dep_vec: Vec<String> = removed_deps.iter().cloned().collect();
-                println!(
+        println!(
             "\nDependencies that may no longer be needed: \x1B[93m{}\x1B[0m",
             dep_vec.join(", ")
-                );
-            }
-            
-            // Always prompt for confirmation unless --yes flag is used
-                if states.get("yes").is_none_or(|x: &bool| !*x) {
-                let prompt = if purge { "Proceed with purging?" } else { "Proceed with removal?" };
-                match choice(prompt, true) {
-                        Err(message) => return PostAction::Fuck(message),
-                        Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
-                        Ok(true) => (),
-                    };
-            }
+        );
+    }
+    
+    // Always prompt for confirmation unless --yes flag is used
+    let prompt = if purge { "Proceed with purging?" } else { "Proceed with removal?" };
+    match plan.confirm(prompt, states.flag_set("yes")) {
+        Err(message) => return PostAction::Fuck(message),
+        Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
+        Ok(true) => (),
+    }
     
     // Actually remove the packages
     for package_name in &package_names {
@@ -121,11 +137,13 @@ fn run(states: &StateBox, args: Option<&[String]>, purge: bool) -> PostAction {
     
     // Find orphaned dependencies AFTER removing packages (only for purge)
     let orphans = if purge {
-        find_orphaned_dependencies(&package_names, &removed_deps)
+        find_orphaned_dependencies(&package_names, &removed_deps, no_deps)
     } else {
         Vec::new()
     };
     
+    let mut removed = package_names.clone();
+
     // Clean up orphaned dependencies (only for purge)
     if !orphans.is_empty() {
         println!("\n\x1B[93mThe following dependencies are no longer needed:\x1B[0m \x1B[93m{}\x1B[0m", orphans.join(", "));
@@ -134,61 +152,25 @@ fn run(states: &StateBox, args: Option<&[String]>, purge: bool) -> PostAction {
         let mut input = String::new();
         if io::stdin().read_line(&mut input).is_ok() && input.trim().to_lowercase() == "y" {
             for orphan in &orphans {
-                let _ = remove_package(orphan, purge);
+                if remove_package(orphan, purge).is_ok() {
+                    removed.push(orphan.clone());
+                }
             }
             println!("\x1B[92mRemoved orphaned dependencies: {}\x1B[0m", orphans.join(", "));
             }
     }
+    metadata::run_hooks(metadata::HookKind::PostRemove, &removed);
     
             PostAction::Return
         }
 
-fn find_orphaned_dependencies(removed_packages: &[String], _removed_deps: &std::collections::HashSet<String>) -> Vec<String> {
-    
+fn find_orphaned_dependencies(removed_packages: &[String], removed_deps: &std::collections::HashSet<String>, no_deps: bool) -> Vec<String> {
     // Get all currently installed packages
     let all_packages = match metadata::list_installed_packages(false, false, None) {
         Ok(packages) => packages,
         Err(_) => return Vec::new(),
     };
-
-    // Build a set of remaining packages (not being removed)
-    let remaining_packages: std::collections::HashSet<String> = all_packages.iter()
-        .map(|p| p.name.clone())
-        .filter(|name| !removed_packages.contains(name))
-        .collect();
-
-    // Build a dependency map for efficient lookup
-    let mut dependency_map: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
-    for package in &all_packages {
-        if remaining_packages.contains(&package.name) {
-            for dep in &package.dependencies {
-                dependency_map.entry(dep.name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(package.name.clone());
-            }
-        }
-    }
-    
-    // Collect dependencies that were installed by the removed packages
-    let mut potential_orphans = std::collections::HashSet::new();
-    for package in &all_packages {
-        if let Some(installed_by) = &package.installed_by {
-            if removed_packages.contains(installed_by) {
-                potential_orphans.insert(package.name.clone());
-            }
-        }
-    }
-    
-    // Check which of these are actually orphans (not needed by any remaining package)
-    let mut orphans = Vec::new();
-    for orphan_candidate in &potential_orphans {
-        // Check if any remaining package depends on this orphan
-        if !dependency_map.contains_key(orphan_candidate) {
-            orphans.push(orphan_candidate.clone());
-        }
-    }
-    
-    orphans
+    metadata::orphaned_by_removal(&all_packages, removed_packages, removed_deps, no_deps)
 }
 
 fn remove_package(package_name: &str, purge: bool) -> Result<(), String> {
@@ -208,10 +190,7 @@ fn remove_package(package_name: &str, purge: bool) -> Result<(), String> {
     }
 
     // Remove the package's file manifest
-    let manifest_file = installed_dir.join("manifests").join(format!("{}.yaml", package_name));
-        if manifest_file.exists() {
-            let _ = fs::remove_file(&manifest_file);
-    }
+    let _ = metadata::file_tracking::FileManifest::delete(package_name);
     
     // Remove the package metadata file
     fs::remove_file(&package_file)
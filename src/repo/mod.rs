@@ -1,5 +1,6 @@
 use commands::Command;
 use flags::Flag;
+use metadata::TrustStore;
 use settings::{OriginKind, SettingsYaml, check_root_required};
 use statebox::StateBox;
 use utils::{PostAction, get_dir};
@@ -54,6 +55,20 @@ pub fn build(hierarchy: &[String]) -> Command {
         },
     );
 
+    let keyring = Flag::new(
+        None,
+        "keyring",
+        "Trust the public key in this file for the repository being added",
+        true,
+        false,
+        |states, arg| {
+            if let Some(path) = arg {
+                states.shove("keyring", path.clone());
+            }
+        },
+    )
+    .conflicts_with(&["no-keyring"]);
+
     let pax_flag = Flag::new(
         None,
         "pax",
@@ -104,7 +119,7 @@ pub fn build(hierarchy: &[String]) -> Command {
         "repo",
         vec![String::from("repositories")],
         "Manage package repositories",
-        vec![list, test, add, remove, no_keyring, pax_flag, deb_flag, rpm_flag],
+        vec![list, test, add, remove, no_keyring, keyring, pax_flag, deb_flag, rpm_flag],
         None,
         run,
         hierarchy,
@@ -117,7 +132,7 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         Err(fault) => return PostAction::Fuck(fault),
     };
 
-    if states.get::<bool>("list_repos").is_some_and(|x| *x) {
+    if states.flag_set("list_repos") {
         return list_repositories(&settings);
     }
 
@@ -125,7 +140,7 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         return test_repository(repo_url);
     }
 
-    if states.get::<bool>("add_repo").is_some_and(|x| *x) {
+    if states.flag_set("add_repo") {
         // Check if we need root for adding repositories
         if let Some(action) = check_root_required(true) {
             return action;
@@ -143,7 +158,8 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         };
         
         let repo_type = states.get::<String>("repo_type").map(|s| s.as_str());
-        return add_repository(&mut settings, &repo_url, repo_type, states.get::<bool>("no_keyring").copied().unwrap_or(false));
+        let keyring = states.get::<String>("keyring").map(|s| s.as_str());
+        return add_repository(&mut settings, &repo_url, repo_type, keyring, states.flag_set("no_keyring"));
     }
 
     if let Some(repo_identifier) = states.get::<String>("remove_repo") {
@@ -239,7 +255,13 @@ fn list_repositories(settings: &SettingsYaml) -> PostAction {
     PostAction::Return
 }
 
-fn add_repository(settings: &mut SettingsYaml, repo_url: &str, repo_type: Option<&str>, no_keyring: bool) -> PostAction {
+fn add_repository(
+    settings: &mut SettingsYaml,
+    repo_url: &str,
+    repo_type: Option<&str>,
+    keyring: Option<&str>,
+    no_keyring: bool,
+) -> PostAction {
     // Validate URL format
     if !is_valid_url(repo_url) {
         println!("\x1B[91mError: Invalid URL format: {}\x1B[0m", repo_url);
@@ -300,9 +322,24 @@ fn add_repository(settings: &mut SettingsYaml, repo_url: &str, repo_type: Option
         }
     }
 
-    // Check if keyring verification is required
-    if !no_keyring {
-        println!("  \x1B[90mKeyring verification:\x1B[0m \x1B[93mNot yet supported for custom repositories\x1B[0m");
+    // Import or look up the key signatures from this repository are checked against
+    if let Some(key_file) = keyring {
+        match TrustStore::system().and_then(|store| store.add(clean_url_trimmed, Path::new(key_file))) {
+            Ok(key) => println!("  \x1B[90mKeyring verification:\x1B[0m trusting key {}", key.fingerprint()),
+            Err(fault) => {
+                println!("\x1B[91mError: Failed to import key: {}\x1B[0m", fault);
+                return PostAction::Fuck(fault);
+            }
+        }
+    } else if !no_keyring {
+        match TrustStore::system().and_then(|store| store.key_for(clean_url_trimmed)) {
+            Ok(Some(key)) => println!("  \x1B[90mKeyring verification:\x1B[0m trusted key {}", key.fingerprint()),
+            Ok(None) => println!(
+                "  \x1B[90mKeyring verification:\x1B[0m \x1B[93mNo trusted key; add one with `pax key add {} <keyfile>`\x1B[0m",
+                clean_url_trimmed
+            ),
+            Err(fault) => println!("\x1B[93m[WARN] Failed to read the trust store: {}\x1B[0m", fault),
+        }
     }
 
     // Determine repository type - use explicit type if provided, otherwise infer from URL
@@ -1,9 +1,8 @@
 use commands::Command;
 use flags::Flag;
-use metadata::search_packages;
+use metadata::{category_counts, search_packages};
 use settings::{check_root_required, SettingsYaml};
 use statebox::StateBox;
-use tokio::runtime::Runtime;
 use utils::{PostAction};
 
 pub fn build(hierarchy: &[String]) -> Command {
@@ -49,13 +48,27 @@ pub fn build(hierarchy: &[String]) -> Command {
         |states, _| {
             states.shove("remote", true);
         },
+    )
+    .conflicts_with(&["installed"]);
+
+    let category = Flag::new(
+        Some('c'),
+        "category",
+        "Only show packages in the given category",
+        true,
+        false,
+        |states, arg| {
+            if let Some(category) = arg {
+                states.shove("category", category.clone());
+            }
+        },
     );
 
     Command::new(
         "search",
         vec![String::from("s")],
-        "Search for packages by name or description",
-        vec![exact, installed, show_deps, remote],
+        "Search for packages by name, keyword, category or description",
+        vec![exact, installed, show_deps, remote, category],
         None,
         run,
         hierarchy,
@@ -67,20 +80,16 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     if let Some(action) = check_root_required(false) {
         return action;
     }
-    let args = match args {
-        None => return PostAction::Fuck(String::from("No search term provided!")),
-        Some(args) => args,
-    };
-
-    if args.is_empty() {
+    // With --category the search term is optional, to browse the category
+    let category = states.get::<String>("category").cloned();
+    let search_term = args.unwrap_or_default().join(" ");
+    if search_term.is_empty() && category.is_none() {
         return PostAction::Fuck(String::from("No search term provided!"));
     }
-
-    let search_term = args.join(" ");
-    let exact_match = states.get::<bool>("exact").is_some_and(|x| *x);
-    let installed_only = states.get::<bool>("installed").is_some_and(|x| *x) ||
-        !states.get::<bool>("remote").is_some_and(|x| *x); // Default to installed only unless --remote is specified
-    let show_deps = states.get::<bool>("show_deps").is_some_and(|x| *x);
+    let exact_match = states.flag_set("exact");
+    let installed_only = states.flag_set("installed") ||
+        !states.flag_set("remote"); // Default to installed only unless --remote is specified
+    let show_deps = states.flag_set("show_deps");
 
     // Get settings if we're not searching installed only
     let settings = if !installed_only {
@@ -92,8 +101,9 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         None
     };
 
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
     };
 
     match runtime.block_on(search_packages(
@@ -101,19 +111,28 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         exact_match,
         installed_only,
         show_deps,
+        category.as_deref(),
         settings.as_ref(),
     )) {
         Ok(results) => {
+            let description = match (&category, search_term.is_empty()) {
+                (Some(category), true) => format!("in category '{}'", category),
+                (Some(category), false) => format!("matching '{}' in category '{}'", search_term, category),
+                (None, _) => format!("matching '{}'", search_term),
+            };
             if results.is_empty() {
-                println!("\x1B[95mNo packages found matching '{}'\x1B[0m", search_term);
+                println!("\x1B[95mNo packages found {}\x1B[0m", description);
             } else {
-                println!("\x1B[92mFound {} package(s) matching '{}':\x1B[0m", results.len(), search_term);
+                println!("\x1B[92mFound {} package(s) {}:\x1B[0m", results.len(), description);
                 println!();
                 
                 for (i, result) in results.iter().enumerate() {
                     println!("\x1B[94m{}. {}\x1B[0m", i + 1, result.name);
                     println!("   \x1B[90mVersion:\x1B[0m {}", result.version);
                     println!("   \x1B[90mDescription:\x1B[0m {}", result.description);
+                    if !result.keywords.is_empty() {
+                        println!("   \x1B[90mKeywords:\x1B[0m {}", result.keywords.join(", "));
+                    }
                     
                     if show_deps && !result.dependencies.is_empty() {
                         println!("   \x1B[90mDependencies:\x1B[0m {}", result.dependencies.join(", "));
@@ -131,3 +150,63 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         Err(fault) => PostAction::Fuck(fault),
     }
 }
+
+pub fn build_categories(hierarchy: &[String]) -> Command {
+    let remote = Flag::new(
+        Some('r'),
+        "remote",
+        "Count packages in remote repositories as well as installed ones",
+        false,
+        false,
+        |states, _| {
+            states.shove("remote", true);
+        },
+    );
+
+    Command::new(
+        "categories",
+        Vec::new(),
+        "List package categories with how many packages are in each",
+        vec![remote],
+        None,
+        categories,
+        hierarchy,
+    )
+}
+
+fn categories(states: &StateBox, _args: Option<&[String]>) -> PostAction {
+    // Browsing is read-only, doesn't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+    let installed_only = !states.flag_set("remote");
+    let settings = if !installed_only {
+        match SettingsYaml::get_settings() {
+            Ok(settings) => Some(settings),
+            Err(_) => return PostAction::PullSources,
+        }
+    } else {
+        None
+    };
+
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+    // An empty search matches everything
+    let packages = match runtime.block_on(search_packages("", false, installed_only, false, None, settings.as_ref())) {
+        Ok(packages) => packages,
+        Err(fault) => return PostAction::Fuck(fault),
+    };
+
+    let counts = category_counts(&packages);
+    if counts.is_empty() {
+        println!("\x1B[95mNo categorised packages found\x1B[0m");
+        return PostAction::Return;
+    }
+    let width = counts.keys().map(String::len).max().unwrap_or(0);
+    for (category, count) in counts {
+        println!("\x1B[94m{:<width$}\x1B[0m  {}", category, count);
+    }
+    PostAction::Return
+}
@@ -2,8 +2,7 @@ use commands::Command;
 use metadata::{collect_updates, upgrade_packages};
 use settings::acquire_lock;
 use statebox::StateBox;
-use tokio::runtime::Runtime;
-use utils::{PostAction, choice};
+use utils::PostAction;
 
 pub fn build(hierarchy: &[String]) -> Command {
     Command::new(
@@ -24,18 +23,24 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
         _ => (),
     }
 
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
     };
 
     // Collect available updates
-    let refresh_cache = states.get("refresh_cache").is_some_and(|x: &bool| *x);
-    let updates = match runtime.block_on(collect_updates(refresh_cache)) {
-        Ok(updates) => updates,
+    let refresh_cache = states.flag_set("refresh_cache");
+    let check = match runtime.block_on(collect_updates(refresh_cache)) {
+        Ok(check) => check,
         Err(fault) => return PostAction::Fuck(fault),
     };
+    let updates = &check.upgradable;
 
     if updates.is_empty() {
+        let plan = check.to_plan();
+        if !plan.held.is_empty() || !plan.held_back.is_empty() {
+            println!("{}", plan.render_plan());
+        }
         println!("No updates available.");
         return PostAction::Return;
     }
@@ -45,7 +50,7 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     println!();
 
-    for update in &updates {
+    for update in updates {
         println!("  \x1B[94m{}\x1B[0m -> \x1B[92m{}\x1B[0m", update.name, update.version);
         if !update.description.is_empty() {
             println!("    {}", update.description);
@@ -53,23 +58,21 @@ fn run(states: &StateBox, _args: Option<&[String]>) -> PostAction {
         println!();
     }
 
-    println!("Total: {} package(s) to upgrade", updates.len());
+    let mut plan = check.to_plan();
+    runtime.block_on(plan.resolve_download_sizes(&updates.iter().collect::<Vec<_>>()));
 
     // Add confirmation prompt unless --yes flag is used
-    if states.get("yes").is_none_or(|x: &bool| !*x) {
-        match choice("Continue with updates?", true) {
-            Err(message) => return PostAction::Fuck(message),
-            Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
-            Ok(true) => (),
-        };
+    match plan.confirm("Continue with updates?", states.flag_set("yes")) {
+        Err(message) => return PostAction::Fuck(message),
+        Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
+        Ok(true) => (),
     }
 
     // Perform the upgrades
     println!("\x1B[92mUpgrading packages...\x1B[0m");
 
-    let refresh_cache = states.get("refresh_cache").is_some_and(|x: &bool| *x);
-    let package_names: Vec<String> = updates.iter().map(|u| u.name.clone()).collect();
-    match runtime.block_on(upgrade_packages(package_names, refresh_cache)) {
+    let refresh_cache = states.flag_set("refresh_cache");
+    match runtime.block_on(upgrade_packages(plan.upgrade, refresh_cache)) {
         Ok(_) => {
             println!("\x1B[92mAll packages upgraded successfully!\x1B[0m");
             PostAction::Return
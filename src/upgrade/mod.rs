@@ -2,15 +2,14 @@ use commands::Command;
 use metadata::{upgrade_all, upgrade_only, upgrade_packages};
 use settings::acquire_lock;
 use statebox::StateBox;
-use utils::{PostAction, choice};
-use tokio::runtime::Runtime;
+use utils::PostAction;
 
 pub fn build(hierarchy: &[String]) -> Command {
     Command::new(
         "upgrade",
         vec![String::from("g")],
         "Upgrades a non-phased package from its upgrade metadata.",
-        vec![utils::yes_flag(), utils::refresh_flag()],
+        vec![utils::yes_flag(), utils::explain_flag(), utils::refresh_flag(), utils::pre_flag()],
         None,
         run,
         hierarchy,
@@ -24,10 +23,14 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
 
         _ => (),
     }
+    if let Err(fault) = metadata::cancel::install_interrupt_handler() {
+        println!("\x1B[93m[WARN] {}\x1B[0m", fault);
+    }
+    metadata::github::set_include_prereleases(states.flag_set("pre"));
     let args = if let Some(args) = args {
         let mut args = args.iter();
         let mut data = Vec::new();
-        if states.get("specific").is_some_and(|x| *x) {
+        if states.flag_set("specific") {
             while let Some(name) = args.next()
                 && let Some(ver) = args.next()
             {
@@ -40,10 +43,11 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
     } else {
         Vec::new()
     };
-    let Ok(runtime) = Runtime::new() else {
-        return PostAction::Fuck(String::from("Error creating runtime!"));
+    let runtime = match metadata::runtime() {
+        Ok(runtime) => runtime,
+        Err(fault) => return PostAction::Fuck(fault),
     };
-    let refresh_cache = states.get("refresh_cache").is_some_and(|x: &bool| *x);
+    let refresh_cache = states.flag_set("refresh_cache");
     let data = match if args.is_empty() {
         runtime.block_on(upgrade_all(refresh_cache))
     } else {
@@ -54,21 +58,33 @@ fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
         Err(fault) => return PostAction::Fuck(fault),
     };
     if data.is_empty() {
+        // Still say why available upgrades were skipped
+        if !data.held.is_empty() || !data.held_back.is_empty() {
+            println!("{}", data.render_plan());
+        }
         return PostAction::NothingToDo;
     }
-    println!(
-        "The following package(s) will be UPGRADED: \x1B[94m{}\x1B[0m",
-        data.join(" ")
-    );
-    if states.get("yes").is_none_or(|x: &bool| !*x) {
-        match choice("Continue?", true) {
-            Err(message) => return PostAction::Fuck(message),
-            Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
-            Ok(true) => (),
-        };
+    if states.flag_set("explain") {
+        for name in &data.upgrade {
+            if args.iter().any(|(requested, _)| *requested == name) {
+                println!("{}: explicitly requested.", name);
+            } else {
+                let from = data.previous.get(name).map(String::as_str).unwrap_or("?");
+                let to = data.versions.get(name).map(String::as_str).unwrap_or("?");
+                println!("{}: installed at {}, and {} is available.", name, from, to);
+            }
+        }
+        println!();
+    }
+    match data.confirm("Continue?", states.flag_set("yes")) {
+        Err(message) => return PostAction::Fuck(message),
+        Ok(false) => return PostAction::Fuck(String::from("Aborted.")),
+        Ok(true) => (),
     }
-    if let Err(fault) = runtime.block_on(upgrade_packages(data, refresh_cache)) {
+    let upgraded = data.upgrade.clone();
+    if let Err(fault) = runtime.block_on(upgrade_packages(data.upgrade, refresh_cache)) {
         return PostAction::Fuck(fault);
     }
+    metadata::run_hooks(metadata::HookKind::PostUpgrade, &upgraded);
     PostAction::Return
 }
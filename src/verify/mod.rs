@@ -0,0 +1,127 @@
+use commands::Command;
+use flags::Flag;
+use metadata::file_tracking::{FileManifest, calculate_file_checksum, load_all_manifests};
+use metadata::integrity::{IntegrityLog, IntegrityProblem};
+use settings::check_root_required;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    let integrity = Flag::new(
+        None,
+        "integrity",
+        "Check manifests against the integrity log instead of checking files",
+        false,
+        false,
+        |states, _| {
+            states.shove("integrity", true);
+        },
+    );
+
+    Command::new(
+        "verify",
+        Vec::new(),
+        "Check installed files against their package manifests",
+        vec![integrity],
+        None,
+        run,
+        hierarchy,
+    )
+}
+
+fn run(states: &StateBox, args: Option<&[String]>) -> PostAction {
+    // Verify is read-only, doesn't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+
+    let packages = args.unwrap_or_default();
+    let result = if states.flag_set("integrity") {
+        verify_integrity(packages)
+    } else {
+        verify_files(packages)
+    };
+    match result {
+        Ok(true) => PostAction::Return,
+        Ok(false) => PostAction::Err(1),
+        Err(fault) => PostAction::Fuck(fault),
+    }
+}
+
+/// Flag installed files that are missing or whose checksum has changed.
+/// Packages assumed installed have no manifest, so they are skipped.
+fn verify_files(packages: &[String]) -> Result<bool, String> {
+    let manifests = if packages.is_empty() {
+        load_all_manifests()?
+    } else {
+        packages
+            .iter()
+            .filter(|name| {
+                let assumed = metadata::is_assumed_installed(name);
+                if assumed {
+                    println!("{} is assumed installed (provided outside pax); skipping.", name);
+                }
+                !assumed
+            })
+            .map(|name| FileManifest::load(name))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut clean = true;
+    for manifest in &manifests {
+        for file in &manifest.files {
+            let path = utils::in_install_root(&file.path);
+            let problem = if !path.exists() {
+                Some("missing")
+            } else {
+                match calculate_file_checksum(&path) {
+                    Ok(checksum) if checksum == file.checksum => None,
+                    Ok(_) => Some("modified"),
+                    Err(_) => Some("unreadable"),
+                }
+            };
+            if let Some(problem) = problem {
+                println!("\x1B[91m{}: {} ({})\x1B[0m", manifest.package_name, file.path.display(), problem);
+                clean = false;
+            }
+        }
+    }
+
+    if clean {
+        println!("\x1B[92mVerified {} package(s).\x1B[0m", manifests.len());
+    }
+    Ok(clean)
+}
+
+/// Flag packages whose manifest no longer matches the integrity log.
+fn verify_integrity(packages: &[String]) -> Result<bool, String> {
+    if IntegrityLog::entries()?.is_empty() {
+        return Err(format!(
+            "No integrity log at {}; set PAX_INTEGRITY_LOG=1 when installing to record one.",
+            IntegrityLog::location()?.display()
+        ));
+    }
+
+    let mut clean = true;
+    for (package, problem) in IntegrityLog::check()? {
+        if (!packages.is_empty() && !packages.contains(&package)) || metadata::is_assumed_installed(&package) {
+            continue;
+        }
+        let detail = match problem {
+            IntegrityProblem::Modified { expected, found } => {
+                format!("manifest hash {} does not match logged {}", found, expected)
+            }
+            IntegrityProblem::Missing { expected } => {
+                format!("manifest is missing (logged {})", expected)
+            }
+            IntegrityProblem::Unrecorded => String::from("manifest was never recorded in the log"),
+        };
+        println!("\x1B[91m{}: {}\x1B[0m", package, detail);
+        clean = false;
+    }
+
+    if clean {
+        println!("\x1B[92mAll manifests match the integrity log.\x1B[0m");
+    }
+    Ok(clean)
+}
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use commands::Command;
+use metadata::file_tracking::get_file_owner;
+use settings::check_root_required;
+use statebox::StateBox;
+use utils::PostAction;
+
+pub fn build(hierarchy: &[String]) -> Command {
+    Command::new(
+        "which",
+        vec![String::from("owner")],
+        "Show which installed package owns a file",
+        Vec::new(),
+        None,
+        run,
+        hierarchy,
+    )
+}
+
+fn run(_states: &StateBox, args: Option<&[String]>) -> PostAction {
+    // Which is read-only, doesn't require root
+    if let Some(action) = check_root_required(false) {
+        return action;
+    }
+
+    let args = match args {
+        None | Some([]) => return PostAction::Fuck(String::from("No path provided!")),
+        Some(args) => args,
+    };
+
+    let mut unowned = false;
+    for arg in args {
+        let path = absolute(Path::new(arg));
+        // Manifests record install paths, which may sit behind a symlinked
+        // directory (e.g. /bin -> /usr/bin), so try the resolved path too
+        let owner = get_file_owner(&path).or_else(|fault| {
+            match path.canonicalize() {
+                Ok(resolved) if resolved != path => get_file_owner(&resolved),
+                _ => Err(fault),
+            }
+        });
+        match owner {
+            Ok(owner) => println!("{} is owned by \x1B[94m{}\x1B[0m", path.display(), owner),
+            Err(_) => {
+                println!("\x1B[91m{} is not owned by any package\x1B[0m", path.display());
+                unowned = true;
+            }
+        }
+    }
+
+    if unowned {
+        PostAction::Err(1)
+    } else {
+        PostAction::Return
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
@@ -74,6 +74,43 @@ impl StateBox {
     pub fn get<T: 'static>(&self, key: &str) -> Option<&T> {
         self.store.get(key)?.downcast_ref::<T>()
     }
+    /// Gets **a clone of** the value from the statebox with the specified key.
+    ///
+    /// If the statebox does not contain this key, or it holds a value of another
+    /// type, `default` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use statebox::StateBox;
+    ///
+    /// let mut states = StateBox::new();
+    /// assert_eq!(states.get_or("from_repo", String::from("pax")), String::from("pax"));
+    /// states.insert("from_repo", String::from("apt")).unwrap();
+    /// assert_eq!(states.get_or("from_repo", String::from("pax")), String::from("apt"));
+    /// ```
+    pub fn get_or<T: Clone + 'static>(&self, key: &str, default: T) -> T {
+        self.get::<T>(key).cloned().unwrap_or(default)
+    }
+    /// Returns whether the boolean flag with the specified key is set to `true`.
+    ///
+    /// Missing keys, and keys holding a non-`bool` value, count as unset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use statebox::StateBox;
+    ///
+    /// let mut states = StateBox::new();
+    /// assert!(!states.flag_set("yes"));
+    /// states.shove("yes", true);
+    /// assert!(states.flag_set("yes"));
+    /// states.shove("yes", 1u8);
+    /// assert!(!states.flag_set("yes"));
+    /// ```
+    pub fn flag_set(&self, key: &str) -> bool {
+        self.get_or(key, false)
+    }
     /// Replaces the value with the specified key. Note that this value does not need
     /// to be of the same type as the original value.
     ///
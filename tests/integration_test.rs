@@ -52,5 +52,44 @@ mod tests {
         // This is a basic smoke test
         assert!(true); // Placeholder
     }
-}
 
+    #[test]
+    fn test_list_with_config_dir() {
+        // Read-only commands run against a relocated config dir need no root
+        let config_dir = tempfile::tempdir().unwrap();
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pax"))
+            .arg("--config-dir")
+            .arg(config_dir.path())
+            .args(["list", "--json"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "[]");
+        assert!(config_dir.path().join("installed").is_dir());
+    }
+
+    #[test]
+    fn test_search_while_locked() {
+        // A read-only command neither waits on nor releases another run's lock
+        let config_dir = tempfile::tempdir().unwrap();
+        let pax = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pax"))
+                .arg("--config-dir")
+                .arg(config_dir.path())
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        // As an install in progress leaves it
+        let settings_path = config_dir.path().join("settings.yaml");
+        std::fs::write(
+            &settings_path,
+            format!("locked: true\nversion: {}\narch: NoArch\nexec: null\nsources: []\n", settings::SETTINGS_YAML_VERSION),
+        )
+        .unwrap();
+
+        let output = pax(&["search", "anything"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(std::fs::read_to_string(&settings_path).unwrap().contains("locked: true"));
+    }
+}
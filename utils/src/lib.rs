@@ -1,10 +1,10 @@
 pub mod logging;
 
-use std::{cmp::Ordering, fs::DirBuilder, io::Write, path::PathBuf, process::Command};
+use std::{cmp::Ordering, collections::HashMap, fs::DirBuilder, io::Write, path::{Path, PathBuf}, str::FromStr};
 
 use flags::Flag;
 use nix::unistd;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 // The action to perform once a command has run
 pub enum PostAction {
@@ -17,12 +17,21 @@ pub enum PostAction {
     Return,
 }
 
+// Where pax keeps its state; set for the whole invocation by `--config-dir`
+// or `PAX_CONFIG_DIR`
+pub fn config_dir() -> PathBuf {
+    match std::env::var("PAX_CONFIG_DIR") {
+        Ok(dir) if !dir.trim().is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from("/etc/pax"),
+    }
+}
+
 pub fn get_dir() -> Result<PathBuf, String> {
-    let path = PathBuf::from("/etc/pax");
+    let path = config_dir();
     if !path.exists() {
         // Try to create directory, but don't fail if we don't have permission
         // This allows read-only operations to work without root
-        let _ = DirBuilder::new().create(&path);
+        let _ = DirBuilder::new().recursive(true).create(&path);
     }
     if path.exists() {
         Ok(path)
@@ -31,10 +40,33 @@ pub fn get_dir() -> Result<PathBuf, String> {
     }
 }
 
+// Where packages are installed; set for the whole invocation by `--root`
+pub fn install_root() -> PathBuf {
+    match std::env::var("PAX_ROOT") {
+        Ok(root) if !root.trim().is_empty() => PathBuf::from(root),
+        _ => PathBuf::from("/"),
+    }
+}
+
+/// Where a path recorded as seen from inside the install root, like the
+/// paths in file manifests, lives on this system.
+pub fn in_install_root(path: &Path) -> PathBuf {
+    install_root().join(path.strip_prefix("/").unwrap_or(path))
+}
+
+pub fn is_alternate_root() -> bool {
+    install_root() != Path::new("/")
+}
+
 pub fn get_metadata_dir() -> Result<PathBuf, String> {
-    let mut path = get_dir()?;
+    // Installed metadata belongs to the root it describes, not to the host
+    let mut path = if is_alternate_root() {
+        install_root().join("etc/pax")
+    } else {
+        get_dir()?
+    };
     path.push("installed");
-    if !path.exists() && DirBuilder::new().create(&path).is_err() {
+    if !path.exists() && DirBuilder::new().recursive(true).create(&path).is_err() {
         err!("Failed to create pax installation directory!")
     } else {
         Ok(path)
@@ -55,10 +87,83 @@ pub fn is_root() -> bool {
     unistd::geteuid().as_raw() == 0
 }
 
+/// Create an empty file in the system temp directory and return its path.
+/// The file is created with `O_EXCL`, so a name is never handed out twice,
+/// and mode 0600 like `mktemp`'s.
 pub fn tmpfile() -> Option<PathBuf> {
-    Some(PathBuf::from(
-        String::from_utf8_lossy(&Command::new("mktemp").output().ok()?.stdout).trim(),
-    ))
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let dir = std::env::temp_dir();
+    for _ in 0..64 {
+        let seed = COUNTER.fetch_add(1, AtomicOrdering::Relaxed);
+        let suffix = RandomState::new().hash_one((std::process::id(), seed));
+        let path = dir.join(format!("pax.{:016x}", suffix));
+        match std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(_) => return Some(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Replace `path` with `contents` so that readers see either the old file or
+/// the new one, never a torn write: the data goes to a temporary file in the
+/// same directory, is synced, then renamed over the target.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    write_atomic_with(path, |file| file.write_all(contents))
+}
+
+fn write_atomic_with(path: &Path, fill: impl FnOnce(&mut std::fs::File) -> std::io::Result<()>) -> Result<(), String> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Some(name) = path.file_name() else {
+        return err!("Cannot write to {}: not a file path", path.display());
+    };
+    // Unique per call, so concurrent writers in one process don't share it
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let seq = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let tmp = dir.join(format!(".{}.{}.{}.tmp", name.to_string_lossy(), std::process::id(), seq));
+    let written = std::fs::File::create(&tmp).and_then(|mut file| {
+        fill(&mut file)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| std::fs::rename(&tmp, path)) {
+        let _ = std::fs::remove_file(&tmp);
+        return err!("Failed to write {}: {}", path.display(), e);
+    }
+    // Persist the rename itself; not every filesystem lets a directory be synced
+    if let Ok(dir) = std::fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Run `f` while holding an exclusive advisory lock for `path`, so
+/// read-modify-write cycles on it from other threads or processes wait
+/// their turn. The lock is taken on a `.<name>.lock` file beside `path`,
+/// since `write_atomic` replaces `path` itself.
+pub fn with_file_lock<T>(path: &Path, f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    let Some(name) = path.file_name() else {
+        return err!("Cannot lock {}: not a file path", path.display());
+    };
+    let lock_path = path.with_file_name(format!(".{}.lock", name.to_string_lossy()));
+    let lock = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("Failed to open {}: {}", lock_path.display(), e))?;
+    lock.lock().map_err(|e| format!("Failed to lock {}: {}", lock_path.display(), e))?;
+    let result = f();
+    let _ = lock.unlock();
+    result
 }
 
 pub fn yes_flag() -> Flag {
@@ -74,6 +179,133 @@ pub fn yes_flag() -> Flag {
     )
 }
 
+pub fn explain_flag() -> Flag {
+    Flag::new(
+        None,
+        "explain",
+        "Show why each package is part of the transaction",
+        false,
+        false,
+        |states, _| {
+            states.shove("explain", true);
+        },
+    )
+}
+
+pub fn pre_flag() -> Flag {
+    Flag::new(
+        None,
+        "pre",
+        "Allow pre-releases when picking the newest GitHub release",
+        false,
+        false,
+        |states, _| {
+            states.shove("pre", true);
+        },
+    )
+}
+
+pub fn root_flag() -> Flag {
+    Flag::new(
+        None,
+        "root",
+        "Install into (and read installed metadata from) an alternate root directory.",
+        true,
+        false,
+        |_states, value| {
+            if let Some(root) = value {
+                let root = std::path::absolute(&root).unwrap_or_else(|_| PathBuf::from(&root));
+                // SAFETY: flags are parsed before any other threads are started
+                unsafe { std::env::set_var("PAX_ROOT", root) };
+            }
+        },
+    )
+}
+
+pub fn config_dir_flag() -> Flag {
+    Flag::new(
+        None,
+        "config-dir",
+        "Keep settings, installed metadata and caches in this directory instead of /etc/pax.",
+        true,
+        false,
+        |_states, value| {
+            if let Some(dir) = value {
+                let dir = std::path::absolute(&dir).unwrap_or_else(|_| PathBuf::from(&dir));
+                // SAFETY: flags are parsed before any other threads are started
+                unsafe { std::env::set_var("PAX_CONFIG_DIR", dir) };
+            }
+        },
+    )
+}
+
+pub fn offline_flag() -> Flag {
+    Flag::new(
+        None,
+        "offline",
+        "Refuse all network access; only local directories and files are used.",
+        false,
+        false,
+        |_states, _| {
+            // SAFETY: flags are parsed before any other threads are started
+            unsafe { std::env::set_var("PAX_OFFLINE", "1") };
+        },
+    )
+}
+
+// Set for the whole invocation by `--offline` or `PAX_OFFLINE=1`
+pub fn is_offline() -> bool {
+    std::env::var("PAX_OFFLINE").is_ok_and(|value| value == "1")
+}
+
+pub fn verbose_flag() -> Flag {
+    Flag::counter(
+        Some('v'),
+        "verbose",
+        "Print more detail; repeat for more (-v info, -vv debug, -vvv trace).",
+        |states, _| logging::set_verbosity(states.get_or("verbose", 0usize)),
+    )
+}
+
+pub fn limit_rate_flag() -> Flag {
+    Flag::new(
+        None,
+        "limit-rate",
+        "Cap download speed in bytes per second; accepts suffixes like 500k or 2M.",
+        true,
+        false,
+        |_states, value| {
+            if let Some(rate) = value {
+                // SAFETY: flags are parsed before any other threads are started
+                unsafe { std::env::set_var("PAX_LIMIT_RATE", rate) };
+            }
+        },
+    )
+}
+
+/// Parse a byte rate such as `1024`, `500k` or `2M` (binary multiples).
+pub fn parse_rate(rate: &str) -> Result<u64, String> {
+    let rate = rate.trim();
+    let (digits, multiplier) = match rate.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => (&rate[..rate.len() - 1], 1024),
+        Some('m') => (&rate[..rate.len() - 1], 1024 * 1024),
+        Some('g') => (&rate[..rate.len() - 1], 1024 * 1024 * 1024),
+        _ => (rate, 1),
+    };
+    match digits.parse::<u64>() {
+        Ok(value) if value > 0 => Ok(value.saturating_mul(multiplier)),
+        _ => err!("Invalid rate `{}`; expected bytes per second like 500k or 2M", rate),
+    }
+}
+
+// Set for the whole invocation by `--limit-rate` or `PAX_LIMIT_RATE`
+pub fn limit_rate() -> Result<Option<u64>, String> {
+    match std::env::var("PAX_LIMIT_RATE") {
+        Ok(rate) if !rate.trim().is_empty() => parse_rate(&rate).map(Some),
+        _ => Ok(None),
+    }
+}
+
 pub fn from_flag() -> Flag {
     Flag::new(
         Some('f'),
@@ -135,6 +367,176 @@ macro_rules! err {
     ($fmt:literal $(, $args:expr)*) => {Err(format!($fmt $(, $args)*))};
 }
 
+/// Expands `${VAR}` references in a command from `vars`. Anything else,
+/// including `$VAR`, `$(...)` and `${VAR:-default}`, is left for the shell.
+/// Referencing a variable that isn't in `vars` is an error rather than an
+/// empty string; write `$${VAR}` to hand a literal `${VAR}` to the shell.
+pub fn expand_vars(command: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(command.len());
+    let mut rest = command;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (escaped, braced) = match after.strip_prefix("${") {
+            Some(braced) => (true, braced),
+            None => match after.strip_prefix('{') {
+                Some(braced) => (false, braced),
+                None => {
+                    expanded.push('$');
+                    rest = after;
+                    continue;
+                }
+            },
+        };
+        let Some(end) = braced.find('}') else {
+            return err!("Unterminated `${{` in command `{}`!", command);
+        };
+        let name = &braced[..end];
+        let is_identifier = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if escaped || !is_identifier {
+            expanded.push_str("${");
+            expanded.push_str(name);
+            expanded.push('}');
+        } else {
+            match vars.get(name) {
+                Some(value) => expanded.push_str(value),
+                None => return err!("Undefined variable `{}` in command `{}`!", name, command),
+            }
+        }
+        rest = &braced[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Human readable byte count, e.g. "12.3 MiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+nix::ioctl_read_bad!(get_window_size, nix::libc::TIOCGWINSZ, nix::pty::Winsize);
+
+/// Width of the terminal attached to stdout, falling back to `$COLUMNS` and
+/// then 80 when stdout isn't a terminal or reports a zero width.
+pub fn terminal_width() -> usize {
+    let mut size = nix::pty::Winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes a `winsize` struct through the pointer
+    if unsafe { get_window_size(nix::libc::STDOUT_FILENO, &mut size) }.is_ok() && size.ws_col > 0 {
+        return size.ws_col as usize;
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse::<usize>().ok())
+        .filter(|columns| *columns > 0)
+        .unwrap_or(80)
+}
+
+/// Shorten `text` to at most `max_chars` characters by keeping its tail behind
+/// a leading "...". Cuts on char boundaries so multi-byte names never panic.
+pub fn truncate_start(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let keep = max_chars.saturating_sub(3);
+    let skip = text.chars().count() - keep;
+    match text.char_indices().nth(skip) {
+        Some((start, _)) => format!("...{}", &text[start..]),
+        None => String::from("..."),
+    }
+}
+
+/// Split a manifest command script into individual commands. Scripts are one
+/// command per line (a trailing `\` continues a line; blanks and `#` comments
+/// are skipped). Older single-line scripts chained with `&&` are split on it,
+/// outside of quotes, so each link is run and reported on its own.
+pub fn split_commands(script: &str) -> Vec<String> {
+    let script = script.trim();
+    if !script.contains('\n') {
+        return split_and_chain(script);
+    }
+    let mut commands = Vec::new();
+    let mut current = String::new();
+    for line in script.lines() {
+        let line = line.trim();
+        if current.is_empty() && (line.is_empty() || line.starts_with('#')) {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(partial) => {
+                current.push_str(partial.trim_end());
+                current.push(' ');
+            }
+            None => {
+                current.push_str(line);
+                commands.push(std::mem::take(&mut current).trim().to_string());
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        commands.push(current.trim().to_string());
+    }
+    commands
+}
+
+/// The links of a `cmd && cmd` chain, leaving `&&` inside quotes alone.
+fn split_and_chain(chain: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = chain.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '&') if chars.peek() == Some(&'&') => {
+                chars.next();
+                commands.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    commands.push(current);
+    commands
+        .into_iter()
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+/// One line of input, without its newline. `read_line` retries reads a
+/// signal interrupted, which would leave Ctrl-C waiting on Enter; here an
+/// interrupted read is returned as an error so the prompt can be cancelled.
+fn read_answer(input: &mut impl std::io::Read) -> std::io::Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while input.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
 pub fn choice(message: &str, default_yes: bool) -> Result<bool, String> {
     print!(
         "{} [{}]: ",
@@ -142,10 +544,13 @@ pub fn choice(message: &str, default_yes: bool) -> Result<bool, String> {
         if default_yes { "Y/n" } else { "y/N" }
     );
     let _ = std::io::stdout().flush();
-    let mut input = String::new();
-    if std::io::stdin().read_line(&mut input).is_err() {
-        return err!("\nFailed to read terminal input!");
-    }
+    let input = match read_answer(&mut std::io::stdin().lock()) {
+        Ok(input) => input,
+        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+            return err!("\nInterrupted; nothing further was changed.");
+        }
+        Err(_) => return err!("\nFailed to read terminal input!"),
+    };
     if default_yes {
         if ["no", "n", "false", "f"].contains(&input.to_lowercase().trim()) {
             Ok(false)
@@ -159,7 +564,8 @@ pub fn choice(message: &str, default_yes: bool) -> Result<bool, String> {
     }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+// Serialized as its `Display` form, e.g. "1.2.3-rc1"
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct Version {
     pub major: usize,
     pub minor: usize,
@@ -222,6 +628,54 @@ impl Version {
     }
 }
 
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        Self::parse(src)
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Metadata written before versions were serialized as strings stores them as
+// `{major, minor, patch, pre}`. Accept both until that format is gone.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VersionRepr {
+    Text(String),
+    Fields {
+        major: usize,
+        minor: usize,
+        patch: usize,
+        #[serde(default)]
+        pre: String,
+    },
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match VersionRepr::deserialize(deserializer)? {
+            VersionRepr::Text(text) => Self::parse(&text).map_err(serde::de::Error::custom),
+            VersionRepr::Fields {
+                major,
+                minor,
+                patch,
+                pre,
+            } => Ok(Self {
+                major,
+                minor,
+                patch,
+                pre,
+            }),
+        }
+    }
+}
+
 impl std::fmt::Display for Version {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.pre.is_empty() {
@@ -445,6 +899,44 @@ impl VerReq {
     }
 }
 
+impl std::fmt::Display for VerReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerReq::Gt(version) => write!(f, ">{}", version),
+            VerReq::Ge(version) => write!(f, ">={}", version),
+            VerReq::Eq(version) => write!(f, "={}", version),
+            VerReq::Le(version) => write!(f, "<={}", version),
+            VerReq::Lt(version) => write!(f, "<{}", version),
+            VerReq::NoBound => f.write_str("*"),
+        }
+    }
+}
+
+impl FromStr for VerReq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(VerReq::NoBound);
+        }
+        let (make, version): (fn(Version) -> VerReq, &str) = if let Some(version) = s.strip_prefix(">=") {
+            (VerReq::Ge, version)
+        } else if let Some(version) = s.strip_prefix("<=") {
+            (VerReq::Le, version)
+        } else if let Some(version) = s.strip_prefix('>') {
+            (VerReq::Gt, version)
+        } else if let Some(version) = s.strip_prefix('<') {
+            (VerReq::Lt, version)
+        } else if let Some(version) = s.strip_prefix('=') {
+            (VerReq::Eq, version)
+        } else {
+            return err!("Invalid version requirement `{s}`!");
+        };
+        Ok(make(Version::parse(version.trim())?))
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Range {
     pub lower: VerReq,
@@ -461,6 +953,25 @@ impl Range {
         }
     }
 
+    /// Read a range back from its `Display` form, e.g. `>=1.0, <2.0`.
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let bounds = src
+            .split(',')
+            .map(VerReq::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        match bounds.as_slice() {
+            [VerReq::Eq(version)] => Ok(Range { lower: VerReq::Eq(version.clone()), upper: VerReq::Eq(version.clone()) }),
+            [upper @ (VerReq::Le(_) | VerReq::Lt(_))] => Ok(Range { lower: VerReq::NoBound, upper: upper.clone() }),
+            [lower] => Ok(Range { lower: lower.clone(), upper: VerReq::NoBound }),
+            [lower, upper] => Ok(Range { lower: lower.clone(), upper: upper.clone() }),
+            _ => err!("Invalid version range `{src}`!"),
+        }
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.lower == VerReq::NoBound && self.upper == VerReq::NoBound
+    }
+
     pub fn is_sane(&self) -> bool {
         match &self.lower {
             VerReq::Gt(gt) => match &self.upper {
@@ -488,6 +999,73 @@ impl Range {
     pub fn negotiate(&self, prior: Option<Self>) -> Option<Self> {
         self.upper.negotiate(self.lower.negotiate(prior))
     }
+
+    /// The versions both ranges contain, or `None` when they share none.
+    /// `=v` counts as both `>=v` and `<=v`.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        // (version, exclusive) for each side; None means unbounded
+        fn lower_of(req: &VerReq) -> Option<(&Version, bool)> {
+            match req {
+                VerReq::Gt(v) => Some((v, true)),
+                VerReq::Ge(v) | VerReq::Eq(v) => Some((v, false)),
+                _ => None,
+            }
+        }
+        fn upper_of(req: &VerReq) -> Option<(&Version, bool)> {
+            match req {
+                VerReq::Lt(v) => Some((v, true)),
+                VerReq::Le(v) | VerReq::Eq(v) => Some((v, false)),
+                _ => None,
+            }
+        }
+        let lower = match (lower_of(&self.lower), lower_of(&other.lower)) {
+            (Some((a, a_ex)), Some((b, b_ex))) if a > b || (a == b && a_ex && !b_ex) => &self.lower,
+            (Some(_), Some(_)) | (None, Some(_)) => &other.lower,
+            (Some(_), None) => &self.lower,
+            (None, None) => &VerReq::NoBound,
+        };
+        let upper = match (upper_of(&self.upper), upper_of(&other.upper)) {
+            (Some((a, a_ex)), Some((b, b_ex))) if a < b || (a == b && a_ex && !b_ex) => &self.upper,
+            (Some(_), Some(_)) | (None, Some(_)) => &other.upper,
+            (Some(_), None) => &self.upper,
+            (None, None) => &VerReq::NoBound,
+        };
+        if let (Some((low, low_ex)), Some((high, high_ex))) = (lower_of(lower), upper_of(upper))
+            && (low > high || (low == high && (low_ex || high_ex)))
+        {
+            return None;
+        }
+        Some(Range { lower: lower.clone(), upper: upper.clone() })
+    }
+}
+
+/// `>=1.0, <2.0`; a single bound on its own, `=1.2.3` for an exact version
+/// and `*` when there is no bound at all.
+impl std::fmt::Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.lower, &self.upper) {
+            (VerReq::NoBound, VerReq::NoBound) => f.write_str("*"),
+            (VerReq::Eq(lower), VerReq::Eq(upper)) if lower == upper => write!(f, "={}", lower),
+            (bound, VerReq::NoBound) | (VerReq::NoBound, bound) => write!(f, "{}", bound),
+            (lower, upper) => write!(f, "{}, {}", lower, upper),
+        }
+    }
+}
+
+/// Picks the highest version in `candidates` that `range` contains.
+/// With `prefer_stable`, pre-releases (a non-empty `pre`) are only
+/// considered when no stable version is in range.
+pub fn select_best_version(
+    candidates: &[Version],
+    range: &Range,
+    prefer_stable: bool,
+) -> Option<Version> {
+    let in_range = candidates.iter().filter(|version| range.contains(version));
+    let stable = in_range.clone().filter(|version| version.pre.is_empty()).max();
+    match stable {
+        Some(stable) if prefer_stable => Some(stable.clone()),
+        _ => in_range.max().cloned(),
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -498,11 +1076,19 @@ pub struct DepVer {
 
 impl std::fmt::Display for DepVer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.name)
+        if self.range.is_unbounded() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} ({})", self.name, self.range)
+        }
     }
 }
 
 impl DepVer {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn get_installed_specific(&self) -> Result<Specific, String> {
         let installed_dir = get_metadata_dir()?;
         let package_file = installed_dir.join(format!("{}.json", self.name));
@@ -543,43 +1129,49 @@ pub struct Specific {
 
 impl std::fmt::Display for Specific {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.name)
+        write!(f, "{} {}", self.name, self.version)
     }
 }
 
+/// Record `their_name` `their_ver` in the `dependents` of the installed
+/// package file at `path`. The read-modify-write happens under the file's
+/// lock so concurrent installs don't drop each other's entries.
+fn add_dependent(path: &Path, their_name: &str, their_ver: &str) -> Result<(), String> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    with_file_lock(path, || {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read package file: {}", e))?;
+        let mut data: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse package metadata: {}", e))?;
+
+        // Add dependent to the dependents array
+        if let Some(dependents_array) = data.get_mut("dependents").and_then(|dependents| dependents.as_array_mut()) {
+            let their_dep = serde_json::json!({
+                "name": their_name,
+                "version": their_ver
+            });
+            if !dependents_array.contains(&their_dep) {
+                dependents_array.push(their_dep);
+            }
+        }
+
+        let content = serde_json::to_string_pretty(&data)
+            .map_err(|e| format!("Failed to serialize package metadata: {}", e))?;
+        write_atomic(path, content.as_bytes())
+    })
+}
+
 impl Specific {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn write_dependent(&self, their_name: &str, their_ver: &str) -> Result<(), String> {
         let installed_dir = get_metadata_dir()?;
-        let package_file = installed_dir.join(format!("{}.json", self.name));
-        let path = package_file;
-        if path.exists() && path.is_file() {
-            let content = std::fs::read_to_string(&path)
-                .map_err(|e| format!("Failed to read package file: {}", e))?;
-            let mut data: serde_json::Value = serde_json::from_str(&content)
-                .map_err(|e| format!("Failed to parse package metadata: {}", e))?;
-            
-            // Add dependent to the dependents array
-            if let Some(dependents) = data.get_mut("dependents") {
-                if let Some(dependents_array) = dependents.as_array_mut() {
-                    let their_dep = serde_json::json!({
-                        "name": their_name,
-                        "version": their_ver
-                    });
-                    if !dependents_array.contains(&their_dep) {
-                        dependents_array.push(their_dep);
-                    }
-                }
-            }
-            
-            let content = serde_json::to_string_pretty(&data)
-                .map_err(|e| format!("Failed to serialize package metadata: {}", e))?;
-            let mut file = std::fs::File::create(&path)
-                .map_err(|e| format!("Failed to create package file: {}", e))?;
-            use std::io::Write;
-            file.write_all(content.as_bytes())
-                .map_err(|e| format!("Failed to write package file: {}", e))?;
-        }
-        Ok(())
+        let path = installed_dir.join(format!("{}.json", self.name));
+        add_dependent(&path, their_name, their_ver)
     }
 
     pub fn get_dependents(&self, _queued: &mut Vec<String>) -> Result<(), String> {
@@ -602,3 +1194,254 @@ impl Specific {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_serializes_as_string() {
+        let version: Version = "1.2.3-rc1".parse().unwrap();
+        let json = serde_json::to_string(&version).unwrap();
+        assert_eq!(json, "\"1.2.3-rc1\"");
+        assert_eq!(serde_json::from_str::<Version>(&json).unwrap(), version);
+    }
+
+    #[test]
+    fn test_select_best_version() {
+        let candidates: Vec<Version> = ["1.2.0", "1.4.1", "2.0.0-rc1", "2.0.0-rc2", "1.9.3"]
+            .iter()
+            .map(|v| v.parse().unwrap())
+            .collect();
+        let any = Range {
+            lower: VerReq::NoBound,
+            upper: VerReq::NoBound,
+        };
+        let below_two = Range {
+            lower: VerReq::Ge(Version::parse("1.3").unwrap()),
+            upper: VerReq::Lt(Version::parse("2").unwrap()),
+        };
+        let pre_only = Range {
+            lower: VerReq::Gt(Version::parse("1.9.3").unwrap()),
+            upper: VerReq::NoBound,
+        };
+
+        assert_eq!(select_best_version(&candidates, &any, true), Some(Version::parse("1.9.3").unwrap()));
+        assert_eq!(select_best_version(&candidates, &any, false), Some(Version::parse("2.0.0-rc2").unwrap()));
+        assert_eq!(select_best_version(&candidates, &below_two, true), Some(Version::parse("1.9.3").unwrap()));
+        assert_eq!(select_best_version(&candidates, &pre_only, true), Some(Version::parse("2.0.0-rc2").unwrap()));
+        assert_eq!(select_best_version(&[], &any, true), None);
+    }
+
+    #[test]
+    fn test_expand_vars() {
+        let vars = HashMap::from([
+            ("PAX_NAME".to_string(), "hello".to_string()),
+            ("PREFIX".to_string(), "/usr".to_string()),
+        ]);
+        assert_eq!(
+            expand_vars("make -j$(nproc) PREFIX=${PREFIX} NAME=${PAX_NAME} HOME=$HOME", &vars).unwrap(),
+            "make -j$(nproc) PREFIX=/usr NAME=hello HOME=$HOME"
+        );
+        assert_eq!(
+            expand_vars("echo ${LIBDIR:-lib}", &vars).unwrap(),
+            "echo ${LIBDIR:-lib}"
+        );
+        assert_eq!(
+            expand_vars("echo $$ $${SRCDIR} ${PREFIX}", &vars).unwrap(),
+            "echo $$ ${SRCDIR} /usr"
+        );
+        assert!(expand_vars("echo ${MISSING}", &vars).unwrap_err().contains("MISSING"));
+        assert!(expand_vars("echo ${PREFIX", &vars).is_err());
+    }
+
+    #[test]
+    fn test_version_accepts_struct_form() {
+        let json = r#"{"major":1,"minor":2,"patch":3,"pre":""}"#;
+        let version = serde_json::from_str::<Version>(json).unwrap();
+        assert_eq!(version, Version::parse("1.2.3").unwrap());
+    }
+
+    #[test]
+    fn test_truncate_start_multibyte() {
+        let name = "/usr/share/doc/café-crème/éàü-naïve-résumé-ñandú-überlänge.txt";
+        let truncated = truncate_start(name, 40);
+        assert_eq!(truncated.chars().count(), 40);
+        assert!(truncated.starts_with("..."));
+        assert!(truncated.ends_with("überlänge.txt"));
+
+        assert_eq!(truncate_start("short.txt", 40), "short.txt");
+        assert_eq!(truncate_start("ééééé", 4), "...é");
+    }
+
+    #[test]
+    fn test_split_commands() {
+        assert_eq!(
+            split_commands("make && make install DESTDIR=$DESTDIR"),
+            vec!["make", "make install DESTDIR=$DESTDIR"]
+        );
+        assert_eq!(
+            split_commands("sh -c 'true && echo ok' && echo \"a && b\""),
+            vec!["sh -c 'true && echo ok'", "echo \"a && b\""]
+        );
+        assert_eq!(
+            split_commands("# build\nmkdir -p out\n\ncp a \\\n  b out/\ntrue && echo ok\n"),
+            vec!["mkdir -p out", "cp a b out/", "true && echo ok"]
+        );
+        assert!(split_commands("  ").is_empty());
+    }
+
+    #[test]
+    fn test_tmpfile_distinct() {
+        let first = tmpfile().unwrap();
+        let second = tmpfile().unwrap();
+        assert_ne!(first, second);
+        assert!(first.is_file() && second.is_file());
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&first).unwrap().permissions().mode() & 0o777, 0o600);
+        let _ = std::fs::remove_file(first);
+        let _ = std::fs::remove_file(second);
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("1024"), Ok(1024));
+        assert_eq!(parse_rate("500k"), Ok(500 * 1024));
+        assert_eq!(parse_rate("2M"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_rate(" 1G "), Ok(1024 * 1024 * 1024));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("fast").is_err());
+        assert!(parse_rate("").is_err());
+    }
+
+    #[test]
+    fn test_dep_ver_display() {
+        let v = |version: &str| Version::parse(version).unwrap();
+        let dep = |lower: VerReq, upper: VerReq| DepVer {
+            name: String::from("foo"),
+            range: Range { lower, upper },
+        };
+        assert_eq!(dep(VerReq::NoBound, VerReq::NoBound).to_string(), "foo");
+        assert_eq!(dep(VerReq::Ge(v("1.2")), VerReq::Lt(v("2.0"))).to_string(), "foo (>=1.2.0, <2.0.0)");
+        assert_eq!(dep(VerReq::Gt(v("1.2")), VerReq::Le(v("2.0"))).to_string(), "foo (>1.2.0, <=2.0.0)");
+        assert_eq!(dep(VerReq::Ge(v("1.2")), VerReq::NoBound).to_string(), "foo (>=1.2.0)");
+        assert_eq!(dep(VerReq::NoBound, VerReq::Lt(v("2.0"))).to_string(), "foo (<2.0.0)");
+        assert_eq!(dep(VerReq::Eq(v("1.2.3")), VerReq::Eq(v("1.2.3"))).to_string(), "foo (=1.2.3)");
+        assert_eq!(dep(VerReq::Eq(v("1.2.3")), VerReq::NoBound).to_string(), "foo (=1.2.3)");
+        assert_eq!(dep(VerReq::NoBound, VerReq::NoBound).name(), "foo");
+
+        let specific = Specific { name: String::from("foo"), version: v("1.2.3-rc1") };
+        assert_eq!(specific.to_string(), "foo 1.2.3-rc1");
+        assert_eq!(specific.name(), "foo");
+    }
+
+    #[test]
+    fn test_range_display() {
+        let v = |version: &str| Version::parse(version).unwrap();
+        assert_eq!(VerReq::Gt(v("1.0")).to_string(), ">1.0.0");
+        assert_eq!(VerReq::Ge(v("1.0")).to_string(), ">=1.0.0");
+        assert_eq!(VerReq::Eq(v("1.0")).to_string(), "=1.0.0");
+        assert_eq!(VerReq::Le(v("1.0")).to_string(), "<=1.0.0");
+        assert_eq!(VerReq::Lt(v("1.0")).to_string(), "<1.0.0");
+        assert_eq!(VerReq::NoBound.to_string(), "*");
+
+        let ranges = [
+            (Range { lower: VerReq::NoBound, upper: VerReq::NoBound }, "*"),
+            (Range { lower: VerReq::Ge(v("1.0")), upper: VerReq::Lt(v("2.0")) }, ">=1.0.0, <2.0.0"),
+            (Range { lower: VerReq::Gt(v("1.0")), upper: VerReq::NoBound }, ">1.0.0"),
+            (Range { lower: VerReq::NoBound, upper: VerReq::Le(v("2.0")) }, "<=2.0.0"),
+            (Range { lower: VerReq::Eq(v("1.2.3")), upper: VerReq::Eq(v("1.2.3")) }, "=1.2.3"),
+            (Range { lower: VerReq::Ge(v("1.0.0-rc1")), upper: VerReq::Lt(v("2.0")) }, ">=1.0.0-rc1, <2.0.0"),
+        ];
+        for (range, shown) in ranges {
+            assert_eq!(range.to_string(), shown);
+            assert_eq!(Range::parse(shown).unwrap(), range);
+        }
+        assert!(Range::parse("~1.0").is_err());
+        assert!(Range::parse(">=1.0, <2.0, <3.0").is_err());
+    }
+
+    #[test]
+    fn test_failed_atomic_write_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pkg.json");
+        write_atomic(&path, b"{\"version\": \"1.0\"}").unwrap();
+
+        let result = write_atomic_with(&path, |file| {
+            file.write_all(b"{\"vers")?;
+            Err(std::io::Error::other("disk full"))
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"version\": \"1.0\"}");
+        // The half-written temporary file is cleaned up
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        write_atomic(&path, b"{\"version\": \"2.0\"}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"version\": \"2.0\"}");
+    }
+
+    #[test]
+    fn test_concurrent_dependents_are_all_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("openssl.json");
+        std::fs::write(&path, r#"{"name": "openssl", "dependents": []}"#).unwrap();
+
+        let threads: Vec<_> = ["curl", "git"]
+            .into_iter()
+            .map(|prefix| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        add_dependent(&path, &format!("{prefix}{i}"), "1.0.0").unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let data: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(data["dependents"].as_array().unwrap().len(), 50);
+    }
+
+    #[test]
+    fn test_range_intersect() {
+        let range = |src: &str| Range::parse(src).unwrap();
+        assert_eq!(range(">=1.0.0").intersect(&range("<2.0.0")), Some(range(">=1.0.0, <2.0.0")));
+        assert_eq!(range(">=1.0.0, <3.0.0").intersect(&range(">1.5.0, <=2.0.0")), Some(range(">1.5.0, <=2.0.0")));
+        assert_eq!(range("=1.2.0").intersect(&range(">=1.0.0, <2.0.0")), Some(range("=1.2.0")));
+        assert_eq!(range("<2.0.0").intersect(&range(">=2.0.0")), None);
+        assert_eq!(range("<=2.0.0").intersect(&range(">=2.0.0")), Some(range(">=2.0.0, <=2.0.0")));
+        assert_eq!(range("=1.0.0").intersect(&range("=1.1.0")), None);
+        let any = Range { lower: VerReq::NoBound, upper: VerReq::NoBound };
+        assert_eq!(any.intersect(&range(">=1.0.0")), Some(range(">=1.0.0")));
+    }
+
+    #[test]
+    fn test_interrupted_answer_is_not_retried() {
+        struct Interrupted;
+        impl std::io::Read for Interrupted {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::Interrupted.into())
+            }
+        }
+        let fault = read_answer(&mut Interrupted).unwrap_err();
+        assert_eq!(fault.kind(), std::io::ErrorKind::Interrupted);
+        assert_eq!(read_answer(&mut "y\nrest".as_bytes()).unwrap(), "y");
+        assert_eq!(read_answer(&mut "".as_bytes()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_log_level_from_count() {
+        use logging::LogLevel;
+
+        assert_eq!(LogLevel::from_count(0), LogLevel::Warn);
+        assert_eq!(LogLevel::from_count(1), LogLevel::Info);
+        // -vv
+        assert_eq!(LogLevel::from_count(2), LogLevel::Debug);
+        assert_eq!(LogLevel::from_count(3), LogLevel::Trace);
+        assert_eq!(LogLevel::from_count(7), LogLevel::Trace);
+        assert!(LogLevel::Debug < LogLevel::Info);
+    }
+}
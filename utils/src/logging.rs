@@ -8,17 +8,32 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Ordered from most to least verbose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum LogLevel {
+    Trace,
     Debug,
     Info,
     Warn,
     Error,
 }
 
+impl LogLevel {
+    /// The level for `count` repeats of `-v`; more than three is still trace.
+    pub fn from_count(count: usize) -> Self {
+        match count {
+            0 => LogLevel::Warn,
+            1 => LogLevel::Info,
+            2 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
 impl std::fmt::Display for LogLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            LogLevel::Trace => write!(f, "TRACE"),
             LogLevel::Debug => write!(f, "DEBUG"),
             LogLevel::Info => write!(f, "INFO"),
             LogLevel::Warn => write!(f, "WARN"),
@@ -79,7 +94,7 @@ impl Logger {
         
         let entry = LogEntry {
             timestamp,
-            level: level.clone(),
+            level,
             module: module.to_string(),
             message: message.to_string(),
             details: details.map(|s| s.to_string()),
@@ -88,7 +103,7 @@ impl Logger {
         // Console output
         if self.console_output {
             let color = match level {
-                LogLevel::Debug => "\x1B[90m", // Gray
+                LogLevel::Trace | LogLevel::Debug => "\x1B[90m", // Gray
                 LogLevel::Info => "\x1B[94m",  // Blue
                 LogLevel::Warn => "\x1B[93m",  // Yellow
                 LogLevel::Error => "\x1B[91m", // Red
@@ -119,13 +134,7 @@ impl Logger {
     }
     
     fn should_log(&self, level: &LogLevel) -> bool {
-        match (&self.min_level, level) {
-            (LogLevel::Debug, _) => true,
-            (LogLevel::Info, LogLevel::Info | LogLevel::Warn | LogLevel::Error) => true,
-            (LogLevel::Warn, LogLevel::Warn | LogLevel::Error) => true,
-            (LogLevel::Error, LogLevel::Error) => true,
-            _ => false,
-        }
+        *level >= self.min_level
     }
 }
 
@@ -149,6 +158,33 @@ pub fn init_logger(log_file: Option<PathBuf>, min_level: LogLevel, console_outpu
     }
 }
 
+/// Apply `count` repeats of `-v`/`--verbose`: `enabled` and the global
+/// logger both go by it from here on.
+pub fn set_verbosity(count: usize) {
+    // SAFETY: flags are parsed before any other threads are started
+    unsafe { std::env::set_var("PAX_VERBOSE", count.to_string()) };
+    if let Ok(mut logger) = LOGGER.lock()
+        && let Some(logger) = logger.as_mut()
+    {
+        logger.min_level = LogLevel::from_count(count);
+    }
+}
+
+/// The level asked for with `-v`/`--verbose` or `PAX_VERBOSE=<count>`;
+/// warnings and errors only without either.
+pub fn verbosity() -> LogLevel {
+    let count = std::env::var("PAX_VERBOSE")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0);
+    LogLevel::from_count(count)
+}
+
+/// Whether output at `level` is wanted in this invocation.
+pub fn enabled(level: LogLevel) -> bool {
+    level >= verbosity()
+}
+
 pub fn log_debug(module: &str, message: &str, details: Option<&str>) {
     if let Ok(logger) = LOGGER.lock() {
         if let Some(ref logger) = *logger {